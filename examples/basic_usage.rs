@@ -13,15 +13,14 @@
 use olap_storage_engine::{
     // Storage 层
     common::{AggregateType, ColumnType, CompactionType, KeysType, Version},
-    meta::{ColumnSchema, RowsetMeta, TabletMeta, TabletSchema},
+    meta::{ColumnSchema, RowsetMeta, TabletSchema},
     partition::{
         BucketType, MaterializedIndex, Partition, PartitionInfo,
         RangeBound, RangePartitionItem,
     },
     storage::{PartitionSpec, StorageEngine},
-    table::OlapTable,
     // Segment 层
-    field_type::{ColumnMeta, CompressionType, EncodingType, FieldType, Value},
+    field_type::Value,
     segment::SegmentWriter,
 };
 
@@ -37,7 +36,7 @@ fn main() -> olap_storage_engine::common::Result<()> {
     // =========================================================================
     println!("【1】启动 StorageEngine ...");
     let engine = StorageEngine::new_single_dir("/tmp/olap-data");
-    println!("    data_dir = {}\n", engine.data_dir);
+    println!("    data_dirs = {:?}\n", engine.data_dirs);
 
     // =========================================================================
     // 2. 创建数据库
@@ -100,8 +99,8 @@ fn main() -> olap_storage_engine::common::Result<()> {
     let partition_info = PartitionInfo::range(
         vec!["order_date".into()],
         vec![
-            RangePartitionItem { partition_id: 10, upper_bound: RangeBound("2024-07-01".into()) },
-            RangePartitionItem { partition_id: 11, upper_bound: RangeBound("2025-01-01".into()) },
+            RangePartitionItem { partition_id: 10, upper_bound: RangeBound::Key("2024-07-01".into()) },
+            RangePartitionItem { partition_id: 11, upper_bound: RangeBound::Key("2025-01-01".into()) },
         ],
         partitions,
     );
@@ -157,23 +156,8 @@ fn main() -> olap_storage_engine::common::Result<()> {
     // =========================================================================
     println!("【6】Segment V2 列存写入 ...");
 
-    // Segment 层的列元数据（与 TabletSchema 对应）
-    let seg_schema = vec![
-        ColumnMeta::new(0, "order_date", FieldType::Int32)
-            .with_encoding(EncodingType::DeltaBinary),
-        ColumnMeta::new(1, "order_id",   FieldType::Int64)
-            .with_encoding(EncodingType::DeltaBinary),
-        ColumnMeta::new(2, "user_id",    FieldType::Int64)
-            .with_encoding(EncodingType::DeltaBinary),
-        ColumnMeta::new(3, "amount",     FieldType::Float64)
-            .with_encoding(EncodingType::Plain),
-        ColumnMeta::new(4, "status",     FieldType::Bytes)
-            .with_encoding(EncodingType::Dictionary)
-            .with_compression(CompressionType::Lz4)
-            .nullable(),
-    ];
-
-    let mut seg_writer = SegmentWriter::new(seg_schema.clone());
+    // Segment 层的列元数据由 TabletSchema 推导（整数列 Delta、字符串列字典编码）
+    let mut seg_writer = SegmentWriter::from_tablet_schema(&schema);
 
     // 写入 2000 行模拟数据
     let statuses = ["pending", "paid", "shipped", "delivered", "cancelled"];
@@ -202,7 +186,7 @@ fn main() -> olap_storage_engine::common::Result<()> {
     println!("【7】Segment V2 列存读取 ...");
 
     use olap_storage_engine::segment::SegmentReader;
-    let reader = SegmentReader::open(seg_bytes, seg_schema)?;
+    let reader = SegmentReader::open(seg_bytes, schema.segment_schema())?;
     println!("    Segment 总行数 = {}", reader.num_rows());
 
     // 读取 order_id 列（Delta 编码）
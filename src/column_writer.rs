@@ -1,125 +1,337 @@
 //! 列写入器（对应 OLAP Segment V2 column_writer）
 //!
 //! 每列独立维护：
-//!   - Data Page 缓冲区
+//!   - 当前 Data Page（写满即刷入 PageSink，不在内存中累积）
 //!   - OrdinalIndex（行号→页偏移）
 //!   - ZoneMapIndex（min/max 剪枝）
 //!   - BloomFilter（等值过滤）
+//!   - GlobalDictionary（可选，Dictionary 编码的字符串列，各页共享）
+//!   - BitmapIndex（可选，值→行号位图）
 
-use crate::common::Result;
-use crate::field_type::{ColumnMeta, Value};
-use crate::index::{BloomFilter, OrdinalIndex, ShortKeyIndex, ZoneMapIndex};
-use crate::page::PageBuilder;
+use std::io::Write;
+use xxhash_rust::xxh64::Xxh64;
+use crate::common::{OlapError, Result};
+use crate::encryption::EncryptionKey;
+use crate::field_type::{ChecksumType, ColumnMeta, EncodingType, FieldType, Value};
+use crate::index::{
+    BitmapIndex, BloomFilter, BloomGranularity, GlobalDictionary, OrdinalIndex, PageBloomIndex, ShortKeyIndex,
+    ZoneMapIndex, SHORT_KEY_INTERVAL,
+};
+use crate::page::{PageBuilder, PAGE_MAX_ROWS, PAGE_TARGET_BYTES};
+
+// ── PageSink ──────────────────────────────────────────────────────────────────
+
+/// Segment 输出端：包装底层 Write，并记录已写出的文件偏移与内容摘要
+pub struct PageSink<W: Write> {
+    inner:  W,
+    pos:    u64,
+    hasher: Xxh64,
+}
+
+impl<W: Write> PageSink<W> {
+    pub fn new(inner: W) -> Self {
+        Self { inner, pos: 0, hasher: Xxh64::new(0) }
+    }
+
+    /// 下一个字节将落在的文件偏移
+    pub fn position(&self) -> u64 { self.pos }
+
+    /// 已写出全部字节的 xxHash64
+    pub fn digest(&self) -> u64 { self.hasher.digest() }
+
+    pub fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        self.inner.write_all(buf).map_err(|e| OlapError::SegmentIo(e.to_string()))?;
+        self.hasher.update(buf);
+        self.pos += buf.len() as u64;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W { self.inner }
+}
+
+// ── ColumnWriter ──────────────────────────────────────────────────────────────
 
 pub struct ColumnWriter {
     pub meta:           ColumnMeta,
-    // 已完成的页（序列化字节）
-    pages:              Vec<Vec<u8>>,
     current:            PageBuilder,
+    /// 每页的行数上限与目标字节数
+    page_max_rows:      usize,
+    page_max_bytes:     usize,
+    /// 数据页校验算法
+    checksum:           ChecksumType,
+    /// Some 时加密各数据页的 payload
+    encryption:         Option<EncryptionKey>,
     // 当前行计数（跨页累计）
     next_row_id:        u32,
     // 页级别 min/max（用于 ZoneMap）
     page_min:           Option<Vec<u8>>,
     page_max:           Option<Vec<u8>>,
+    page_has_null:      bool,
     page_ordinal:       u32,
-    // 写入指针（数据区偏移）
-    data_offset:        u64,
+    // 已写出的数据页总字节数
+    data_size:          u64,
     // 索引
     pub ordinal_index:  OrdinalIndex,
     pub zone_map:       ZoneMapIndex,
     pub bloom_filter:   BloomFilter,
+    pub page_blooms:    PageBloomIndex,
+    bloom_fpp:          f64,
+    bloom_granularity:  BloomGranularity,
+    // Page 粒度时当前页的 BloomFilter
+    page_bloom:         Option<BloomFilter>,
+    /// Some 时各页只存字典码（`PAGE_DICT_CODE_ENCODING`），字典写入索引区
+    pub dictionary:     Option<GlobalDictionary>,
+    /// 是否允许使用全局字典；实际启用还要求本列为 Dictionary 编码的字符串列
+    global_dictionary:  bool,
+    /// Some 时为每个非 NULL 值记录行号位图
+    pub bitmap_index:   Option<BitmapIndex>,
 }
 
+/// 使用 `GlobalDictionary` 的列，其数据页存放字典码的编码方式
+pub const PAGE_DICT_CODE_ENCODING: EncodingType = EncodingType::ForBitPacked;
+
 impl ColumnWriter {
     pub fn new(meta: ColumnMeta) -> Self {
         let bf = BloomFilter::new(4096);
         let page = PageBuilder::new(0, meta.encoding, meta.compression);
         Self {
-            meta, pages: Vec::new(),
+            meta,
             current: page, next_row_id: 0,
-            page_min: None, page_max: None,
-            page_ordinal: 0, data_offset: 0,
+            page_max_rows: PAGE_MAX_ROWS, page_max_bytes: PAGE_TARGET_BYTES,
+            checksum: ChecksumType::default(),
+            encryption: None,
+            page_min: None, page_max: None, page_has_null: false,
+            page_ordinal: 0, data_size: 0,
             ordinal_index: OrdinalIndex::default(),
             zone_map: ZoneMapIndex::default(),
             bloom_filter: bf,
+            page_blooms: PageBloomIndex::default(),
+            bloom_fpp: BloomFilter::DEFAULT_FPP,
+            bloom_granularity: BloomGranularity::Segment,
+            page_bloom: None,
+            dictionary: None,
+            global_dictionary: false,
+            bitmap_index: None,
         }
     }
 
-    /// 追加一个值到本列
-    pub fn add_value(&mut self, value: Value) -> Result<()> {
+    /// 设置目标页大小：页内原始值字节数达到 `bytes` 即刷出，与行数上限先到先得
+    pub fn with_page_max_bytes(mut self, bytes: usize) -> Self {
+        self.page_max_bytes = bytes;
+        self.current = self.new_page();
+        self
+    }
+
+    /// 设置每页行数上限
+    pub fn with_page_max_rows(mut self, rows: usize) -> Self {
+        self.page_max_rows = rows.max(1);
+        self.current = self.new_page();
+        self
+    }
+
+    /// 更换本列编码，只能在写入任何值之前调用（按样本选定编码时使用）
+    pub fn set_encoding(&mut self, encoding: EncodingType) {
+        debug_assert_eq!(self.next_row_id, 0, "encoding changed after values were written");
+        self.meta.encoding = encoding;
+        self.dictionary    = global_dictionary_for(&self.meta, self.global_dictionary);
+        self.current       = self.new_page();
+    }
+
+    /// 是否为本列使用全局字典；只对 Dictionary 编码的字符串列生效，只能在写入任何值之前调用
+    ///
+    /// 字典随不同值的个数增长且不设上限，只应对取值很少的列开启。
+    pub fn with_global_dictionary(mut self, enabled: bool) -> Self {
+        self.global_dictionary = enabled;
+        self.dictionary        = global_dictionary_for(&self.meta, enabled);
+        self.current           = self.new_page();
+        self
+    }
+
+    /// 设置数据页校验算法
+    pub fn with_checksum(mut self, checksum: ChecksumType) -> Self {
+        self.checksum = checksum;
+        self.current  = self.new_page();
+        self
+    }
+
+    /// 设置数据页加密密钥，None 时不加密
+    pub fn with_encryption(mut self, key: Option<EncryptionKey>) -> Self {
+        self.encryption = key;
+        self.current    = self.new_page();
+        self
+    }
+
+    /// 是否为本列构建 BitmapIndex，适合取值很少的列
+    pub fn with_bitmap_index(mut self, enabled: bool) -> Self {
+        self.bitmap_index = enabled.then(BitmapIndex::default);
+        self
+    }
+
+    /// 设置 BloomFilter 目标误判率
+    pub fn with_bloom_fpp(mut self, fpp: f64) -> Self {
+        self.bloom_fpp    = fpp;
+        self.bloom_filter = BloomFilter::with_fpp(4096, fpp);
+        self
+    }
+
+    /// 设置 BloomFilter 粒度；Page 粒度在列级过滤器之外再为每页建一个
+    pub fn with_bloom_granularity(mut self, granularity: BloomGranularity) -> Self {
+        self.bloom_granularity = granularity; self
+    }
+
+    fn new_page(&self) -> PageBuilder {
+        let encoding = if self.dictionary.is_some() { PAGE_DICT_CODE_ENCODING } else { self.meta.encoding };
+        PageBuilder::new(self.next_row_id, encoding, self.meta.compression)
+            .with_max_rows(self.page_max_rows)
+            .with_max_bytes(self.page_max_bytes)
+            .with_checksum(self.checksum)
+            .with_encryption(self.encryption.clone())
+    }
+
+    /// 追加一个值到本列；当前页写满时直接刷入 `sink`
+    pub fn add_value<W: Write>(&mut self, value: Value, sink: &mut PageSink<W>) -> Result<()> {
         // 1. BloomFilter
         let key = value.to_sort_key();
         self.bloom_filter.add(&key);
-
-        // 2. ZoneMap：更新页内 min/max
-        if self.page_min.as_deref().map(|m| key.as_slice() < m).unwrap_or(true) {
-            self.page_min = Some(key.clone());
+        if self.bloom_granularity == BloomGranularity::Page {
+            let (rows, fpp) = (self.page_max_rows, self.bloom_fpp);
+            self.page_bloom
+                .get_or_insert_with(|| BloomFilter::with_fpp(rows, fpp))
+                .add(&key);
+        }
+        if let Some(bitmap) = &mut self.bitmap_index {
+            if value != Value::Null {
+                bitmap.add(&key, self.next_row_id);
+            }
         }
-        if self.page_max.as_deref().map(|m| key.as_slice() > m).unwrap_or(true) {
-            self.page_max = Some(key);
+
+        // 2. ZoneMap：更新页内 min/max（NULL 不参与，只记标志）
+        if value == Value::Null {
+            self.page_has_null = true;
+        } else {
+            if self.page_min.as_deref().map(|m| key.as_slice() < m).unwrap_or(true) {
+                self.page_min = Some(key.clone());
+            }
+            if self.page_max.as_deref().map(|m| key.as_slice() > m).unwrap_or(true) {
+                self.page_max = Some(key);
+            }
         }
 
+        // 3. 字典列：页内只存字典码
+        let value = match (&mut self.dictionary, value) {
+            (Some(dict), Value::Bytes(b)) => Value::Int32(dict.code_of(&b) as i32),
+            (_, v)                        => v,
+        };
         self.current.add(value);
         self.next_row_id += 1;
 
         if self.current.is_full() {
-            self.flush_page()?;
+            self.flush_page(sink)?;
         }
         Ok(())
     }
 
-    fn flush_page(&mut self) -> Result<()> {
+    fn flush_page<W: Write>(&mut self, sink: &mut PageSink<W>) -> Result<()> {
         let first_rid = self.current.first_row_id;
-        let bytes     = std::mem::replace(
-            &mut self.current,
-            PageBuilder::new(self.next_row_id, self.meta.encoding, self.meta.compression),
-        ).build()?;
+        let next      = self.new_page();
+        let bytes     = std::mem::replace(&mut self.current, next).build()?;
 
         let page_len  = bytes.len() as u64;
 
-        self.ordinal_index.add(first_rid, self.data_offset);
+        self.ordinal_index.add(first_rid, sink.position(), page_len);
+        // 页非空，min 为 None 说明没有任何非 NULL 值
+        let all_null = self.page_min.is_none();
         self.zone_map.add_page(
             self.page_ordinal,
             self.page_min.take().unwrap_or_default(),
             self.page_max.take().unwrap_or_default(),
-            false,
+            std::mem::take(&mut self.page_has_null),
+            all_null,
         );
 
-        self.pages.push(bytes);
-        self.data_offset  += page_len;
+        if let Some(bf) = self.page_bloom.take() {
+            self.page_blooms.add_page(bf);
+        }
+
+        sink.write_all(&bytes)?;
+        self.data_size    += page_len;
         self.page_ordinal += 1;
         Ok(())
     }
 
-    /// 完成写入，返回列的数据字节和总字节数
-    pub fn finalize(mut self) -> Result<(Vec<u8>, u64)> {
+    /// 刷出未满的当前页，使索引覆盖全部已写入的行
+    pub fn flush<W: Write>(&mut self, sink: &mut PageSink<W>) -> Result<()> {
         if !self.current.is_empty() {
-            self.flush_page()?;
+            self.flush_page(sink)?;
         }
-        let data: Vec<u8> = self.pages.into_iter().flatten().collect();
-        let size = data.len() as u64;
-        Ok((data, size))
+        Ok(())
     }
 
     pub fn num_rows(&self) -> u32 { self.next_row_id }
+
+    /// 已写出的数据页总字节数
+    pub fn data_size(&self) -> u64 { self.data_size }
+
+    /// 全局字典区域的字节；不使用全局字典时为空
+    pub fn dictionary_bytes(&self) -> Vec<u8> {
+        self.dictionary.as_ref().map(GlobalDictionary::serialize).unwrap_or_default()
+    }
+
+    /// BitmapIndex 区域的字节；未启用时为空
+    pub fn bitmap_bytes(&self) -> Vec<u8> {
+        self.bitmap_index.as_ref().map(BitmapIndex::serialize).unwrap_or_default()
+    }
+
+    /// BloomFilter 区域的字节：列级过滤器，Page 粒度时其后紧跟 `PageBloomIndex`
+    pub fn bloom_bytes(&self) -> Vec<u8> {
+        let mut out = self.bloom_filter.serialize();
+        if self.bloom_granularity == BloomGranularity::Page {
+            out.extend_from_slice(&self.page_blooms.serialize());
+        }
+        out
+    }
+}
+
+/// 允许使用全局字典且本列为 Dictionary 编码的字符串列时，返回一个空字典
+fn global_dictionary_for(meta: &ColumnMeta, enabled: bool) -> Option<GlobalDictionary> {
+    let eligible = meta.field_type == FieldType::Bytes && meta.encoding == EncodingType::Dictionary;
+    (enabled && eligible).then(GlobalDictionary::default)
 }
 
 // ── ShortKeyIndexBuilder（仅 key 列使用）────────────────────────────────────
 
 pub struct ShortKeyIndexBuilder {
     pub index: ShortKeyIndex,
+    /// 每隔多少行记录一次前缀
+    interval:  u32,
 }
 
 impl ShortKeyIndexBuilder {
     pub fn new() -> Self {
-        Self { index: ShortKeyIndex::default() }
+        Self { index: ShortKeyIndex::default(), interval: SHORT_KEY_INTERVAL }
+    }
+
+    pub fn with_interval(mut self, interval: u32) -> Self {
+        self.interval = interval.max(1); self
+    }
+
+    /// `row_id` 是否为采样行，即 `maybe_add` 是否会记录其前缀
+    pub fn is_sampled(&self, row_id: u32) -> bool {
+        row_id.is_multiple_of(self.interval)
     }
 
     pub fn maybe_add(&mut self, row_id: u32, key_columns: &[Value]) {
+        if !self.is_sampled(row_id) {
+            return;
+        }
         // 将所有 key 列拼接为前缀
         let prefix: Vec<u8> = key_columns.iter()
             .flat_map(|v| v.to_sort_key())
             .collect();
-        self.index.maybe_add(row_id, prefix);
+        self.index.add(row_id, prefix);
     }
 }
+
+impl Default for ShortKeyIndexBuilder {
+    fn default() -> Self { Self::new() }
+}
@@ -21,6 +21,9 @@ pub struct Version {
 }
 
 impl Version {
+    /// 占位版本：`publish_rowset` 在 Tablet 写锁内替换为 `Tablet::next_version()`
+    pub const AUTO: Version = Version { start: -1, end: -1 };
+
     pub fn new(start: i64, end: i64) -> Self { Self { start, end } }
     pub fn point(v: i64) -> Self { Self::new(v, v) }
 }
@@ -43,12 +46,29 @@ pub enum KeysType {
     Duplicate,
 }
 
+impl KeysType {
+    /// `self as u8` 的逆映射
+    pub fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(Self::Aggregate),
+            1 => Some(Self::Unique),
+            2 => Some(Self::Duplicate),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ColumnType {
     Int8, Int16, Int32, Int64,
     Float32, Float64,
     Varchar,
     Date,
+    /// 时间戳，微秒精度
+    DateTime,
+    Bool,
+    /// 定点小数：共 `precision` 位有效数字，其中 `scale` 位小数
+    Decimal { precision: u8, scale: u8 },
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -56,6 +76,20 @@ pub enum AggregateType {
     None, Sum, Max, Min, Replace,
 }
 
+impl AggregateType {
+    /// `self as u8` 的逆映射
+    pub fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(Self::None),
+            1 => Some(Self::Sum),
+            2 => Some(Self::Max),
+            3 => Some(Self::Min),
+            4 => Some(Self::Replace),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum StorageMedium { Hdd, Ssd }
 
@@ -68,28 +102,109 @@ pub enum CompactionType { Base, Cumulative }
 pub enum OlapError {
     #[error("tablet not found: tablet_id={0}")]
     TabletNotFound(TabletId),
+    #[error("tablet already exists: tablet_id={0}")]
+    TabletExists(TabletId),
     #[error("table not found: db_id={0} table_id={1}")]
     TableNotFound(DbId, TableId),
+    #[error("table not found: db_id={0} name={1}")]
+    TableNameNotFound(DbId, String),
+    #[error("table already exists: db_id={0} name={1}")]
+    TableExists(DbId, String),
+    #[error("column not found: column_id={0}")]
+    ColumnNotFound(u32),
     #[error("database not found: db_id={0}")]
     DatabaseNotFound(DbId),
     #[error("partition not found for key: {0}")]
     PartitionNotFound(String),
+    #[error("partition range overlaps: {0}")]
+    PartitionOverlap(String),
     #[error("version already exists: {0}")]
     VersionExists(Version),
     #[error("missing versions in range {0}")]
     MissingVersions(String),
     #[error("segment I/O error: {0}")]
     SegmentIo(String),
+    #[error("WAL error: {0}")]
+    Wal(String),
+    #[error("catalog I/O error: {0}")]
+    CatalogIo(String),
     #[error("encoding error: {0}")]
     Encoding(String),
     #[error("compression error: {0}")]
     Compression(String),
     #[error("checksum mismatch")]
     ChecksumMismatch,
-    #[error("schema mismatch")]
-    SchemaMismatch,
+    #[error("encryption error: {0}")]
+    Encryption(String),
+    #[error("schema mismatch: {0}")]
+    SchemaMismatch(String),
+    #[error("row width mismatch: expected {0} values, got {1}")]
+    RowWidthMismatch(usize, usize),
+    #[error("value too long for column {0}: {1} bytes exceeds max_length {2}")]
+    ValueTooLong(usize, usize, u32),
     #[error("unsupported: {0}")]
     Unsupported(String),
 }
 
 pub type Result<T> = std::result::Result<T, OlapError>;
+
+// ── 元数据编解码 ──────────────────────────────────────────────────────────────
+
+/// 写入 `len u32 | bytes`
+pub(crate) fn put_bytes(out: &mut Vec<u8>, b: &[u8]) {
+    out.extend_from_slice(&(b.len() as u32).to_le_bytes());
+    out.extend_from_slice(b);
+}
+
+/// 小端元数据的顺序读取器；越界或格式错误返回带 `what` 前缀的 `OlapError::Encoding`
+pub(crate) struct ByteReader<'a> {
+    data: &'a [u8],
+    pos:  usize,
+    what: &'static str,
+}
+
+impl<'a> ByteReader<'a> {
+    pub fn new(data: &'a [u8], what: &'static str) -> Self {
+        Self { data, pos: 0, what }
+    }
+
+    pub fn err(&self, msg: impl std::fmt::Display) -> OlapError {
+        OlapError::Encoding(format!("{}: {msg}", self.what))
+    }
+
+    pub fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        let b = self.data.get(self.pos..self.pos + n).ok_or_else(|| self.err("truncated"))?;
+        self.pos += n;
+        Ok(b)
+    }
+
+    pub fn u8(&mut self) -> Result<u8> { Ok(self.take(1)?[0]) }
+    pub fn bool(&mut self) -> Result<bool> { Ok(self.u8()? != 0) }
+    pub fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+    pub fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+    pub fn i64(&mut self) -> Result<i64> { Ok(self.u64()? as i64) }
+
+    /// 读取 `put_bytes` 写入的字节串
+    pub fn bytes(&mut self) -> Result<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+    pub fn string(&mut self) -> Result<String> {
+        let b = self.bytes()?;
+        String::from_utf8(b.to_vec()).map_err(|_| self.err("string is not UTF-8"))
+    }
+
+    pub fn remaining(&self) -> usize { self.data.len() - self.pos }
+
+    /// 要求数据恰好读完
+    pub fn finish(&self) -> Result<()> {
+        if self.pos != self.data.len() {
+            return Err(self.err("trailing bytes"));
+        }
+        Ok(())
+    }
+}
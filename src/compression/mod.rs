@@ -1,14 +1,24 @@
-//! 压缩/解压（LZ4 / None）
+//! 压缩/解压（LZ4 / Zstd / None）
 
 use crate::common::{OlapError, Result};
 use crate::field_type::CompressionType;
 
+/// Zstd 合法压缩级别
+const ZSTD_LEVELS: std::ops::RangeInclusive<i32> = 1..=22;
+
 pub fn compress(data: &[u8], codec: CompressionType) -> Result<Vec<u8>> {
     match codec {
         CompressionType::None => Ok(data.to_vec()),
         CompressionType::Lz4  =>
             lz4::block::compress(data, None, false)
                 .map_err(|e| OlapError::Compression(e.to_string())),
+        CompressionType::Zstd { level } => {
+            if !ZSTD_LEVELS.contains(&level) {
+                return Err(OlapError::Compression(format!("invalid zstd level {level}")));
+            }
+            zstd::bulk::compress(data, level)
+                .map_err(|e| OlapError::Compression(e.to_string()))
+        }
     }
 }
 
@@ -22,5 +32,113 @@ pub fn decompress(
         CompressionType::Lz4  =>
             lz4::block::decompress(data, Some(uncompressed_len as i32))
                 .map_err(|e| OlapError::Compression(e.to_string())),
+        CompressionType::Zstd { .. } =>
+            zstd::bulk::decompress(data, uncompressed_len)
+                .map_err(|e| OlapError::Compression(e.to_string())),
+    }
+}
+
+/// 不带长度前缀的 LZ4 block 在 `data` 开头占用的字节数，`uncompressed_len` 为其解压后的长度
+///
+/// 只解析各序列的 token 与长度字段，不解压；`data` 可以在 block 之后还有其他内容。
+pub fn lz4_block_len(data: &[u8], uncompressed_len: usize) -> Result<usize> {
+    let corrupt = || OlapError::Compression("truncated or malformed lz4 block".into());
+    // 4 位长度字段为 15 时后续字节逐个累加，直到某字节小于 255
+    let ext_len = |pos: &mut usize, nibble: usize| -> Result<usize> {
+        let mut len = nibble;
+        if nibble == 15 {
+            loop {
+                let b = *data.get(*pos).ok_or_else(corrupt)?;
+                *pos += 1;
+                len  += b as usize;
+                if b != 255 { break; }
+            }
+        }
+        Ok(len)
+    };
+    let mut pos = 0usize;
+    let mut out = 0usize;
+    loop {
+        let token = *data.get(pos).ok_or_else(corrupt)?;
+        pos += 1;
+        let literals = ext_len(&mut pos, (token >> 4) as usize)?;
+        pos += literals;
+        out += literals;
+        if pos > data.len() || out > uncompressed_len {
+            return Err(corrupt());
+        }
+        // 最后一个序列只有字面量
+        if out == uncompressed_len {
+            return Ok(pos);
+        }
+        pos += 2;
+        let matched = ext_len(&mut pos, (token & 0x0f) as usize)? + 4;
+        out += matched;
+        if pos > data.len() || out > uncompressed_len {
+            return Err(corrupt());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Vec<u8> {
+        (0..4096u32).flat_map(|i| (i % 97).to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn every_codec_round_trips() {
+        let data = sample();
+        for codec in [CompressionType::None, CompressionType::Lz4, CompressionType::Zstd { level: 1 },
+                      CompressionType::Zstd { level: 22 }] {
+            let packed = compress(&data, codec).unwrap();
+            assert_eq!(decompress(&packed, codec, data.len()).unwrap(), data, "{codec:?}");
+        }
+    }
+
+    #[test]
+    fn baseline_lz4_block_still_decodes() {
+        // 基线写入 LZ4 页的方式：不带长度前缀的 block，解压时由页头给出原始长度
+        let data   = sample();
+        let packed = lz4::block::compress(&data, None, false).unwrap();
+        assert_eq!(decompress(&packed, CompressionType::Lz4, data.len()).unwrap(), data);
+    }
+
+    #[test]
+    fn lz4_block_len_stops_at_the_end_of_the_block() {
+        for data in [sample(), Vec::new(), b"abc".to_vec(), vec![7u8; 100_000]] {
+            let packed = lz4::block::compress(&data, None, false).unwrap();
+            let mut followed = packed.clone();
+            followed.extend_from_slice(&[0xff; 16]);
+            assert_eq!(lz4_block_len(&followed, data.len()).unwrap(), packed.len(), "{} bytes", data.len());
+            assert!(lz4_block_len(&packed[..packed.len() - 1], data.len()).is_err());
+            assert!(lz4_block_len(&packed, data.len() + 1).is_err());
+        }
+    }
+
+    #[test]
+    fn zstd_level_outside_1_to_22_is_rejected() {
+        for level in [i32::MIN, -1, 0, 23] {
+            let err = compress(b"abc", CompressionType::Zstd { level }).unwrap_err();
+            assert!(matches!(err, OlapError::Compression(_)), "level {level}: {err:?}");
+        }
+    }
+
+    #[test]
+    fn codec_tags_are_distinct_and_keep_lz4_at_1() {
+        let codecs = [CompressionType::None, CompressionType::Lz4, CompressionType::Zstd { level: 9 }];
+        let tags: Vec<u8> = codecs.iter().map(|c| c.tag()).collect();
+        assert_eq!(tags, [0, 1, 2]);
+        for codec in codecs {
+            let decoded = CompressionType::from_tag(codec.tag()).unwrap();
+            assert_eq!(std::mem::discriminant(&decoded), std::mem::discriminant(&codec));
+        }
+        assert_eq!(CompressionType::from_tag(3), None);
+        // 按 Zstd 标签解出的页不依赖写入时的级别
+        let packed = compress(&sample(), CompressionType::Zstd { level: 19 }).unwrap();
+        let codec  = CompressionType::from_tag(2).unwrap();
+        assert_eq!(decompress(&packed, codec, sample().len()).unwrap(), sample());
     }
 }
@@ -1,38 +1,259 @@
 //! 列编码
 //!
-//! 支持四种编码：
-//! - **Plain**      — 原始字节，无转换
-//! - **RunLength**  — (count, value) 对，适合低基数枚举列
-//! - **DeltaBinary**— 有序整数增量编码，大幅压缩时间戳/ID 列
-//! - **Dictionary** — 字典编码，低基数字符串列节省 60-80% 空间
+//! 支持九种编码：
+//! - **Plain**        — 原始字节，无转换
+//! - **RunLength**    — (count, value) 对，适合低基数枚举列
+//! - **DeltaBinary**  — 有序整数增量编码，大幅压缩时间戳/ID 列
+//! - **Dictionary**   — 字典编码，低基数字符串列节省 60-80% 空间
+//! - **BoolBitPacked**— 布尔列按位打包，每值 1 bit
+//! - **ForBitPacked** — 以页内最小值为基准，差值按最小位宽打包，适合取值范围小的整数列
+//! - **FloatXor**     — Gorilla 异或编码，缓变的浮点指标列
+//! - **PrefixDelta**  — 前缀编码，有序的高基数字符串列（路径、URL）
+//! - **DeltaDelta**   — 二阶差分编码，近似等间隔的时间戳列
 
+use std::collections::HashSet;
 use crate::common::{OlapError, Result};
-use crate::field_type::{EncodingType, Value};
+use crate::field_type::{EncodingType, FieldType, Value};
 
 // ── 统一编/解码入口 ───────────────────────────────────────────────────────────
 
 pub fn encode(values: &[Value], enc: EncodingType) -> Result<Vec<u8>> {
     match enc {
-        EncodingType::Plain       => plain::encode(values),
-        EncodingType::RunLength   => rle::encode(values),
-        EncodingType::DeltaBinary => delta::encode(values),
-        EncodingType::Dictionary  => dict::encode(values),
+        EncodingType::Plain         => plain::encode(values),
+        EncodingType::RunLength     => rle::encode(values),
+        EncodingType::DeltaBinary   => delta::encode(values),
+        EncodingType::Dictionary    => dict::encode(values),
+        EncodingType::BoolBitPacked => bitpack::encode(values),
+        EncodingType::ForBitPacked  => for_bitpack::encode(values),
+        EncodingType::FloatXor      => float_xor::encode(values),
+        EncodingType::PrefixDelta   => prefix::encode(values),
+        EncodingType::DeltaDelta    => delta_delta::encode(values),
     }
 }
 
-pub fn decode(data: &[u8], enc: EncodingType, count: usize) -> Result<Vec<Value>> {
+/// 不实际编码，估算 `encode(values, enc)` 的输出字节数
+///
+/// 除 FloatXor（按每值新开窗口估算，偏大）外与真实长度一致或接近；
+/// Dictionary 按不同值计入字典，DeltaBinary 的差值按定长 8 字节存储，与差值大小无关。
+pub fn estimate_size(values: &[Value], enc: EncodingType) -> usize {
     match enc {
-        EncodingType::Plain       => plain::decode(data, count),
-        EncodingType::RunLength   => rle::decode(data),
-        EncodingType::DeltaBinary => delta::decode(data, count),
-        EncodingType::Dictionary  => dict::decode(data, count),
+        EncodingType::Plain         => plain::estimate(values),
+        EncodingType::RunLength     => rle::estimate(values),
+        EncodingType::DeltaBinary   => delta::estimate(values),
+        EncodingType::Dictionary    => dict::estimate(values),
+        EncodingType::BoolBitPacked => values.len().div_ceil(8),
+        EncodingType::ForBitPacked  => for_bitpack::estimate(values),
+        EncodingType::FloatXor      => float_xor::estimate(values),
+        EncodingType::PrefixDelta   => prefix::estimate(values),
+        EncodingType::DeltaDelta    => delta_delta::estimate(values),
     }
 }
 
+/// Plain 编码下单个值的字节数
+fn plain_width(v: &Value) -> usize {
+    match v {
+        Value::Null | Value::Int8(_) | Value::Bool(_)            => 1,
+        Value::Int16(_)                                          => 2,
+        Value::Int32(_) | Value::Float32(_)                      => 4,
+        Value::Int64(_) | Value::Float64(_) | Value::DateTime(_) => 8,
+        Value::Decimal(_)                                        => 16,
+        Value::Bytes(b)                                          => 4 + b.len(),
+    }
+}
+
+/// 解码需要列的物理类型，以还原正确的 `Value` 变体
+pub fn decode(
+    data:       &[u8],
+    enc:        EncodingType,
+    field_type: FieldType,
+    count:      usize,
+) -> Result<Vec<Value>> {
+    match enc {
+        EncodingType::Plain         => plain::decode(data, field_type, count),
+        EncodingType::RunLength     => rle::decode(data),
+        EncodingType::DeltaBinary   => delta::decode(data, field_type, count),
+        EncodingType::Dictionary    => dict::decode(data, count),
+        EncodingType::BoolBitPacked => bitpack::decode(data, count),
+        EncodingType::ForBitPacked  => for_bitpack::decode(data, field_type, count),
+        EncodingType::FloatXor      => float_xor::decode(data, field_type, count),
+        EncodingType::PrefixDelta   => prefix::decode(data, count),
+        EncodingType::DeltaDelta    => delta_delta::decode(data, field_type, count),
+    }
+}
+
+/// 解码基线 version 2 Segment 的页
+///
+/// Plain 与 DeltaBinary 的格式未变；RunLength 不带类型标签，Dictionary 的字典码固定为 u32，
+/// 见 `baseline`。基线没有其余编码，遇到时返回 `Encoding`。
+pub fn decode_baseline(
+    data:       &[u8],
+    enc:        EncodingType,
+    field_type: FieldType,
+    count:      usize,
+) -> Result<Vec<Value>> {
+    match enc {
+        EncodingType::Plain | EncodingType::DeltaBinary => decode(data, enc, field_type, count),
+        EncodingType::RunLength  => baseline::decode_rle(data, field_type, count),
+        EncodingType::Dictionary => baseline::decode_dict(data, count),
+        other => Err(OlapError::Encoding(format!("{other:?} is not a version 2 segment encoding"))),
+    }
+}
+
+// ── 编码选择 ──────────────────────────────────────────────────────────────────
+
+/// 相邻值非递减的占比达到此值即视为有序
+const SORTED_RATIO: f64 = 0.95;
+
+/// 整数列的候选编码，按并列时的优先顺序排列
+const INT_CANDIDATES: [EncodingType; 4] = [
+    EncodingType::Plain, EncodingType::ForBitPacked, EncodingType::DeltaDelta, EncodingType::DeltaBinary,
+];
+
+/// 按样本的有序度、游程数与不同值占比为列挑选编码，NULL 不计入样本
+///
+/// - 布尔 → BoolBitPacked；游程数 ≤ 样本数 / 4 → RunLength
+/// - 整数：在 `INT_CANDIDATES` 中取样本上 `estimate_size` 最小者
+/// - 字符串：不同值 ≤ 样本数 / 2 → Dictionary；有序 → PrefixDelta；否则 Plain
+/// - 浮点 → FloatXor；其余 → Plain
+pub fn choose_encoding(sample: &[Value], field_type: FieldType) -> EncodingType {
+    if field_type == FieldType::Bool {
+        return EncodingType::BoolBitPacked;
+    }
+    let values: Vec<&Value> = sample.iter().filter(|v| **v != Value::Null).collect();
+    if values.is_empty() {
+        return EncodingType::Plain;
+    }
+    let n    = values.len();
+    let runs = 1 + values.windows(2).filter(|w| w[0] != w[1]).count();
+    if n >= 4 && runs <= n / 4 {
+        return EncodingType::RunLength;
+    }
+    let keys: Vec<Vec<u8>> = values.iter().map(|v| v.to_sort_key()).collect();
+    let ascending = keys.windows(2).filter(|w| w[0] <= w[1]).count();
+    let sorted    = n < 2 || ascending as f64 >= SORTED_RATIO * (n - 1) as f64;
+
+    if field_type.is_integer() {
+        // 按样本估算各候选的输出大小取最小；并列时取靠前者，Plain 优先
+        let owned: Vec<Value> = values.into_iter().cloned().collect();
+        return INT_CANDIDATES.into_iter()
+            .min_by_key(|&enc| estimate_size(&owned, enc))
+            .unwrap();
+    }
+    match field_type {
+        FieldType::Bytes => {
+            let distinct = keys.iter().collect::<HashSet<_>>().len();
+            if distinct * 2 <= n {
+                EncodingType::Dictionary
+            } else if sorted {
+                EncodingType::PrefixDelta
+            } else {
+                EncodingType::Plain
+            }
+        }
+        FieldType::Float32 | FieldType::Float64 => EncodingType::FloatXor,
+        _ => EncodingType::Plain,
+    }
+}
+
+/// 逐个产出 `decode` 的结果，不一次性物化整列
+///
+/// Plain / RunLength / DeltaBinary 按需解析，只保存读取位置与当前 run、前值等少量状态；
+/// 其余编码先整体解码再逐个产出。出错后产出一次 `Err` 即结束。
+/// `data` 可以是借用的切片，也可以是解压后拥有的缓冲区。
+pub fn decode_iter<D: AsRef<[u8]>>(
+    data:       D,
+    enc:        EncodingType,
+    field_type: FieldType,
+    count:      usize,
+) -> impl Iterator<Item = Result<Value>> {
+    match enc {
+        EncodingType::Plain       => DecodeIter::Plain(plain::Iter::new(data, field_type, count)),
+        EncodingType::RunLength   => DecodeIter::Rle(rle::Iter::new(data)),
+        EncodingType::DeltaBinary => DecodeIter::Delta(delta::Iter::new(data, field_type, count)),
+        _ => match decode(data.as_ref(), enc, field_type, count) {
+            Ok(values) => DecodeIter::Eager(values.into_iter()),
+            Err(e)     => DecodeIter::Failed(Some(e)),
+        },
+    }
+}
+
+enum DecodeIter<D> {
+    Plain(plain::Iter<D>),
+    Rle(rle::Iter<D>),
+    Delta(delta::Iter<D>),
+    Eager(std::vec::IntoIter<Value>),
+    Failed(Option<OlapError>),
+}
+
+impl<D: AsRef<[u8]>> Iterator for DecodeIter<D> {
+    type Item = Result<Value>;
+
+    fn next(&mut self) -> Option<Result<Value>> {
+        match self {
+            Self::Plain(it)  => it.next(),
+            Self::Rle(it)    => it.next(),
+            Self::Delta(it)  => it.next(),
+            Self::Eager(it)  => it.next().map(Ok),
+            Self::Failed(e)  => e.take().map(Err),
+        }
+    }
+}
+
+/// 将 i64 还原为整数列对应宽度的 Value（Date 以 i32 天数存储）
+fn int_value(v: i64, field_type: FieldType) -> Value {
+    match field_type {
+        FieldType::Int8                    => Value::Int8(v as i8),
+        FieldType::Int16                   => Value::Int16(v as i16),
+        FieldType::Int32 | FieldType::Date => Value::Int32(v as i32),
+        FieldType::Bool                    => Value::Bool(v != 0),
+        FieldType::DateTime                => Value::DateTime(v),
+        _                                  => Value::Int64(v),
+    }
+}
+
+// ── Varint ────────────────────────────────────────────────────────────────────
+//
+// LEB128：每字节低 7 位有效，最高位表示后面还有字节；有符号数先 zigzag 映射到无符号。
+
+fn varint_len(v: u64) -> usize {
+    (64 - (v | 1).leading_zeros() as usize).div_ceil(7)
+}
+
+fn put_varint(out: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        out.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+fn get_varint(data: &[u8], pos: &mut usize) -> Result<u64> {
+    let mut v = 0u64;
+    for shift in (0..64).step_by(7) {
+        let Some(&b) = data.get(*pos) else {
+            return Err(OlapError::Encoding("truncated varint".into()));
+        };
+        *pos += 1;
+        v |= ((b & 0x7f) as u64) << shift;
+        if b & 0x80 == 0 {
+            return Ok(v);
+        }
+    }
+    Err(OlapError::Encoding("varint too long".into()))
+}
+
+fn zigzag(v: i64) -> u64 { ((v << 1) ^ (v >> 63)) as u64 }
+
+fn unzigzag(v: u64) -> i64 { (v >> 1) as i64 ^ -((v & 1) as i64) }
+
 // ── Plain ─────────────────────────────────────────────────────────────────────
 mod plain {
     use super::*;
 
+    pub fn estimate(values: &[Value]) -> usize {
+        values.iter().map(plain_width).sum()
+    }
+
     pub fn encode(values: &[Value]) -> Result<Vec<u8>> {
         let mut out = Vec::new();
         for v in values {
@@ -48,28 +269,113 @@ mod plain {
                     out.extend_from_slice(&(b.len() as u32).to_le_bytes());
                     out.extend_from_slice(b);
                 }
+                Value::Decimal(x) => out.extend_from_slice(&x.to_le_bytes()),
+                Value::Bool(x)    => out.push(*x as u8),
+                Value::DateTime(x) => out.extend_from_slice(&x.to_le_bytes()),
             }
         }
         Ok(out)
     }
 
-    pub fn decode(data: &[u8], count: usize) -> Result<Vec<Value>> {
-        // Plain 解码需要类型信息；此处简化为 Int64（实际由 ColumnMeta 指导）
-        let mut out = Vec::with_capacity(count);
-        let mut pos = 0;
-        while out.len() < count && pos + 8 <= data.len() {
-            let v = i64::from_le_bytes(data[pos..pos+8].try_into().unwrap());
-            out.push(Value::Int64(v));
-            pos += 8;
+    pub fn decode(data: &[u8], field_type: FieldType, count: usize) -> Result<Vec<Value>> {
+        Iter::new(data, field_type, count).collect()
+    }
+
+    /// 定长值、Bytes 长度前缀或值被截断时报错
+    pub struct Iter<D> {
+        data:       D,
+        pos:        usize,
+        field_type: FieldType,
+        remaining:  usize,
+    }
+
+    impl<D: AsRef<[u8]>> Iter<D> {
+        pub fn new(data: D, field_type: FieldType, count: usize) -> Self {
+            Self { data, pos: 0, field_type, remaining: count }
+        }
+
+        fn fail(&mut self, msg: &str) -> Option<Result<Value>> {
+            self.remaining = 0;
+            Some(Err(OlapError::Encoding(msg.into())))
+        }
+    }
+
+    impl<D: AsRef<[u8]>> Iterator for Iter<D> {
+        type Item = Result<Value>;
+
+        fn next(&mut self) -> Option<Result<Value>> {
+            if self.remaining == 0 { return None; }
+            let data = self.data.as_ref();
+            let pos  = self.pos;
+
+            if self.field_type == FieldType::Bytes {
+                // 每个值前有 4 字节长度前缀
+                if pos + 4 > data.len() {
+                    return self.fail("plain: truncated length prefix");
+                }
+                let len = u32::from_le_bytes(data[pos..pos+4].try_into().unwrap()) as usize;
+                if pos + 4 + len > data.len() {
+                    return self.fail("plain: truncated bytes value");
+                }
+                let v = Value::Bytes(data[pos+4..pos+4+len].to_vec());
+                self.pos       += 4 + len;
+                self.remaining -= 1;
+                return Some(Ok(v));
+            }
+
+            let width = self.field_type.fixed_size().unwrap_or(8);
+            if pos + width > data.len() {
+                return self.fail("plain: truncated fixed-width value");
+            }
+            let b = &data[pos..pos+width];
+            let v = match self.field_type {
+                FieldType::Int8       => Value::Int8(b[0] as i8),
+                FieldType::Int16      => Value::Int16(i16::from_le_bytes(b.try_into().unwrap())),
+                FieldType::Int32 |
+                FieldType::Date       => Value::Int32(i32::from_le_bytes(b.try_into().unwrap())),
+                FieldType::Int64      => Value::Int64(i64::from_le_bytes(b.try_into().unwrap())),
+                FieldType::Float32    => Value::Float32(f32::from_le_bytes(b.try_into().unwrap())),
+                FieldType::Float64    => Value::Float64(f64::from_le_bytes(b.try_into().unwrap())),
+                FieldType::Decimal128 => Value::Decimal(i128::from_le_bytes(b.try_into().unwrap())),
+                FieldType::Bool       => Value::Bool(b[0] != 0),
+                FieldType::DateTime   => Value::DateTime(i64::from_le_bytes(b.try_into().unwrap())),
+                FieldType::Bytes      => unreachable!(),
+            };
+            self.pos       += width;
+            self.remaining -= 1;
+            Some(Ok(v))
         }
-        Ok(out)
     }
 }
 
 // ── Run-Length Encoding ───────────────────────────────────────────────────────
+//
+// 每个 run：run_len (u32 LE) | tag (u8) | payload
+// tag 决定 payload 的格式，解码时据此还原原始 Value 变体。
 mod rle {
     use super::*;
 
+    const TAG_NULL:    u8 = 0;
+    const TAG_INT8:    u8 = 1;
+    const TAG_INT16:   u8 = 2;
+    const TAG_INT32:   u8 = 3;
+    const TAG_INT64:   u8 = 4;
+    const TAG_FLOAT32: u8 = 5;
+    const TAG_FLOAT64: u8 = 6;
+    const TAG_BYTES:   u8 = 7;
+    const TAG_DECIMAL: u8 = 8;
+    const TAG_BOOL:    u8 = 9;
+    const TAG_DATETIME: u8 = 10;
+
+    /// 每个 run 为 run_len + tag + 值（NULL 无 payload）
+    pub fn estimate(values: &[Value]) -> usize {
+        let mut prev = None;
+        values.iter()
+            .filter(|&v| prev.replace(v) != Some(v))
+            .map(|v| 5 + if *v == Value::Null { 0 } else { plain_width(v) })
+            .sum()
+    }
+
     pub fn encode(values: &[Value]) -> Result<Vec<u8>> {
         if values.is_empty() { return Ok(vec![]); }
         let mut out = Vec::new();
@@ -92,25 +398,102 @@ mod rle {
     fn write_run(out: &mut Vec<u8>, run: u32, v: &Value) {
         out.extend_from_slice(&run.to_le_bytes());
         match v {
-            Value::Int64(x) => out.extend_from_slice(&x.to_le_bytes()),
-            Value::Bytes(b) => {
+            Value::Null       => out.push(TAG_NULL),
+            Value::Int8(x)    => { out.push(TAG_INT8); out.push(*x as u8); }
+            Value::Int16(x)   => { out.push(TAG_INT16); out.extend_from_slice(&x.to_le_bytes()); }
+            Value::Int32(x)   => { out.push(TAG_INT32); out.extend_from_slice(&x.to_le_bytes()); }
+            Value::Int64(x)   => { out.push(TAG_INT64); out.extend_from_slice(&x.to_le_bytes()); }
+            Value::Float32(x) => { out.push(TAG_FLOAT32); out.extend_from_slice(&x.to_bits().to_le_bytes()); }
+            Value::Float64(x) => { out.push(TAG_FLOAT64); out.extend_from_slice(&x.to_bits().to_le_bytes()); }
+            Value::Bytes(b)   => {
+                out.push(TAG_BYTES);
                 out.extend_from_slice(&(b.len() as u32).to_le_bytes());
                 out.extend_from_slice(b);
             }
-            _ => out.extend_from_slice(&v.as_i64().unwrap_or(0).to_le_bytes()),
+            Value::Decimal(x) => { out.push(TAG_DECIMAL); out.extend_from_slice(&x.to_le_bytes()); }
+            Value::Bool(x)    => { out.push(TAG_BOOL); out.push(*x as u8); }
+            Value::DateTime(x) => { out.push(TAG_DATETIME); out.extend_from_slice(&x.to_le_bytes()); }
+        }
+    }
+
+    fn take<'a>(data: &'a [u8], pos: &mut usize, n: usize) -> Result<&'a [u8]> {
+        if *pos + n > data.len() {
+            return Err(OlapError::Encoding("rle: truncated run".into()));
         }
+        let s = &data[*pos..*pos + n];
+        *pos += n;
+        Ok(s)
     }
 
     pub fn decode(data: &[u8]) -> Result<Vec<Value>> {
-        let mut out = Vec::new();
-        let mut pos = 0;
-        while pos + 12 <= data.len() {
-            let run = u32::from_le_bytes(data[pos..pos+4].try_into().unwrap()) as usize;
-            let val = i64::from_le_bytes(data[pos+4..pos+12].try_into().unwrap());
-            pos += 12;
-            for _ in 0..run { out.push(Value::Int64(val)); }
+        Iter::new(data).collect()
+    }
+
+    /// 只保存当前 run 的值与剩余次数
+    pub struct Iter<D> {
+        data:   D,
+        pos:    usize,
+        run:    Option<(Value, usize)>,
+        failed: bool,
+    }
+
+    impl<D: AsRef<[u8]>> Iter<D> {
+        pub fn new(data: D) -> Self {
+            Self { data, pos: 0, run: None, failed: false }
         }
-        Ok(out)
+    }
+
+    impl<D: AsRef<[u8]>> Iterator for Iter<D> {
+        type Item = Result<Value>;
+
+        fn next(&mut self) -> Option<Result<Value>> {
+            loop {
+                if let Some((v, left)) = &mut self.run {
+                    if *left > 0 {
+                        *left -= 1;
+                        return Some(Ok(v.clone()));
+                    }
+                }
+                if self.failed || self.pos >= self.data.as_ref().len() {
+                    return None;
+                }
+                match read_run(self.data.as_ref(), &mut self.pos) {
+                    Ok(run) => self.run = Some(run),
+                    Err(e)  => {
+                        self.failed = true;
+                        return Some(Err(e));
+                    }
+                }
+            }
+        }
+    }
+
+    /// 读出一个 run：(值, 重复次数)
+    fn read_run(data: &[u8], pos: &mut usize) -> Result<(Value, usize)> {
+        let run = u32::from_le_bytes(take(data, pos, 4)?.try_into().unwrap()) as usize;
+        let tag = take(data, pos, 1)?[0];
+        let val = match tag {
+            TAG_NULL    => Value::Null,
+            TAG_INT8    => Value::Int8(take(data, pos, 1)?[0] as i8),
+            TAG_INT16   => Value::Int16(i16::from_le_bytes(take(data, pos, 2)?.try_into().unwrap())),
+            TAG_INT32   => Value::Int32(i32::from_le_bytes(take(data, pos, 4)?.try_into().unwrap())),
+            TAG_INT64   => Value::Int64(i64::from_le_bytes(take(data, pos, 8)?.try_into().unwrap())),
+            TAG_FLOAT32 => Value::Float32(f32::from_bits(
+                u32::from_le_bytes(take(data, pos, 4)?.try_into().unwrap()))),
+            TAG_FLOAT64 => Value::Float64(f64::from_bits(
+                u64::from_le_bytes(take(data, pos, 8)?.try_into().unwrap()))),
+            TAG_BYTES   => {
+                let len = u32::from_le_bytes(take(data, pos, 4)?.try_into().unwrap()) as usize;
+                Value::Bytes(take(data, pos, len)?.to_vec())
+            }
+            TAG_DECIMAL => Value::Decimal(
+                i128::from_le_bytes(take(data, pos, 16)?.try_into().unwrap())),
+            TAG_BOOL    => Value::Bool(take(data, pos, 1)?[0] != 0),
+            TAG_DATETIME => Value::DateTime(
+                i64::from_le_bytes(take(data, pos, 8)?.try_into().unwrap())),
+            t => return Err(OlapError::Encoding(format!("rle: unknown value tag {t}"))),
+        };
+        Ok((val, run))
     }
 }
 
@@ -118,6 +501,11 @@ mod rle {
 mod delta {
     use super::*;
 
+    /// base 与每个差值都占 8 字节
+    pub fn estimate(values: &[Value]) -> usize {
+        values.len() * 8
+    }
+
     pub fn encode(values: &[Value]) -> Result<Vec<u8>> {
         let ints: Vec<i64> = values.iter()
             .map(|v| v.as_i64().unwrap_or(0))
@@ -127,34 +515,136 @@ mod delta {
         let mut out = Vec::with_capacity(ints.len() * 8);
         out.extend_from_slice(&ints[0].to_le_bytes()); // base
         let mut prev = ints[0];
+        // wrapping_sub / wrapping_add 互逆，极值相邻时也能无损往返
         for &x in &ints[1..] {
-            out.extend_from_slice(&(x - prev).to_le_bytes());
+            out.extend_from_slice(&x.wrapping_sub(prev).to_le_bytes());
             prev = x;
         }
         Ok(out)
     }
 
-    pub fn decode(data: &[u8], count: usize) -> Result<Vec<Value>> {
-        if data.len() < 8 { return Ok(vec![]); }
-        let base = i64::from_le_bytes(data[0..8].try_into().unwrap());
+    pub fn decode(data: &[u8], field_type: FieldType, count: usize) -> Result<Vec<Value>> {
+        Iter::new(data, field_type, count).collect()
+    }
+
+    /// 只保存前一个值；数据不足时提前结束
+    pub struct Iter<D> {
+        data:       D,
+        pos:        usize,
+        prev:       i64,
+        field_type: FieldType,
+        remaining:  usize,
+    }
+
+    impl<D: AsRef<[u8]>> Iter<D> {
+        pub fn new(data: D, field_type: FieldType, count: usize) -> Self {
+            Self { data, pos: 0, prev: 0, field_type, remaining: count }
+        }
+    }
+
+    impl<D: AsRef<[u8]>> Iterator for Iter<D> {
+        type Item = Result<Value>;
+
+        fn next(&mut self) -> Option<Result<Value>> {
+            let data = self.data.as_ref();
+            if self.remaining == 0 || self.pos + 8 > data.len() {
+                return None;
+            }
+            let x = i64::from_le_bytes(data[self.pos..self.pos+8].try_into().unwrap());
+            // 首个 8 字节为 base，其后为差值
+            self.prev       = if self.pos == 0 { x } else { self.prev.wrapping_add(x) };
+            self.pos       += 8;
+            self.remaining -= 1;
+            Some(Ok(int_value(self.prev, self.field_type)))
+        }
+    }
+}
+
+// ── Delta of Delta ────────────────────────────────────────────────────────────
+//
+// base (i64) | zigzag(delta_1) (varint) | zigzag(delta_i - delta_{i-1}) (varint) × (n-2)
+// 等间隔序列的二阶差分恒为 0，带少量抖动时也多在 1 字节内。
+mod delta_delta {
+    use super::*;
+
+    pub fn estimate(values: &[Value]) -> usize {
+        terms(values).map_or(0, |(_, dods)| 8 + dods.map(varint_len).sum::<usize>())
+    }
+
+    pub fn encode(values: &[Value]) -> Result<Vec<u8>> {
+        let mut out = Vec::with_capacity(values.len() + 8);
+        if let Some((base, dods)) = terms(values) {
+            out.extend_from_slice(&base.to_le_bytes());
+            dods.for_each(|z| put_varint(&mut out, z));
+        }
+        Ok(out)
+    }
+
+    pub fn decode(data: &[u8], field_type: FieldType, count: usize) -> Result<Vec<Value>> {
         let mut out = Vec::with_capacity(count);
-        out.push(Value::Int64(base));
-        let mut prev = base;
-        let mut pos = 8;
-        while out.len() < count && pos + 8 <= data.len() {
-            let delta = i64::from_le_bytes(data[pos..pos+8].try_into().unwrap());
-            prev += delta;
-            out.push(Value::Int64(prev));
-            pos += 8;
+        if count == 0 { return Ok(out); }
+        let Some(base) = data.get(0..8) else {
+            return Err(OlapError::Encoding("delta_delta: truncated base".into()));
+        };
+        // 两个累加器：差分累加出当前差值，差值累加出当前值
+        let mut prev  = i64::from_le_bytes(base.try_into().unwrap());
+        let mut delta = 0i64;
+        let mut pos   = 8;
+        out.push(int_value(prev, field_type));
+        while out.len() < count {
+            delta = delta.wrapping_add(unzigzag(get_varint(data, &mut pos)?));
+            prev  = prev.wrapping_add(delta);
+            out.push(int_value(prev, field_type));
         }
         Ok(out)
     }
+
+    /// 首值与其后各值 zigzag 后的二阶差分（首项即一阶差分）；没有值时返回 None
+    fn terms(values: &[Value]) -> Option<(i64, impl Iterator<Item = u64> + '_)> {
+        let mut ints = values.iter().map(|v| v.as_i64().unwrap_or(0));
+        let first = ints.next()?;
+        let (mut prev, mut prev_delta) = (first, 0i64);
+        Some((first, ints.map(move |x| {
+            let delta = x.wrapping_sub(prev);
+            let dod   = delta.wrapping_sub(prev_delta);
+            (prev, prev_delta) = (x, delta);
+            zigzag(dod)
+        })))
+    }
 }
 
 // ── Dictionary ────────────────────────────────────────────────────────────────
+//
+// dict_len (u32) | [len (u32) | bytes] × dict_len | code_width (u8) | codes
+// code_width 由字典大小决定：< 256 → 1 字节，< 65536 → 2 字节，否则 4 字节。
 mod dict {
+    use std::borrow::Cow;
     use super::*;
 
+    fn code_width(dict_len: usize) -> u8 {
+        if dict_len <= u8::MAX as usize + 1 {
+            1
+        } else if dict_len <= u16::MAX as usize + 1 {
+            2
+        } else {
+            4
+        }
+    }
+
+    /// 非 Bytes 值在字典中以文本存储，按排序键长度近似
+    pub fn estimate(values: &[Value]) -> usize {
+        let mut distinct: HashSet<Cow<[u8]>> = HashSet::new();
+        let mut dict_bytes = 0;
+        for v in values {
+            let key = v.as_bytes().map_or_else(|| Cow::Owned(v.to_sort_key()), Cow::Borrowed);
+            let len = key.len();
+            if distinct.insert(key) {
+                dict_bytes += 4 + len;
+            }
+        }
+        4 + dict_bytes + 1 + values.len() * code_width(distinct.len()) as usize
+    }
+
     pub fn encode(values: &[Value]) -> Result<Vec<u8>> {
         let mut dict: Vec<Vec<u8>> = Vec::new();
         let mut codes: Vec<u32>    = Vec::new();
@@ -175,8 +665,14 @@ mod dict {
             out.extend_from_slice(&(entry.len() as u32).to_le_bytes());
             out.extend_from_slice(entry);
         }
-        for c in &codes {
-            out.extend_from_slice(&c.to_le_bytes());
+        let width = code_width(dict.len());
+        out.push(width);
+        for &c in &codes {
+            match width {
+                1 => out.push(c as u8),
+                2 => out.extend_from_slice(&(c as u16).to_le_bytes()),
+                _ => out.extend_from_slice(&c.to_le_bytes()),
+            }
         }
         Ok(out)
     }
@@ -198,13 +694,805 @@ mod dict {
             pos += slen;
         }
 
+        let width = match data.get(pos) {
+            Some(&w @ (1 | 2 | 4)) => w as usize,
+            Some(&w) => return Err(OlapError::Encoding(format!("dict: bad code width {w}"))),
+            None     => return Ok(vec![]),
+        };
+        pos += 1;
+
         let mut out = Vec::with_capacity(count);
         for _ in 0..count {
-            if pos + 4 > data.len() { break; }
-            let code = u32::from_le_bytes(data[pos..pos+4].try_into().unwrap()) as usize;
-            pos += 4;
+            if pos + width > data.len() { break; }
+            let b    = &data[pos..pos+width];
+            let code = match width {
+                1 => b[0] as usize,
+                2 => u16::from_le_bytes(b.try_into().unwrap()) as usize,
+                _ => u32::from_le_bytes(b.try_into().unwrap()) as usize,
+            };
+            pos += width;
             out.push(Value::Bytes(dict.get(code).cloned().unwrap_or_default()));
         }
         Ok(out)
     }
 }
+
+// ── Bool Bit-Packed ───────────────────────────────────────────────────────────
+//
+// 第 i 个值存于第 i/8 字节的第 i%8 位（低位在前），共 ceil(n/8) 字节。
+mod bitpack {
+    use super::*;
+
+    pub fn encode(values: &[Value]) -> Result<Vec<u8>> {
+        let mut out = vec![0u8; values.len().div_ceil(8)];
+        for (i, v) in values.iter().enumerate() {
+            match v {
+                Value::Bool(true)  => out[i / 8] |= 1 << (i % 8),
+                Value::Bool(false) => {}
+                other => return Err(OlapError::Encoding(format!("bitpack: non-bool value {other:?}"))),
+            }
+        }
+        Ok(out)
+    }
+
+    pub fn decode(data: &[u8], count: usize) -> Result<Vec<Value>> {
+        if data.len() < count.div_ceil(8) {
+            return Err(OlapError::Encoding("bitpack: truncated bitmap".into()));
+        }
+        Ok((0..count).map(|i| Value::Bool(data[i / 8] >> (i % 8) & 1 == 1)).collect())
+    }
+}
+
+// ── Frame-of-Reference Bit-Packed ─────────────────────────────────────────────
+//
+// min (i64 LE) | bit_width (u8) | 残差 (x - min) 按 bit_width 位打包，低位在前
+//
+// 残差用 wrapping_sub 计算并按 u64 解释，跨越整个 i64 范围也能无损往返。
+mod for_bitpack {
+    use super::*;
+
+    const HEADER: usize = 9;
+
+    pub fn estimate(values: &[Value]) -> usize {
+        let ints = values.iter().filter_map(Value::as_i64);
+        let (Some(min), Some(max)) = (ints.clone().min(), ints.max()) else { return 0 };
+        let width = 64 - (max.wrapping_sub(min) as u64).leading_zeros() as usize;
+        HEADER + (values.len() * width).div_ceil(8)
+    }
+
+    pub fn encode(values: &[Value]) -> Result<Vec<u8>> {
+        let ints = values.iter()
+            .map(|v| v.as_i64().ok_or_else(|| {
+                OlapError::Encoding(format!("for_bitpack: non-integer value {v:?}"))
+            }))
+            .collect::<Result<Vec<i64>>>()?;
+        let Some(&min) = ints.iter().min() else { return Ok(vec![]) };
+        let max_residual = ints.iter().map(|&x| x.wrapping_sub(min) as u64).max().unwrap_or(0);
+        let width = 64 - max_residual.leading_zeros() as usize;
+
+        let mut out = Vec::with_capacity(HEADER + (ints.len() * width).div_ceil(8));
+        out.extend_from_slice(&min.to_le_bytes());
+        out.push(width as u8);
+        // 累加器最多暂存 7 + 64 位，u128 足够
+        let (mut acc, mut bits) = (0u128, 0usize);
+        for &x in &ints {
+            acc  |= (x.wrapping_sub(min) as u64 as u128) << bits;
+            bits += width;
+            while bits >= 8 {
+                out.push(acc as u8);
+                acc  >>= 8;
+                bits -= 8;
+            }
+        }
+        if bits > 0 {
+            out.push(acc as u8);
+        }
+        Ok(out)
+    }
+
+    pub fn decode(data: &[u8], field_type: FieldType, count: usize) -> Result<Vec<Value>> {
+        if count == 0 { return Ok(vec![]); }
+        if data.len() < HEADER {
+            return Err(OlapError::Encoding("for_bitpack: truncated header".into()));
+        }
+        let min   = i64::from_le_bytes(data[0..8].try_into().unwrap());
+        let width = data[8] as usize;
+        if width > 64 {
+            return Err(OlapError::Encoding(format!("for_bitpack: invalid bit width {width}")));
+        }
+        let packed = &data[HEADER..];
+        if packed.len() < (count * width).div_ceil(8) {
+            return Err(OlapError::Encoding("for_bitpack: truncated data".into()));
+        }
+
+        let mask = if width == 64 { u64::MAX } else { (1u64 << width) - 1 };
+        let mut out   = Vec::with_capacity(count);
+        let mut bytes = packed.iter();
+        let (mut acc, mut bits) = (0u128, 0usize);
+        for _ in 0..count {
+            while bits < width {
+                acc  |= (*bytes.next().unwrap() as u128) << bits;
+                bits += 8;
+            }
+            let residual = acc as u64 & mask;
+            acc  >>= width;
+            bits -= width;
+            out.push(int_value(min.wrapping_add(residual as i64), field_type));
+        }
+        Ok(out)
+    }
+}
+
+// ── Float XOR (Gorilla) ───────────────────────────────────────────────────────
+//
+// 首值写 64 位原始 bits；之后每个值与前值异或（高位在前的位流）：
+//   '0'                         — 与前值相同
+//   '10' + 有效位               — 有效位落在上一个 [leading, 64-trailing) 窗口内
+//   '11' + leading(5) + len(6) + 有效位 — 新窗口，len=64 记为 0
+// Float32 先无损扩展为 f64 再编码。
+mod float_xor {
+    use super::*;
+
+    /// 相同值 1 位，否则按新窗口计 2 + 5 + 6 位控制位加有效位
+    pub fn estimate(values: &[Value]) -> usize {
+        let bits = |v: &Value| match v {
+            Value::Float64(x) => x.to_bits(),
+            Value::Float32(x) => (*x as f64).to_bits(),
+            _                 => 0,
+        };
+        let Some(first) = values.first() else { return 0 };
+        let mut prev = bits(first);
+        let mut total = 64;
+        for v in &values[1..] {
+            let cur = bits(v);
+            let xor = cur ^ prev;
+            total += if xor == 0 {
+                1
+            } else {
+                13 + 64 - xor.leading_zeros().min(31) as usize - xor.trailing_zeros() as usize
+            };
+            prev = cur;
+        }
+        total.div_ceil(8)
+    }
+
+    pub fn encode(values: &[Value]) -> Result<Vec<u8>> {
+        let mut w = BitWriter::default();
+        let mut prev: Option<u64> = None;
+        // 当前窗口 (leading, trailing)
+        let mut window: Option<(u32, u32)> = None;
+        for v in values {
+            let bits = match v {
+                Value::Float64(x) => x.to_bits(),
+                Value::Float32(x) => (*x as f64).to_bits(),
+                other => return Err(OlapError::Encoding(format!("float_xor: non-float value {other:?}"))),
+            };
+            let Some(p) = prev.replace(bits) else {
+                w.write(bits, 64);
+                continue;
+            };
+            let xor = bits ^ p;
+            if xor == 0 {
+                w.write(0, 1);
+                continue;
+            }
+            let leading  = xor.leading_zeros().min(31);
+            let trailing = xor.trailing_zeros();
+            match window {
+                Some((l, t)) if leading >= l && trailing >= t => {
+                    w.write(0b10, 2);
+                    w.write(xor >> t, 64 - l - t);
+                }
+                _ => {
+                    let len = 64 - leading - trailing;
+                    w.write(0b11, 2);
+                    w.write(leading as u64, 5);
+                    w.write((len % 64) as u64, 6);
+                    w.write(xor >> trailing, len);
+                    window = Some((leading, trailing));
+                }
+            }
+        }
+        Ok(w.finish())
+    }
+
+    pub fn decode(data: &[u8], field_type: FieldType, count: usize) -> Result<Vec<Value>> {
+        let to_value = |bits: u64| {
+            let x = f64::from_bits(bits);
+            if field_type == FieldType::Float32 { Value::Float32(x as f32) } else { Value::Float64(x) }
+        };
+        let truncated = || OlapError::Encoding("float_xor: truncated bit stream".into());
+
+        let mut r   = BitReader { data, pos: 0 };
+        let mut out = Vec::with_capacity(count);
+        if count == 0 { return Ok(out); }
+        let mut prev = r.read(64).ok_or_else(truncated)?;
+        out.push(to_value(prev));
+        let mut window = (0u32, 0u32);
+        while out.len() < count {
+            if r.read(1).ok_or_else(truncated)? == 1 {
+                if r.read(1).ok_or_else(truncated)? == 1 {
+                    let leading = r.read(5).ok_or_else(truncated)? as u32;
+                    let len     = match r.read(6).ok_or_else(truncated)? as u32 { 0 => 64, n => n };
+                    if leading + len > 64 {
+                        return Err(OlapError::Encoding(format!(
+                            "float_xor: invalid window leading={leading} len={len}"
+                        )));
+                    }
+                    window = (leading, 64 - leading - len);
+                }
+                let (l, t) = window;
+                prev ^= r.read(64 - l - t).ok_or_else(truncated)? << t;
+            }
+            out.push(to_value(prev));
+        }
+        Ok(out)
+    }
+
+    /// 高位在前的位写入器
+    #[derive(Default)]
+    struct BitWriter {
+        out:  Vec<u8>,
+        /// 最后一个字节已用的位数（0 表示需要新字节）
+        used: u32,
+    }
+
+    impl BitWriter {
+        /// 写入 `v` 的低 `n` 位（n ≤ 64）
+        fn write(&mut self, v: u64, n: u32) {
+            for i in (0..n).rev() {
+                if self.used == 0 {
+                    self.out.push(0);
+                }
+                let bit = (v >> i) & 1;
+                *self.out.last_mut().unwrap() |= (bit as u8) << (7 - self.used);
+                self.used = (self.used + 1) % 8;
+            }
+        }
+
+        fn finish(self) -> Vec<u8> { self.out }
+    }
+
+    struct BitReader<'a> {
+        data: &'a [u8],
+        /// 下一个待读的位序号
+        pos:  usize,
+    }
+
+    impl BitReader<'_> {
+        /// 读 `n` 位（n ≤ 64），数据不足返回 None
+        fn read(&mut self, n: u32) -> Option<u64> {
+            if self.pos + n as usize > self.data.len() * 8 {
+                return None;
+            }
+            let mut v = 0u64;
+            for _ in 0..n {
+                let bit = self.data[self.pos / 8] >> (7 - self.pos % 8) & 1;
+                v = (v << 1) | bit as u64;
+                self.pos += 1;
+            }
+            Some(v)
+        }
+    }
+}
+
+// ── Prefix Delta (front coding) ───────────────────────────────────────────────
+//
+// 每个值：prefix_len (varint) | suffix_len (varint) | suffix
+// prefix_len 为与前一个值的公共前缀长度，首值的 prefix_len 为 0。
+mod prefix {
+    use super::*;
+
+    pub fn estimate(values: &[Value]) -> usize {
+        let mut prev: &[u8] = &[];
+        let mut total = 0;
+        for cur in values.iter().filter_map(Value::as_bytes) {
+            let shared = prev.iter().zip(cur).take_while(|(a, b)| a == b).count();
+            total += varint_len(shared as u64) + varint_len((cur.len() - shared) as u64) + cur.len() - shared;
+            prev = cur;
+        }
+        total
+    }
+
+    pub fn encode(values: &[Value]) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        let mut prev: &[u8] = &[];
+        for v in values {
+            let Value::Bytes(cur) = v else {
+                return Err(OlapError::Encoding(format!("prefix: non-bytes value {v:?}")));
+            };
+            let shared = prev.iter().zip(cur).take_while(|(a, b)| a == b).count();
+            put_varint(&mut out, shared as u64);
+            put_varint(&mut out, (cur.len() - shared) as u64);
+            out.extend_from_slice(&cur[shared..]);
+            prev = cur;
+        }
+        Ok(out)
+    }
+
+    pub fn decode(data: &[u8], count: usize) -> Result<Vec<Value>> {
+        let mut out  = Vec::with_capacity(count);
+        let mut pos  = 0;
+        let mut prev = Vec::new();
+        while out.len() < count {
+            let shared = get_varint(data, &mut pos)? as usize;
+            let len    = get_varint(data, &mut pos)? as usize;
+            if shared > prev.len() {
+                return Err(OlapError::Encoding(format!(
+                    "prefix: shared length {shared} exceeds previous value length {}", prev.len(),
+                )));
+            }
+            let Some(suffix) = pos.checked_add(len).and_then(|end| data.get(pos..end)) else {
+                return Err(OlapError::Encoding("prefix: truncated suffix".into()));
+            };
+            pos += len;
+            prev.truncate(shared);
+            prev.extend_from_slice(suffix);
+            out.push(Value::Bytes(prev.clone()));
+        }
+        Ok(out)
+    }
+}
+
+// ── 基线 version 2 格式 ────────────────────────────────────────────────────────
+//
+// RunLength：run (u32) | 值；Bytes 列的值为 len (u32) + bytes，其余列统一为 i64
+// Dictionary：dict_len (u32) | (len (u32) + bytes) × dict_len | code (u32) × n
+mod baseline {
+    use super::*;
+
+    fn take<'a>(data: &'a [u8], pos: &mut usize, n: usize) -> Result<&'a [u8]> {
+        let s = pos.checked_add(n).and_then(|end| data.get(*pos..end))
+            .ok_or_else(|| OlapError::Encoding("baseline: truncated value".into()))?;
+        *pos += n;
+        Ok(s)
+    }
+
+    fn take_u32(data: &[u8], pos: &mut usize) -> Result<usize> {
+        Ok(u32::from_le_bytes(take(data, pos, 4)?.try_into().unwrap()) as usize)
+    }
+
+    pub fn decode_rle(data: &[u8], field_type: FieldType, count: usize) -> Result<Vec<Value>> {
+        let mut out = Vec::with_capacity(count);
+        let mut pos = 0;
+        while pos < data.len() {
+            let run   = take_u32(data, &mut pos)?;
+            let value = if field_type == FieldType::Bytes {
+                let len = take_u32(data, &mut pos)?;
+                Value::Bytes(take(data, &mut pos, len)?.to_vec())
+            } else {
+                int_value(i64::from_le_bytes(take(data, &mut pos, 8)?.try_into().unwrap()), field_type)
+            };
+            if out.len() + run > count {
+                return Err(OlapError::Encoding(format!("baseline rle: more than {count} values")));
+            }
+            out.extend(std::iter::repeat_n(value, run));
+        }
+        Ok(out)
+    }
+
+    pub fn decode_dict(data: &[u8], count: usize) -> Result<Vec<Value>> {
+        let mut pos  = 0;
+        let dict_len = take_u32(data, &mut pos)?;
+        let dict = (0..dict_len)
+            .map(|_| {
+                let len = take_u32(data, &mut pos)?;
+                Ok(take(data, &mut pos, len)?.to_vec())
+            })
+            .collect::<Result<Vec<_>>>()?;
+        (0..count)
+            .map(|_| {
+                let code = take_u32(data, &mut pos)?;
+                dict.get(code).map(|v| Value::Bytes(v.clone())).ok_or_else(|| {
+                    OlapError::Encoding(format!("baseline dict: code {code} out of range ({dict_len})"))
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ints(values: &[i64]) -> Vec<Value> {
+        values.iter().map(|&v| Value::Int64(v)).collect()
+    }
+
+    // ── 截断输入 ──────────────────────────────────────────────────────────────
+
+    #[test]
+    fn plain_truncated_fixed_width_value_is_an_error() {
+        let data = encode(&ints(&[1, 2, 3]), EncodingType::Plain).unwrap();
+        let cut  = &data[..data.len() - 3];
+        assert!(matches!(
+            decode(cut, EncodingType::Plain, FieldType::Int64, 3),
+            Err(OlapError::Encoding(_)),
+        ));
+
+        let mut it = decode_iter(cut, EncodingType::Plain, FieldType::Int64, 3);
+        assert_eq!(it.next().unwrap().unwrap(), Value::Int64(1));
+        assert_eq!(it.next().unwrap().unwrap(), Value::Int64(2));
+        assert!(it.next().unwrap().is_err());
+        assert!(it.next().is_none());
+    }
+
+    #[test]
+    fn plain_round_trips_every_fixed_width_type() {
+        let cases = [
+            (FieldType::Int8,       Value::Int8(-3)),
+            (FieldType::Int16,      Value::Int16(-300)),
+            (FieldType::Int32,      Value::Int32(70_000)),
+            (FieldType::Float64,    Value::Float64(2.5)),
+            (FieldType::Decimal128, Value::Decimal(-12345)),
+            (FieldType::DateTime,   Value::DateTime(1_700_000_000_000_000)),
+        ];
+        for (ft, v) in cases {
+            let data = encode(std::slice::from_ref(&v), EncodingType::Plain).unwrap();
+            assert_eq!(decode(&data, EncodingType::Plain, ft, 1).unwrap(), vec![v]);
+        }
+    }
+
+    // ── 类型感知的 Plain ──────────────────────────────────────────────────────
+
+    #[test]
+    fn plain_round_trips_every_field_type() {
+        let cases = [
+            (FieldType::Int8,       vec![Value::Int8(i8::MIN), Value::Int8(0), Value::Int8(i8::MAX)]),
+            (FieldType::Int16,      vec![Value::Int16(i16::MIN), Value::Int16(7), Value::Int16(i16::MAX)]),
+            (FieldType::Int32,      vec![Value::Int32(i32::MIN), Value::Int32(-1), Value::Int32(i32::MAX)]),
+            (FieldType::Int64,      vec![Value::Int64(i64::MIN), Value::Int64(42), Value::Int64(i64::MAX)]),
+            (FieldType::Float32,    vec![Value::Float32(-1.5), Value::Float32(0.0), Value::Float32(f32::MAX)]),
+            (FieldType::Float64,    vec![Value::Float64(-2.25), Value::Float64(1e300), Value::Float64(f64::MIN_POSITIVE)]),
+            (FieldType::Bytes,      vec![Value::Bytes(b"abc".to_vec()), Value::Bytes(vec![]), Value::Bytes(vec![0xff; 300])]),
+            (FieldType::Date,       vec![Value::Int32(0), Value::Int32(19_000), Value::Int32(-365)]),
+            (FieldType::DateTime,   vec![Value::DateTime(-1), Value::DateTime(0), Value::DateTime(i64::MAX)]),
+            (FieldType::Decimal128, vec![Value::Decimal(i128::MIN), Value::Decimal(12_345), Value::Decimal(i128::MAX)]),
+            (FieldType::Bool,       vec![Value::Bool(true), Value::Bool(false), Value::Bool(true)]),
+        ];
+        for (ft, values) in cases {
+            let data = encode(&values, EncodingType::Plain).unwrap();
+            assert_eq!(decode(&data, EncodingType::Plain, ft, values.len()).unwrap(), values, "{ft:?}");
+            let streamed: Vec<Value> = decode_iter(&data, EncodingType::Plain, ft, values.len())
+                .map(Result::unwrap)
+                .collect();
+            assert_eq!(streamed, values, "{ft:?}");
+        }
+    }
+
+    #[test]
+    fn plain_bytes_read_each_length_prefix_including_empty_values() {
+        let values: Vec<Value> = [&b""[..], b"a", b"", b"hello world", &[0u8; 1000], b""]
+            .iter()
+            .map(|b| Value::Bytes(b.to_vec()))
+            .collect();
+        let data = encode(&values, EncodingType::Plain).unwrap();
+        assert_eq!(data.len(), 4 * values.len() + 1 + 11 + 1000);
+        assert_eq!(data[0..4], 0u32.to_le_bytes());
+        assert_eq!(data[4..8], 1u32.to_le_bytes());
+        assert_eq!(decode(&data, EncodingType::Plain, FieldType::Bytes, values.len()).unwrap(), values);
+
+        // 长度前缀声明的字节数超过剩余数据
+        assert!(decode(&data[..data.len() - 5], EncodingType::Plain, FieldType::Bytes, values.len()).is_err());
+    }
+
+    // ── RunLength ─────────────────────────────────────────────────────────────
+
+    #[test]
+    fn rle_round_trips_repeated_status_strings() {
+        let statuses: Vec<Value> = ["paid", "paid", "paid", "shipped", "shipped", "paid"]
+            .iter()
+            .cycle()
+            .take(600)
+            .map(|s| Value::Bytes(s.as_bytes().to_vec()))
+            .collect();
+        let data = encode(&statuses, EncodingType::RunLength).unwrap();
+        assert_eq!(decode(&data, EncodingType::RunLength, FieldType::Bytes, statuses.len()).unwrap(), statuses);
+    }
+
+    #[test]
+    fn rle_preserves_value_types() {
+        let values = vec![
+            Value::Int32(5), Value::Int32(5), Value::Null, Value::Float64(0.5), Value::Float64(0.5),
+            Value::Int8(-1), Value::Bool(true), Value::DateTime(9), Value::Decimal(-7),
+        ];
+        let data = encode(&values, EncodingType::RunLength).unwrap();
+        assert_eq!(decode(&data, EncodingType::RunLength, FieldType::Int64, values.len()).unwrap(), values);
+    }
+
+    // ── DeltaBinary ───────────────────────────────────────────────────────────
+
+    #[test]
+    fn delta_round_trips_adjacent_extremes() {
+        let values = ints(&[i64::MIN, i64::MAX, i64::MIN, 0, i64::MAX, -1]);
+        let data = encode(&values, EncodingType::DeltaBinary).unwrap();
+        assert_eq!(decode(&data, EncodingType::DeltaBinary, FieldType::Int64, values.len()).unwrap(), values);
+    }
+
+    #[test]
+    fn delta_decodes_to_the_column_type() {
+        let values = vec![Value::Int32(i32::MIN), Value::Int32(i32::MAX), Value::Int32(0)];
+        let data = encode(&values, EncodingType::DeltaBinary).unwrap();
+        assert_eq!(decode(&data, EncodingType::DeltaBinary, FieldType::Int32, 3).unwrap(), values);
+
+        let days = vec![Value::Int32(19_000), Value::Int32(19_001), Value::Int32(-365)];
+        let data = encode(&days, EncodingType::DeltaBinary).unwrap();
+        assert_eq!(decode(&data, EncodingType::DeltaBinary, FieldType::Date, 3).unwrap(), days);
+    }
+
+    // ── DeltaDelta ────────────────────────────────────────────────────────────
+
+    #[test]
+    fn delta_delta_beats_delta_binary_on_jittered_millisecond_stamps() {
+        let mut rng = crate::partition::XorShift64::new(7);
+        let mut ts  = 1_700_000_000_000i64;
+        let stamps: Vec<Value> = (0..4096)
+            .map(|_| {
+                ts += 995 + (rng.next_u64() % 11) as i64;
+                Value::DateTime(ts)
+            })
+            .collect();
+        let dod   = encode(&stamps, EncodingType::DeltaDelta).unwrap();
+        let delta = encode(&stamps, EncodingType::DeltaBinary).unwrap();
+        assert!(dod.len() < delta.len(), "delta_delta {} vs delta {}", dod.len(), delta.len());
+        assert_eq!(decode(&dod, EncodingType::DeltaDelta, FieldType::DateTime, stamps.len()).unwrap(), stamps);
+
+        let extremes = ints(&[i64::MIN, i64::MAX, 0, i64::MIN, -1]);
+        let data = encode(&extremes, EncodingType::DeltaDelta).unwrap();
+        assert_eq!(decode(&data, EncodingType::DeltaDelta, FieldType::Int64, extremes.len()).unwrap(), extremes);
+    }
+
+    // ── Dictionary ────────────────────────────────────────────────────────────
+
+    #[test]
+    fn dict_codes_take_one_byte_for_small_dictionaries() {
+        let statuses = ["paid", "shipped", "cancelled", "refunded", "pending"];
+        let values: Vec<Value> = (0..2000)
+            .map(|i| Value::Bytes(statuses[i * 7 % 5].as_bytes().to_vec()))
+            .collect();
+        let data = encode(&values, EncodingType::Dictionary).unwrap();
+
+        let dict_bytes: usize = 4 + statuses.iter().map(|s| 4 + s.len()).sum::<usize>();
+        assert_eq!(data.len(), dict_bytes + 1 + 2000);
+        assert_eq!(estimate_size(&values, EncodingType::Dictionary), data.len());
+        assert_eq!(decode(&data, EncodingType::Dictionary, FieldType::Bytes, values.len()).unwrap(), values);
+    }
+
+    #[test]
+    fn dict_codes_widen_past_256_entries() {
+        let values: Vec<Value> = (0..300).map(|i| Value::Bytes(format!("k{i}").into_bytes())).collect();
+        let data = encode(&values, EncodingType::Dictionary).unwrap();
+        let dict_bytes: usize = 4 + values.iter().map(|v| 4 + v.as_bytes().unwrap().len()).sum::<usize>();
+        assert_eq!(data[dict_bytes], 2);
+        assert_eq!(data.len(), dict_bytes + 1 + 300 * 2);
+        assert_eq!(decode(&data, EncodingType::Dictionary, FieldType::Bytes, values.len()).unwrap(), values);
+    }
+
+    // ── BoolBitPacked ─────────────────────────────────────────────────────────
+
+    #[test]
+    fn bool_bitpacking_stores_one_bit_per_value() {
+        let values: Vec<Value> = (0..8000).map(|i| Value::Bool(i % 3 == 0)).collect();
+        let data = encode(&values, EncodingType::BoolBitPacked).unwrap();
+        assert_eq!(data.len(), 1000);
+        assert_eq!(decode(&data, EncodingType::BoolBitPacked, FieldType::Bool, 8000).unwrap(), values);
+
+        let odd: Vec<Value> = values[..13].to_vec();
+        let data = encode(&odd, EncodingType::BoolBitPacked).unwrap();
+        assert_eq!(data.len(), 2);
+        assert_eq!(decode(&data, EncodingType::BoolBitPacked, FieldType::Bool, 13).unwrap(), odd);
+    }
+
+    // ── ForBitPacked ──────────────────────────────────────────────────────────
+
+    #[test]
+    fn for_bitpacking_uses_the_residual_bit_width() {
+        // [10000, 10999]：残差 < 1024，每值 10 位
+        let values: Vec<Value> = (0..4000).map(|i| Value::Int64(10_000 + i * 7919 % 1000)).collect();
+        let data = encode(&values, EncodingType::ForBitPacked).unwrap();
+        assert_eq!(data[8], 10);
+        assert_eq!(data.len(), 9 + 4000 * 10 / 8);
+        assert_eq!(estimate_size(&values, EncodingType::ForBitPacked), data.len());
+        assert_eq!(decode(&data, EncodingType::ForBitPacked, FieldType::Int64, values.len()).unwrap(), values);
+    }
+
+    #[test]
+    fn for_bitpacking_handles_extremes_and_constant_pages() {
+        let values = vec![Value::Int32(i32::MIN), Value::Int32(i32::MAX), Value::Int32(0)];
+        let data = encode(&values, EncodingType::ForBitPacked).unwrap();
+        assert_eq!(decode(&data, EncodingType::ForBitPacked, FieldType::Int32, 3).unwrap(), values);
+
+        let values = ints(&[i64::MIN, i64::MAX]);
+        let data = encode(&values, EncodingType::ForBitPacked).unwrap();
+        assert_eq!(data[8], 64);
+        assert_eq!(decode(&data, EncodingType::ForBitPacked, FieldType::Int64, 2).unwrap(), values);
+
+        let same = ints(&[42; 100]);
+        let data = encode(&same, EncodingType::ForBitPacked).unwrap();
+        assert_eq!(data.len(), 9);
+        assert_eq!(decode(&data, EncodingType::ForBitPacked, FieldType::Int64, 100).unwrap(), same);
+        assert!(decode(&data[..8], EncodingType::ForBitPacked, FieldType::Int64, 100).is_err());
+    }
+
+    // ── FloatXor ──────────────────────────────────────────────────────────────
+
+    #[test]
+    fn float_xor_shrinks_a_slowly_varying_series() {
+        // 温度传感器：每 10 个采样变化 0.25，常有重复值
+        let values: Vec<Value> = (0..2000).map(|i| Value::Float64(21.5 + (i / 10 % 8) as f64 * 0.25)).collect();
+        let xor   = encode(&values, EncodingType::FloatXor).unwrap();
+        let plain = encode(&values, EncodingType::Plain).unwrap();
+        assert!(xor.len() * 4 < plain.len(), "xor {} vs plain {}", xor.len(), plain.len());
+        assert_eq!(decode(&xor, EncodingType::FloatXor, FieldType::Float64, values.len()).unwrap(), values);
+    }
+
+    #[test]
+    fn float_xor_round_trips_special_values_bit_for_bit() {
+        let specials = [0.0, -0.0, f64::INFINITY, f64::NEG_INFINITY, f64::MIN_POSITIVE, f64::MAX, 1e-300, f64::NAN, 3.5];
+        let values: Vec<Value> = specials.iter().map(|&f| Value::Float64(f)).collect();
+        let data    = encode(&values, EncodingType::FloatXor).unwrap();
+        let decoded = decode(&data, EncodingType::FloatXor, FieldType::Float64, values.len()).unwrap();
+        for (want, got) in specials.iter().zip(decoded) {
+            let Value::Float64(got) = got else { panic!("expected Float64, got {got:?}") };
+            assert_eq!(got.to_bits(), want.to_bits());
+        }
+
+        let floats: Vec<Value> = [1.5f32, -2.25, 1.5].iter().map(|&f| Value::Float32(f)).collect();
+        let data = encode(&floats, EncodingType::FloatXor).unwrap();
+        assert_eq!(decode(&data, EncodingType::FloatXor, FieldType::Float32, 3).unwrap(), floats);
+    }
+
+    // ── PrefixDelta ───────────────────────────────────────────────────────────
+
+    #[test]
+    fn prefix_huge_suffix_length_is_an_error() {
+        let mut data = Vec::new();
+        put_varint(&mut data, 0);
+        put_varint(&mut data, u64::MAX);
+        assert!(matches!(
+            decode(&data, EncodingType::PrefixDelta, FieldType::Bytes, 1),
+            Err(OlapError::Encoding(_)),
+        ));
+    }
+
+    #[test]
+    fn prefix_round_trips_sorted_strings() {
+        let values: Vec<Value> = ["apple", "applet", "apply", "banana", ""]
+            .iter()
+            .map(|s| Value::Bytes(s.as_bytes().to_vec()))
+            .collect();
+        let data = encode(&values, EncodingType::PrefixDelta).unwrap();
+        assert_eq!(decode(&data, EncodingType::PrefixDelta, FieldType::Bytes, values.len()).unwrap(), values);
+        assert!(decode(&data[..data.len() - 1], EncodingType::PrefixDelta, FieldType::Bytes, values.len()).is_err());
+    }
+
+    #[test]
+    fn prefix_beats_dictionary_on_sorted_urls() {
+        let mut urls: Vec<String> = (0..1000)
+            .map(|i| format!("https://example.com/shop/category-{}/item/{i:06}.html", i % 12))
+            .collect();
+        urls.sort();
+        let values: Vec<Value> = urls.iter().map(|u| Value::Bytes(u.as_bytes().to_vec())).collect();
+
+        let prefix = encode(&values, EncodingType::PrefixDelta).unwrap();
+        let dict   = encode(&values, EncodingType::Dictionary).unwrap();
+        assert!(prefix.len() * 3 < dict.len(), "prefix {} vs dict {}", prefix.len(), dict.len());
+        assert_eq!(decode(&prefix, EncodingType::PrefixDelta, FieldType::Bytes, values.len()).unwrap(), values);
+    }
+
+
+    // ── 编码选择 ──────────────────────────────────────────────────────────────
+
+    fn random_ints(seed: u64, n: usize) -> Vec<i64> {
+        let mut rng = crate::partition::XorShift64::new(seed);
+        (0..n).map(|_| rng.next_u64() as i64).collect()
+    }
+
+    #[test]
+    fn random_unsorted_ints_do_not_choose_delta() {
+        let wide = ints(&random_ints(1, 1024));
+        assert_eq!(choose_encoding(&wide, FieldType::Int64), EncodingType::Plain);
+        let narrow: Vec<i64> = random_ints(2, 1024).iter().map(|v| v.rem_euclid(1000)).collect();
+        assert_eq!(choose_encoding(&ints(&narrow), FieldType::Int64), EncodingType::ForBitPacked);
+
+        // 有序的小值差分后更紧凑，DeltaBinary 每值 8 字节反而不占优
+        let mut sorted = narrow;
+        sorted.sort_unstable();
+        sorted.dedup();
+        assert_eq!(choose_encoding(&ints(&sorted), FieldType::Int64), EncodingType::DeltaDelta);
+    }
+
+    #[test]
+    fn sorted_narrow_integers_are_no_larger_than_plain() {
+        let cases: Vec<(FieldType, Vec<Value>)> = vec![
+            (FieldType::Int8,  (0..2000).map(|i| Value::Int8((i / 20) as i8)).collect()),
+            (FieldType::Int16, (0..2000).map(|i| Value::Int16(i * 7)).collect()),
+            (FieldType::Int32, (0..2000).map(|i| Value::Int32(1_000_000 + i * 3 + i % 2)).collect()),
+            (FieldType::Date,  (0..2000).map(|i| Value::Int32(19_000 + i / 3)).collect()),
+        ];
+        for (ft, values) in cases {
+            let enc   = choose_encoding(&values, ft);
+            let size  = encode(&values, enc).unwrap().len();
+            let plain = encode(&values, EncodingType::Plain).unwrap().len();
+            assert!(size <= plain, "{ft:?}: {enc:?} {size} bytes vs plain {plain}");
+            assert_ne!(enc, EncodingType::DeltaBinary, "{ft:?}");
+            assert_eq!(decode(&encode(&values, enc).unwrap(), enc, ft, values.len()).unwrap(), values);
+        }
+    }
+
+    #[test]
+    fn string_samples_choose_by_cardinality_and_order() {
+        let bytes = |s: String| Value::Bytes(s.into_bytes());
+        let low:    Vec<Value> = (0..1000).map(|i| bytes(format!("s{}", i * 7 % 5))).collect();
+        let sorted: Vec<Value> = (0..1000).map(|i| bytes(format!("/path/{i:05}"))).collect();
+        let random: Vec<Value> = random_ints(3, 1000).into_iter().map(|v| bytes(format!("{v:x}"))).collect();
+        assert_eq!(choose_encoding(&low, FieldType::Bytes), EncodingType::Dictionary);
+        assert_eq!(choose_encoding(&sorted, FieldType::Bytes), EncodingType::PrefixDelta);
+        assert_eq!(choose_encoding(&random, FieldType::Bytes), EncodingType::Plain);
+        assert_eq!(choose_encoding(&[Value::Null, Value::Null], FieldType::Int64), EncodingType::Plain);
+    }
+
+
+    // ── 大小估算 ──────────────────────────────────────────────────────────────
+
+    #[test]
+    fn estimates_stay_within_a_factor_of_two_of_real_sizes() {
+        let status  = |i: i64| Value::Bytes(["paid", "pending", "cancelled"][(i / 40 % 3) as usize].as_bytes().to_vec());
+        let ids     = ints(&(0..2000).map(|i| 1_000_000 + i * 3).collect::<Vec<_>>());
+        let small   = ints(&random_ints(4, 2000).iter().map(|v| v.rem_euclid(500)).collect::<Vec<_>>());
+        let stamps: Vec<Value> = (0..2000).map(|i| Value::DateTime(1_700_000_000_000_000 + i * 1_000_000 + i % 3)).collect();
+        let floats: Vec<Value> = (0..2000).map(|i| Value::Float64(20.0 + (i / 10) as f64 * 0.25)).collect();
+        let paths:  Vec<Value> = (0..2000).map(|i| Value::Bytes(format!("/data/t/{i:06}.seg").into_bytes())).collect();
+        let flags:  Vec<Value> = (0..2000).map(|i| Value::Bool(i % 3 == 0)).collect();
+        let cases: Vec<(EncodingType, Vec<Value>)> = vec![
+            (EncodingType::Plain,         ids.clone()),
+            (EncodingType::Plain,         paths.clone()),
+            (EncodingType::RunLength,     (0..2000).map(status).collect()),
+            (EncodingType::DeltaBinary,   ids),
+            (EncodingType::Dictionary,    (0..2000).map(status).collect()),
+            (EncodingType::BoolBitPacked, flags),
+            (EncodingType::ForBitPacked,  small),
+            (EncodingType::FloatXor,      floats),
+            (EncodingType::PrefixDelta,   paths),
+            (EncodingType::DeltaDelta,    stamps),
+        ];
+        for (enc, values) in cases {
+            let real     = encode(&values, enc).unwrap().len();
+            let estimate = estimate_size(&values, enc);
+            assert!(estimate * 2 >= real && estimate <= real * 2, "{enc:?}: estimate {estimate}, real {real}");
+        }
+    }
+
+
+    // ── 流式解码 ──────────────────────────────────────────────────────────────
+
+    #[test]
+    fn decode_iter_matches_eager_decode_element_by_element() {
+        let status = |i: i64| Value::Bytes(["paid", "pending", "cancelled"][(i / 40 % 3) as usize].as_bytes().to_vec());
+        let cases: Vec<(EncodingType, FieldType, Vec<Value>)> = vec![
+            (EncodingType::Plain,       FieldType::Int32, (0..3000).map(|i| Value::Int32(i * 7 - 9000)).collect()),
+            (EncodingType::Plain,       FieldType::Bytes, (0..3000).map(status).collect()),
+            (EncodingType::RunLength,   FieldType::Bytes, (0..3000).map(status).collect()),
+            (EncodingType::DeltaBinary, FieldType::Int64, ints(&random_ints(5, 3000))),
+            (EncodingType::Dictionary,  FieldType::Bytes, (0..3000).map(status).collect()),
+            (EncodingType::FloatXor,    FieldType::Float64, (0..3000).map(|i| Value::Float64(i as f64 / 8.0)).collect()),
+        ];
+        for (enc, ft, values) in cases {
+            let data  = encode(&values, enc).unwrap();
+            let eager = decode(&data, enc, ft, values.len()).unwrap();
+            let mut n = 0;
+            for (i, (lazy, want)) in decode_iter(data.as_slice(), enc, ft, values.len()).zip(&eager).enumerate() {
+                assert_eq!(&lazy.unwrap(), want, "{enc:?} value {i}");
+                n += 1;
+            }
+            assert_eq!(n, eager.len(), "{enc:?}");
+            assert_eq!(eager, values);
+
+            // 截断的输入：只产出完好的前缀，之后至多一个 Err（DeltaBinary 直接结束）
+            let out: Vec<Result<Value>> = decode_iter(&data[..data.len() / 2], enc, ft, values.len()).collect();
+            let ok: Vec<Value> = out.iter().map_while(|r| r.as_ref().ok().cloned()).collect();
+            assert!(ok.len() < values.len() && out.len() <= ok.len() + 1, "{enc:?}");
+            assert_eq!(ok, eager[..ok.len()], "{enc:?}");
+        }
+    }
+}
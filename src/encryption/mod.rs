@@ -0,0 +1,82 @@
+//! 数据页加密（AES-256-GCM）
+//!
+//! 只加密数据页压缩后的 payload，每页使用随机 nonce 并存于页头；页头与 null bitmap
+//! 作为附加认证数据（AAD），被篡改时解密失败。
+//! 索引区（OrdinalIndex / ZoneMap / BloomFilter / 全局字典 / BitmapIndex / ShortKey）
+//! 与 footer 仍为明文，其中 ZoneMap 的 min/max、全局字典与 ShortKey 前缀含列值。
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use crate::common::{OlapError, Result};
+
+/// 每页 nonce 的字节数
+pub const NONCE_SIZE: usize = 12;
+/// 认证标签的字节数，附在密文末尾
+pub const TAG_SIZE: usize = 16;
+/// footer 中密钥校验块的字节数（nonce + 空明文的认证标签）
+pub const KEY_CHECK_SIZE: usize = NONCE_SIZE + TAG_SIZE;
+
+/// AES-256 密钥；`Debug` 不输出密钥内容
+#[derive(Clone, PartialEq, Eq)]
+pub struct EncryptionKey([u8; 32]);
+
+impl EncryptionKey {
+    pub fn new(bytes: [u8; 32]) -> Self { Self(bytes) }
+
+    /// 长度不是 32 字节时返回 `Encryption`
+    pub fn from_slice(bytes: &[u8]) -> Result<Self> {
+        let key = bytes.try_into().map_err(|_| {
+            OlapError::Encryption(format!("key must be 32 bytes, got {}", bytes.len()))
+        })?;
+        Ok(Self(key))
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(&self.0.into())
+    }
+}
+
+impl std::fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EncryptionKey(..)")
+    }
+}
+
+/// 生成一个随机 nonce
+pub fn random_nonce() -> Result<[u8; NONCE_SIZE]> {
+    let mut nonce = [0u8; NONCE_SIZE];
+    getrandom::getrandom(&mut nonce).map_err(|e| OlapError::Encryption(e.to_string()))?;
+    Ok(nonce)
+}
+
+/// 加密 `plaintext`，返回密文 + 认证标签
+pub fn encrypt(key: &EncryptionKey, nonce: &[u8; NONCE_SIZE], aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+    key.cipher()
+        .encrypt(Nonce::from_slice(nonce), Payload { msg: plaintext, aad })
+        .map_err(|_| OlapError::Encryption("page encryption failed".into()))
+}
+
+/// 解密并认证；密钥错误或数据被篡改时返回 `Encryption`
+pub fn decrypt(key: &EncryptionKey, nonce: &[u8; NONCE_SIZE], aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>> {
+    key.cipher()
+        .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad })
+        .map_err(|_| OlapError::Encryption("page authentication failed (wrong key or corrupt page)".into()))
+}
+
+/// 密钥校验块：随机 nonce + 空明文的认证标签，打开 Segment 时据此识别错误的密钥
+pub fn key_check(key: &EncryptionKey) -> Result<[u8; KEY_CHECK_SIZE]> {
+    let nonce = random_nonce()?;
+    let tag   = encrypt(key, &nonce, &[], &[])?;
+    let mut out = [0u8; KEY_CHECK_SIZE];
+    out[..NONCE_SIZE].copy_from_slice(&nonce);
+    out[NONCE_SIZE..].copy_from_slice(&tag);
+    Ok(out)
+}
+
+/// 用 `key_check` 的结果校验密钥，不匹配时返回 `Encryption`
+pub fn verify_key(key: &EncryptionKey, check: &[u8; KEY_CHECK_SIZE]) -> Result<()> {
+    let nonce: &[u8; NONCE_SIZE] = check[..NONCE_SIZE].try_into().unwrap();
+    decrypt(key, nonce, &[], &check[NONCE_SIZE..])
+        .map(|_| ())
+        .map_err(|_| OlapError::Encryption("wrong encryption key".into()))
+}
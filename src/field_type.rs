@@ -1,6 +1,6 @@
 //! Segment V2 列的物理字段类型
 
-use crate::common::ColumnType;
+use crate::common::{AggregateType, ColumnType};
 
 /// 列在 Segment 文件中的存储类型
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -11,19 +11,27 @@ pub enum FieldType {
     Bytes,
     /// 日期存储为 i32（天数）
     Date,
+    /// 时间戳存储为 i64（距 1970-01-01T00:00:00Z 的微秒数）
+    DateTime,
+    /// 定点小数，存储未缩放的 i128
+    Decimal128,
+    Bool,
 }
 
 impl From<ColumnType> for FieldType {
     fn from(ct: ColumnType) -> Self {
         match ct {
-            ColumnType::Int8    => Self::Int8,
-            ColumnType::Int16   => Self::Int16,
-            ColumnType::Int32   => Self::Int32,
-            ColumnType::Int64   => Self::Int64,
-            ColumnType::Float32 => Self::Float32,
-            ColumnType::Float64 => Self::Float64,
-            ColumnType::Varchar => Self::Bytes,
-            ColumnType::Date    => Self::Date,
+            ColumnType::Int8           => Self::Int8,
+            ColumnType::Int16          => Self::Int16,
+            ColumnType::Int32          => Self::Int32,
+            ColumnType::Int64          => Self::Int64,
+            ColumnType::Float32        => Self::Float32,
+            ColumnType::Float64        => Self::Float64,
+            ColumnType::Varchar        => Self::Bytes,
+            ColumnType::Date           => Self::Date,
+            ColumnType::DateTime       => Self::DateTime,
+            ColumnType::Decimal { .. } => Self::Decimal128,
+            ColumnType::Bool           => Self::Bool,
         }
     }
 }
@@ -32,17 +40,38 @@ impl FieldType {
     /// 固定字节宽度；变长类型返回 None
     pub fn fixed_size(self) -> Option<usize> {
         match self {
-            Self::Int8               => Some(1),
-            Self::Int16              => Some(2),
-            Self::Int32 | Self::Date => Some(4),
-            Self::Int64              => Some(8),
-            Self::Float32            => Some(4),
-            Self::Float64            => Some(8),
-            Self::Bytes              => None,
+            Self::Int8 | Self::Bool      => Some(1),
+            Self::Int16                  => Some(2),
+            Self::Int32 | Self::Date     => Some(4),
+            Self::Int64 | Self::DateTime => Some(8),
+            Self::Float32                => Some(4),
+            Self::Float64                => Some(8),
+            Self::Decimal128             => Some(16),
+            Self::Bytes                  => None,
         }
     }
+    /// 非 NULL 值能否按本类型写入（宽度必须一致，NULL 另由可空性判断）
+    pub fn accepts(self, value: &Value) -> bool {
+        matches!(
+            (self, value),
+            (_,                 Value::Null)
+            | (Self::Int8,       Value::Int8(_))
+            | (Self::Int16,      Value::Int16(_))
+            | (Self::Int32 | Self::Date, Value::Int32(_))
+            | (Self::Int64,      Value::Int64(_))
+            | (Self::Float32,    Value::Float32(_))
+            | (Self::Float64,    Value::Float64(_))
+            | (Self::Bytes,      Value::Bytes(_))
+            | (Self::DateTime,   Value::DateTime(_))
+            | (Self::Decimal128, Value::Decimal(_))
+            | (Self::Bool,       Value::Bool(_))
+        )
+    }
     pub fn is_integer(self) -> bool {
-        matches!(self, Self::Int8 | Self::Int16 | Self::Int32 | Self::Int64 | Self::Date)
+        matches!(
+            self,
+            Self::Int8 | Self::Int16 | Self::Int32 | Self::Int64 | Self::Date | Self::DateTime
+        )
     }
 }
 
@@ -53,6 +82,48 @@ pub enum EncodingType {
     RunLength,
     DeltaBinary,
     Dictionary,
+    /// 布尔值按位打包，每值 1 bit
+    BoolBitPacked,
+    /// 整数减去页内最小值后按最小位宽打包
+    ForBitPacked,
+    /// 浮点数与前值按位异或（Gorilla），缓变序列压缩率高
+    FloatXor,
+    /// 字符串与前值的公共前缀长度 + 后缀，有序的高基数字符串列压缩率高
+    PrefixDelta,
+    /// 差值的差值按 zigzag varint 存储，近似等间隔的时间戳列每值约 1 字节
+    DeltaDelta,
+}
+
+impl EncodingType {
+    /// 持久化用的单字节编号
+    pub fn tag(self) -> u8 {
+        match self {
+            Self::Plain         => 0,
+            Self::RunLength     => 1,
+            Self::DeltaBinary   => 2,
+            Self::Dictionary    => 3,
+            Self::BoolBitPacked => 4,
+            Self::ForBitPacked  => 5,
+            Self::FloatXor      => 6,
+            Self::PrefixDelta   => 7,
+            Self::DeltaDelta    => 8,
+        }
+    }
+
+    pub fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(Self::Plain),
+            1 => Some(Self::RunLength),
+            2 => Some(Self::DeltaBinary),
+            3 => Some(Self::Dictionary),
+            4 => Some(Self::BoolBitPacked),
+            5 => Some(Self::ForBitPacked),
+            6 => Some(Self::FloatXor),
+            7 => Some(Self::PrefixDelta),
+            8 => Some(Self::DeltaDelta),
+            _ => None,
+        }
+    }
 }
 
 /// 压缩方式
@@ -60,34 +131,124 @@ pub enum EncodingType {
 pub enum CompressionType {
     None,
     Lz4,
+    /// 高压缩比，适合冷数据归档分区；level ∈ [1, 22]
+    Zstd { level: i32 },
+}
+
+impl CompressionType {
+    /// Zstd 默认压缩级别
+    pub const ZSTD_DEFAULT_LEVEL: i32 = 3;
+
+    /// 持久化用的单字节编号（level 只影响压缩，不参与解压，故不写入）
+    pub fn tag(self) -> u8 {
+        match self {
+            Self::None        => 0,
+            Self::Lz4         => 1,
+            Self::Zstd { .. } => 2,
+        }
+    }
+
+    pub fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(Self::None),
+            1 => Some(Self::Lz4),
+            2 => Some(Self::Zstd { level: Self::ZSTD_DEFAULT_LEVEL }),
+            _ => None,
+        }
+    }
+}
+
+/// 数据页与 footer 的校验算法，整个 Segment 统一使用一种
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChecksumType {
+    #[default]
+    Crc32,
+    /// 大页上更快，64 位校验值
+    XxHash64,
+}
+
+impl ChecksumType {
+    pub fn tag(self) -> u8 {
+        match self {
+            Self::Crc32    => 0,
+            Self::XxHash64 => 1,
+        }
+    }
+
+    pub fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(Self::Crc32),
+            1 => Some(Self::XxHash64),
+            _ => None,
+        }
+    }
+
+    /// 校验值落盘字节数
+    pub fn size(self) -> usize {
+        match self {
+            Self::Crc32    => 4,
+            Self::XxHash64 => 8,
+        }
+    }
+
+    pub fn compute(self, data: &[u8]) -> u64 {
+        match self {
+            Self::Crc32    => crc32fast::hash(data) as u64,
+            Self::XxHash64 => xxhash_rust::xxh64::xxh64(data, 0),
+        }
+    }
+
+    /// `data` 的落盘校验值（小端，`size()` 字节）
+    pub fn encode(self, data: &[u8]) -> Vec<u8> {
+        self.compute(data).to_le_bytes()[..self.size()].to_vec()
+    }
+
+    /// `stored` 为 `encode` 写出的校验值
+    pub fn verify(self, data: &[u8], stored: &[u8]) -> bool {
+        stored.len() == self.size()
+            && self.compute(data).to_le_bytes()[..self.size()] == *stored
+    }
 }
 
 /// 每列的元数据（写入 Footer）
 #[derive(Debug, Clone)]
 pub struct ColumnMeta {
-    pub column_id:   u32,
-    pub name:        String,
-    pub field_type:  FieldType,
-    pub is_nullable: bool,
-    pub encoding:    EncodingType,
-    pub compression: CompressionType,
-    pub max_length:  u32,
+    pub column_id:     u32,
+    pub name:          String,
+    pub field_type:    FieldType,
+    pub is_nullable:   bool,
+    pub encoding:      EncodingType,
+    pub compression:   CompressionType,
+    /// Bytes 值的最大字节数，0 表示不限（默认）；schema 为 VARCHAR 设了长度时才有上限
+    pub max_length:    u32,
+    /// 是否为 key 列（合并时按 key 列排序/去重）
+    pub is_key:        bool,
+    /// value 列在 Unique/Aggregate 合并时的聚合方式
+    pub aggregate:     AggregateType,
+    /// Segment 中不存在该列（加列前写入）时补齐的值，None 即 NULL
+    pub default_value: Option<Value>,
+    /// 已逻辑删除：写入时以 NULL 占位，读取时不解码
+    pub is_dropped:    bool,
 }
 
 impl ColumnMeta {
     pub fn new(column_id: u32, name: &str, field_type: FieldType) -> Self {
-        // 根据字段类型自动选择编码：有序整数用 Delta；字符串用字典；其余用 Plain
+        // 根据字段类型自动选择编码：有序整数用 Delta；字符串用字典；布尔按位打包；其余用 Plain
         let encoding = if field_type.is_integer() {
             EncodingType::DeltaBinary
         } else if field_type == FieldType::Bytes {
             EncodingType::Dictionary
+        } else if field_type == FieldType::Bool {
+            EncodingType::BoolBitPacked
         } else {
             EncodingType::Plain
         };
         Self {
             column_id, name: name.into(), field_type,
             is_nullable: false, encoding,
-            compression: CompressionType::Lz4, max_length: 65535,
+            compression: CompressionType::Lz4, max_length: 0,
+            is_key: false, aggregate: AggregateType::None,
+            default_value: None, is_dropped: false,
         }
     }
 
@@ -100,10 +261,25 @@ impl ColumnMeta {
     pub fn nullable(mut self) -> Self {
         self.is_nullable = true; self
     }
+    pub fn key(mut self) -> Self {
+        self.is_key = true; self
+    }
+    pub fn with_aggregate(mut self, agg: AggregateType) -> Self {
+        self.aggregate = agg; self
+    }
+    pub fn with_default(mut self, v: Value) -> Self {
+        self.default_value = Some(v); self
+    }
+    pub fn with_max_length(mut self, len: u32) -> Self {
+        self.max_length = len; self
+    }
 }
 
 /// 列值（运行时表示）
-#[derive(Debug, Clone, PartialEq)]
+///
+/// 相等与排序均由 `Ord` 定义：浮点数按 IEEE-754 total order 比较（NaN 与自身相等），
+/// 故 `Value` 可作为排序与去重的键。
+#[derive(Debug, Clone)]
 pub enum Value {
     Null,
     Int8(i8),
@@ -113,15 +289,22 @@ pub enum Value {
     Float32(f32),
     Float64(f64),
     Bytes(Vec<u8>),
+    /// 未缩放的定点小数，scale 由列类型决定
+    Decimal(i128),
+    Bool(bool),
+    /// 距 Unix 纪元的微秒数
+    DateTime(i64),
 }
 
 impl Value {
     pub fn as_i64(&self) -> Option<i64> {
         match self {
-            Self::Int8(v)  => Some(*v as i64),
-            Self::Int16(v) => Some(*v as i64),
-            Self::Int32(v) => Some(*v as i64),
-            Self::Int64(v) => Some(*v),
+            Self::Int8(v)     => Some(*v as i64),
+            Self::Int16(v)    => Some(*v as i64),
+            Self::Int32(v)    => Some(*v as i64),
+            Self::Int64(v)    => Some(*v),
+            Self::Bool(v)     => Some(*v as i64),
+            Self::DateTime(v) => Some(*v),
             _ => None,
         }
     }
@@ -129,18 +312,124 @@ impl Value {
         match self { Self::Bytes(b) => Some(b), _ => None }
     }
     /// 将值序列化为用于索引比较的字节串
+    ///
+    /// 同类型值的字节序与数值序一致：有符号整数翻转符号位后按大端写出；
+    /// 浮点数做 IEEE-754 total-order 变换（负数全部取反，非负数翻转符号位）。
     pub fn to_sort_key(&self) -> Vec<u8> {
         match self {
             Self::Null       => vec![],
-            Self::Int64(v)   => v.to_be_bytes().to_vec(),
-            Self::Int32(v)   => v.to_be_bytes().to_vec(),
-            Self::Float64(v) => v.to_bits().to_be_bytes().to_vec(),
+            Self::Int8(v)    => vec![(*v as u8) ^ 0x80],
+            Self::Int16(v)   => ((*v as u16) ^ (1 << 15)).to_be_bytes().to_vec(),
+            Self::Int32(v)   => ((*v as u32) ^ (1 << 31)).to_be_bytes().to_vec(),
+            Self::Int64(v) | Self::DateTime(v) => ((*v as u64) ^ (1 << 63)).to_be_bytes().to_vec(),
+            Self::Float32(v) => {
+                let bits = v.to_bits();
+                let key  = if bits >> 31 == 1 { !bits } else { bits | (1 << 31) };
+                key.to_be_bytes().to_vec()
+            }
+            Self::Float64(v) => {
+                let bits = v.to_bits();
+                let key  = if bits >> 63 == 1 { !bits } else { bits | (1 << 63) };
+                key.to_be_bytes().to_vec()
+            }
             Self::Bytes(b)   => b.clone(),
-            _                => format!("{:?}", self).into_bytes(),
+            Self::Decimal(v) => ((*v as u128) ^ (1 << 127)).to_be_bytes().to_vec(),
+            Self::Bool(v)    => vec![*v as u8],
+        }
+    }
+
+    /// `to_sort_key` 的逆变换；长度与 `field_type` 不符时返回 None
+    pub fn from_sort_key(key: &[u8], field_type: FieldType) -> Option<Self> {
+        let v = match field_type {
+            FieldType::Int8       => Self::Int8((<[u8; 1]>::try_from(key).ok()?[0] ^ 0x80) as i8),
+            FieldType::Int16      => Self::Int16((u16::from_be_bytes(key.try_into().ok()?) ^ (1 << 15)) as i16),
+            FieldType::Int32 | FieldType::Date
+                                  => Self::Int32((u32::from_be_bytes(key.try_into().ok()?) ^ (1 << 31)) as i32),
+            FieldType::Int64 | FieldType::DateTime => {
+                let x = (u64::from_be_bytes(key.try_into().ok()?) ^ (1 << 63)) as i64;
+                if field_type == FieldType::DateTime { Self::DateTime(x) } else { Self::Int64(x) }
+            }
+            FieldType::Float32 => {
+                let k = u32::from_be_bytes(key.try_into().ok()?);
+                Self::Float32(f32::from_bits(if k >> 31 == 1 { k & !(1 << 31) } else { !k }))
+            }
+            FieldType::Float64 => {
+                let k = u64::from_be_bytes(key.try_into().ok()?);
+                Self::Float64(f64::from_bits(if k >> 63 == 1 { k & !(1 << 63) } else { !k }))
+            }
+            FieldType::Bytes      => Self::Bytes(key.to_vec()),
+            FieldType::Decimal128 => Self::Decimal((u128::from_be_bytes(key.try_into().ok()?) ^ (1 << 127)) as i128),
+            FieldType::Bool       => Self::Bool(<[u8; 1]>::try_from(key).ok()?[0] != 0),
+        };
+        Some(v)
+    }
+}
+
+// ── 排序 ──────────────────────────────────────────────────────────────────────
+//
+// 先按类型族排序：NULL < Bool < 整数 < 浮点 < Decimal < DateTime < Bytes；
+// 同族内按数值比较（不同宽度的整数提升为 i64，Float32 提升为 f64），
+// 数值相等时再按变体区分，保证与 `Eq` 一致。
+
+impl Value {
+    /// (类型族, 族内变体序号)
+    fn type_rank(&self) -> (u8, u8) {
+        match self {
+            Self::Null        => (0, 0),
+            Self::Bool(_)     => (1, 0),
+            Self::Int8(_)     => (2, 0),
+            Self::Int16(_)    => (2, 1),
+            Self::Int32(_)    => (2, 2),
+            Self::Int64(_)    => (2, 3),
+            Self::Float32(_)  => (3, 0),
+            Self::Float64(_)  => (3, 1),
+            Self::Decimal(_)  => (4, 0),
+            Self::DateTime(_) => (5, 0),
+            Self::Bytes(_)    => (6, 0),
         }
     }
 }
 
+impl Ord for Value {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        let (family, variant) = self.type_rank();
+        let (other_family, other_variant) = other.type_rank();
+        let by_value = match (self, other) {
+            _ if family != other_family => family.cmp(&other_family),
+            (Self::Bool(a), Self::Bool(b))         => a.cmp(b),
+            (Self::Decimal(a), Self::Decimal(b))   => a.cmp(b),
+            (Self::DateTime(a), Self::DateTime(b)) => a.cmp(b),
+            (Self::Bytes(a), Self::Bytes(b))       => a.cmp(b),
+            (Self::Float32(a), Self::Float32(b))   => a.total_cmp(b),
+            (Self::Float32(_) | Self::Float64(_), Self::Float32(_) | Self::Float64(_)) => {
+                let as_f64 = |v: &Self| match v {
+                    Self::Float32(x) => *x as f64,
+                    Self::Float64(x) => *x,
+                    _ => unreachable!(),
+                };
+                as_f64(self).total_cmp(&as_f64(other))
+            }
+            // 剩余同族情形只有 NULL 与整数
+            _ => self.as_i64().cmp(&other.as_i64()),
+        };
+        by_value.then(variant.cmp(&other_variant))
+    }
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Value {}
+
 impl std::fmt::Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -152,6 +441,109 @@ impl std::fmt::Display for Value {
             Self::Float32(v) => write!(f, "{v}"),
             Self::Float64(v) => write!(f, "{v}"),
             Self::Bytes(b)   => write!(f, "{}", String::from_utf8_lossy(b)),
+            Self::Decimal(v) => write!(f, "{v}"),
+            Self::Bool(v)    => write!(f, "{v}"),
+            Self::DateTime(v) => {
+                // ISO-8601，UTC：YYYY-MM-DDTHH:MM:SS.ffffffZ
+                let secs   = v.div_euclid(1_000_000);
+                let micros = v.rem_euclid(1_000_000);
+                let (y, m, d) = civil_from_days(secs.div_euclid(86_400));
+                let tod    = secs.rem_euclid(86_400);
+                write!(
+                    f, "{y:04}-{m:02}-{d:02}T{:02}:{:02}:{:02}.{micros:06}Z",
+                    tod / 3600, tod % 3600 / 60, tod % 60,
+                )
+            }
+        }
+    }
+}
+
+// ── 日历换算（proleptic Gregorian，Howard Hinnant 算法）─────────────────────────
+
+/// (年, 月, 日) → 距 1970-01-01 的天数
+pub(crate) fn days_from_civil(y: i64, m: u32, d: u32) -> i64 {
+    let (m, d) = (m as i64, d as i64);
+    let y   = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// 距 1970-01-01 的天数 → (年, 月, 日)
+pub(crate) fn civil_from_days(z: i64) -> (i64, u32, u32) {
+    let z   = z + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp  = (5 * doy + 2) / 153;
+    let d   = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m   = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    (yoe + era * 400 + (m <= 2) as i64, m, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ── 排序键 ────────────────────────────────────────────────────────────────
+
+    #[test]
+    fn decimal_sort_key_preserves_signed_order() {
+        let values = [i128::MIN, -10_000, -1, 0, 1, 12_345, i128::MAX];
+        let keys: Vec<Vec<u8>> = values.iter().map(|&v| Value::Decimal(v).to_sort_key()).collect();
+        assert!(keys.windows(2).all(|w| w[0] < w[1]));
+        for (&v, key) in values.iter().zip(&keys) {
+            assert_eq!(key.len(), 16);
+            assert_eq!(Value::from_sort_key(key, FieldType::Decimal128), Some(Value::Decimal(v)));
+        }
+        assert_eq!(FieldType::from(ColumnType::Decimal { precision: 18, scale: 2 }), FieldType::Decimal128);
+    }
+
+    // ── DateTime ──────────────────────────────────────────────────────────────
+
+    #[test]
+    fn datetime_displays_as_iso_8601_utc() {
+        assert_eq!(Value::DateTime(0).to_string(), "1970-01-01T00:00:00.000000Z");
+        assert_eq!(Value::DateTime(1_700_000_000_123_456).to_string(), "2023-11-14T22:13:20.123456Z");
+        assert_eq!(Value::DateTime(-1).to_string(), "1969-12-31T23:59:59.999999Z");
+        assert_eq!(ColumnMeta::new(0, "ts", FieldType::DateTime).encoding, EncodingType::DeltaBinary);
+    }
+
+    // ── 全序 ──────────────────────────────────────────────────────────────────
+
+    #[test]
+    fn value_order_is_numeric_for_negatives_and_mixed_widths() {
+        let mut values = vec![Value::Int64(5), Value::Int32(-300), Value::Int8(-1), Value::Int16(0), Value::Int64(-70_000)];
+        values.sort();
+        assert_eq!(values, vec![Value::Int64(-70_000), Value::Int32(-300), Value::Int8(-1), Value::Int16(0), Value::Int64(5)]);
+
+        // 数值相等、宽度不同：仍可区分，且与 Eq 一致
+        assert!(Value::Int8(1) < Value::Int64(1));
+        assert_ne!(Value::Int8(1), Value::Int64(1));
+        assert!(Value::Float32(-1.5) < Value::Float64(-1.25));
+        assert!(Value::Bytes(b"ab".to_vec()) < Value::Bytes(b"b".to_vec()));
+    }
+
+    #[test]
+    fn value_order_places_null_first_and_nan_last() {
+        let nan = Value::Float64(f64::NAN);
+        let mut values = [
+            nan.clone(), Value::Float64(f64::INFINITY), Value::Null, Value::Float64(-0.0),
+            Value::Float64(0.0), Value::Float64(f64::NEG_INFINITY),
+        ];
+        values.sort();
+        assert_eq!(values[0], Value::Null);
+        assert_eq!(values[1], Value::Float64(f64::NEG_INFINITY));
+        assert!(values[2] < values[3], "-0.0 sorts before 0.0");
+        assert_eq!(values[4], Value::Float64(f64::INFINITY));
+        assert_eq!(values[5], nan);
+        assert_eq!(nan.cmp(&nan), std::cmp::Ordering::Equal);
+
+        for v in [Value::Bool(false), Value::Int64(i64::MIN), Value::Float64(f64::NEG_INFINITY), Value::Bytes(vec![])] {
+            assert!(Value::Null < v, "{v:?}");
         }
     }
 }
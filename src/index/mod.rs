@@ -4,39 +4,74 @@
 //! - **OrdinalIndex**  — 稀疏行号→页偏移，O(log n) 按 row_id 定位页
 //! - **ZoneMapIndex**  — 每页 min/max，range 查询时跳过无关页
 //! - **ShortKeyIndex** — 每 1024 行记录一次 key 前缀，有序扫描加速
-//! - **BloomFilter**   — 双哈希位数组，等值查询快速过滤，FPP ≈ 5%
+//! - **BloomFilter**   — 双哈希位数组，等值查询快速过滤，FPP 可配置（默认 5%）；
+//!   可选按页构建（`PageBloomIndex`），等值探测时逐页跳过
+//!
+//! 另有 **GlobalDictionary**：Dictionary 编码的字符串列在整个 Segment 内共享一份字典，
+//! 各页只存字典码；可选的 **BitmapIndex** 为低基数列的每个值记录行号位图。
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use crate::common::{OlapError, Result};
 
 // ── Ordinal Index ─────────────────────────────────────────────────────────────
 
 #[derive(Debug, Default, Clone)]
 pub struct OrdinalIndex {
-    /// (first_row_id, page_file_offset) 有序列表
-    entries: Vec<(u32, u64)>,
+    /// (first_row_id, page_file_offset, page_size) 有序列表
+    ///
+    /// 各列的页在数据区内按写满顺序交错存放，故每页自带字节长度。
+    entries: Vec<(u32, u64, u64)>,
 }
 
 impl OrdinalIndex {
-    pub fn add(&mut self, first_row_id: u32, page_offset: u64) {
-        self.entries.push((first_row_id, page_offset));
+    pub fn add(&mut self, first_row_id: u32, page_offset: u64, page_size: u64) {
+        self.entries.push((first_row_id, page_offset, page_size));
     }
 
     /// 找包含 row_id 的页面偏移（二分查找）
     pub fn find_page_offset(&self, row_id: u32) -> Option<u64> {
-        if self.entries.is_empty() { return None; }
-        let pos = self.entries.partition_point(|(rid, _)| *rid <= row_id);
-        let idx = if pos == 0 { 0 } else { pos - 1 };
-        self.entries.get(idx).map(|(_, off)| *off)
+        self.find_page(row_id).map(|(_, off, _)| off)
+    }
+
+    /// 包含 row_id 的页 (first_row_id, offset, size)；末页视为延伸到 Segment 末尾
+    pub fn find_page(&self, row_id: u32) -> Option<(u32, u64, u64)> {
+        self.find_page_index(row_id).map(|i| self.entries[i])
+    }
+
+    /// 包含 row_id 的页在 `entries()` 中的下标；索引为空时为 None
+    ///
+    /// 按各页记录的 first_row_id 二分，不假定每页行数（按字节数提前刷出的页行数较少）；
+    /// 页的行区间为 [entries[i].0, entries[i + 1].0)，字节范围即条目中的 (offset, size)。
+    pub fn find_page_index(&self, row_id: u32) -> Option<usize> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        Some(self.entries.partition_point(|(rid, _, _)| *rid <= row_id).saturating_sub(1))
+    }
+
+    /// 与行区间 [lo, hi) 相交的页下标闭区间 (first, last)
+    ///
+    /// 末页视为延伸到 Segment 末尾，超出末行的区间落在末页；`hi <= lo` 时返回
+    /// 包含 `lo` 的单页。索引为空时返回 (0, 0)，调用方应先检查 `page_count`。
+    pub fn page_range_for_rows(&self, lo: u32, hi: u32) -> (usize, usize) {
+        let page_of = |rid: u32| self.find_page_index(rid).unwrap_or(0);
+        let first = page_of(lo);
+        let last  = if hi > lo { page_of(hi - 1) } else { first };
+        (first, last)
     }
 
     pub fn page_count(&self) -> usize { self.entries.len() }
 
+    /// (first_row_id, page_file_offset, page_size) 有序列表
+    pub fn entries(&self) -> &[(u32, u64, u64)] { &self.entries }
+
     pub fn serialize(&self) -> Vec<u8> {
         let mut out = Vec::new();
         out.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
-        for (rid, off) in &self.entries {
+        for (rid, off, size) in &self.entries {
             out.extend_from_slice(&rid.to_le_bytes());
             out.extend_from_slice(&off.to_le_bytes());
+            out.extend_from_slice(&size.to_le_bytes());
         }
         out
     }
@@ -46,11 +81,12 @@ impl OrdinalIndex {
         let n = u32::from_le_bytes(data[0..4].try_into().unwrap()) as usize;
         let mut entries = Vec::with_capacity(n);
         for i in 0..n {
-            let b = 4 + i * 12;
-            if b + 12 > data.len() { break; }
-            let rid = u32::from_le_bytes(data[b..b+4].try_into().unwrap());
-            let off = u64::from_le_bytes(data[b+4..b+12].try_into().unwrap());
-            entries.push((rid, off));
+            let b = 4 + i * 20;
+            if b + 20 > data.len() { break; }
+            let rid  = u32::from_le_bytes(data[b..b+4].try_into().unwrap());
+            let off  = u64::from_le_bytes(data[b+4..b+12].try_into().unwrap());
+            let size = u64::from_le_bytes(data[b+12..b+20].try_into().unwrap());
+            entries.push((rid, off, size));
         }
         Self { entries }
     }
@@ -63,9 +99,16 @@ pub struct ZoneMapEntry {
     pub page_idx: u32,
     pub min:      Vec<u8>,
     pub max:      Vec<u8>,
+    /// 页内至少有一个 NULL
     pub has_null: bool,
+    /// 页内全是 NULL；此时 min/max 为空，不参与范围比较
+    pub all_null: bool,
 }
 
+// 序列化时 has_null / all_null 合入一个标志字节；旧文件只写过 0/1，all_null 读出恒为 false
+const ZONE_HAS_NULL: u8 = 0x01;
+const ZONE_ALL_NULL: u8 = 0x02;
+
 #[derive(Debug, Default, Clone)]
 pub struct ZoneMapIndex {
     entries: Vec<ZoneMapEntry>,
@@ -78,26 +121,40 @@ impl ZoneMapIndex {
         min:      Vec<u8>,
         max:      Vec<u8>,
         has_null: bool,
+        all_null: bool,
     ) {
-        self.entries.push(ZoneMapEntry { page_idx, min, max, has_null });
+        self.entries.push(ZoneMapEntry { page_idx, min, max, has_null, all_null });
     }
 
-    /// 返回与 [probe_min, probe_max] 有重叠的页面索引列表
+    pub fn entries(&self) -> &[ZoneMapEntry] { &self.entries }
+
+    /// 返回与 [probe_min, probe_max] 有重叠的页面索引列表；全 NULL 页不可能命中非 NULL 探测值，直接跳过
     pub fn filter(&self, probe_min: &[u8], probe_max: &[u8]) -> Vec<u32> {
         self.entries.iter()
             .filter(|e| {
-                e.min.as_slice() <= probe_max && e.max.as_slice() >= probe_min
+                !e.all_null && e.min.as_slice() <= probe_max && e.max.as_slice() >= probe_min
             })
             .map(|e| e.page_idx)
             .collect()
     }
 
+    /// 含 NULL 的页面索引列表（IS NULL 查询）
+    pub fn null_pages(&self) -> Vec<u32> {
+        self.entries.iter()
+            .filter(|e| e.has_null)
+            .map(|e| e.page_idx)
+            .collect()
+    }
+
     pub fn serialize(&self) -> Vec<u8> {
         let mut out = Vec::new();
         out.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
         for e in &self.entries {
             out.extend_from_slice(&e.page_idx.to_le_bytes());
-            out.push(e.has_null as u8);
+            let mut flags = 0u8;
+            if e.has_null { flags |= ZONE_HAS_NULL; }
+            if e.all_null { flags |= ZONE_ALL_NULL; }
+            out.push(flags);
             out.extend_from_slice(&(e.min.len() as u32).to_le_bytes());
             out.extend_from_slice(&e.min);
             out.extend_from_slice(&(e.max.len() as u32).to_le_bytes());
@@ -105,6 +162,32 @@ impl ZoneMapIndex {
         }
         out
     }
+
+    pub fn deserialize(data: &[u8]) -> Self {
+        fn read_bytes(data: &[u8], pos: &mut usize) -> Option<Vec<u8>> {
+            let len = u32::from_le_bytes(data.get(*pos..*pos+4)?.try_into().unwrap()) as usize;
+            let b   = data.get(*pos+4..*pos+4+len)?.to_vec();
+            *pos += 4 + len;
+            Some(b)
+        }
+
+        if data.len() < 4 { return Self::default(); }
+        let n = u32::from_le_bytes(data[0..4].try_into().unwrap()) as usize;
+        let mut entries = Vec::with_capacity(n);
+        let mut pos = 4usize;
+        for _ in 0..n {
+            if pos + 5 > data.len() { break; }
+            let page_idx = u32::from_le_bytes(data[pos..pos+4].try_into().unwrap());
+            let flags    = data[pos+4];
+            let has_null = flags & ZONE_HAS_NULL != 0;
+            let all_null = flags & ZONE_ALL_NULL != 0;
+            pos += 5;
+            let Some(min) = read_bytes(data, &mut pos) else { break };
+            let Some(max) = read_bytes(data, &mut pos) else { break };
+            entries.push(ZoneMapEntry { page_idx, min, max, has_null, all_null });
+        }
+        Self { entries }
+    }
 }
 
 // ── Short Key Index ───────────────────────────────────────────────────────────
@@ -120,18 +203,40 @@ pub struct ShortKeyIndex {
 impl ShortKeyIndex {
     /// 每隔 SHORT_KEY_INTERVAL 行插入一条记录
     pub fn maybe_add(&mut self, row_id: u32, key_prefix: Vec<u8>) {
-        if row_id % SHORT_KEY_INTERVAL == 0 {
-            self.entries.push((row_id, key_prefix));
+        if row_id.is_multiple_of(SHORT_KEY_INTERVAL) {
+            self.add(row_id, key_prefix);
         }
     }
 
-    /// 返回 key_prefix >= probe 的最小 row_id（下界查找）
-    pub fn lower_bound(&self, probe: &[u8]) -> u32 {
-        self.entries.iter()
-            .take_while(|(_, pfx)| pfx.as_slice() < probe)
-            .last()
-            .map(|(rid, _)| *rid)
-            .unwrap_or(0)
+    /// 无条件插入一条记录，row_id 须递增
+    pub fn add(&mut self, row_id: u32, key_prefix: Vec<u8>) {
+        self.entries.push((row_id, key_prefix));
+    }
+
+    /// 下界查找：返回首个 key_prefix >= probe 的条目的 row_id（二分）；
+    /// 没有这样的条目时返回 None
+    ///
+    /// 只比较采样行：该条目之前（上一个采样点之后）仍可能有 >= probe 的行，
+    /// 扫描的起点用 `seek_start`。
+    pub fn lower_bound(&self, probe: &[u8]) -> Option<u32> {
+        let lt = self.entries.partition_point(|(_, pfx)| pfx.as_slice() < probe);
+        self.entries.get(lt).map(|(rid, _)| *rid)
+    }
+
+    /// 上界查找：返回首个 key_prefix > probe 的条目的 row_id（二分），
+    /// 其后的行都大于 probe；没有这样的条目时返回 None，即上界为 Segment 末尾
+    pub fn upper_bound(&self, probe: &[u8]) -> Option<u32> {
+        let le = self.entries.partition_point(|(_, pfx)| pfx.as_slice() <= probe);
+        self.entries.get(le).map(|(rid, _)| *rid)
+    }
+
+    /// 扫描起点：`lower_bound` 所在条目的前一个条目的 row_id，首个 >= probe 的行不会早于它；
+    /// probe 不大于首条目时返回 0
+    ///
+    /// 不取与 probe 相等的条目：重复的 key 可能跨越采样点，相等的行可能在该条目之前。
+    pub fn seek_start(&self, probe: &[u8]) -> u32 {
+        let lt = self.entries.partition_point(|(_, pfx)| pfx.as_slice() < probe);
+        lt.checked_sub(1).map_or(0, |i| self.entries[i].0)
     }
 
     pub fn serialize(&self) -> Vec<u8> {
@@ -144,51 +249,106 @@ impl ShortKeyIndex {
         }
         out
     }
+
+    pub fn deserialize(data: &[u8]) -> Self {
+        if data.len() < 4 { return Self::default(); }
+        let n = u32::from_le_bytes(data[0..4].try_into().unwrap()) as usize;
+        let mut entries = Vec::with_capacity(n);
+        let mut pos = 4usize;
+        for _ in 0..n {
+            if pos + 8 > data.len() { break; }
+            let rid  = u32::from_le_bytes(data[pos..pos+4].try_into().unwrap());
+            let plen = u32::from_le_bytes(data[pos+4..pos+8].try_into().unwrap()) as usize;
+            pos += 8;
+            if pos + plen > data.len() { break; }
+            entries.push((rid, data[pos..pos+plen].to_vec()));
+            pos += plen;
+        }
+        Self { entries }
+    }
 }
 
 // ── Bloom Filter ──────────────────────────────────────────────────────────────
 
-/// 双哈希 Bloom Filter（FNV-1a），FPP ≈ 5%，7 个哈希函数
+/// 双哈希 Bloom Filter（Kirsch-Mitzenmacher），位数与哈希次数由目标 FPP 推导
 #[derive(Debug, Clone)]
 pub struct BloomFilter {
-    bits:     Vec<u8>,
-    num_bits: usize,
+    bits:       Vec<u8>,
+    num_bits:   usize,
+    num_hashes: u32,
 }
 
 impl BloomFilter {
-    /// 根据期望基数创建（num_bits ≈ ndv × 9.6）
+    /// 默认 FPP
+    pub const DEFAULT_FPP: f64 = 0.05;
+
+    /// 根据期望基数创建，FPP = 5%
     pub fn new(expected_ndv: usize) -> Self {
-        let num_bits  = (expected_ndv * 10).max(64);
-        let num_bytes = (num_bits + 7) / 8;
-        Self { bits: vec![0u8; num_bytes], num_bits }
+        Self::with_fpp(expected_ndv, Self::DEFAULT_FPP)
     }
 
-    fn probe_bits(value: &[u8]) -> impl Iterator<Item = usize> + '_ {
+    /// 根据期望基数与目标误判率创建
+    ///
+    /// - num_bits   = -n·ln(p) / (ln2)²
+    /// - num_hashes = num_bits / n · ln2
+    pub fn with_fpp(expected_ndv: usize, fpp: f64) -> Self {
+        let n   = expected_ndv.max(1) as f64;
+        let p   = fpp.clamp(1e-9, 0.5);
+        let ln2 = std::f64::consts::LN_2;
+        let num_bits   = ((-n * p.ln() / (ln2 * ln2)).ceil() as usize).max(64);
+        let num_hashes = ((num_bits as f64 / n * ln2).round() as u32).clamp(1, 30);
+        Self { bits: vec![0u8; num_bits.div_ceil(8)], num_bits, num_hashes }
+    }
+
+    pub fn num_hashes(&self) -> u32 { self.num_hashes }
+
+    /// g_i(x) = h1(x) + i·h2(x)  (mod num_bits)
+    ///
+    /// h1 为 FNV-1a 64-bit，h2 为对 h1 做 murmur3 fmix64 后置奇，
+    /// 保证步长与 num_bits 无公因子 2，探测位不会退化到同一位置。
+    fn probe_bits(value: &[u8], num_bits: usize, num_hashes: u32) -> impl Iterator<Item = usize> {
         let mut h1: u64 = 0xcbf29ce484222325;
-        let mut h2: u64 = 0x517cc1b727220a95;
         for &b in value {
             h1 ^= b as u64;
             h1 = h1.wrapping_mul(0x100000001b3);
-            h2 ^= b as u64;
-            h2 = h2.wrapping_mul(0x00000100000001b3);
         }
-        let nb = h1.wrapping_add((h2 >> 32) ^ h2); // mix
-        (0u64..7).map(move |i| {
-            (nb.wrapping_add(i.wrapping_mul(h1)) % h1.max(1)) as usize
-        })
+        let mut h2 = h1;
+        h2 ^= h2 >> 33;
+        h2 = h2.wrapping_mul(0xff51afd7ed558ccd);
+        h2 ^= h2 >> 33;
+        h2 = h2.wrapping_mul(0xc4ceb9fe1a85ec53);
+        h2 ^= h2 >> 33;
+        h2 |= 1;
+
+        let m = num_bits.max(1) as u64;
+        (0..num_hashes as u64).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % m) as usize)
     }
 
     pub fn add(&mut self, value: &[u8]) {
-        let nb = self.num_bits;
-        for bit in Self::probe_bits(value) {
-            let bit = bit % nb;
+        for bit in Self::probe_bits(value, self.num_bits, self.num_hashes) {
             self.bits[bit / 8] |= 1 << (bit % 8);
         }
     }
 
+    /// 按位或合并另一个过滤器，结果对两者的成员都返回“可能包含”
+    ///
+    /// 两者的 num_bits 与哈希次数必须相同（即以相同参数创建）。
+    pub fn merge(&mut self, other: &BloomFilter) -> Result<()> {
+        if self.num_bits != other.num_bits || self.num_hashes != other.num_hashes {
+            return Err(OlapError::Unsupported(format!(
+                "merge bloom filters of different shape: {}x{} vs {}x{}",
+                self.num_bits, self.num_hashes, other.num_bits, other.num_hashes,
+            )));
+        }
+        for (a, b) in self.bits.iter_mut().zip(&other.bits) {
+            *a |= b;
+        }
+        Ok(())
+    }
+
     pub fn may_contain(&self, value: &[u8]) -> bool {
-        for bit in Self::probe_bits(value) {
-            let bit = bit % self.num_bits;
+        if self.num_bits == 0 { return true; }
+        for bit in Self::probe_bits(value, self.num_bits, self.num_hashes) {
             if self.bits[bit / 8] & (1 << (bit % 8)) == 0 {
                 return false;
             }
@@ -199,13 +359,552 @@ impl BloomFilter {
     pub fn serialize(&self) -> Vec<u8> {
         let mut out = Vec::new();
         out.extend_from_slice(&(self.num_bits as u32).to_le_bytes());
+        out.extend_from_slice(&self.num_hashes.to_le_bytes());
         out.extend_from_slice(&self.bits);
         out
     }
 
     pub fn deserialize(data: &[u8]) -> Self {
-        if data.len() < 4 { return Self { bits: vec![], num_bits: 0 }; }
-        let num_bits = u32::from_le_bytes(data[0..4].try_into().unwrap()) as usize;
-        Self { bits: data[4..].to_vec(), num_bits }
+        if data.len() < 8 { return Self { bits: vec![], num_bits: 0, num_hashes: 0 }; }
+        let num_bits   = u32::from_le_bytes(data[0..4].try_into().unwrap()) as usize;
+        let num_hashes = u32::from_le_bytes(data[4..8].try_into().unwrap());
+        // 位数组长度与 num_bits 不符时视为损坏，退化为“可能包含”
+        let Some(bits) = data.get(8..8 + num_bits.div_ceil(8)) else {
+            return Self { bits: vec![], num_bits: 0, num_hashes: 0 };
+        };
+        // 哈希次数超出 `with_fpp` 的取值范围（1..=30）同样视为损坏；保留位数组以免打乱其后的内容
+        if !(1..=30).contains(&num_hashes) {
+            return Self { bits: bits.to_vec(), num_bits: 0, num_hashes: 0 };
+        }
+        Self { bits: bits.to_vec(), num_bits, num_hashes }
+    }
+
+    /// `serialize` 输出的字节数；区域中其后的内容属于 `PageBloomIndex`
+    pub fn serialized_len(&self) -> usize { 8 + self.bits.len() }
+}
+
+// ── Page Bloom Index ──────────────────────────────────────────────────────────
+
+/// BloomFilter 的构建粒度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BloomGranularity {
+    /// 每列一个，只能整段跳过
+    #[default]
+    Segment,
+    /// 另为每个数据页建一个，等值探测可跳过单页
+    Page,
+}
+
+/// 与 ZoneMapIndex 平行的逐页 BloomFilter，第 i 项对应第 i 页
+///
+/// 序列化后紧跟在列级 BloomFilter 之后，旧 Segment 中该部分为空。
+#[derive(Debug, Default, Clone)]
+pub struct PageBloomIndex {
+    filters: Vec<BloomFilter>,
+}
+
+impl PageBloomIndex {
+    pub fn add_page(&mut self, bf: BloomFilter) {
+        self.filters.push(bf);
+    }
+
+    pub fn is_empty(&self) -> bool { self.filters.is_empty() }
+    pub fn len(&self)      -> usize { self.filters.len() }
+
+    /// 第 `page_idx` 页可能包含 `value`；无该页的过滤器时返回 true
+    pub fn may_contain(&self, page_idx: usize, value: &[u8]) -> bool {
+        self.filters.get(page_idx).is_none_or(|bf| bf.may_contain(value))
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&(self.filters.len() as u32).to_le_bytes());
+        for bf in &self.filters {
+            out.extend_from_slice(&bf.serialize());
+        }
+        out
+    }
+
+    pub fn deserialize(data: &[u8]) -> Self {
+        if data.len() < 4 { return Self::default(); }
+        let n = u32::from_le_bytes(data[0..4].try_into().unwrap()) as usize;
+        let mut filters = Vec::with_capacity(n.min(4096));
+        let mut pos = 4usize;
+        for _ in 0..n {
+            let bf = BloomFilter::deserialize(&data[pos..]);
+            // 损坏时丢弃后续页，缺失的页视为“可能包含”
+            if bf.num_bits == 0 { break; }
+            pos += bf.serialized_len();
+            filters.push(bf);
+        }
+        Self { filters }
+    }
+}
+
+// ── Global Dictionary ─────────────────────────────────────────────────────────
+
+/// Segment 级字典：值按首次出现的顺序编号，同一列的所有页共用
+///
+/// 序列化格式：`count u32 | [len u32 | bytes] × count`。
+#[derive(Debug, Default, Clone)]
+pub struct GlobalDictionary {
+    entries: Vec<Vec<u8>>,
+    codes:   HashMap<Vec<u8>, u32>,
+}
+
+impl GlobalDictionary {
+    /// 返回 `value` 的字典码，首次出现时加入字典
+    pub fn code_of(&mut self, value: &[u8]) -> u32 {
+        if let Some(&code) = self.codes.get(value) {
+            return code;
+        }
+        let code = self.entries.len() as u32;
+        self.entries.push(value.to_vec());
+        self.codes.insert(value.to_vec(), code);
+        code
+    }
+
+    /// 值对应的字典码，不在字典中时返回 None；可用于字典级的谓词判定
+    pub fn lookup(&self, value: &[u8]) -> Option<u32> {
+        self.codes.get(value).copied()
+    }
+
+    pub fn get(&self, code: u32) -> Option<&[u8]> {
+        self.entries.get(code as usize).map(Vec::as_slice)
+    }
+
+    /// 按字典码顺序排列的全部值
+    pub fn entries(&self) -> &[Vec<u8>] { &self.entries }
+    pub fn len(&self)      -> usize { self.entries.len() }
+    pub fn is_empty(&self) -> bool  { self.entries.is_empty() }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+        for e in &self.entries {
+            out.extend_from_slice(&(e.len() as u32).to_le_bytes());
+            out.extend_from_slice(e);
+        }
+        out
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<Self> {
+        let truncated = || OlapError::SegmentIo("global dictionary truncated".into());
+        let n = u32::from_le_bytes(data.get(0..4).ok_or_else(truncated)?.try_into().unwrap()) as usize;
+        let mut entries = Vec::with_capacity(n.min(data.len() / 4));
+        let mut pos = 4usize;
+        for _ in 0..n {
+            let len = u32::from_le_bytes(
+                data.get(pos..pos + 4).ok_or_else(truncated)?.try_into().unwrap(),
+            ) as usize;
+            pos += 4;
+            entries.push(data.get(pos..pos + len).ok_or_else(truncated)?.to_vec());
+            pos += len;
+        }
+        let codes = entries.iter().enumerate().map(|(i, e)| (e.clone(), i as u32)).collect();
+        Ok(Self { entries, codes })
+    }
+}
+
+// ── Bitmap Index ──────────────────────────────────────────────────────────────
+
+/// 数组容器的元素数上限，超过后转为位图容器（此时两者都约 8KB）
+const ARRAY_CONTAINER_MAX: usize = 4096;
+const CONTAINER_ARRAY:  u8 = 0;
+const CONTAINER_BITMAP: u8 = 1;
+
+/// 行号低 16 位的集合
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Container {
+    /// 有序数组，稀疏时使用
+    Array(Vec<u16>),
+    /// 65536 位定长位图，稠密时使用
+    Bitmap(Box<[u64; 1024]>),
+}
+
+impl Container {
+    fn insert(&mut self, low: u16) {
+        match self {
+            Self::Array(a) => {
+                if let Err(pos) = a.binary_search(&low) {
+                    a.insert(pos, low);
+                }
+                if a.len() > ARRAY_CONTAINER_MAX {
+                    let mut bits = Box::new([0u64; 1024]);
+                    for &x in a.iter() {
+                        bits[x as usize / 64] |= 1 << (x % 64);
+                    }
+                    *self = Self::Bitmap(bits);
+                }
+            }
+            Self::Bitmap(bits) => bits[low as usize / 64] |= 1 << (low % 64),
+        }
+    }
+
+    fn for_each(&self, mut f: impl FnMut(u16)) {
+        match self {
+            Self::Array(a) => a.iter().copied().for_each(f),
+            Self::Bitmap(bits) => {
+                for (i, &word) in bits.iter().enumerate() {
+                    let mut w = word;
+                    while w != 0 {
+                        f((i * 64) as u16 + w.trailing_zeros() as u16);
+                        w &= w - 1;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Roaring 风格的行号位图：按行号高 16 位分块，每块按疏密选择数组或位图容器
+///
+/// 序列化格式：`n u32 | [high u16 | kind u8 | Array: len u32 + u16 × len / Bitmap: u64 × 1024] × n`。
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RowBitmap {
+    /// 按 high 升序
+    containers: Vec<(u16, Container)>,
+}
+
+impl RowBitmap {
+    pub fn insert(&mut self, row_id: u32) {
+        let (high, low) = ((row_id >> 16) as u16, row_id as u16);
+        let idx = match self.containers.binary_search_by_key(&high, |(h, _)| *h) {
+            Ok(idx)  => idx,
+            Err(idx) => {
+                self.containers.insert(idx, (high, Container::Array(Vec::new())));
+                idx
+            }
+        };
+        self.containers[idx].1.insert(low);
+    }
+
+    /// 升序排列的全部行号
+    pub fn to_vec(&self) -> Vec<u32> {
+        let mut out = Vec::new();
+        for (high, c) in &self.containers {
+            c.for_each(|low| out.push((*high as u32) << 16 | low as u32));
+        }
+        out
+    }
+
+    pub fn serialize_into(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&(self.containers.len() as u32).to_le_bytes());
+        for (high, c) in &self.containers {
+            out.extend_from_slice(&high.to_le_bytes());
+            match c {
+                Container::Array(a) => {
+                    out.push(CONTAINER_ARRAY);
+                    out.extend_from_slice(&(a.len() as u32).to_le_bytes());
+                    a.iter().for_each(|x| out.extend_from_slice(&x.to_le_bytes()));
+                }
+                Container::Bitmap(bits) => {
+                    out.push(CONTAINER_BITMAP);
+                    bits.iter().for_each(|w| out.extend_from_slice(&w.to_le_bytes()));
+                }
+            }
+        }
+    }
+
+    /// 从 `data[*pos..]` 读出一个位图并前移 `pos`
+    pub fn read_from(data: &[u8], pos: &mut usize) -> Result<Self> {
+        let truncated = || OlapError::SegmentIo("row bitmap truncated".into());
+        let mut take = |n: usize| -> Result<&[u8]> {
+            let s = data.get(*pos..*pos + n).ok_or_else(truncated)?;
+            *pos += n;
+            Ok(s)
+        };
+        let n = u32::from_le_bytes(take(4)?.try_into().unwrap()) as usize;
+        let mut containers = Vec::with_capacity(n.min(u16::MAX as usize + 1));
+        for _ in 0..n {
+            let high = u16::from_le_bytes(take(2)?.try_into().unwrap());
+            let c = match take(1)?[0] {
+                CONTAINER_ARRAY => {
+                    let len = u32::from_le_bytes(take(4)?.try_into().unwrap()) as usize;
+                    let raw = take(len * 2)?;
+                    Container::Array(raw.chunks_exact(2)
+                        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+                        .collect())
+                }
+                CONTAINER_BITMAP => {
+                    let raw = take(1024 * 8)?;
+                    let mut bits = Box::new([0u64; 1024]);
+                    for (w, b) in bits.iter_mut().zip(raw.chunks_exact(8)) {
+                        *w = u64::from_le_bytes(b.try_into().unwrap());
+                    }
+                    Container::Bitmap(bits)
+                }
+                k => return Err(OlapError::SegmentIo(format!("unknown bitmap container kind {k}"))),
+            };
+            containers.push((high, c));
+        }
+        Ok(Self { containers })
+    }
+}
+
+/// 位图索引：每个不同值（以 `Value::to_sort_key` 为键）对应一个行号位图，NULL 不入索引
+///
+/// 序列化格式：`n u32 | [key_len u32 | key | RowBitmap] × n`，按键升序。
+#[derive(Debug, Default, Clone)]
+pub struct BitmapIndex {
+    bitmaps: BTreeMap<Vec<u8>, RowBitmap>,
+}
+
+impl BitmapIndex {
+    pub fn add(&mut self, key: &[u8], row_id: u32) {
+        match self.bitmaps.get_mut(key) {
+            Some(bm) => bm.insert(row_id),
+            None     => self.bitmaps.entry(key.to_vec()).or_default().insert(row_id),
+        }
+    }
+
+    /// 等于 `key` 的行号（升序）；值不存在时为空
+    pub fn rows_for_value(&self, key: &[u8]) -> Vec<u32> {
+        self.bitmaps.get(key).map(RowBitmap::to_vec).unwrap_or_default()
+    }
+
+    /// 不同值个数
+    pub fn len(&self)      -> usize { self.bitmaps.len() }
+    pub fn is_empty(&self) -> bool  { self.bitmaps.is_empty() }
+
+    /// 按键升序的全部不同值
+    pub fn keys(&self) -> impl Iterator<Item = &[u8]> {
+        self.bitmaps.keys().map(Vec::as_slice)
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&(self.bitmaps.len() as u32).to_le_bytes());
+        for (key, bm) in &self.bitmaps {
+            out.extend_from_slice(&(key.len() as u32).to_le_bytes());
+            out.extend_from_slice(key);
+            bm.serialize_into(&mut out);
+        }
+        out
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<Self> {
+        let truncated = || OlapError::SegmentIo("bitmap index truncated".into());
+        let n = u32::from_le_bytes(data.get(0..4).ok_or_else(truncated)?.try_into().unwrap());
+        let mut pos = 4usize;
+        let mut bitmaps = BTreeMap::new();
+        for _ in 0..n {
+            let len = u32::from_le_bytes(
+                data.get(pos..pos + 4).ok_or_else(truncated)?.try_into().unwrap(),
+            ) as usize;
+            pos += 4;
+            let key = data.get(pos..pos + len).ok_or_else(truncated)?.to_vec();
+            pos += len;
+            bitmaps.insert(key, RowBitmap::read_from(data, &mut pos)?);
+        }
+        Ok(Self { bitmaps })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ── OrdinalIndex ──────────────────────────────────────────────────────────
+
+    /// 行数不等的 3 页：[0, 100)、[100, 150)、[150, ...)
+    fn ordinal() -> OrdinalIndex {
+        let mut idx = OrdinalIndex::default();
+        idx.add(0, 0, 400);
+        idx.add(100, 400, 200);
+        idx.add(150, 600, 400);
+        idx
+    }
+
+    #[test]
+    fn page_range_inside_a_single_page() {
+        let idx = ordinal();
+        assert_eq!(idx.page_range_for_rows(10, 90), (0, 0));
+        assert_eq!(idx.page_range_for_rows(100, 150), (1, 1));
+        assert_eq!(idx.page_range_for_rows(120, 121), (1, 1));
+    }
+
+    #[test]
+    fn page_range_spanning_all_pages() {
+        let idx = ordinal();
+        assert_eq!(idx.page_range_for_rows(0, 250), (0, 2));
+        assert_eq!(idx.page_range_for_rows(99, 151), (0, 2));
+    }
+
+    #[test]
+    fn page_range_beyond_the_last_row_falls_on_the_last_page() {
+        let idx = ordinal();
+        assert_eq!(idx.page_range_for_rows(120, 10_000), (1, 2));
+        assert_eq!(idx.page_range_for_rows(5_000, 10_000), (2, 2));
+        assert_eq!(OrdinalIndex::default().page_range_for_rows(0, 10), (0, 0));
+    }
+
+    #[test]
+    fn find_page_index_follows_each_pages_first_row_id() {
+        let idx = ordinal();
+        for (row_id, want) in [(0, 0), (99, 0), (100, 1), (149, 1), (150, 2), (1_000_000, 2)] {
+            assert_eq!(idx.find_page_index(row_id), Some(want), "row {row_id}");
+        }
+        assert_eq!(idx.find_page(120), Some((100, 400, 200)));
+        assert_eq!(idx.find_page_offset(150), Some(600));
+        assert_eq!(OrdinalIndex::default().find_page_index(0), None);
+    }
+
+    // ── ZoneMapIndex ──────────────────────────────────────────────────────────
+
+    fn zone_map() -> ZoneMapIndex {
+        let mut zm = ZoneMapIndex::default();
+        zm.add_page(0, b"a".to_vec(), b"f".to_vec(), false, false);
+        zm.add_page(1, b"g".to_vec(), b"m".to_vec(), true, false);
+        zm.add_page(2, vec![], vec![], true, true);
+        zm.add_page(3, b"n".to_vec(), b"zz".to_vec(), false, false);
+        zm
+    }
+
+    #[test]
+    fn zone_map_round_trips() {
+        let zm     = zone_map();
+        let parsed = ZoneMapIndex::deserialize(&zm.serialize());
+        assert_eq!(parsed.entries().len(), zm.entries().len());
+        for (a, b) in parsed.entries().iter().zip(zm.entries()) {
+            assert_eq!((a.page_idx, &a.min, &a.max, a.has_null, a.all_null),
+                       (b.page_idx, &b.min, &b.max, b.has_null, b.all_null));
+        }
+        assert_eq!(parsed.serialize(), zm.serialize());
+        assert!(ZoneMapIndex::deserialize(&[]).entries().is_empty());
+    }
+
+    #[test]
+    fn deserialized_zone_map_filters_like_the_original() {
+        let zm     = zone_map();
+        let parsed = ZoneMapIndex::deserialize(&zm.serialize());
+        let probes: [(&[u8], &[u8]); 6] = [
+            (b"a", b"a"), (b"f", b"g"), (b"h", b"o"), (b"0", b"1"), (b"z", b"zzz"), (b"", b"\xff"),
+        ];
+        for (lo, hi) in probes {
+            assert_eq!(parsed.filter(lo, hi), zm.filter(lo, hi), "{lo:?}..{hi:?}");
+        }
+        assert_eq!(parsed.filter(b"f", b"g"), [0, 1]);
+        assert_eq!(parsed.null_pages(), zm.null_pages());
+    }
+
+    // ── ShortKeyIndex ─────────────────────────────────────────────────────────
+
+    fn short_keys() -> ShortKeyIndex {
+        let mut index = ShortKeyIndex::default();
+        for (i, key) in ["b", "d", "f", "h"].iter().enumerate() {
+            index.add(i as u32 * SHORT_KEY_INTERVAL, key.as_bytes().to_vec());
+        }
+        ShortKeyIndex::deserialize(&index.serialize())
+    }
+
+    #[test]
+    fn short_key_lower_and_upper_bound_find_first_sample_at_or_above_probe() {
+        let index = short_keys();
+        for (probe, lower, upper) in [
+            (&b""[..], Some(0),                      Some(0)),
+            (b"a",     Some(0),                      Some(0)),
+            (b"b",     Some(0),                      Some(SHORT_KEY_INTERVAL)),
+            (b"e",     Some(2 * SHORT_KEY_INTERVAL), Some(2 * SHORT_KEY_INTERVAL)),
+            (b"h",     Some(3 * SHORT_KEY_INTERVAL), None),
+            (b"z",     None,                         None),
+        ] {
+            let name = String::from_utf8_lossy(probe);
+            assert_eq!(index.lower_bound(probe), lower, "{name}");
+            assert_eq!(index.upper_bound(probe), upper, "{name}");
+        }
+        assert_eq!(ShortKeyIndex::default().lower_bound(b"x"), None);
+        assert_eq!(ShortKeyIndex::default().upper_bound(b"x"), None);
+    }
+
+    #[test]
+    fn short_key_seek_start_before_first_and_after_last() {
+        let index = short_keys();
+        assert_eq!(index.seek_start(b"a"), 0);
+        assert_eq!(index.seek_start(b""), 0);
+        assert_eq!(index.seek_start(b"z"), 3 * SHORT_KEY_INTERVAL);
+        assert_eq!(index.seek_start(b"e"), SHORT_KEY_INTERVAL);
+        assert_eq!(ShortKeyIndex::default().seek_start(b"x"), 0);
+    }
+
+    #[test]
+    fn short_key_seek_start_on_exact_match_starts_at_previous_sample() {
+        // 等于采样 key 的行可能在采样点之前（重复 key 跨采样点），因此从前一个采样点开始
+        let index = short_keys();
+        assert_eq!(index.seek_start(b"b"), 0);
+        assert_eq!(index.seek_start(b"d"), 0);
+        assert_eq!(index.seek_start(b"f"), SHORT_KEY_INTERVAL);
+        assert_eq!(index.seek_start(b"h"), 2 * SHORT_KEY_INTERVAL);
+    }
+
+    // ── BloomFilter ───────────────────────────────────────────────────────────
+
+    fn filter_with(keys: &[&str]) -> BloomFilter {
+        let mut bf = BloomFilter::with_fpp(keys.len(), 0.01);
+        for k in keys {
+            bf.add(k.as_bytes());
+        }
+        bf
+    }
+
+    #[test]
+    fn bloom_round_trips_and_excludes_absent_keys() {
+        let keys: Vec<String> = (0..200).map(|i| format!("key{i}")).collect();
+        let refs: Vec<&str>   = keys.iter().map(String::as_str).collect();
+        let bf = BloomFilter::deserialize(&filter_with(&refs).serialize());
+        assert!(refs.iter().all(|k| bf.may_contain(k.as_bytes())));
+        let false_hits = (0..1000).filter(|i| bf.may_contain(format!("absent{i}").as_bytes())).count();
+        assert!(false_hits < 50, "{false_hits} false positives");
+    }
+
+    #[test]
+    fn bloom_measured_fpp_stays_under_six_percent() {
+        // 连续整数的排序键只在末尾几个字节不同，最容易暴露哈希退化
+        let key = |i: u64| (i ^ (1 << 63)).to_be_bytes();
+        let ndv = 10_000;
+        let mut bf = BloomFilter::new(ndv);
+        for i in 0..ndv as u64 {
+            bf.add(&key(i));
+        }
+        assert!((0..ndv as u64).all(|i| bf.may_contain(&key(i))));
+        let probes = 100_000u64;
+        let false_hits = (ndv as u64..ndv as u64 + probes).filter(|&i| bf.may_contain(&key(i))).count();
+        let fpp = false_hits as f64 / probes as f64;
+        assert!(fpp < 0.06, "measured fpp {fpp}");
+    }
+
+    #[test]
+    fn bloom_with_out_of_range_num_hashes_degrades_to_may_contain() {
+        let bf = filter_with(&["a", "b"]);
+        for bad in [0u32, 31, u32::MAX] {
+            let mut data = bf.serialize();
+            data[4..8].copy_from_slice(&bad.to_le_bytes());
+            let parsed = BloomFilter::deserialize(&data);
+            assert!(parsed.may_contain(b"definitely-not-added"), "num_hashes {bad}");
+            assert_eq!(parsed.serialized_len(), data.len());
+        }
+    }
+
+
+    #[test]
+    fn merged_bloom_contains_members_of_both_disjoint_sets() {
+        let left:  Vec<String> = (0..100).map(|i| format!("left{i}")).collect();
+        let right: Vec<String> = (0..100).map(|i| format!("right{i}")).collect();
+        let mut a = BloomFilter::with_fpp(200, 0.01);
+        let mut b = BloomFilter::with_fpp(200, 0.01);
+        left.iter().for_each(|k| a.add(k.as_bytes()));
+        right.iter().for_each(|k| b.add(k.as_bytes()));
+        assert!(right.iter().any(|k| !a.may_contain(k.as_bytes())));
+
+        a.merge(&b).unwrap();
+        assert!(left.iter().chain(&right).all(|k| a.may_contain(k.as_bytes())));
+    }
+
+    #[test]
+    fn merging_blooms_of_different_size_is_unsupported() {
+        let mut a = filter_with(&["a"]);
+        let b = BloomFilter::with_fpp(10_000, 0.01);
+        assert!(matches!(a.merge(&b), Err(OlapError::Unsupported(_))));
+        assert!(a.may_contain(b"a"));
     }
 }
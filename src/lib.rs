@@ -24,7 +24,7 @@
 //! │   ┌────────────────────────────────────────────────┐     │
 //! │   │  ColumnWriter × N                              │     │
 //! │   │   ├─ encoding  (Plain/RLE/Delta/Dict)          │     │
-//! │   │   ├─ compression (LZ4/None)                    │     │
+//! │   │   ├─ compression (LZ4/Zstd/None)               │     │
 //! │   │   ├─ OrdinalIndex  (行号→页偏移)                │     │
 //! │   │   ├─ ZoneMapIndex  (min/max 剪枝)              │     │
 //! │   │   └─ BloomFilter   (等值加速)                  │     │
@@ -40,12 +40,17 @@ pub mod partition;
 pub mod tablet;
 pub mod table;
 pub mod storage;
+pub mod wal;
 
 // ── Segment 层（来自 olap-segment-engine）────────────────────────────────────
 pub mod field_type;
 pub mod encoding;
 pub mod compression;
+pub mod encryption;
 pub mod page;
 pub mod index;
 pub mod column_writer;
 pub mod segment;
+
+#[cfg(test)]
+mod test_util;
@@ -1,11 +1,12 @@
 //! Tablet 持久化元数据
-use std::num::Wrapping;
 
 use std::collections::HashMap;
 use crate::common::{
-    AggregateType, ColumnType, OlapError, KeysType,
-    PartitionId, Result, RowsetId, SchemaHash, TabletId, Version,
+    put_bytes, AggregateType, ByteReader, ColumnType, KeysType, OlapError, Result,
+    PartitionId, RowsetId, SchemaHash, TabletId, Version,
 };
+use crate::encoding;
+use crate::field_type::{ColumnMeta, EncodingType, FieldType, Value};
 
 // ── 列定义 ────────────────────────────────────────────────────────────────────
 
@@ -19,6 +20,10 @@ pub struct ColumnSchema {
     pub aggregate_type: AggregateType,
     /// VARCHAR 最大字节长度
     pub length:         u32,
+    /// 加列前写入的 Segment 没有该列，读取时以此值补齐（None 即 NULL）
+    pub default_value:  Option<Value>,
+    /// 已逻辑删除：保留在 columns 中以维持 Segment 列序，读写都跳过其数据
+    pub is_dropped:     bool,
 }
 
 impl ColumnSchema {
@@ -28,6 +33,7 @@ impl ColumnSchema {
             column_id: id, name: name.into(),
             column_type, is_key: true, is_nullable: false,
             aggregate_type: AggregateType::None, length: 0,
+            default_value: None, is_dropped: false,
         }
     }
     /// 构建 value 列
@@ -36,6 +42,7 @@ impl ColumnSchema {
             column_id: id, name: name.into(),
             column_type, is_key: false, is_nullable: true,
             aggregate_type: agg, length: 0,
+            default_value: None, is_dropped: false,
         }
     }
     /// 构建 VARCHAR 列
@@ -45,6 +52,7 @@ impl ColumnSchema {
             column_type: ColumnType::Varchar,
             is_key, is_nullable: !is_key,
             aggregate_type: AggregateType::None, length: max_len,
+            default_value: None, is_dropped: false,
         }
     }
 }
@@ -62,8 +70,7 @@ pub struct TabletSchema {
 
 impl TabletSchema {
     pub fn new(keys_type: KeysType, columns: Vec<ColumnSchema>) -> Self {
-        // 简化 schema_hash：各列 id 的 xor
-        let hash = columns.iter().fold(0u32, |h, c| h ^ c.column_id.wrapping_mul(2654435761));
+        let hash = schema_hash(keys_type, &columns);
         Self {
             schema_version: 1, keys_type, columns,
             schema_hash: hash, num_rows_per_row_block: 1024,
@@ -71,12 +78,247 @@ impl TabletSchema {
     }
 
     pub fn key_columns(&self) -> impl Iterator<Item = &ColumnSchema> {
-        self.columns.iter().filter(|c| c.is_key)
+        self.live_columns().filter(|c| c.is_key)
     }
     pub fn value_columns(&self) -> impl Iterator<Item = &ColumnSchema> {
-        self.columns.iter().filter(|c| !c.is_key)
+        self.live_columns().filter(|c| !c.is_key)
     }
+    /// 未被删除的列
+    pub fn live_columns(&self) -> impl Iterator<Item = &ColumnSchema> {
+        self.columns.iter().filter(|c| !c.is_dropped)
+    }
+    /// 物理列数，含已删除的占位列；与 `columns`、Segment 列序按位置一一对应
     pub fn num_columns(&self) -> usize { self.columns.len() }
+    /// 未被删除的列数
+    pub fn num_live_columns(&self) -> usize { self.live_columns().count() }
+
+    /// 在末尾追加一列：递增 schema_version 并重算 schema_hash
+    ///
+    /// 旧 Segment 中缺失的该列读为 `default`。只支持追加 value 列；
+    /// 非空列的默认值不能是 NULL，非 NULL 默认值的类型须与列类型一致（否则 `SchemaMismatch`）。
+    pub fn add_column(&mut self, mut col: ColumnSchema, default: Value) -> Result<()> {
+        if col.is_key {
+            return Err(OlapError::Unsupported(format!("add key column {}", col.name)));
+        }
+        if self.columns.iter().any(|c| {
+            c.column_id == col.column_id || (!c.is_dropped && c.name == col.name)
+        }) {
+            return Err(OlapError::Unsupported(format!(
+                "column {} (id={}) already exists", col.name, col.column_id
+            )));
+        }
+        if default == Value::Null && !col.is_nullable {
+            return Err(OlapError::Unsupported(format!(
+                "NULL default for non-nullable column {}", col.name
+            )));
+        }
+        if !FieldType::from(col.column_type).accepts(&default) {
+            return Err(OlapError::SchemaMismatch(format!(
+                "default {default:?} does not match {:?} column {}", col.column_type, col.name
+            )));
+        }
+        col.default_value = (default != Value::Null).then_some(default);
+        self.columns.push(col);
+        self.schema_version += 1;
+        self.schema_hash     = schema_hash(self.keys_type, &self.columns);
+        Ok(())
+    }
+
+    /// 逻辑删除一列：递增 schema_version 并重算 schema_hash
+    ///
+    /// 列仍留在 `columns` 中占位，已有 Segment 读取时跳过其数据区；
+    /// key 列决定排序与分桶，不允许删除。
+    pub fn drop_column(&mut self, column_id: u32) -> Result<()> {
+        let col = self.columns.iter_mut()
+            .find(|c| c.column_id == column_id && !c.is_dropped)
+            .ok_or(OlapError::ColumnNotFound(column_id))?;
+        if col.is_key {
+            return Err(OlapError::Unsupported(format!("drop key column {}", col.name)));
+        }
+        col.is_dropped = true;
+        self.schema_version += 1;
+        self.schema_hash     = schema_hash(self.keys_type, &self.columns);
+        Ok(())
+    }
+
+    /// 对应的 Segment 列定义（编码/压缩取 `ColumnMeta::new` 的默认值）
+    pub fn segment_schema(&self) -> Vec<ColumnMeta> {
+        self.columns.iter()
+            .map(|c| {
+                let mut m = ColumnMeta::new(c.column_id, &c.name, c.column_type.into());
+                m.is_nullable   = c.is_nullable;
+                m.is_key        = c.is_key;
+                m.aggregate     = c.aggregate_type;
+                m.default_value = c.default_value.clone();
+                m.is_dropped    = c.is_dropped;
+                if c.is_dropped {
+                    // 新 Segment 中该列只写 NULL 占位
+                    m.is_nullable = true;
+                }
+                if c.column_type == ColumnType::Varchar && c.length > 0 {
+                    m.max_length = c.length;
+                }
+                m
+            })
+            .collect()
+    }
+}
+
+// ── Schema 序列化 ─────────────────────────────────────────────────────────────
+//
+// schema_version u32 | keys_type u8 | num_rows_per_row_block u32 | schema_hash u32
+// | n_columns u32 | n_columns × ColumnSchema
+// ColumnSchema = column_id u32 | name | column_type [u8; 3] | is_key u8 | is_nullable u8
+//              | aggregate u8 | length u32 | is_dropped u8 | default（len u32 | 单值 RLE，空为 None）
+
+impl TabletSchema {
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.serialize_into(&mut out);
+        out
+    }
+
+    pub(crate) fn serialize_into(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.schema_version.to_le_bytes());
+        out.push(self.keys_type as u8);
+        out.extend_from_slice(&self.num_rows_per_row_block.to_le_bytes());
+        out.extend_from_slice(&self.schema_hash.to_le_bytes());
+        out.extend_from_slice(&(self.columns.len() as u32).to_le_bytes());
+        for c in &self.columns {
+            out.extend_from_slice(&c.column_id.to_le_bytes());
+            put_bytes(out, c.name.as_bytes());
+            out.extend_from_slice(&column_type_tag(c.column_type));
+            out.extend_from_slice(&[c.is_key as u8, c.is_nullable as u8, c.aggregate_type as u8]);
+            out.extend_from_slice(&c.length.to_le_bytes());
+            out.push(c.is_dropped as u8);
+            match &c.default_value {
+                Some(v) => put_value(out, v),
+                None    => put_bytes(out, &[]),
+            }
+        }
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<Self> {
+        let mut r = ByteReader::new(data, "tablet schema");
+        let schema = Self::read_from(&mut r)?;
+        r.finish()?;
+        Ok(schema)
+    }
+
+    pub(crate) fn read_from(r: &mut ByteReader) -> Result<Self> {
+        let schema_version = r.u32()?;
+        let keys_tag       = r.u8()?;
+        let keys_type      = KeysType::from_tag(keys_tag)
+            .ok_or_else(|| r.err(format!("unknown keys type {keys_tag}")))?;
+        let num_rows_per_row_block = r.u32()?;
+        let schema_hash    = r.u32()?;
+        let n_columns      = r.u32()? as usize;
+        let mut columns = Vec::with_capacity(n_columns.min(4096));
+        for _ in 0..n_columns {
+            let column_id   = r.u32()?;
+            let name        = r.string()?;
+            let type_tag: [u8; 3] = r.take(3)?.try_into().unwrap();
+            let column_type = column_type_from_tag(type_tag)
+                .ok_or_else(|| r.err(format!("unknown column type {type_tag:?}")))?;
+            let is_key      = r.bool()?;
+            let is_nullable = r.bool()?;
+            let agg_tag     = r.u8()?;
+            let aggregate_type = AggregateType::from_tag(agg_tag)
+                .ok_or_else(|| r.err(format!("unknown aggregate type {agg_tag}")))?;
+            let length      = r.u32()?;
+            let is_dropped  = r.bool()?;
+            let default     = r.bytes()?;
+            let default_value = if default.is_empty() { None } else { Some(decode_value(default)?) };
+            columns.push(ColumnSchema {
+                column_id, name, column_type, is_key, is_nullable,
+                aggregate_type, length, default_value, is_dropped,
+            });
+        }
+        Ok(Self { schema_version, keys_type, columns, schema_hash, num_rows_per_row_block })
+    }
+}
+
+/// 单个值写为 `len u32 | 单值 RLE`（RLE 自带类型标签）
+fn put_value(out: &mut Vec<u8>, v: &Value) {
+    let bytes = encoding::encode(std::slice::from_ref(v), EncodingType::RunLength)
+        .expect("RLE encoding of a single value cannot fail");
+    put_bytes(out, &bytes);
+}
+
+/// `put_value` 写入的单值 RLE；RLE 不依赖列类型，`FieldType` 只是占位
+fn decode_value(bytes: &[u8]) -> Result<Value> {
+    encoding::decode(bytes, EncodingType::RunLength, FieldType::Bytes, 1)?
+        .pop()
+        .ok_or_else(|| OlapError::Encoding("empty value".into()))
+}
+
+/// 对 keys_type 与完整列定义（按顺序）做 FNV-1a 64-bit 哈希，再折叠为 32 位
+///
+/// 只依赖字段的稳定编码，跨进程/跨版本结果一致；列名带长度前缀避免拼接歧义。
+/// 已删除列只计入 column_id 与删除标记。
+fn schema_hash(keys_type: KeysType, columns: &[ColumnSchema]) -> SchemaHash {
+    const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+    const FNV_PRIME:  u64 = 0x100000001b3;
+
+    let mut h = FNV_OFFSET;
+    let mut feed = |bytes: &[u8]| {
+        for &b in bytes {
+            h ^= b as u64;
+            h = h.wrapping_mul(FNV_PRIME);
+        }
+    };
+    feed(&[keys_type as u8]);
+    feed(&(columns.len() as u32).to_le_bytes());
+    for c in columns {
+        feed(&c.column_id.to_le_bytes());
+        if c.is_dropped {
+            // 已删除列只保留占位，其定义不再影响 schema
+            feed(&[0xff]);
+            continue;
+        }
+        feed(&(c.name.len() as u32).to_le_bytes());
+        feed(c.name.as_bytes());
+        feed(&column_type_tag(c.column_type));
+        feed(&[c.is_key as u8, c.is_nullable as u8, c.aggregate_type as u8]);
+        feed(&c.length.to_le_bytes());
+    }
+    ((h >> 32) ^ h) as SchemaHash
+}
+
+/// 列类型的稳定编码：类型编号 + Decimal 的 (precision, scale)
+fn column_type_tag(ct: ColumnType) -> [u8; 3] {
+    match ct {
+        ColumnType::Int8    => [0, 0, 0],
+        ColumnType::Int16   => [1, 0, 0],
+        ColumnType::Int32   => [2, 0, 0],
+        ColumnType::Int64   => [3, 0, 0],
+        ColumnType::Float32 => [4, 0, 0],
+        ColumnType::Float64 => [5, 0, 0],
+        ColumnType::Varchar => [6, 0, 0],
+        ColumnType::Date    => [7, 0, 0],
+        ColumnType::Bool    => [9, 0, 0],
+        ColumnType::DateTime => [10, 0, 0],
+        ColumnType::Decimal { precision, scale } => [8, precision, scale],
+    }
+}
+
+/// `column_type_tag` 的逆映射
+fn column_type_from_tag(tag: [u8; 3]) -> Option<ColumnType> {
+    let ct = match tag {
+        [0, 0, 0]  => ColumnType::Int8,
+        [1, 0, 0]  => ColumnType::Int16,
+        [2, 0, 0]  => ColumnType::Int32,
+        [3, 0, 0]  => ColumnType::Int64,
+        [4, 0, 0]  => ColumnType::Float32,
+        [5, 0, 0]  => ColumnType::Float64,
+        [6, 0, 0]  => ColumnType::Varchar,
+        [7, 0, 0]  => ColumnType::Date,
+        [9, 0, 0]  => ColumnType::Bool,
+        [10, 0, 0] => ColumnType::DateTime,
+        [8, precision, scale] => ColumnType::Decimal { precision, scale },
+        _ => return None,
+    };
+    Some(ct)
 }
 
 // ── Rowset 状态 ───────────────────────────────────────────────────────────────
@@ -84,6 +326,158 @@ impl TabletSchema {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum RowsetState { Prepared, Committed, Visible, Stale }
 
+impl RowsetState {
+    pub fn tag(&self) -> u8 {
+        match self {
+            Self::Prepared  => 0,
+            Self::Committed => 1,
+            Self::Visible   => 2,
+            Self::Stale     => 3,
+        }
+    }
+
+    pub fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(Self::Prepared),
+            1 => Some(Self::Committed),
+            2 => Some(Self::Visible),
+            3 => Some(Self::Stale),
+            _ => None,
+        }
+    }
+}
+
+// ── 删除条件 ──────────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp { Eq, Ne, Lt, Le, Gt, Ge }
+
+impl CompareOp {
+    pub fn tag(self) -> u8 { self as u8 }
+
+    pub fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(Self::Eq),
+            1 => Some(Self::Ne),
+            2 => Some(Self::Lt),
+            3 => Some(Self::Le),
+            4 => Some(Self::Gt),
+            5 => Some(Self::Ge),
+            _ => None,
+        }
+    }
+}
+
+/// `column op value` 形式的单个比较
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeleteCondition {
+    pub column: String,
+    pub op:     CompareOp,
+    pub value:  Value,
+}
+
+/// `DELETE WHERE` 的条件：各 `DeleteCondition` 的合取，空条件匹配所有行
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DeletePredicate {
+    pub conditions: Vec<DeleteCondition>,
+}
+
+impl DeletePredicate {
+    pub fn new() -> Self { Self::default() }
+
+    /// 追加一个合取项
+    pub fn and(mut self, column: &str, op: CompareOp, value: Value) -> Self {
+        self.conditions.push(DeleteCondition { column: column.into(), op, value });
+        self
+    }
+
+    /// `row` 按 `schema.columns` 的顺序排列；命中时该行应被删除
+    ///
+    /// 列不存在或已删除时不命中；NULL 与任何值比较都不成立。
+    /// 整数（不同宽度亦可）、浮点与定点数之间按数值比较，见 `numeric_cmp`；出现 NaN 时不命中。
+    pub fn matches(&self, row: &[Value], schema: &TabletSchema) -> bool {
+        self.conditions.iter().all(|cond| {
+            let Some(idx) = schema.columns.iter()
+                .position(|c| !c.is_dropped && c.name == cond.column)
+            else {
+                return false;
+            };
+            let Some(v) = row.get(idx) else { return false };
+            if *v == Value::Null || cond.value == Value::Null {
+                return false;
+            }
+            let scale = match schema.columns[idx].column_type {
+                ColumnType::Decimal { scale, .. } => scale as u32,
+                _                                 => 0,
+            };
+            let ord = if is_numeric(v) && is_numeric(&cond.value) {
+                match numeric_cmp(v, &cond.value, scale) {
+                    Some(ord) => ord,
+                    None      => return false,
+                }
+            } else {
+                v.cmp(&cond.value)
+            };
+            match cond.op {
+                CompareOp::Eq => ord.is_eq(),
+                CompareOp::Ne => ord.is_ne(),
+                CompareOp::Lt => ord.is_lt(),
+                CompareOp::Le => ord.is_le(),
+                CompareOp::Gt => ord.is_gt(),
+                CompareOp::Ge => ord.is_ge(),
+            }
+        })
+    }
+
+    fn serialize_into(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&(self.conditions.len() as u32).to_le_bytes());
+        for c in &self.conditions {
+            put_bytes(out, c.column.as_bytes());
+            out.push(c.op.tag());
+            put_value(out, &c.value);
+        }
+    }
+
+    fn read_from(r: &mut ByteReader) -> Result<Self> {
+        let n = r.u32()? as usize;
+        let mut conditions = Vec::with_capacity(n.min(1024));
+        for _ in 0..n {
+            let column = r.string()?;
+            let op_tag = r.u8()?;
+            let op     = CompareOp::from_tag(op_tag)
+                .ok_or_else(|| r.err(format!("unknown compare op {op_tag}")))?;
+            let value  = decode_value(r.bytes()?)?;
+            conditions.push(DeleteCondition { column, op, value });
+        }
+        Ok(Self { conditions })
+    }
+}
+
+fn is_numeric(v: &Value) -> bool {
+    v.as_i64().is_some() || matches!(v, Value::Float32(_) | Value::Float64(_) | Value::Decimal(_))
+}
+
+/// 按共同的数值类型比较：整数放大 10^scale 后与定点数按 i128 比较（定点数按列的 `scale` 解释）；
+/// 任一侧为浮点时两侧都转为 f64。NaN 或放大溢出时返回 None
+fn numeric_cmp(a: &Value, b: &Value, scale: u32) -> Option<std::cmp::Ordering> {
+    let factor = 10i128.checked_pow(scale)?;
+    let scaled = |v: &Value| match v {
+        Value::Decimal(d) => Some(*d),
+        _                 => (v.as_i64()? as i128).checked_mul(factor),
+    };
+    let float = |v: &Value| match v {
+        Value::Float32(f) => Some(*f as f64),
+        Value::Float64(f) => Some(*f),
+        _                 => Some(scaled(v)? as f64 / factor as f64),
+    };
+    let is_float = |v: &Value| matches!(v, Value::Float32(_) | Value::Float64(_));
+    if is_float(a) || is_float(b) {
+        float(a)?.partial_cmp(&float(b)?)
+    } else {
+        Some(scaled(a)?.cmp(&scaled(b)?))
+    }
+}
+
 // ── Rowset 元数据 ─────────────────────────────────────────────────────────────
 
 #[derive(Debug, Clone)]
@@ -96,8 +490,13 @@ pub struct RowsetMeta {
     pub data_disk_size: u64,
     pub num_segments:   u32,
     pub state:          RowsetState,
-    /// 对应的 Segment 文件相对路径列表
+    /// 各 Segment 的文件名 `{tablet_id}_{rowset_id}_{seg_idx}.seg`（见 `segment_file_name`），不含目录；
+    /// 磁盘上的位置由 `StorageEngine::segment_path` 按 id 决定
     pub segment_paths:  Vec<String>,
+    /// 标记为 Stale 的时间（Unix 秒），未过期为 None
+    pub stale_at:       Option<u64>,
+    /// Some 时本 Rowset 不含数据，只记录对更早版本生效的删除条件
+    pub delete_predicate: Option<DeletePredicate>,
 }
 
 impl RowsetMeta {
@@ -111,22 +510,136 @@ impl RowsetMeta {
     ) -> Self {
         let num_segments = ((num_rows / 1_000_000) + 1) as u32;
         let segment_paths = (0..num_segments)
-            .map(|i| format!("{}_{}_{}.seg", tablet_id, rowset_id, i))
+            .map(|i| Self::segment_file_name(tablet_id, rowset_id, i))
             .collect();
         Self {
             rowset_id, tablet_id, partition_id, version,
             num_rows, data_disk_size, num_segments,
             state: RowsetState::Prepared, segment_paths,
+            stale_at: None, delete_predicate: None,
+        }
+    }
+
+    /// 删除条件 Rowset：没有 Segment，读取版本 ≤ `version.end` 的数据时按 `predicate` 过滤
+    pub fn new_delete(
+        rowset_id:    RowsetId,
+        tablet_id:    TabletId,
+        partition_id: PartitionId,
+        version:      Version,
+        predicate:    DeletePredicate,
+    ) -> Self {
+        Self {
+            rowset_id, tablet_id, partition_id, version,
+            num_rows: 0, data_disk_size: 0, num_segments: 0,
+            state: RowsetState::Prepared, segment_paths: vec![],
+            stale_at: None, delete_predicate: Some(predicate),
+        }
+    }
+
+    pub fn is_delete(&self) -> bool { self.delete_predicate.is_some() }
+
+    /// `segment_paths` 中第 `seg_idx` 个 Segment 的文件名
+    pub fn segment_file_name(tablet_id: TabletId, rowset_id: RowsetId, seg_idx: u32) -> String {
+        format!("{tablet_id}_{rowset_id}_{seg_idx}.seg")
+    }
+
+    /// 序列化格式（小端）：
+    /// ```text
+    /// rowset_id u64 | tablet_id u64 | partition_id u64 | version.start i64 | version.end i64
+    /// | num_rows u64 | data_disk_size u64 | num_segments u32 | state u8
+    /// | stale_at u64 | has_stale_at u8
+    /// | n_paths u32 | n_paths × (len u32 | UTF-8 bytes)
+    /// | has_delete u8 | [n u32 | n × (column | op u8 | value)]
+    /// ```
+    /// 早期记录没有末尾的删除条件字段，读取时视为 None。
+    /// 早期 compaction 输出的 `segment_paths` 为绝对路径，读取时规范化为文件名。
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&self.rowset_id.to_le_bytes());
+        out.extend_from_slice(&self.tablet_id.to_le_bytes());
+        out.extend_from_slice(&self.partition_id.to_le_bytes());
+        out.extend_from_slice(&self.version.start.to_le_bytes());
+        out.extend_from_slice(&self.version.end.to_le_bytes());
+        out.extend_from_slice(&self.num_rows.to_le_bytes());
+        out.extend_from_slice(&self.data_disk_size.to_le_bytes());
+        out.extend_from_slice(&self.num_segments.to_le_bytes());
+        out.push(self.state.tag());
+        out.extend_from_slice(&self.stale_at.unwrap_or(0).to_le_bytes());
+        out.push(self.stale_at.is_some() as u8);
+        out.extend_from_slice(&(self.segment_paths.len() as u32).to_le_bytes());
+        for p in &self.segment_paths {
+            out.extend_from_slice(&(p.len() as u32).to_le_bytes());
+            out.extend_from_slice(p.as_bytes());
+        }
+        out.push(self.delete_predicate.is_some() as u8);
+        if let Some(pred) = &self.delete_predicate {
+            pred.serialize_into(&mut out);
         }
+        out
+    }
+
+    /// `serialize` 的逆操作；数据截断、状态非法、路径非 UTF-8 或有多余字节时返回错误
+    pub fn deserialize(data: &[u8]) -> Result<Self> {
+        let mut r = ByteReader::new(data, "rowset meta");
+        let rowset_id      = r.u64()?;
+        let tablet_id      = r.u64()?;
+        let partition_id   = r.u64()?;
+        let start          = r.i64()?;
+        let end            = r.i64()?;
+        let num_rows       = r.u64()?;
+        let data_disk_size = r.u64()?;
+        let num_segments   = r.u32()?;
+        let state_tag      = r.u8()?;
+        let state = RowsetState::from_tag(state_tag)
+            .ok_or_else(|| r.err(format!("unknown state tag {state_tag}")))?;
+        let stale_secs = r.u64()?;
+        let stale_at   = r.bool()?.then_some(stale_secs);
+        let n_paths    = r.u32()? as usize;
+        let mut segment_paths = Vec::with_capacity(n_paths.min(1024));
+        for _ in 0..n_paths {
+            segment_paths.push(normalize_segment_path(tablet_id, r.string()?));
+        }
+        let delete_predicate = if r.remaining() > 0 && r.bool()? {
+            Some(DeletePredicate::read_from(&mut r)?)
+        } else {
+            None
+        };
+        r.finish()?;
+
+        Ok(Self {
+            rowset_id, tablet_id, partition_id,
+            version: Version::new(start, end),
+            num_rows, data_disk_size, num_segments, state, segment_paths, stale_at,
+            delete_predicate,
+        })
     }
 
     pub fn is_visible(&self) -> bool { self.state == RowsetState::Visible }
-    pub fn mark_stale(&mut self) { self.state = RowsetState::Stale; }
+    pub fn mark_stale(&mut self) {
+        self.state    = RowsetState::Stale;
+        self.stale_at = Some(unix_now_secs());
+    }
+}
+
+/// 早期 compaction 输出记录的是 `{data_dir}/{tablet_id}/{rowset_id}_{seg_idx}.seg` 绝对路径，
+/// 改写为 `RowsetMeta::segment_file_name` 的格式；不含目录的文件名原样保留
+pub(crate) fn normalize_segment_path(tablet_id: TabletId, path: String) -> String {
+    match path.rsplit_once('/') {
+        Some((_, file)) => format!("{tablet_id}_{file}"),
+        None            => path,
+    }
+}
+
+pub(crate) fn unix_now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
 }
 
 // ── Tablet 元数据 ─────────────────────────────────────────────────────────────
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TabletMeta {
     pub tablet_id:    TabletId,
     pub partition_id: PartitionId,
@@ -151,3 +664,256 @@ impl TabletMeta {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn orders(amount_type: ColumnType) -> TabletSchema {
+        TabletSchema::new(KeysType::Duplicate, vec![
+            ColumnSchema::key(0, "order_id", ColumnType::Int64),
+            ColumnSchema::value(1, "amount", amount_type, AggregateType::None),
+            ColumnSchema::varchar(2, "status", 16, false),
+        ])
+    }
+
+    // ── schema_hash ───────────────────────────────────────────────────────────
+
+    #[test]
+    fn schema_hash_differs_by_column_type() {
+        assert_eq!(orders(ColumnType::Float64).schema_hash, orders(ColumnType::Float64).schema_hash);
+        assert_ne!(orders(ColumnType::Float64).schema_hash, orders(ColumnType::Float32).schema_hash);
+        assert_ne!(
+            orders(ColumnType::Decimal { precision: 18, scale: 2 }).schema_hash,
+            orders(ColumnType::Decimal { precision: 18, scale: 4 }).schema_hash,
+        );
+    }
+
+    #[test]
+    fn schema_hash_covers_keys_type_and_column_order() {
+        let base = orders(ColumnType::Float64);
+        let unique = TabletSchema::new(KeysType::Unique, base.columns.clone());
+        assert_ne!(base.schema_hash, unique.schema_hash);
+
+        let mut swapped = base.columns.clone();
+        swapped.swap(1, 2);
+        assert_ne!(base.schema_hash, TabletSchema::new(KeysType::Duplicate, swapped).schema_hash);
+    }
+
+    // ── 加列 ──────────────────────────────────────────────────────────────────
+
+    /// 用 `schema` 写出 (order_id, amount, status) 行，之后的列依次补 `extra`
+    fn segment(schema: &TabletSchema, n: i64, extra: &[Value]) -> Vec<u8> {
+        let mut w = crate::segment::SegmentWriter::from_tablet_schema(schema);
+        for i in 0..n {
+            let mut row = vec![Value::Int64(i), Value::Float64(i as f64), Value::Bytes(b"paid".to_vec())];
+            row.extend_from_slice(extra);
+            w.append_row(row).unwrap();
+        }
+        w.finish().unwrap().0
+    }
+
+    fn open(data: Vec<u8>, schema: &TabletSchema) -> crate::segment::SegmentReader {
+        crate::segment::SegmentReader::open(data, schema.segment_schema()).unwrap()
+    }
+
+    #[test]
+    fn added_column_reads_as_default_from_old_segments() {
+        let mut schema = orders(ColumnType::Float64);
+        let old = segment(&schema, 50, &[]);
+        let (version, hash) = (schema.schema_version, schema.schema_hash);
+
+        let mut channel = ColumnSchema::varchar(3, "channel", 8, false);
+        channel.is_nullable = false;
+        schema.add_column(channel, Value::Bytes(b"web".to_vec())).unwrap();
+        assert_eq!(schema.schema_version, version + 1);
+        assert_ne!(schema.schema_hash, hash);
+        assert_eq!(schema.num_columns(), 4);
+
+        let reader = open(old, &schema);
+        assert_eq!(reader.read_column(3).unwrap(), vec![Value::Bytes(b"web".to_vec()); 50]);
+        assert_eq!(reader.get_row(7).unwrap()[3], Value::Bytes(b"web".to_vec()));
+
+        let new = open(segment(&schema, 20, &[Value::Bytes(b"app".to_vec())]), &schema);
+        assert_eq!(new.read_column(3).unwrap(), vec![Value::Bytes(b"app".to_vec()); 20]);
+    }
+
+    #[test]
+    fn add_column_rejects_keys_duplicates_and_null_defaults() {
+        let mut schema = orders(ColumnType::Float64);
+        let version = schema.schema_version;
+        let key = ColumnSchema::key(3, "region", ColumnType::Int32);
+        assert!(matches!(schema.add_column(key, Value::Int32(0)), Err(OlapError::Unsupported(_))));
+        let dup = ColumnSchema::value(4, "amount", ColumnType::Int64, AggregateType::None);
+        assert!(matches!(schema.add_column(dup, Value::Int64(0)), Err(OlapError::Unsupported(_))));
+        let mut not_null = ColumnSchema::value(5, "qty", ColumnType::Int32, AggregateType::Sum);
+        not_null.is_nullable = false;
+        assert!(matches!(schema.add_column(not_null, Value::Null), Err(OlapError::Unsupported(_))));
+        for (column_type, default) in [
+            (ColumnType::Int64, Value::Bytes(b"0".to_vec())),
+            (ColumnType::Int64, Value::Int32(0)),
+            (ColumnType::Varchar, Value::Int64(0)),
+            (ColumnType::Decimal { precision: 10, scale: 2 }, Value::Float64(1.5)),
+        ] {
+            let col = ColumnSchema::value(6, "typed", column_type, AggregateType::None);
+            assert!(matches!(schema.add_column(col, default), Err(OlapError::SchemaMismatch(_))), "{column_type:?}");
+        }
+        assert_eq!(schema.schema_version, version);
+
+        let nullable = ColumnSchema::value(5, "note", ColumnType::Varchar, AggregateType::None);
+        schema.add_column(nullable, Value::Null).unwrap();
+        let reader = open(segment(&orders(ColumnType::Float64), 3, &[]), &schema);
+        assert_eq!(reader.read_column(3).unwrap(), vec![Value::Null; 3]);
+    }
+
+    // ── 删列 ──────────────────────────────────────────────────────────────────
+
+    #[test]
+    fn dropped_value_column_is_skipped_when_reading_old_segments() {
+        let mut schema = orders(ColumnType::Float64);
+        let old = segment(&schema, 40, &[]);
+        let (version, hash) = (schema.schema_version, schema.schema_hash);
+
+        schema.drop_column(1).unwrap();
+        assert_eq!(schema.schema_version, version + 1);
+        assert_ne!(schema.schema_hash, hash);
+        assert_eq!((schema.num_columns(), schema.num_live_columns()), (3, 2));
+        assert_eq!(schema.live_columns().map(|c| c.column_id).collect::<Vec<_>>(), vec![0, 2]);
+
+        let reader = open(old, &schema);
+        assert!(matches!(reader.read_column(1), Err(OlapError::ColumnNotFound(1))));
+        assert_eq!(reader.get_row(5).unwrap(), vec![Value::Int64(5), Value::Null, Value::Bytes(b"paid".to_vec())]);
+        assert!(reader.row_iter().all(|r| r.unwrap()[1] == Value::Null));
+
+        // 新 Segment 中该列只写 NULL 占位
+        let new = open(segment(&schema, 3, &[]), &schema);
+        assert_eq!(new.read_column(2).unwrap().len(), 3);
+        assert!(matches!(schema.drop_column(1), Err(OlapError::ColumnNotFound(1))));
+    }
+
+    #[test]
+    fn dropping_a_key_column_is_rejected() {
+        let mut schema = orders(ColumnType::Float64);
+        let version = schema.schema_version;
+        assert!(matches!(schema.drop_column(0), Err(OlapError::Unsupported(_))));
+        assert_eq!(schema.schema_version, version);
+        assert_eq!(schema.num_columns(), 3);
+    }
+
+
+    // ── RowsetMeta 序列化 ─────────────────────────────────────────────────────
+
+    fn assert_same_rowset(a: &RowsetMeta, b: &RowsetMeta) {
+        assert_eq!((a.rowset_id, a.tablet_id, a.partition_id), (b.rowset_id, b.tablet_id, b.partition_id));
+        assert_eq!((a.version, a.num_rows, a.data_disk_size), (b.version, b.num_rows, b.data_disk_size));
+        assert_eq!((a.num_segments, &a.state, a.stale_at), (b.num_segments, &b.state, b.stale_at));
+        assert_eq!(a.segment_paths, b.segment_paths);
+        assert_eq!(a.delete_predicate.is_some(), b.delete_predicate.is_some());
+    }
+
+    #[test]
+    fn multi_segment_rowset_meta_round_trips() {
+        let mut rs = RowsetMeta::new(7, 10_001, 3, Version::new(5, 9), 2_500_000, 1 << 33);
+        rs.state = RowsetState::Visible;
+        assert_eq!(rs.num_segments, 3);
+        let back = RowsetMeta::deserialize(&rs.serialize()).unwrap();
+        assert_same_rowset(&back, &rs);
+        assert_eq!(back.segment_paths, ["10001_7_0.seg", "10001_7_1.seg", "10001_7_2.seg"]);
+
+        rs.mark_stale();
+        rs.segment_paths.push("额外.seg".into());
+        assert_same_rowset(&RowsetMeta::deserialize(&rs.serialize()).unwrap(), &rs);
+
+        // 早期 compaction 记录的绝对路径读取时规范化为文件名
+        rs.segment_paths = vec!["/data/olap/10001/7_0.seg".into()];
+        let back = RowsetMeta::deserialize(&rs.serialize()).unwrap();
+        assert_eq!(back.segment_paths, ["10001_7_0.seg"]);
+    }
+
+    #[test]
+    fn truncated_or_tampered_rowset_meta_is_an_error() {
+        let data = RowsetMeta::new(1, 2, 3, Version::point(4), 10, 100).serialize();
+        // 末尾的删除条件字节缺失时按早期记录读取，再往前截断则出错
+        assert!(RowsetMeta::deserialize(&data[..data.len() - 1]).unwrap().delete_predicate.is_none());
+        for len in [0, 8, 60, data.len() - 2] {
+            assert!(RowsetMeta::deserialize(&data[..len]).is_err(), "len {len}");
+        }
+        // state 字节位于 8 个 u64/i64 与 num_segments 之后
+        let mut bad_state = data.clone();
+        bad_state[60] = 0xff;
+        assert!(RowsetMeta::deserialize(&bad_state).is_err());
+        let mut trailing = data;
+        trailing.extend_from_slice(&[0, 0]);
+        assert!(RowsetMeta::deserialize(&trailing).is_err());
+    }
+
+
+    // ── 删除条件 ──────────────────────────────────────────────────────────────
+
+    fn order(id: i64, amount: i64, status: Option<&str>) -> Vec<Value> {
+        let status = status.map_or(Value::Null, |s| Value::Bytes(s.as_bytes().to_vec()));
+        vec![Value::Int64(id), Value::Int64(amount), status]
+    }
+
+    #[test]
+    fn status_cancelled_delete_matches_only_cancelled_rows() {
+        let schema = orders(ColumnType::Int64);
+        let pred   = DeletePredicate::new().and("status", CompareOp::Eq, Value::Bytes(b"cancelled".to_vec()));
+        assert!(pred.matches(&order(1, 10, Some("cancelled")), &schema));
+        assert!(!pred.matches(&order(2, 10, Some("paid")), &schema));
+        assert!(!pred.matches(&order(3, 10, Some("cancelled ")), &schema));
+        assert!(!pred.matches(&order(4, 10, None), &schema));
+
+        // 合取：只删除金额小于 100 的已取消订单；整数宽度不同也按数值比较
+        let small = pred.clone().and("amount", CompareOp::Lt, Value::Int32(100));
+        assert!(small.matches(&order(5, 99, Some("cancelled")), &schema));
+        assert!(!small.matches(&order(6, 100, Some("cancelled")), &schema));
+        // 未知列不命中，空条件命中所有行
+        assert!(!DeletePredicate::new().and("region", CompareOp::Eq, Value::Int64(1)).matches(&order(7, 1, None), &schema));
+        assert!(DeletePredicate::new().matches(&order(8, 1, None), &schema));
+    }
+
+    #[test]
+    fn float_and_decimal_columns_compare_numerically_with_integer_literals() {
+        let price = |v: Value| vec![Value::Int64(1), v, Value::Null];
+        let cheap = DeletePredicate::new().and("amount", CompareOp::Lt, Value::Int64(10));
+
+        let schema = orders(ColumnType::Float64);
+        assert!(cheap.matches(&price(Value::Float64(9.99)), &schema));
+        assert!(cheap.matches(&price(Value::Float64(-1e300)), &schema));
+        assert!(!cheap.matches(&price(Value::Float64(10.0)), &schema));
+        assert!(!cheap.matches(&price(Value::Float64(f64::NAN)), &schema));
+        let ten = DeletePredicate::new().and("amount", CompareOp::Eq, Value::Int32(10));
+        assert!(ten.matches(&price(Value::Float64(10.0)), &schema));
+        let schema = orders(ColumnType::Float32);
+        assert!(cheap.matches(&price(Value::Float32(9.5)), &schema));
+        assert!(!cheap.matches(&price(Value::Float32(10.5)), &schema));
+
+        // Decimal(18, 2)：999 即 9.99，1000 即 10.00
+        let schema = orders(ColumnType::Decimal { precision: 18, scale: 2 });
+        assert!(cheap.matches(&price(Value::Decimal(999)), &schema));
+        assert!(!cheap.matches(&price(Value::Decimal(1000)), &schema));
+        assert!(ten.matches(&price(Value::Decimal(1000)), &schema));
+        assert!(!cheap.matches(&price(Value::Decimal(5000)), &schema));
+        let half = DeletePredicate::new().and("amount", CompareOp::Ge, Value::Float64(0.5));
+        assert!(half.matches(&price(Value::Decimal(50)), &schema));
+        assert!(!half.matches(&price(Value::Decimal(49)), &schema));
+        // 定点数字面量按列的 scale 解释
+        let le = DeletePredicate::new().and("amount", CompareOp::Le, Value::Decimal(1999));
+        assert!(le.matches(&price(Value::Decimal(1999)), &schema));
+        assert!(!le.matches(&price(Value::Decimal(2000)), &schema));
+    }
+
+    #[test]
+    fn delete_rowset_meta_round_trips_its_predicate() {
+        let pred = DeletePredicate::new()
+            .and("status", CompareOp::Eq, Value::Bytes(b"cancelled".to_vec()))
+            .and("amount", CompareOp::Ge, Value::Int64(-5));
+        let rs   = RowsetMeta::new_delete(3, 10, 1, Version::point(7), pred.clone());
+        assert!(rs.is_delete());
+        assert_eq!((rs.num_rows, rs.num_segments), (0, 0));
+        let back = RowsetMeta::deserialize(&rs.serialize()).unwrap();
+        assert_eq!(back.delete_predicate, Some(pred));
+        assert_eq!(back.version, Version::point(7));
+    }
+}
@@ -5,20 +5,28 @@
 //! │ value_count  (u32 LE)            │
 //! │ first_row_id (u32 LE)            │
 //! │ uncomp_size  (u32 LE)            │
+//! │ codec        (u8)                │  实际使用的压缩方式，压缩无收益时为 None
 //! │ has_nulls    (u8)                │
-//! │ [null_bitmap (bit-packed)]       │  仅 nullable 列
-//! │ data         (encoded+compressed)│
-//! │ CRC32        (u32 LE)            │
+//! │ [nonce       (12 bytes)]         │  仅加密 Segment 的页（`PageFormat::Encrypted`）
+//! │ [null_bitmap (bit-packed)]       │  仅 has_nulls=1 时存在，1 位/行
+//! │ data         (encoded+compressed)│  只含非 NULL 值；加密页为密文 + 16 字节认证标签
+//! │ checksum     (4 或 8 bytes LE)   │  按 ChecksumType：CRC32 / xxHash64
 //! └──────────────────────────────────┘
 //! ```
+//! Segment version ≤ 3 写出的页没有 codec 字节（`PageFormat::Legacy`），按列的压缩方式解压。
+//! 基线 version 2 文件的页头与之相同，但 RunLength / Dictionary 为旧编码格式（`PageFormat::Baseline`）。
+//! 加密页以 nonce 之前的页头与 null bitmap 作为 AES-GCM 的附加认证数据，见 `crate::encryption`。
 
 use crate::common::{OlapError, Result};
 use crate::encoding;
 use crate::compression;
-use crate::field_type::{CompressionType, EncodingType, Value};
+use crate::encryption::{self, EncryptionKey, NONCE_SIZE};
+use crate::field_type::{ChecksumType, CompressionType, EncodingType, FieldType, Value};
 
 /// 每页最多容纳的行数
 pub const PAGE_MAX_ROWS: usize = 1024;
+/// 默认的页目标字节数（按编码前的原始值大小估算）
+pub const PAGE_TARGET_BYTES: usize = 64 * 1024;
 
 // ── PageBuilder ───────────────────────────────────────────────────────────────
 
@@ -26,7 +34,22 @@ pub struct PageBuilder {
     pub first_row_id: u32,
     encoding:         EncodingType,
     compression:      CompressionType,
+    checksum:         ChecksumType,
+    /// Some 时加密压缩后的 payload
+    encryption:       Option<EncryptionKey>,
+    /// 仅非 NULL 值
     values:           Vec<Value>,
+    /// 页内总行数（含 NULL）
+    num_rows:         usize,
+    /// 第 i 位为 1 表示页内第 i 行为 NULL
+    null_bitmap:      Vec<u8>,
+    has_nulls:        bool,
+    /// 已加入值的原始字节数之和
+    raw_bytes:        usize,
+    /// num_rows 达到此值即视为写满
+    max_rows:         usize,
+    /// raw_bytes 达到此值即视为写满
+    max_bytes:        usize,
 }
 
 impl PageBuilder {
@@ -35,43 +58,211 @@ impl PageBuilder {
         encoding:     EncodingType,
         compression:  CompressionType,
     ) -> Self {
-        Self { first_row_id, encoding, compression, values: Vec::new() }
+        Self {
+            first_row_id, encoding, compression,
+            checksum: ChecksumType::default(),
+            encryption: None,
+            values: Vec::new(), num_rows: 0,
+            null_bitmap: Vec::new(), has_nulls: false,
+            raw_bytes: 0, max_rows: PAGE_MAX_ROWS, max_bytes: PAGE_TARGET_BYTES,
+        }
+    }
+
+    pub fn with_max_rows(mut self, max_rows: usize) -> Self {
+        self.max_rows = max_rows; self
+    }
+
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes; self
+    }
+
+    pub fn with_checksum(mut self, checksum: ChecksumType) -> Self {
+        self.checksum = checksum; self
+    }
+
+    /// 用 AES-256-GCM 加密 payload，页头多出 `NONCE_SIZE` 字节的随机 nonce
+    pub fn with_encryption(mut self, key: Option<EncryptionKey>) -> Self {
+        self.encryption = key; self
     }
 
     pub fn add(&mut self, v: Value) {
-        self.values.push(v);
+        let ordinal = self.num_rows;
+        if ordinal.is_multiple_of(8) {
+            self.null_bitmap.push(0);
+        }
+        if v == Value::Null {
+            self.null_bitmap[ordinal / 8] |= 1 << (ordinal % 8);
+            self.has_nulls = true;
+        } else {
+            self.raw_bytes += raw_size(&v);
+            self.values.push(v);
+        }
+        self.num_rows += 1;
     }
 
-    pub fn len(&self)      -> usize { self.values.len() }
-    pub fn is_empty(&self) -> bool  { self.values.is_empty() }
-    pub fn is_full(&self)  -> bool  { self.values.len() >= PAGE_MAX_ROWS }
+    pub fn len(&self)      -> usize { self.num_rows }
+    pub fn is_empty(&self) -> bool  { self.num_rows == 0 }
+    /// 行数达到上限（默认 `PAGE_MAX_ROWS`）或原始字节数达到目标页大小
+    pub fn is_full(&self)  -> bool  {
+        self.num_rows >= self.max_rows || self.raw_bytes >= self.max_bytes
+    }
 
-    /// 序列化为页字节（encode → compress → 加 header+CRC）
+    /// 序列化为页字节（encode → compress → [encrypt] → 加 header+checksum）
+    ///
+    /// 压缩后不比编码结果小时（随机数据、极小的页）直接存编码结果，页头 codec 记为 None。
+    /// 校验值覆盖加密后的字节，损坏先于认证失败被发现。
     pub fn build(self) -> Result<Vec<u8>> {
-        let count       = self.values.len() as u32;
+        let count       = self.num_rows as u32;
         let encoded     = encoding::encode(&self.values, self.encoding)?;
         let uncomp_size = encoded.len() as u32;
         let compressed  = compression::compress(&encoded, self.compression)?;
+        let (codec, payload) = if compressed.len() < encoded.len() {
+            (self.compression, compressed)
+        } else {
+            (CompressionType::None, encoded)
+        };
 
         let mut page = Vec::new();
         page.extend_from_slice(&count.to_le_bytes());
         page.extend_from_slice(&self.first_row_id.to_le_bytes());
         page.extend_from_slice(&uncomp_size.to_le_bytes());
-        page.push(0u8); // has_nulls = false（简化：不支持 null bitmap）
-        page.extend_from_slice(&compressed);
+        page.push(codec.tag());
+        page.push(self.has_nulls as u8);
+        let nonce = self.encryption.as_ref().map(|_| encryption::random_nonce()).transpose()?;
+        if let Some(nonce) = &nonce {
+            page.extend_from_slice(nonce);
+        }
+        if self.has_nulls {
+            page.extend_from_slice(&self.null_bitmap);
+        }
+        match (&self.encryption, &nonce) {
+            (Some(key), Some(nonce)) => {
+                let sealed = encryption::encrypt(key, nonce, &page, &payload)?;
+                page.extend_from_slice(&sealed);
+            }
+            _ => page.extend_from_slice(&payload),
+        }
 
-        let crc = crc32fast::hash(&page);
-        page.extend_from_slice(&crc.to_le_bytes());
+        let sum = self.checksum.encode(&page);
+        page.extend_from_slice(&sum);
         Ok(page)
     }
 }
 
-// ── PageDecoder ───────────────────────────────────────────────────────────────
+/// 值编码前的估算字节数：定长类型取宽度，变长类型含 4 字节长度前缀
+fn raw_size(v: &Value) -> usize {
+    match v {
+        Value::Null                          => 0,
+        Value::Int8(_) | Value::Bool(_)      => 1,
+        Value::Int16(_)                      => 2,
+        Value::Int32(_) | Value::Float32(_)  => 4,
+        Value::Int64(_) | Value::Float64(_)
+        | Value::DateTime(_)                 => 8,
+        Value::Decimal(_)                    => 16,
+        Value::Bytes(b)                      => 4 + b.len(),
+    }
+}
 
-pub struct PageDecoder {
+// ── PageHeader ────────────────────────────────────────────────────────────────
+
+/// 页头固定部分的字节数（value_count + first_row_id + uncomp_size + codec + has_nulls）
+pub const PAGE_HEADER_SIZE: usize = 14;
+/// `PageFormat::Legacy` 页头的字节数（无 codec）
+pub const LEGACY_PAGE_HEADER_SIZE: usize = 13;
+/// `PageFormat::Encrypted` 页头的字节数（`PAGE_HEADER_SIZE` + nonce）
+pub const ENCRYPTED_PAGE_HEADER_SIZE: usize = PAGE_HEADER_SIZE + NONCE_SIZE;
+
+/// 页头布局，由所在 Segment 的版本决定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageFormat {
+    /// Segment version ≤ 3：页头无 codec 字节
+    Legacy,
+    /// 基线 version 2 文件：页头同 `Legacy`，payload 按 `encoding::decode_baseline` 解码
+    Baseline,
+    /// 页头记录实际使用的 codec
+    Current,
+    /// `Current` 之后跟每页的 nonce，payload 已加密；footer 标记了加密的 Segment
+    Encrypted,
+}
+
+impl PageFormat {
+    pub fn header_size(self) -> usize {
+        match self {
+            Self::Legacy | Self::Baseline => LEGACY_PAGE_HEADER_SIZE,
+            Self::Current                 => PAGE_HEADER_SIZE,
+            Self::Encrypted               => ENCRYPTED_PAGE_HEADER_SIZE,
+        }
+    }
+}
+
+/// 只解析页头，不校验 CRC、不解压
+#[derive(Debug, Clone, Copy)]
+pub struct PageHeader {
     pub value_count:  usize,
     pub first_row_id: u32,
-    pub values:       Vec<Value>,
+    /// 编码后、压缩前的 payload 字节数
+    pub uncomp_size:  usize,
+    /// 页内记录的 codec；`PageFormat::Legacy` 页为 None，取列的压缩方式
+    pub codec:        Option<CompressionType>,
+    pub has_nulls:    bool,
+    /// `PageFormat::Encrypted` 页的 nonce
+    pub nonce:        Option<[u8; NONCE_SIZE]>,
+    pub format:       PageFormat,
+}
+
+impl PageHeader {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        Self::parse_format(data, PageFormat::Current)
+    }
+
+    pub fn parse_format(data: &[u8], format: PageFormat) -> Result<Self> {
+        let size = format.header_size();
+        if data.len() < size + 4 {
+            return Err(OlapError::SegmentIo("page data too short".into()));
+        }
+        let codec = match format {
+            PageFormat::Legacy | PageFormat::Baseline => None,
+            PageFormat::Current | PageFormat::Encrypted => Some(CompressionType::from_tag(data[12]).ok_or_else(|| {
+                OlapError::SegmentIo(format!("unknown page codec {}", data[12]))
+            })?),
+        };
+        let (has_nulls, nonce) = match format {
+            PageFormat::Legacy | PageFormat::Baseline => (data[12], None),
+            PageFormat::Current                       => (data[13], None),
+            PageFormat::Encrypted                     => (data[13], Some(data[PAGE_HEADER_SIZE..size].try_into().unwrap())),
+        };
+        Ok(Self {
+            value_count:  u32::from_le_bytes(data[0..4].try_into().unwrap()) as usize,
+            first_row_id: u32::from_le_bytes(data[4..8].try_into().unwrap()),
+            uncomp_size:  u32::from_le_bytes(data[8..12].try_into().unwrap()) as usize,
+            codec,
+            has_nulls:    has_nulls != 0,
+            nonce,
+            format,
+        })
+    }
+
+    /// 页头固定部分的字节数
+    pub fn header_len(&self) -> usize { self.format.header_size() }
+
+    /// null bitmap 的字节数
+    pub fn bitmap_len(&self) -> usize {
+        if self.has_nulls { self.value_count.div_ceil(8) } else { 0 }
+    }
+}
+
+// ── PageDecoder ───────────────────────────────────────────────────────────────
+
+pub struct PageDecoder {
+    pub value_count:       usize,
+    pub first_row_id:      u32,
+    pub values:            Vec<Value>,
+    /// 实际用于解码 payload 的编码；全局字典列的页报告 `Dictionary`，而非字典码的编码
+    pub encoding_used:     EncodingType,
+    /// 实际用于解压的 codec：页头记录的 codec，旧格式页为列的压缩方式
+    pub compression_used:  CompressionType,
+    /// 解压后的 payload 字节数
+    pub uncompressed_size: usize,
 }
 
 impl PageDecoder {
@@ -79,27 +270,203 @@ impl PageDecoder {
         data:        &[u8],
         encoding:    EncodingType,
         compression: CompressionType,
+        field_type:  FieldType,
     ) -> Result<Self> {
-        if data.len() < 17 {
-            return Err(OlapError::SegmentIo("page data too short".into()));
+        Self::decode_format(
+            data, PageFormat::Current, ChecksumType::default(), None, encoding, compression, field_type,
+        )
+    }
+
+    /// 按 `format` 解析页头、按 `checksum` 校验；页内记录了 codec 时以其为准，否则用 `compression`
+    ///
+    /// `PageFormat::Encrypted` 页用 `key` 解密，未给出密钥或认证失败时返回 `Encryption`。
+    pub fn decode_format(
+        data:        &[u8],
+        format:      PageFormat,
+        checksum:    ChecksumType,
+        key:         Option<&EncryptionKey>,
+        encoding:    EncodingType,
+        compression: CompressionType,
+        field_type:  FieldType,
+    ) -> Result<Self> {
+        let page     = RawPage::open(data, format, checksum, key, compression)?;
+        let non_null = page.non_null_count();
+        let decoded  = match format {
+            PageFormat::Baseline => encoding::decode_baseline(&page.payload, encoding, field_type, non_null)?,
+            _                    => encoding::decode(&page.payload, encoding, field_type, non_null)?,
+        };
+        if decoded.len() < non_null {
+            return Err(OlapError::Encoding(format!(
+                "page expects {non_null} non-null values, decoded {}", decoded.len(),
+            )));
         }
-        let value_count  = u32::from_le_bytes(data[0..4].try_into().unwrap()) as usize;
-        let first_row_id = u32::from_le_bytes(data[4..8].try_into().unwrap());
-        let uncomp_size  = u32::from_le_bytes(data[8..12].try_into().unwrap()) as usize;
-        // byte[12] = has_nulls，byte[13..] = payload，last 4 = CRC
-        let payload_end  = data.len() - 4;
-        let payload      = &data[13..payload_end];
-
-        // 校验 CRC
-        let stored_crc = u32::from_le_bytes(data[payload_end..].try_into().unwrap());
-        let actual_crc = crc32fast::hash(&data[..payload_end]);
-        if stored_crc != actual_crc {
+
+        // 按 bitmap 把 NULL 插回原位置
+        let mut decoded = decoded.into_iter();
+        let values = (0..page.header.value_count)
+            .map(|i| if page.is_null(i) { Value::Null } else { decoded.next().unwrap() })
+            .collect();
+
+        let PageHeader { value_count, first_row_id, uncomp_size, .. } = page.header;
+        Ok(Self {
+            value_count, first_row_id, values,
+            encoding_used:     encoding,
+            compression_used:  page.compression,
+            uncompressed_size: uncomp_size,
+        })
+    }
+}
+
+// ── RawPage ───────────────────────────────────────────────────────────────────
+
+/// 已校验、已解密、已解压但尚未解码的页
+pub struct RawPage<'a> {
+    pub header:      PageHeader,
+    /// 第 i 位为 1 表示页内第 i 行为 NULL；`has_nulls` 为 false 时为空
+    pub null_bitmap: &'a [u8],
+    /// 解压后的编码数据
+    pub payload:     Vec<u8>,
+    /// 实际使用的 codec
+    pub compression: CompressionType,
+}
+
+impl<'a> RawPage<'a> {
+    /// 参数含义同 `PageDecoder::decode_format`
+    pub fn open(
+        data:        &'a [u8],
+        format:      PageFormat,
+        checksum:    ChecksumType,
+        key:         Option<&EncryptionKey>,
+        compression: CompressionType,
+    ) -> Result<Self> {
+        let header = PageHeader::parse_format(data, format)?;
+        let compression = header.codec.unwrap_or(compression);
+        // 页头之后为可选 null bitmap 与 payload，末尾为校验值
+        let header_len   = header.header_len();
+        let bitmap_len   = header.bitmap_len();
+        let payload_end  = data.len().saturating_sub(checksum.size());
+        if header_len + bitmap_len > payload_end {
+            return Err(OlapError::SegmentIo("page null bitmap truncated".into()));
+        }
+        let null_bitmap  = &data[header_len..header_len + bitmap_len];
+        let payload      = &data[header_len + bitmap_len..payload_end];
+
+        if !checksum.verify(&data[..payload_end], &data[payload_end..]) {
             return Err(OlapError::ChecksumMismatch);
         }
 
-        let raw    = compression::decompress(payload, compression, uncomp_size)?;
-        let values = encoding::decode(&raw, encoding, value_count)?;
+        let decrypted;
+        let payload = match (header.nonce, key) {
+            (None, _)                => payload,
+            (Some(nonce), Some(key)) => {
+                decrypted = encryption::decrypt(key, &nonce, &data[..header_len + bitmap_len], payload)?;
+                &decrypted[..]
+            }
+            (Some(_), None)          => {
+                return Err(OlapError::Encryption("page is encrypted but no key was given".into()));
+            }
+        };
+        let payload = compression::decompress(payload, compression, header.uncomp_size)?;
+        Ok(Self { header, null_bitmap, payload, compression })
+    }
+
+    pub fn is_null(&self, i: usize) -> bool {
+        self.header.has_nulls && self.null_bitmap[i / 8] & (1 << (i % 8)) != 0
+    }
+
+    pub fn non_null_count(&self) -> usize {
+        (0..self.header.value_count).filter(|&i| !self.is_null(i)).count()
+    }
+
+    /// 逐个产出页内各行的值（NULL 按 bitmap 插回），编码数据按需解码
+    pub fn into_values(
+        self,
+        encoding:   EncodingType,
+        field_type: FieldType,
+    ) -> impl Iterator<Item = Result<Value>> + 'a {
+        let non_null = self.non_null_count();
+        let value_count = self.header.value_count;
+        let is_null = move |i: usize| {
+            self.header.has_nulls && self.null_bitmap[i / 8] & (1 << (i % 8)) != 0
+        };
+        let mut decoded: Box<dyn Iterator<Item = Result<Value>>> = match self.header.format {
+            PageFormat::Baseline => match encoding::decode_baseline(&self.payload, encoding, field_type, non_null) {
+                Ok(values) => Box::new(values.into_iter().map(Ok)),
+                Err(e)     => Box::new(std::iter::once(Err(e))),
+            },
+            _ => Box::new(encoding::decode_iter(self.payload, encoding, field_type, non_null)),
+        };
+        (0..value_count).map(move |i| {
+            if is_null(i) {
+                return Ok(Value::Null);
+            }
+            decoded.next().unwrap_or_else(|| Err(OlapError::Encoding(format!(
+                "page expects {non_null} non-null values, row {i} has none",
+            ))))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::partition::XorShift64;
+
+    fn random_ints(seed: u64, rows: usize) -> Vec<Value> {
+        let mut rng = XorShift64::new(seed);
+        (0..rows).map(|_| Value::Int64(rng.next_u64() as i64)).collect()
+    }
+
+    // ── 压缩回退 ──────────────────────────────────────────────────────────────
+
+    #[test]
+    fn incompressible_page_falls_back_to_no_compression() {
+        let values = random_ints(7, 500);
+        for codec in [CompressionType::Lz4, CompressionType::Zstd { level: 3 }] {
+            let mut b = PageBuilder::new(100, EncodingType::Plain, codec);
+            values.iter().cloned().for_each(|v| b.add(v));
+            let page = b.build().unwrap();
+            assert_eq!(page[12], CompressionType::None.tag());
+
+            let d = PageDecoder::decode(&page, EncodingType::Plain, codec, FieldType::Int64).unwrap();
+            assert_eq!(d.compression_used, CompressionType::None);
+            assert_eq!((d.first_row_id, &d.values), (100, &values));
+        }
+    }
+
+    #[test]
+    fn compressible_page_keeps_its_codec() {
+        let values: Vec<Value> = (0..500).map(|_| Value::Bytes(b"cancelled".to_vec())).collect();
+        let mut b = PageBuilder::new(0, EncodingType::Plain, CompressionType::Lz4);
+        values.iter().cloned().for_each(|v| b.add(v));
+        let page = b.build().unwrap();
+        assert_eq!(page[12], CompressionType::Lz4.tag());
+        let d = PageDecoder::decode(&page, EncodingType::Plain, CompressionType::None, FieldType::Bytes).unwrap();
+        assert_eq!(d.compression_used, CompressionType::Lz4);
+        assert_eq!(d.values, values);
+    }
+
+
+    // ── 解码信息 ──────────────────────────────────────────────────────────────
+
+    #[test]
+    fn lz4_dictionary_page_reports_the_codec_and_encoding_it_used() {
+        let values: Vec<Value> = (0..1000)
+            .map(|i| match i % 10 {
+                0 => Value::Null,
+                _ => Value::Bytes(["paid", "pending", "cancelled"][i % 3].as_bytes().to_vec()),
+            })
+            .collect();
+        let mut b = PageBuilder::new(4000, EncodingType::Dictionary, CompressionType::Lz4);
+        values.iter().cloned().for_each(|v| b.add(v));
+        let page = b.build().unwrap();
 
-        Ok(Self { value_count, first_row_id, values })
+        let d = PageDecoder::decode(&page, EncodingType::Dictionary, CompressionType::None, FieldType::Bytes).unwrap();
+        assert_eq!(d.encoding_used, EncodingType::Dictionary);
+        assert_eq!(d.compression_used, CompressionType::Lz4);
+        let non_null: Vec<Value> = values.iter().filter(|v| !matches!(v, Value::Null)).cloned().collect();
+        assert_eq!(d.uncompressed_size, encoding::encode(&non_null, EncodingType::Dictionary).unwrap().len());
+        assert!(page.len() < d.uncompressed_size);
+        assert_eq!((d.value_count, d.first_row_id, &d.values), (1000, 4000, &values));
     }
 }
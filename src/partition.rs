@@ -1,13 +1,16 @@
 //! 分区与分桶策略
 
+use std::cell::RefCell;
+use std::cmp::Ordering;
 use std::collections::HashMap;
-use crate::common::{OlapError, PartitionId, Result, TabletId};
+use crate::common::{put_bytes, ByteReader, OlapError, PartitionId, Result, TabletId};
+use crate::field_type::Value;
 
 // ── 分桶策略 ──────────────────────────────────────────────────────────────────
 
 #[derive(Debug, Clone)]
 pub enum BucketType {
-    /// HASH 分桶：对指定列做 FNV1a 哈希取模
+    /// HASH 分桶：按 `bucket_columns` 顺序拼接各列值（多列时每个值前带长度）后做 FNV1a 哈希取模
     Hash {
         bucket_columns: Vec<String>,
         num_buckets:    u32,
@@ -24,27 +27,125 @@ impl BucketType {
         }
     }
 
-    /// 将行键映射到桶索引
+    /// 同 `bucket_for_key`，但 RANDOM 分桶使用调用方提供的随机源（便于复现）
+    pub fn bucket_for_key_with_rng(&self, key: &str, rng: &mut XorShift64) -> u32 {
+        match self {
+            Self::Random { num_buckets } => rng.next_below(*num_buckets),
+            Self::Hash { .. }            => self.bucket_for_key(key),
+        }
+    }
+
+    /// 将行键映射到桶索引（单列字符串键）
     pub fn bucket_for_key(&self, key: &str) -> u32 {
+        self.bucket_for_values(&[Value::Bytes(key.as_bytes().to_vec())])
+    }
+
+    /// 将分桶列的值（按 `bucket_columns` 顺序）映射到桶索引
+    ///
+    /// 各值以文本形式参与哈希，因此 `Int64(42)` 与字符串 `"42"` 落在同一桶。
+    /// 只有一个值时直接哈希其字节，与旧版 `bucket_for_key` 的路由保持一致；
+    /// 多个值时每个值前先哈希其 u32 长度，`["1","23"]` 与 `["12","3"]` 不会因拼接结果相同而撞桶。
+    pub fn bucket_for_values(&self, values: &[Value]) -> u32 {
         match self {
             Self::Hash { num_buckets, .. } => {
                 // FNV-1a 64-bit
                 let mut h: u64 = 0xcbf29ce484222325;
-                for b in key.bytes() {
-                    h ^= b as u64;
-                    h = h.wrapping_mul(0x100000001b3);
+                let mut feed = |bytes: &[u8]| {
+                    for &b in bytes {
+                        h ^= b as u64;
+                        h = h.wrapping_mul(0x100000001b3);
+                    }
+                };
+                match values {
+                    [single] => feed(&bucket_bytes(single)),
+                    _ => for v in values {
+                        let bytes = bucket_bytes(v);
+                        feed(&(bytes.len() as u32).to_le_bytes());
+                        feed(&bytes);
+                    },
                 }
                 (h % *num_buckets as u64) as u32
             }
-            Self::Random { num_buckets } => {
-                let t = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .subsec_nanos();
-                t % num_buckets
-            }
+            Self::Random { num_buckets } =>
+                THREAD_RNG.with(|rng| rng.borrow_mut().next_below(*num_buckets)),
         }
     }
+
+    /// 同 `bucket_for_values`，但按列名取值：`values` 顺序任意，按 `bucket_columns` 声明顺序参与哈希
+    ///
+    /// 缺少某个分桶列时返回 `SchemaMismatch`；RANDOM 分桶忽略 `values`。
+    pub fn bucket_for_named_values(&self, values: &[(&str, &Value)]) -> Result<u32> {
+        let Self::Hash { bucket_columns, .. } = self else {
+            return Ok(self.bucket_for_values(&[]));
+        };
+        let ordered = bucket_columns.iter()
+            .map(|col| {
+                values.iter()
+                    .find(|(name, _)| name == col)
+                    .map(|(_, v)| (*v).clone())
+                    .ok_or_else(|| OlapError::SchemaMismatch(format!("missing bucket column {col}")))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(self.bucket_for_values(&ordered))
+    }
+}
+
+// ── 随机数 ────────────────────────────────────────────────────────────────────
+
+/// xorshift64* 伪随机数发生器，RANDOM 分桶用
+#[derive(Debug, Clone)]
+pub struct XorShift64 {
+    state: u64,
+}
+
+impl XorShift64 {
+    /// 种子为 0 时替换为固定非零值（xorshift 的全零状态不会前进）
+    pub fn new(seed: u64) -> Self {
+        Self { state: if seed == 0 { 0x9e3779b97f4a7c15 } else { seed } }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        x.wrapping_mul(0x2545f4914f6cdd1d)
+    }
+
+    /// [0, n) 内的均匀整数（乘法取高位，避免取模偏差）
+    pub fn next_below(&mut self, n: u32) -> u32 {
+        ((self.next_u64() as u128 * n as u128) >> 64) as u32
+    }
+}
+
+thread_local! {
+    /// 每线程一个发生器，首次使用时以时间与线程局部地址为种子
+    static THREAD_RNG: RefCell<XorShift64> = RefCell::new({
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as u64;
+        let local = 0u8;
+        XorShift64::new(nanos ^ (&local as *const u8 as u64).rotate_left(32))
+    });
+}
+
+/// 分桶哈希的输入字节：字符串取原始字节，数值取十进制文本，NULL 为空
+fn bucket_bytes(v: &Value) -> Vec<u8> {
+    match v {
+        Value::Null       => Vec::new(),
+        Value::Int8(x)    => x.to_string().into_bytes(),
+        Value::Int16(x)   => x.to_string().into_bytes(),
+        Value::Int32(x)   => x.to_string().into_bytes(),
+        Value::Int64(x)   => x.to_string().into_bytes(),
+        Value::Float32(x) => x.to_string().into_bytes(),
+        Value::Float64(x) => x.to_string().into_bytes(),
+        Value::Bytes(b)   => b.clone(),
+        Value::Decimal(x) => x.to_string().into_bytes(),
+        Value::Bool(x)    => x.to_string().into_bytes(),
+        Value::DateTime(x) => x.to_string().into_bytes(),
+    }
 }
 
 // ── MaterializedIndex（一个分区内一个索引的所有 Tablet）────────────────────────
@@ -97,17 +198,138 @@ impl Partition {
         let bucket = self.bucket_type.bucket_for_key(sort_key);
         self.base_index.tablet_for_bucket(bucket)
     }
+
+    /// 根据分桶列的值路由到 TabletId
+    pub fn tablet_for_values(&self, bucket_vals: &[Value]) -> Option<TabletId> {
+        let bucket = self.bucket_type.bucket_for_values(bucket_vals);
+        self.base_index.tablet_for_bucket(bucket)
+    }
+
+    /// 本分区基础索引与各 rollup 索引的全部 Tablet
+    pub fn tablet_ids(&self) -> impl Iterator<Item = TabletId> + '_ {
+        std::iter::once(&self.base_index)
+            .chain(&self.rollup_indexes)
+            .flat_map(|idx| idx.tablets.iter().copied())
+    }
 }
 
 // ── Range 分区辅助 ─────────────────────────────────────────────────────────────
 
-/// RANGE 分区边界（字符串比较）
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
-pub struct RangeBound(pub String);
+/// 带类型的分区键：整数/日期列按数值比较，VARCHAR 列按字节序比较
+///
+/// 不同变体之间不可比较（`partial_cmp` 返回 None）。
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PartitionKey {
+    Int(i64),
+    Str(String),
+}
+
+impl PartitionKey {
+    /// 按 `like` 的类型解析字符串形式的分区键
+    pub fn parse_like(key: &str, like: &PartitionKey) -> Result<Self> {
+        match like {
+            Self::Int(_) => key.trim().parse::<i64>()
+                .map(Self::Int)
+                .map_err(|_| OlapError::PartitionNotFound(key.into())),
+            Self::Str(_) => Ok(Self::Str(key.into())),
+        }
+    }
+
+    /// 从列值构造；浮点与 NULL 不能作为分区键
+    pub fn from_value(v: &Value) -> Option<Self> {
+        match v {
+            Value::Int8(x)  => Some(Self::Int(*x as i64)),
+            Value::Int16(x) => Some(Self::Int(*x as i64)),
+            Value::Int32(x) => Some(Self::Int(*x as i64)),
+            Value::Int64(x) | Value::DateTime(x) => Some(Self::Int(*x)),
+            Value::Bytes(b) => Some(Self::Str(String::from_utf8_lossy(b).into_owned())),
+            _               => None,
+        }
+    }
+}
+
+impl PartialOrd for PartitionKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Self::Int(a), Self::Int(b)) => Some(a.cmp(b)),
+            (Self::Str(a), Self::Str(b)) => Some(a.cmp(b)),
+            _                            => None,
+        }
+    }
+}
+
+impl std::fmt::Display for PartitionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Int(v) => write!(f, "{v}"),
+            Self::Str(s) => write!(f, "{s}"),
+        }
+    }
+}
+
+impl From<i64> for PartitionKey {
+    fn from(v: i64) -> Self { Self::Int(v) }
+}
+impl From<&str> for PartitionKey {
+    fn from(s: &str) -> Self { Self::Str(s.into()) }
+}
+impl From<String> for PartitionKey {
+    fn from(s: String) -> Self { Self::Str(s) }
+}
+
+/// RANGE 分区边界
+///
+/// `Max` 大于任何分区键，与任何边界都可比较；`Key` 之间按 `PartitionKey` 比较。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RangeBound {
+    Key(PartitionKey),
+    /// MAXVALUE：最后一个分区以此为上界即不设上限
+    Max,
+}
 
 impl RangeBound {
     /// 最大上界（超过所有实际数据）
-    pub fn max_value() -> Self { Self("\u{FFFF}".repeat(64)) }
+    pub fn max_value() -> Self { Self::Max }
+
+    /// 边界的分区键；`Max` 为 None
+    pub fn key(&self) -> Option<&PartitionKey> {
+        match self {
+            Self::Key(k) => Some(k),
+            Self::Max    => None,
+        }
+    }
+
+    /// `key` 是否落在此独占上界之下；`Max` 对任何键成立，类型不一致的键不在任何 `Key` 边界之下
+    pub fn is_above(&self, key: &PartitionKey) -> bool {
+        match self {
+            Self::Key(bound) => key < bound,
+            Self::Max        => true,
+        }
+    }
+}
+
+impl PartialOrd for RangeBound {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Self::Key(a), Self::Key(b)) => a.partial_cmp(b),
+            (Self::Max, Self::Max)       => Some(Ordering::Equal),
+            (Self::Max, Self::Key(_))    => Some(Ordering::Greater),
+            (Self::Key(_), Self::Max)    => Some(Ordering::Less),
+        }
+    }
+}
+
+impl std::fmt::Display for RangeBound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Key(k) => write!(f, "{k}"),
+            Self::Max    => f.write_str("MAXVALUE"),
+        }
+    }
+}
+
+impl From<PartitionKey> for RangeBound {
+    fn from(k: PartitionKey) -> Self { Self::Key(k) }
 }
 
 #[derive(Debug, Clone)]
@@ -156,6 +378,23 @@ impl PartitionInfo {
         }
     }
 
+    /// LIST 分区：每组若干取值映射到同一分区，如 `(["CN", "HK"], p_china)`
+    ///
+    /// 同一取值出现在两个分区、或引用了 `partitions` 中不存在的分区时返回错误。
+    pub fn list_from_groups(
+        partition_columns: Vec<String>,
+        groups:            Vec<(Vec<String>, PartitionId)>,
+        partitions:        HashMap<PartitionId, Partition>,
+    ) -> Result<Self> {
+        let mut info = Self::list(partition_columns, HashMap::new(), partitions);
+        for (values, partition_id) in groups {
+            for value in values {
+                info.add_list_value(value, partition_id)?;
+            }
+        }
+        Ok(info)
+    }
+
     pub fn unpartitioned(partition_id: PartitionId, partition: Partition) -> Self {
         let mut partitions = HashMap::new();
         partitions.insert(partition_id, partition);
@@ -169,22 +408,507 @@ impl PartitionInfo {
     // ── 路由 ──────────────────────────────────────────────────────────────────
 
     /// 根据分区键值找到对应的 Partition
+    ///
+    /// RANGE 分区下按边界的类型解析 `key`：整数边界做数值比较。
     pub fn find_partition(&self, key: &str) -> Result<&Partition> {
+        match &self.policy {
+            PartitionPolicy::Range { items } => self.find_partition_by_key(&parse_range_key(key, items)?),
+            _ => self.find_partition_by_key(&PartitionKey::Str(key.into())),
+        }
+    }
+
+    /// 按分区列的值查找；目前只支持单列分区
+    ///
+    /// RANGE 分区下列值会按边界类型转换，如 `Bytes("100")` 对整数边界按 100 比较。
+    pub fn find_partition_for_values(&self, vals: &[Value]) -> Result<&Partition> {
+        if let PartitionPolicy::Unpartitioned { .. } = self.policy {
+            return self.find_partition_by_key(&PartitionKey::Int(0));
+        }
+        let [v] = vals else {
+            return Err(OlapError::Unsupported(format!(
+                "expected 1 partition value, got {}", vals.len()
+            )));
+        };
+        let key = PartitionKey::from_value(v)
+            .ok_or_else(|| OlapError::PartitionNotFound(format!("{v:?}")))?;
+        match &self.policy {
+            PartitionPolicy::Range { items } => self.find_partition_by_key(&parse_range_key(&key.to_string(), items)?),
+            _ => self.find_partition_by_key(&key),
+        }
+    }
+
+    // ── 变更 ──────────────────────────────────────────────────────────────────
+
+    /// 追加一个 RANGE 分区，items 保持按上界升序；校验规则见 `check_range_partition`
+    pub fn add_range_partition(
+        &mut self,
+        item:      RangePartitionItem,
+        partition: Partition,
+    ) -> Result<()> {
+        self.check_range_partition(&item, &partition)?;
+        if let PartitionPolicy::Range { items } = &mut self.policy {
+            items.push(item);
+        }
+        self.partitions.insert(partition.partition_id, partition);
+        Ok(())
+    }
+
+    /// 校验 `add_range_partition` 能否成功，不修改分区信息
+    ///
+    /// 新分区覆盖 [前一上界, 新上界)；若新上界落在已有分区之间会切走后者的区间，
+    /// 视为重叠拒绝。上界重复、类型不一致、partition_id 冲突同样拒绝。
+    pub fn check_range_partition(
+        &self,
+        item:      &RangePartitionItem,
+        partition: &Partition,
+    ) -> Result<()> {
+        let PartitionPolicy::Range { items } = &self.policy else {
+            return Err(OlapError::Unsupported("add_range_partition on non-RANGE table".into()));
+        };
+        if item.partition_id != partition.partition_id {
+            return Err(OlapError::Unsupported(format!(
+                "item partition_id {} != partition {}", item.partition_id, partition.partition_id
+            )));
+        }
+        if self.partitions.contains_key(&item.partition_id) {
+            return Err(OlapError::PartitionOverlap(format!(
+                "partition_id {} already exists", item.partition_id
+            )));
+        }
+        if let Some(last) = items.last() {
+            match item.upper_bound.partial_cmp(&last.upper_bound) {
+                Some(Ordering::Greater) => {}
+                Some(_) => return Err(OlapError::PartitionOverlap(format!(
+                    "upper bound {} not above existing bound {}",
+                    item.upper_bound, last.upper_bound
+                ))),
+                None => return Err(OlapError::PartitionOverlap(format!(
+                    "upper bound {} has a different type from {}",
+                    item.upper_bound, last.upper_bound
+                ))),
+            }
+        }
+        Ok(())
+    }
+
+    /// 向 LIST 分区追加一个取值；重复追加到同一分区是幂等的
+    pub fn add_list_value(&mut self, value: String, partition_id: PartitionId) -> Result<()> {
+        let PartitionPolicy::List { key_to_partition } = &mut self.policy else {
+            return Err(OlapError::Unsupported("add_list_value on non-LIST table".into()));
+        };
+        if !self.partitions.contains_key(&partition_id) {
+            return Err(OlapError::PartitionNotFound(format!(
+                "list value {value} maps to unknown partition_id {partition_id}"
+            )));
+        }
+        match key_to_partition.get(&value) {
+            Some(&pid) if pid != partition_id => Err(OlapError::PartitionOverlap(format!(
+                "list value {value} already in partition {pid}"
+            ))),
+            Some(_) => Ok(()),
+            None => {
+                key_to_partition.insert(value, partition_id);
+                Ok(())
+            }
+        }
+    }
+
+    /// 按类型化分区键查找；类型与 RANGE 边界不一致时视为未命中
+    pub fn find_partition_by_key(&self, key: &PartitionKey) -> Result<&Partition> {
         let pid = match &self.policy {
             PartitionPolicy::Unpartitioned { partition_id } => *partition_id,
 
             PartitionPolicy::List { key_to_partition } =>
-                *key_to_partition.get(key)
-                    .ok_or_else(|| OlapError::PartitionNotFound(key.into()))?,
+                *key_to_partition.get(&key.to_string())
+                    .ok_or_else(|| OlapError::PartitionNotFound(key.to_string()))?,
 
             PartitionPolicy::Range { items } =>
                 items.iter()
-                    .find(|it| key < it.upper_bound.0.as_str())
+                    .find(|it| it.upper_bound.is_above(key))
                     .map(|it| it.partition_id)
-                    .ok_or_else(|| OlapError::PartitionNotFound(key.into()))?,
+                    .ok_or_else(|| OlapError::PartitionNotFound(key.to_string()))?,
         };
 
         self.partitions.get(&pid)
-            .ok_or_else(|| OlapError::PartitionNotFound(format!("pid={pid}")))
+            .ok_or_else(|| OlapError::PartitionNotFound(format!(
+                "key {key} maps to unknown partition_id {pid}"
+            )))
+    }
+}
+
+// ── 序列化 ────────────────────────────────────────────────────────────────────
+//
+// partition_columns（n u32 | n × 字符串）| policy | partitions（n u32 | n × Partition，按 id 升序）
+// policy    = 0 | n × (partition_id u64 | RangeBound)     RANGE
+//           | 1 | n × (取值字符串 | partition_id u64)       LIST，按取值排序
+//           | 2 | partition_id u64                         UNPARTITIONED
+// Partition = partition_id u64 | base_index | n u32 | n × rollup | BucketType | visible_version i64
+// 字符串均为 `len u32 | UTF-8`，RangeBound = 0 | i64 或 1 | 字符串（`Key`），或 2（`Max`）
+
+impl PartitionInfo {
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.serialize_into(&mut out);
+        out
+    }
+
+    pub(crate) fn serialize_into(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&(self.partition_columns.len() as u32).to_le_bytes());
+        for c in &self.partition_columns {
+            put_bytes(out, c.as_bytes());
+        }
+
+        match &self.policy {
+            PartitionPolicy::Range { items } => {
+                out.push(0);
+                out.extend_from_slice(&(items.len() as u32).to_le_bytes());
+                for it in items {
+                    out.extend_from_slice(&it.partition_id.to_le_bytes());
+                    write_bound(out, &it.upper_bound);
+                }
+            }
+            PartitionPolicy::List { key_to_partition } => {
+                out.push(1);
+                let mut entries: Vec<_> = key_to_partition.iter().collect();
+                entries.sort();
+                out.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+                for (value, pid) in entries {
+                    put_bytes(out, value.as_bytes());
+                    out.extend_from_slice(&pid.to_le_bytes());
+                }
+            }
+            PartitionPolicy::Unpartitioned { partition_id } => {
+                out.push(2);
+                out.extend_from_slice(&partition_id.to_le_bytes());
+            }
+        }
+
+        let mut pids: Vec<_> = self.partitions.keys().copied().collect();
+        pids.sort_unstable();
+        out.extend_from_slice(&(pids.len() as u32).to_le_bytes());
+        for pid in pids {
+            let p = &self.partitions[&pid];
+            out.extend_from_slice(&p.partition_id.to_le_bytes());
+            write_index(out, &p.base_index);
+            out.extend_from_slice(&(p.rollup_indexes.len() as u32).to_le_bytes());
+            for idx in &p.rollup_indexes {
+                write_index(out, idx);
+            }
+            match &p.bucket_type {
+                BucketType::Hash { bucket_columns, num_buckets } => {
+                    out.push(0);
+                    out.extend_from_slice(&num_buckets.to_le_bytes());
+                    out.extend_from_slice(&(bucket_columns.len() as u32).to_le_bytes());
+                    for c in bucket_columns {
+                        put_bytes(out, c.as_bytes());
+                    }
+                }
+                BucketType::Random { num_buckets } => {
+                    out.push(1);
+                    out.extend_from_slice(&num_buckets.to_le_bytes());
+                }
+            }
+            out.extend_from_slice(&p.visible_version.to_le_bytes());
+        }
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<Self> {
+        let mut r = ByteReader::new(data, "partition info");
+        let info = Self::read_from(&mut r)?;
+        r.finish()?;
+        Ok(info)
+    }
+
+    pub(crate) fn read_from(r: &mut ByteReader) -> Result<Self> {
+        let n_cols = r.u32()? as usize;
+        let partition_columns = (0..n_cols).map(|_| r.string()).collect::<Result<Vec<_>>>()?;
+
+        let policy = match r.u8()? {
+            0 => {
+                let n = r.u32()? as usize;
+                let mut items = Vec::with_capacity(n.min(4096));
+                for _ in 0..n {
+                    let partition_id = r.u64()?;
+                    let upper_bound  = read_bound(r)?;
+                    items.push(RangePartitionItem { partition_id, upper_bound });
+                }
+                PartitionPolicy::Range { items }
+            }
+            1 => {
+                let n = r.u32()? as usize;
+                let mut key_to_partition = HashMap::with_capacity(n.min(4096));
+                for _ in 0..n {
+                    let value = r.string()?;
+                    key_to_partition.insert(value, r.u64()?);
+                }
+                PartitionPolicy::List { key_to_partition }
+            }
+            2 => PartitionPolicy::Unpartitioned { partition_id: r.u64()? },
+            tag => return Err(r.err(format!("unknown partition policy {tag}"))),
+        };
+
+        let n_parts = r.u32()? as usize;
+        let mut partitions = HashMap::with_capacity(n_parts.min(4096));
+        for _ in 0..n_parts {
+            let partition_id = r.u64()?;
+            let base_index   = read_index(r)?;
+            let n_rollups    = r.u32()? as usize;
+            let rollup_indexes = (0..n_rollups).map(|_| read_index(r)).collect::<Result<Vec<_>>>()?;
+            let bucket_type = match r.u8()? {
+                0 => {
+                    let num_buckets = r.u32()?;
+                    let n = r.u32()? as usize;
+                    let bucket_columns = (0..n).map(|_| r.string()).collect::<Result<Vec<_>>>()?;
+                    BucketType::Hash { bucket_columns, num_buckets }
+                }
+                1 => BucketType::Random { num_buckets: r.u32()? },
+                tag => return Err(r.err(format!("unknown bucket type {tag}"))),
+            };
+            let visible_version = r.i64()?;
+            partitions.insert(partition_id, Partition {
+                partition_id, base_index, rollup_indexes, bucket_type, visible_version,
+            });
+        }
+        Ok(Self { partition_columns, policy, partitions })
+    }
+}
+
+fn write_bound(out: &mut Vec<u8>, bound: &RangeBound) {
+    match bound {
+        RangeBound::Key(PartitionKey::Int(v)) => { out.push(0); out.extend_from_slice(&v.to_le_bytes()); }
+        RangeBound::Key(PartitionKey::Str(s)) => { out.push(1); put_bytes(out, s.as_bytes()); }
+        RangeBound::Max                       => out.push(2),
+    }
+}
+
+fn read_bound(r: &mut ByteReader) -> Result<RangeBound> {
+    match r.u8()? {
+        0   => Ok(RangeBound::Key(PartitionKey::Int(r.i64()?))),
+        1   => Ok(RangeBound::Key(PartitionKey::Str(r.string()?))),
+        2   => Ok(RangeBound::Max),
+        tag => Err(r.err(format!("unknown partition key type {tag}"))),
+    }
+}
+
+/// 按 RANGE 边界的类型解析字符串形式的分区键；全部为 `Max` 时按字符串处理
+fn parse_range_key(key: &str, items: &[RangePartitionItem]) -> Result<PartitionKey> {
+    if items.is_empty() {
+        return Err(OlapError::PartitionNotFound(key.into()));
+    }
+    match items.iter().find_map(|it| it.upper_bound.key()) {
+        Some(like) => PartitionKey::parse_like(key, like),
+        None       => Ok(PartitionKey::Str(key.into())),
+    }
+}
+
+fn write_index(out: &mut Vec<u8>, idx: &MaterializedIndex) {
+    out.extend_from_slice(&idx.index_id.to_le_bytes());
+    out.extend_from_slice(&(idx.tablets.len() as u32).to_le_bytes());
+    for t in &idx.tablets {
+        out.extend_from_slice(&t.to_le_bytes());
+    }
+}
+
+fn read_index(r: &mut ByteReader) -> Result<MaterializedIndex> {
+    let index_id = r.u64()?;
+    let n = r.u32()? as usize;
+    let tablets = (0..n).map(|_| r.u64()).collect::<Result<Vec<_>>>()?;
+    Ok(MaterializedIndex { index_id, tablets })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash_buckets(n: u32) -> BucketType {
+        BucketType::Hash { bucket_columns: vec!["a".into(), "b".into()], num_buckets: n }
+    }
+
+    fn s(v: &str) -> Value { Value::Bytes(v.as_bytes().to_vec()) }
+
+    fn partition(pid: PartitionId) -> Partition {
+        Partition::new(pid, MaterializedIndex::new(pid, vec![pid * 10]), BucketType::Random { num_buckets: 1 })
+    }
+
+    /// 按 `bounds` 升序建 RANGE 分区，第 i 个分区的 id 为 i + 1
+    fn range_info(bounds: Vec<RangeBound>) -> PartitionInfo {
+        let mut info = PartitionInfo::range(vec!["k".into()], vec![], HashMap::new());
+        for (i, upper_bound) in bounds.into_iter().enumerate() {
+            let pid = i as PartitionId + 1;
+            info.add_range_partition(RangePartitionItem { partition_id: pid, upper_bound }, partition(pid)).unwrap();
+        }
+        info
+    }
+
+    fn pid_of(info: &PartitionInfo, key: &str) -> Option<PartitionId> {
+        info.find_partition(key).ok().map(|p| p.partition_id)
+    }
+
+    // ── 分桶 ──────────────────────────────────────────────────────────────────
+
+    #[test]
+    fn multi_value_hash_separates_concatenation_ambiguity() {
+        let b = hash_buckets(1024);
+        assert_ne!(b.bucket_for_values(&[s("1"), s("23")]), b.bucket_for_values(&[s("12"), s("3")]));
+        assert_ne!(b.bucket_for_values(&[s("a"), s("bc")]), b.bucket_for_values(&[s("ab"), s("c")]));
+    }
+
+    #[test]
+    fn single_key_routing_matches_plain_fnv() {
+        let b = hash_buckets(16);
+        for key in ["", "1001001", "2024-07-01", "user_42"] {
+            let mut h: u64 = 0xcbf29ce484222325;
+            for byte in key.bytes() {
+                h ^= byte as u64;
+                h = h.wrapping_mul(0x100000001b3);
+            }
+            assert_eq!(b.bucket_for_key(key), (h % 16) as u32, "key {key:?}");
+        }
+        assert_eq!(b.bucket_for_values(&[Value::Int64(42)]), b.bucket_for_key("42"));
+    }
+
+    #[test]
+    fn named_values_follow_declared_column_order() {
+        let b = hash_buckets(1024);
+        let (x, y) = (s("x"), s("yy"));
+        let named = b.bucket_for_named_values(&[("b", &y), ("a", &x)]).unwrap();
+        assert_eq!(named, b.bucket_for_values(&[x.clone(), y.clone()]));
+        assert!(matches!(b.bucket_for_named_values(&[("a", &x)]), Err(OlapError::SchemaMismatch(_))));
+    }
+
+    // ── RANGE 分区 ────────────────────────────────────────────────────────────
+
+    #[test]
+    fn integer_range_bounds_compare_numerically() {
+        let info = range_info(vec![
+            PartitionKey::Int(99).into(),
+            PartitionKey::Int(100).into(),
+            PartitionKey::Int(1000).into(),
+        ]);
+        assert_eq!(pid_of(&info, "-5"), Some(1));
+        assert_eq!(pid_of(&info, "98"), Some(1));
+        assert_eq!(pid_of(&info, "99"), Some(2));
+        assert_eq!(pid_of(&info, "100"), Some(3));
+        assert_eq!(pid_of(&info, "999"), Some(3));
+        assert_eq!(pid_of(&info, "1000"), None);
+        assert_eq!(pid_of(&info, "abc"), None);
+
+        let by_value = |v: Value| info.find_partition_for_values(&[v]).map(|p| p.partition_id).ok();
+        assert_eq!(by_value(Value::Int64(99)), Some(2));
+        assert_eq!(by_value(Value::Int32(150)), Some(3));
+        assert_eq!(by_value(s("100")), Some(3));
+    }
+
+    #[test]
+    fn varchar_range_bounds_keep_byte_order() {
+        let info = range_info(vec![
+            PartitionKey::from("2024-01-01").into(),
+            PartitionKey::from("2024-07-01").into(),
+        ]);
+        assert_eq!(pid_of(&info, "2023-12-31"), Some(1));
+        assert_eq!(pid_of(&info, "2024-01-01"), Some(2));
+        assert_eq!(pid_of(&info, "2024-07-01"), None);
+
+        // 字符串边界仍按字节序："100" < "99"
+        let info = range_info(vec![PartitionKey::from("99").into()]);
+        assert_eq!(pid_of(&info, "100"), Some(1));
+    }
+
+    #[test]
+    fn range_bound_of_another_type_is_rejected() {
+        let mut info = range_info(vec![PartitionKey::Int(100).into()]);
+        let item = RangePartitionItem { partition_id: 2, upper_bound: PartitionKey::from("200").into() };
+        assert!(matches!(info.add_range_partition(item, partition(2)), Err(OlapError::PartitionOverlap(_))));
+    }
+
+    #[test]
+    fn maxvalue_partition_catches_every_key_above_the_prior_bound() {
+        let info = range_info(vec![PartitionKey::Int(100).into(), PartitionKey::Int(1000).into(), RangeBound::Max]);
+        assert_eq!(pid_of(&info, "99"), Some(1));
+        assert_eq!(pid_of(&info, "999"), Some(2));
+        for key in ["1000", "65535", &i64::MAX.to_string()] {
+            assert_eq!(pid_of(&info, key), Some(3), "key {key}");
+        }
+        assert_eq!(info.find_partition_for_values(&[Value::Int64(i64::MAX)]).unwrap().partition_id, 3);
+
+        // 字符串边界同样适用，多字节键也落在 MAXVALUE 分区
+        let info = range_info(vec![PartitionKey::from("2024-07-01").into(), RangeBound::max_value()]);
+        assert_eq!(pid_of(&info, "2024-06-30"), Some(1));
+        assert_eq!(pid_of(&info, "\u{FFFF}\u{10FFFF}"), Some(2));
+
+        // MAXVALUE 之后不能再追加分区
+        let mut info = info;
+        let item = RangePartitionItem { partition_id: 3, upper_bound: PartitionKey::from("2099-01-01").into() };
+        assert!(matches!(info.add_range_partition(item, partition(3)), Err(OlapError::PartitionOverlap(_))));
+        assert_eq!(RangeBound::Max.to_string(), "MAXVALUE");
+    }
+
+    // ── LIST 分区 ─────────────────────────────────────────────────────────────
+
+    fn list_info() -> PartitionInfo {
+        let partitions = HashMap::from([(1, partition(1)), (2, partition(2))]);
+        PartitionInfo::list_from_groups(
+            vec!["region".into()],
+            vec![
+                (vec!["CN".into(), "HK".into(), "MO".into()], 1),
+                (vec!["US".into(), "CA".into()], 2),
+            ],
+            partitions,
+        ).unwrap()
+    }
+
+    #[test]
+    fn list_groups_route_several_values_to_each_partition() {
+        let mut info = list_info();
+        for (key, pid) in [("CN", 1), ("HK", 1), ("MO", 1), ("US", 2), ("CA", 2)] {
+            assert_eq!(pid_of(&info, key), Some(pid), "key {key}");
+        }
+        assert_eq!(pid_of(&info, "JP"), None);
+
+        info.add_list_value("TW".into(), 1).unwrap();
+        info.add_list_value("CN".into(), 1).unwrap();
+        assert_eq!(pid_of(&info, "TW"), Some(1));
+        assert!(matches!(info.add_list_value("CN".into(), 2), Err(OlapError::PartitionOverlap(_))));
+        assert_eq!(pid_of(&info, "CN"), Some(1));
+    }
+
+    #[test]
+    fn list_value_for_unknown_partition_is_rejected() {
+        let mut info = list_info();
+        match info.add_list_value("JP".into(), 9) {
+            Err(OlapError::PartitionNotFound(msg)) => assert!(msg.contains("unknown partition_id 9"), "{msg}"),
+            other => panic!("{other:?}"),
+        }
+        assert_eq!(pid_of(&info, "JP"), None);
+        let groups = vec![(vec!["JP".into()], 9)];
+        assert!(matches!(
+            PartitionInfo::list_from_groups(vec!["region".into()], groups, HashMap::new()),
+            Err(OlapError::PartitionNotFound(_)),
+        ));
+        let mut range = range_info(vec![PartitionKey::Int(10).into()]);
+        assert!(matches!(range.add_list_value("x".into(), 1), Err(OlapError::Unsupported(_))));
+    }
+
+    // ── RANDOM 分桶 ───────────────────────────────────────────────────────────
+
+    #[test]
+    fn random_buckets_are_near_uniform_and_reproducible() {
+        let b = BucketType::Random { num_buckets: 10 };
+        let mut rng    = XorShift64::new(42);
+        let mut counts = [0u32; 10];
+        for i in 0..100_000 {
+            counts[b.bucket_for_key_with_rng(&i.to_string(), &mut rng) as usize] += 1;
+        }
+        for (bucket, &n) in counts.iter().enumerate() {
+            assert!((9_700..=10_300).contains(&n), "bucket {bucket} got {n} rows");
+        }
+
+        let draw = |seed| {
+            let mut rng = XorShift64::new(seed);
+            (0..32).map(|_| b.bucket_for_key_with_rng("k", &mut rng)).collect::<Vec<_>>()
+        };
+        assert_eq!(draw(7), draw(7));
+        assert_ne!(draw(7), draw(8));
+        assert!((0..1000).all(|_| b.bucket_for_key("k") < 10));
     }
 }
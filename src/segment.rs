@@ -4,46 +4,150 @@
 //! ```text
 //! ┌────────────────────────────────────┐
 //! │  MAGIC  (8 bytes) "OLAPSEG\0"      │
-//! │  Version(4 bytes) = 2              │
+//! │  Version(4 bytes) = 5              │
+//! │  Footer version (1 byte) = 5       │  version=2 的基线文件无此字节
+//! │  Checksum type  (1 byte)           │  version≤4 的文件无此字节，视为 CRC32
 //! ├────────────────────────────────────┤
 //! │  DATA REGION                       │
-//! │    [Data Pages col 0]              │ ← LZ4 + 编码
-//! │    [Data Pages col 1]              │
-//! │    ...                             │
+//! │    [Data Page col 0 #0]            │ ← LZ4 + 编码（+ AES-256-GCM）
+//! │    [Data Page col 1 #0]            │   各列的页按写满顺序交错，
+//! │    ...                             │   由 OrdinalIndex 定位
 //! ├────────────────────────────────────┤
 //! │  INDEX REGION                      │
 //! │    [OrdinalIndex  col N]           │
 //! │    [ZoneMapIndex  col N]           │
 //! │    [BloomFilter   col N]           │
+//! │    [GlobalDictionary col N]        │ ← 仅 global_dictionary_columns 中的字典列
+//! │    [BitmapIndex   col N]           │ ← 仅 bitmap_index_columns 中的列
 //! │    [ShortKeyIndex]                 │
 //! ├────────────────────────────────────┤
 //! │  FOOTER                            │
-//! │    SegmentFooter (自定义二进制)      │
-//! │    Footer CRC32  (4 bytes)         │
+//! │    SegmentFooter (自定义二进制)      │ ← 末尾为 footer 之前全部字节的 xxHash64
+//! │                                    │   与加密标记（+ 密钥校验块）
+//! │    Footer checksum (4 或 8 bytes)  │
 //! │    Footer length (4 bytes)         │
 //! │    MAGIC         (8 bytes)         │
 //! └────────────────────────────────────┘
 //! ```
+//!
+//! 开启 `SegmentWriterOptions::encryption` 时只有数据页 payload 被加密，
+//! 索引区与 footer 仍为明文，见 `crate::encryption`。
+//!
+//! footer 布局只由文件头决定：version 2 一律为基线布局，version ≥ 3 按 footer 版本字节解析
+//! （见 `FooterLayout::of`），布局每变化一次 `FOOTER_VERSION` 递增一次。
+//!
+//! 基线 version 2 文件的列条目只有 48 字节（无编码、压缩与数据区大小），各列的页连续存放，
+//! 页的位置在打开时按页头与压缩块长度逐页解析重建，见 `SegmentFooter::deserialize_baseline`。
 
-use std::io::Write;
-use crate::common::{OlapError, Result};
-use crate::column_writer::{ColumnWriter, ShortKeyIndexBuilder};
-use crate::field_type::{ColumnMeta, Value};
-use crate::index::{BloomFilter, OrdinalIndex, ZoneMapIndex};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap};
+use std::io::{BufRead, Write};
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use crate::common::{AggregateType, KeysType, OlapError, Result};
+use crate::column_writer::{ColumnWriter, PageSink, ShortKeyIndexBuilder, PAGE_DICT_CODE_ENCODING};
+use crate::field_type::{
+    days_from_civil, ChecksumType, ColumnMeta, CompressionType, EncodingType, FieldType, Value,
+};
+use crate::compression;
+use crate::encoding;
+use crate::encryption::{self, EncryptionKey, KEY_CHECK_SIZE};
+use crate::meta::TabletSchema;
+use crate::index::{
+    BitmapIndex, BloomFilter, BloomGranularity, GlobalDictionary, OrdinalIndex, PageBloomIndex, ShortKeyIndex,
+    ZoneMapIndex, SHORT_KEY_INTERVAL,
+};
+use crate::page::{
+    PageDecoder, PageFormat, PageHeader, RawPage, PAGE_MAX_ROWS, PAGE_TARGET_BYTES,
+};
 
 const MAGIC: &[u8; 8] = b"OLAPSEG\0";
-const VERSION: u32     = 2;
+const VERSION: u32     = 5;
+/// 文件头不含 footer 版本字节的旧版本，仍可读取
+const LEGACY_VERSION: u32 = 2;
+/// 数据页头不含 codec 字节的最后一个版本，仍可读取
+const NO_PAGE_CODEC_VERSION: u32 = 3;
+/// 文件头不含校验算法字节（统一为 CRC32）的最后一个版本，仍可读取
+const NO_CHECKSUM_TYPE_VERSION: u32 = 4;
+/// `SegmentFooter` 的布局版本，布局变化时递增，并在 `FooterLayout::of` 中登记
+const FOOTER_VERSION: u8  = 5;
+/// 列条目不含全局字典位置的旧 footer 版本，仍可读取
+const NO_DICT_FOOTER_VERSION: u8 = 1;
+/// 列条目不含 BitmapIndex 位置的旧 footer 版本，仍可读取
+const NO_BITMAP_FOOTER_VERSION: u8 = 2;
+/// 末尾不含内容摘要的旧 footer 版本，仍可读取
+const NO_DIGEST_FOOTER_VERSION: u8 = 3;
+/// 末尾不含加密标记的旧 footer 版本，仍可读取
+const NO_ENCRYPTION_FOOTER_VERSION: u8 = 4;
 
 // ── Footer 结构 ───────────────────────────────────────────────────────────────
 
+/// 每列 footer 条目的字节数（11 × u64 + encoding + compression）
+const COLUMN_META_SIZE: usize = 90;
+/// `NO_BITMAP_FOOTER_VERSION` 的列条目字节数（9 × u64 + encoding + compression）
+const NO_BITMAP_COLUMN_META_SIZE: usize = 74;
+/// `NO_DICT_FOOTER_VERSION` 的列条目字节数（7 × u64 + encoding + compression）
+const NO_DICT_COLUMN_META_SIZE: usize = 58;
+/// 基线 version 2 的列条目字节数（ordinal / zonemap / bloom 的 offset + size）
+const BASELINE_COLUMN_META_SIZE: usize = 48;
+
+/// 各 footer 版本的布局
+#[derive(Debug, Clone, Copy)]
+struct FooterLayout {
+    /// 每列条目的字节数
+    entry_size: usize,
+    /// 列条目含全局字典位置
+    dict:       bool,
+    /// 列条目含 BitmapIndex 位置
+    bitmap:     bool,
+    /// 列条目之后有内容摘要
+    digest:     bool,
+    /// 内容摘要之后有加密标记（+ 密钥校验块）
+    key_check:  bool,
+}
+
+impl FooterLayout {
+    /// 已知的 footer 版本；未知版本返回 None
+    fn of(version: u8) -> Option<Self> {
+        let layout = |entry_size, dict, bitmap, digest, key_check| {
+            Some(Self { entry_size, dict, bitmap, digest, key_check })
+        };
+        match version {
+            NO_DICT_FOOTER_VERSION       => layout(NO_DICT_COLUMN_META_SIZE, false, false, false, false),
+            NO_BITMAP_FOOTER_VERSION     => layout(NO_BITMAP_COLUMN_META_SIZE, true, false, false, false),
+            NO_DIGEST_FOOTER_VERSION     => layout(COLUMN_META_SIZE, true, true, false, false),
+            NO_ENCRYPTION_FOOTER_VERSION => layout(COLUMN_META_SIZE, true, true, true, false),
+            FOOTER_VERSION               => layout(COLUMN_META_SIZE, true, true, true, true),
+            _                            => None,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ColumnIndexMeta {
+    /// 写入时实际使用的编码，读取时不依赖调用方传入的 schema
+    pub encoding:       EncodingType,
+    pub compression:    CompressionType,
+    /// 本列所有数据页的总字节数
+    pub data_size:      u64,
     pub ordinal_offset: u64,
     pub ordinal_size:   u64,
     pub zonemap_offset: u64,
     pub zonemap_size:   u64,
     pub bf_offset:      u64,
     pub bf_size:        u64,
+    /// 全局字典位置；`dict_size > 0` 时各页存 `PAGE_DICT_CODE_ENCODING` 编码的字典码
+    pub dict_offset:    u64,
+    pub dict_size:      u64,
+    /// BitmapIndex 位置，未构建时 size 为 0
+    pub bitmap_offset:  u64,
+    pub bitmap_size:    u64,
+    /// 首次解码该列时加载的全局字典，不参与序列化
+    dictionary:         OnceLock<GlobalDictionary>,
+    /// 基线 version 2 文件打开时重建的 OrdinalIndex（绝对偏移 + 页大小），不参与序列化
+    baseline_ordinal:   Option<OrdinalIndex>,
 }
 
 #[derive(Debug)]
@@ -53,6 +157,10 @@ pub struct SegmentFooter {
     pub short_key_offset: u64,
     pub short_key_size:   u64,
     pub column_metas:     Vec<ColumnIndexMeta>,
+    /// footer 之前全部字节（文件头、数据区、索引区）的 xxHash64；旧版本 footer 为 None
+    pub content_digest:   Option<u64>,
+    /// 数据页已加密时为密钥校验块（`encryption::key_check`），旧版本 footer 为 None
+    pub key_check:        Option<[u8; KEY_CHECK_SIZE]>,
 }
 
 impl SegmentFooter {
@@ -63,17 +171,32 @@ impl SegmentFooter {
         out.extend_from_slice(&self.short_key_offset.to_le_bytes());
         out.extend_from_slice(&self.short_key_size.to_le_bytes());
         for cm in &self.column_metas {
+            out.push(cm.encoding.tag());
+            out.push(cm.compression.tag());
+            out.extend_from_slice(&cm.data_size.to_le_bytes());
             out.extend_from_slice(&cm.ordinal_offset.to_le_bytes());
             out.extend_from_slice(&cm.ordinal_size.to_le_bytes());
             out.extend_from_slice(&cm.zonemap_offset.to_le_bytes());
             out.extend_from_slice(&cm.zonemap_size.to_le_bytes());
             out.extend_from_slice(&cm.bf_offset.to_le_bytes());
             out.extend_from_slice(&cm.bf_size.to_le_bytes());
+            out.extend_from_slice(&cm.dict_offset.to_le_bytes());
+            out.extend_from_slice(&cm.dict_size.to_le_bytes());
+            out.extend_from_slice(&cm.bitmap_offset.to_le_bytes());
+            out.extend_from_slice(&cm.bitmap_size.to_le_bytes());
+        }
+        if let Some(digest) = self.content_digest {
+            out.extend_from_slice(&digest.to_le_bytes());
+        }
+        match &self.key_check {
+            Some(check) => { out.push(1); out.extend_from_slice(check); }
+            None        => out.push(0),
         }
         out
     }
 
-    fn deserialize(data: &[u8]) -> Option<Self> {
+    fn deserialize(data: &[u8], layout: FooterLayout) -> Option<Self> {
+        let entry_size = layout.entry_size;
         if data.len() < 24 { return None; }
         let num_rows    = u32::from_le_bytes(data[0..4].try_into().ok()?);
         let num_columns = u32::from_le_bytes(data[4..8].try_into().ok()?);
@@ -82,230 +205,3106 @@ impl SegmentFooter {
         let mut pos     = 24usize;
         let mut column_metas = Vec::new();
         for _ in 0..num_columns {
-            if pos + 48 > data.len() { break; }
-            let cm = ColumnIndexMeta {
-                ordinal_offset: u64::from_le_bytes(data[pos..pos+8].try_into().ok()?),
-                ordinal_size:   u64::from_le_bytes(data[pos+8..pos+16].try_into().ok()?),
-                zonemap_offset: u64::from_le_bytes(data[pos+16..pos+24].try_into().ok()?),
-                zonemap_size:   u64::from_le_bytes(data[pos+24..pos+32].try_into().ok()?),
-                bf_offset:      u64::from_le_bytes(data[pos+32..pos+40].try_into().ok()?),
-                bf_size:        u64::from_le_bytes(data[pos+40..pos+48].try_into().ok()?),
+            if pos + entry_size > data.len() { return None; }
+            let encoding    = EncodingType::from_tag(data[pos])?;
+            let compression = CompressionType::from_tag(data[pos+1])?;
+            let p = pos + 2;
+            let mut cm = ColumnIndexMeta {
+                encoding,
+                compression,
+                data_size:      u64::from_le_bytes(data[p..p+8].try_into().ok()?),
+                ordinal_offset: u64::from_le_bytes(data[p+8..p+16].try_into().ok()?),
+                ordinal_size:   u64::from_le_bytes(data[p+16..p+24].try_into().ok()?),
+                zonemap_offset: u64::from_le_bytes(data[p+24..p+32].try_into().ok()?),
+                zonemap_size:   u64::from_le_bytes(data[p+32..p+40].try_into().ok()?),
+                bf_offset:      u64::from_le_bytes(data[p+40..p+48].try_into().ok()?),
+                bf_size:        u64::from_le_bytes(data[p+48..p+56].try_into().ok()?),
+                dict_offset:    0,
+                dict_size:      0,
+                bitmap_offset:  0,
+                bitmap_size:    0,
+                dictionary:     OnceLock::new(),
+                baseline_ordinal: None,
             };
+            if layout.dict {
+                cm.dict_offset = u64::from_le_bytes(data[p+56..p+64].try_into().ok()?);
+                cm.dict_size   = u64::from_le_bytes(data[p+64..p+72].try_into().ok()?);
+            }
+            if layout.bitmap {
+                cm.bitmap_offset = u64::from_le_bytes(data[p+72..p+80].try_into().ok()?);
+                cm.bitmap_size   = u64::from_le_bytes(data[p+80..p+88].try_into().ok()?);
+            }
             column_metas.push(cm);
-            pos += 48;
+            pos += entry_size;
         }
+        let content_digest = if layout.digest {
+            let digest = u64::from_le_bytes(data.get(pos..pos+8)?.try_into().ok()?);
+            pos += 8;
+            Some(digest)
+        } else {
+            None
+        };
+        let key_check = match layout.key_check.then(|| data.get(pos)) {
+            None           => None,
+            Some(None)     => return None,
+            Some(Some(0))  => None,
+            Some(Some(1))  => Some(data.get(pos+1..pos+1+KEY_CHECK_SIZE)?.try_into().ok()?),
+            Some(Some(_))  => return None,
+        };
         Some(Self {
             num_rows, num_columns,
             short_key_offset: sk_offset, short_key_size: sk_size,
             column_metas,
+            content_digest,
+            key_check,
+        })
+    }
+
+    /// 解析基线 version 2 的 footer，`data` 为整个文件
+    ///
+    /// 基线 footer 不记录编码与压缩方式，取自 `schema`。基线写出的 OrdinalIndex 只有列内偏移，
+    /// 且漏掉了每列最后一页，不予采用：各列的页从文件头之后按列顺序连续存放，
+    /// 由 `rebuild_baseline_ordinals` 逐页解析重建为 `baseline_ordinal`。
+    /// 基线的 ZoneMap、BloomFilter 与 ShortKey 使用旧的排序键与哈希，视为缺失。
+    fn deserialize_baseline(footer: &[u8], data: &[u8], schema: &[ColumnMeta]) -> Result<Self> {
+        let u64_at = |pos: usize| u64::from_le_bytes(footer[pos..pos+8].try_into().unwrap());
+        let num_rows    = u32::from_le_bytes(footer[0..4].try_into().unwrap());
+        let num_columns = u32::from_le_bytes(footer[4..8].try_into().unwrap()) as usize;
+        if schema.len() < num_columns {
+            return Err(OlapError::SchemaMismatch(format!(
+                "version 2 segment has {num_columns} columns, schema has {}", schema.len(),
+            )));
+        }
+        // 数据区到第一列的 OrdinalIndex 为止
+        let data_end = if num_columns == 0 { 12 } else { u64_at(24) as usize };
+        if !(12..=data.len()).contains(&data_end) {
+            return Err(OlapError::SegmentIo(format!(
+                "version 2 segment: data region ends at {data_end}, file is {} bytes", data.len(),
+            )));
+        }
+        let ordinals = rebuild_baseline_ordinals(data, data_end, num_rows, &schema[..num_columns])?;
+        let column_metas = schema.iter().zip(ordinals).enumerate()
+            .map(|(c, (meta, ordinal))| {
+                let p = 24 + c * BASELINE_COLUMN_META_SIZE;
+                ColumnIndexMeta {
+                    encoding:       meta.encoding,
+                    compression:    meta.compression,
+                    data_size:      ordinal.entries().iter().map(|&(_, _, size)| size).sum(),
+                    ordinal_offset: u64_at(p),
+                    ordinal_size:   u64_at(p + 8),
+                    zonemap_offset: 0,
+                    zonemap_size:   0,
+                    bf_offset:      0,
+                    bf_size:        0,
+                    dict_offset:    0,
+                    dict_size:      0,
+                    bitmap_offset:  0,
+                    bitmap_size:    0,
+                    dictionary:     OnceLock::new(),
+                    baseline_ordinal: Some(ordinal),
+                }
+            })
+            .collect();
+        Ok(Self {
+            num_rows,
+            num_columns:      num_columns as u32,
+            short_key_offset: 0,
+            short_key_size:   0,
+            column_metas,
+            content_digest:   None,
+            key_check:        None,
         })
     }
 }
 
+/// 从 12 字节的文件头之后逐页解析基线数据区 [12, data_end)，为每列重建 (first_row_id, 偏移, 页大小)
+///
+/// 每列恰好 `num_rows` 行，凑满即进入下一列；页的长度由 `baseline_page_len` 算出。
+fn rebuild_baseline_ordinals(
+    data:     &[u8],
+    data_end: usize,
+    num_rows: u32,
+    schema:   &[ColumnMeta],
+) -> Result<Vec<OrdinalIndex>> {
+    let corrupt = |what: String| OlapError::SegmentIo(format!("version 2 segment: {what}"));
+    let mut pos = 12usize;
+    let mut ordinals = Vec::with_capacity(schema.len());
+    for (c, meta) in schema.iter().enumerate() {
+        let mut ordinal = OrdinalIndex::default();
+        let mut rows    = 0u32;
+        while rows < num_rows {
+            let page = data.get(pos..data_end)
+                .ok_or_else(|| corrupt(format!("col {c} ends before row {rows}")))?;
+            let header = PageHeader::parse_format(page, PageFormat::Baseline)?;
+            let next_rows = rows.checked_add(header.value_count as u32)
+                .filter(|&n| header.first_row_id == rows && header.value_count > 0 && n <= num_rows)
+                .ok_or_else(|| corrupt(format!("col {c}: unexpected page at offset {pos}")))?;
+            let len = baseline_page_len(page, &header, meta.compression)
+                .map_err(|e| corrupt(format!("col {c}: page at offset {pos}: {e}")))?;
+            ordinal.add(rows, pos as u64, len as u64);
+            rows = next_rows;
+            pos += len;
+        }
+        ordinals.push(ordinal);
+    }
+    if pos != data_end {
+        return Err(corrupt(format!("column pages end at {pos}, data region ends at {data_end}")));
+    }
+    Ok(ordinals)
+}
+
+/// `page` 开头的基线页的字节数（基线页不记录 payload 长度）
+///
+/// 基线只有 None 与 LZ4 两种压缩：None 的 payload 即 `uncomp_size` 字节，
+/// LZ4 block 的长度由 `compression::lz4_block_len` 解析得出；其后是 4 字节 CRC32。
+fn baseline_page_len(page: &[u8], header: &PageHeader, codec: CompressionType) -> Result<usize> {
+    let start   = header.header_len() + header.bitmap_len();
+    let payload = page.get(start..).ok_or_else(|| OlapError::SegmentIo("page data too short".into()))?;
+    let payload_len = match codec {
+        CompressionType::None => header.uncomp_size,
+        CompressionType::Lz4  => compression::lz4_block_len(payload, header.uncomp_size)?,
+        other => return Err(OlapError::Unsupported(format!("{other:?} in a version 2 segment"))),
+    };
+    let len = start + payload_len + 4;
+    if len > page.len() {
+        return Err(OlapError::SegmentIo("page data too short".into()));
+    }
+    Ok(len)
+}
+
 // ── SegmentWriter ─────────────────────────────────────────────────────────────
 
-pub struct SegmentWriter {
+/// 写入参数；`Default` 与各模块常量一致
+#[derive(Debug, Clone)]
+pub struct SegmentWriterOptions {
+    /// 每页行数上限
+    pub page_max_rows:       usize,
+    /// 每页目标字节数（按原始值大小估算）
+    pub page_max_bytes:      usize,
+    /// ShortKey 索引的采样间隔（行）
+    pub short_key_interval:  u32,
+    /// Some 时覆盖 schema 中各列的压缩方式
+    pub default_compression: Option<CompressionType>,
+    /// 各列 BloomFilter 的目标误判率
+    pub bloom_fpp:           f64,
+    /// 是否额外为每个数据页构建 BloomFilter
+    pub bloom_granularity:   BloomGranularity,
+    /// Bytes 值超过列的 `max_length` 时截断；false 时返回 `ValueTooLong`
+    pub truncate_long_bytes: bool,
+    /// 数据页与 footer 的校验算法
+    pub checksum:            ChecksumType,
+    /// 缓存前 `ENCODING_SAMPLE_ROWS` 行，按样本为各列重新挑选编码（`encoding::choose_encoding`）
+    pub adaptive_encoding:   bool,
+    /// 构建 BitmapIndex 的列（schema 下标），适合取值很少的列
+    pub bitmap_index_columns: Vec<usize>,
+    /// 使用全局字典的列（schema 下标），只对 Dictionary 编码的字符串列生效；
+    /// 字典不设上限，适合取值很少的列，其余列仍使用页内字典
+    pub global_dictionary_columns: Vec<usize>,
+    /// 要求行按 key 列非递减追加，回退时 `append_row` 报错；Unique/Aggregate 表应开启
+    pub enforce_sort_order:  bool,
+    /// Some 时用 AES-256-GCM 加密各数据页压缩后的 payload；
+    /// 索引区（ZoneMap、BloomFilter、全局字典等）仍为明文
+    pub encryption:          Option<EncryptionKey>,
+}
+
+/// 自适应编码的采样行数
+pub const ENCODING_SAMPLE_ROWS: usize = 1024;
+
+impl Default for SegmentWriterOptions {
+    fn default() -> Self {
+        Self {
+            page_max_rows:       PAGE_MAX_ROWS,
+            page_max_bytes:      PAGE_TARGET_BYTES,
+            short_key_interval:  SHORT_KEY_INTERVAL,
+            default_compression: None,
+            bloom_fpp:           BloomFilter::DEFAULT_FPP,
+            bloom_granularity:   BloomGranularity::Segment,
+            truncate_long_bytes: false,
+            checksum:            ChecksumType::Crc32,
+            adaptive_encoding:   false,
+            bitmap_index_columns: Vec::new(),
+            global_dictionary_columns: Vec::new(),
+            enforce_sort_order:  false,
+            encryption:          None,
+        }
+    }
+}
+
+/// 写入完成后的字节分布，用于存储统计与合并代价估算
+///
+/// `header_bytes + Σdata + Σindex + short_key_bytes + footer_bytes == total_bytes`，
+/// 其中 footer 含 footer 本体、校验值、长度与尾部 MAGIC。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentWriteReport {
+    pub total_bytes:            u64,
+    pub header_bytes:           u64,
+    /// 各列数据页（压缩后）字节数
+    pub per_column_data_bytes:  Vec<u64>,
+    /// 各列 Ordinal + ZoneMap + BloomFilter + 全局字典 + BitmapIndex 字节数
+    pub per_column_index_bytes: Vec<u64>,
+    pub short_key_bytes:        u64,
+    pub footer_bytes:           u64,
+    pub num_rows:               u32,
+    /// 写入 footer 的内容摘要，见 `SegmentReader::verify_digest`
+    pub content_digest:         u64,
+}
+
+/// Segment 写入器
+///
+/// 数据页写满即刷入输出端 `W`，内存中只保留各列当前页与索引结构。
+/// 默认 `W = Vec<u8>`：`new` + `finalize` 在内存中组装整个 Segment；
+/// 大 Segment 应使用 `with_sink` + `finish` 直接流式写到文件。
+pub struct SegmentWriter<W: Write = Vec<u8>> {
     schema:      Vec<ColumnMeta>,
     col_writers: Vec<ColumnWriter>,
     sk_builder:  ShortKeyIndexBuilder,
     num_rows:    u32,
     /// key 列在 schema 中的索引
     key_col_ids: Vec<usize>,
+    sink:        PageSink<W>,
+    /// 超长 Bytes 值截断而非报错
+    truncate:    bool,
+    checksum:    ChecksumType,
+    /// 自适应编码时尚未写入的样本行；选定编码后为 None
+    sample:      Option<Vec<Vec<Value>>>,
+    /// 开启 `enforce_sort_order` 时为 Some，保存上一行各 key 列的 sort key
+    last_key:    Option<Vec<Vec<u8>>>,
+    /// 开启加密时写入 footer 的密钥校验块
+    key_check:   Option<[u8; KEY_CHECK_SIZE]>,
 }
 
-impl SegmentWriter {
+impl SegmentWriter<Vec<u8>> {
     pub fn new(schema: Vec<ColumnMeta>) -> Self {
-        let key_col_ids: Vec<usize> = (0..schema.len()).collect(); // 简化：前几列为 key
-        let col_writers: Vec<ColumnWriter> = schema.iter()
-            .map(|m| ColumnWriter::new(m.clone()))
+        Self::with_options(schema, SegmentWriterOptions::default())
+    }
+
+    pub fn with_options(schema: Vec<ColumnMeta>, opts: SegmentWriterOptions) -> Self {
+        Self::with_sink_options(schema, Vec::new(), opts)
+            .expect("writing to Vec<u8> cannot fail")
+    }
+
+    /// 按表 schema 推导各列 `ColumnMeta`（见 `TabletSchema::segment_schema`），
+    /// 读取时用同一个 `segment_schema()` 打开
+    ///
+    /// Unique/Aggregate 表开启 `enforce_sort_order`，Duplicate 表不检查。
+    pub fn from_tablet_schema(schema: &TabletSchema) -> Self {
+        let opts = SegmentWriterOptions {
+            enforce_sort_order: schema.keys_type != KeysType::Duplicate,
+            ..Default::default()
+        };
+        Self::with_options(schema.segment_schema(), opts)
+    }
+
+    /// 完成写入，将整个 Segment 序列化到字节流
+    pub fn finalize<X: Write>(self, writer: X) -> Result<u64> {
+        Ok(self.finalize_with_report(writer)?.total_bytes)
+    }
+
+    /// 同 `finalize`，并返回各区域的字节分布
+    pub fn finalize_with_report<X: Write>(self, mut writer: X) -> Result<SegmentWriteReport> {
+        let (buf, report) = self.finish_with_report()?;
+        writer.write_all(&buf).map_err(|e| OlapError::SegmentIo(e.to_string()))?;
+        Ok(report)
+    }
+}
+
+impl<W: Write> SegmentWriter<W> {
+    /// 流式写入：立即写出文件头，后续数据页写满即写入 `sink`
+    pub fn with_sink(schema: Vec<ColumnMeta>, sink: W) -> Result<Self> {
+        Self::with_sink_options(schema, sink, SegmentWriterOptions::default())
+    }
+
+    pub fn with_sink_options(
+        mut schema: Vec<ColumnMeta>,
+        sink:       W,
+        opts:       SegmentWriterOptions,
+    ) -> Result<Self> {
+        if let Some(comp) = opts.default_compression {
+            schema.iter_mut().for_each(|m| m.compression = comp);
+        }
+        // ShortKey 前缀只由 key 列拼成；没有 key 列时前缀为空
+        let key_col_ids: Vec<usize> = (0..schema.len()).filter(|&i| schema[i].is_key).collect();
+        let col_writers: Vec<ColumnWriter> = schema.iter().enumerate()
+            .map(|(i, m)| {
+                ColumnWriter::new(m.clone())
+                    .with_page_max_rows(opts.page_max_rows)
+                    .with_page_max_bytes(opts.page_max_bytes)
+                    .with_bloom_fpp(opts.bloom_fpp)
+                    .with_bloom_granularity(opts.bloom_granularity)
+                    .with_checksum(opts.checksum)
+                    .with_bitmap_index(opts.bitmap_index_columns.contains(&i))
+                    .with_global_dictionary(opts.global_dictionary_columns.contains(&i))
+                    .with_encryption(opts.encryption.clone())
+            })
             .collect();
-        Self {
+        let key_check = opts.encryption.as_ref().map(encryption::key_check).transpose()?;
+
+        // ── 文件头 ────────────────────────────────────────────────────────────
+        let mut sink = PageSink::new(sink);
+        sink.write_all(MAGIC)?;
+        sink.write_all(&VERSION.to_le_bytes())?;
+        sink.write_all(&[FOOTER_VERSION, opts.checksum.tag()])?;
+
+        Ok(Self {
             schema,
             col_writers,
-            sk_builder: ShortKeyIndexBuilder::new(),
+            sk_builder: ShortKeyIndexBuilder::new().with_interval(opts.short_key_interval),
             num_rows: 0,
             key_col_ids,
-        }
+            sink,
+            truncate: opts.truncate_long_bytes,
+            checksum: opts.checksum,
+            sample: opts.adaptive_encoding.then(Vec::new),
+            last_key: opts.enforce_sort_order.then(Vec::new),
+            key_check,
+        })
     }
 
-    /// 追加一行，`row` 的长度必须等于列数
-    pub fn append_row(&mut self, row: Vec<Value>) -> Result<()> {
+    /// 追加一行，`row` 的长度必须等于列数；已删除列的值被忽略，写入 NULL
+    ///
+    /// 长度不符（含非空 schema 收到空行）时返回 `RowWidthMismatch(列数, 行长度)`；
+    /// 非空列写入 NULL 或值类型与列类型不符时返回带列下标的 `SchemaMismatch`，
+    /// Bytes 值超过 `max_length`（非 0 时）按 `truncate_long_bytes` 截断或返回 `ValueTooLong`；
+    /// 开启 `enforce_sort_order` 时 key 小于上一行返回 `Unsupported`。出错时不写入任何值。
+    pub fn append_row(&mut self, mut row: Vec<Value>) -> Result<()> {
         if row.len() != self.col_writers.len() {
-            return Err(OlapError::SchemaMismatch);
+            return Err(OlapError::RowWidthMismatch(self.col_writers.len(), row.len()));
+        }
+        for (i, value) in row.iter_mut().enumerate() {
+            self.check_value(i, value)?;
+        }
+        if let Some(last) = &mut self.last_key {
+            // 逐列比较，避免变长 key 拼接后比较出错
+            let key: Vec<Vec<u8>> = self.key_col_ids.iter().map(|&i| row[i].to_sort_key()).collect();
+            if key < *last {
+                return Err(OlapError::Unsupported("rows not in key order".into()));
+            }
+            *last = key;
+        }
+
+        if let Some(sample) = &mut self.sample {
+            sample.push(row);
+            if sample.len() >= ENCODING_SAMPLE_ROWS {
+                self.flush_sample()?;
+            }
+            return Ok(());
+        }
+        self.write_row(row)
+    }
+
+    /// 按列追加一批行：`columns[i]` 为第 i 列的全部值，各列长度必须相同
+    ///
+    /// 列数不符返回 `RowWidthMismatch(列数, columns.len())`，各列长度不一返回 `SchemaMismatch`；
+    /// 逐值校验与 `append_row` 相同，任一值出错时整批都不写入。
+    /// 值直接交给各列的 `ColumnWriter`，不逐行组装 `Vec<Value>`；输出与逐行追加逐字节一致。
+    pub fn append_batch(&mut self, mut columns: Vec<Vec<Value>>) -> Result<()> {
+        if columns.len() != self.col_writers.len() {
+            return Err(OlapError::RowWidthMismatch(self.col_writers.len(), columns.len()));
+        }
+        let num_rows = columns.first().map_or(0, Vec::len);
+        if let Some((i, col)) = columns.iter().enumerate().find(|(_, c)| c.len() != num_rows) {
+            return Err(OlapError::SchemaMismatch(format!(
+                "column {i} has {} values, expected {num_rows}", col.len(),
+            )));
+        }
+        for (i, col) in columns.iter_mut().enumerate() {
+            for value in col {
+                self.check_value(i, value)?;
+            }
+        }
+        if let Some(last) = &self.last_key {
+            let mut prev: Option<Vec<Vec<u8>>> = None;
+            let keys = (0..num_rows)
+                .map(|row| self.key_col_ids.iter().map(|&i| columns[i][row].to_sort_key()).collect::<Vec<_>>());
+            for key in keys {
+                if key < *prev.as_ref().unwrap_or(last) {
+                    return Err(OlapError::Unsupported("rows not in key order".into()));
+                }
+                prev = Some(key);
+            }
+            if let Some(key) = prev {
+                self.last_key = Some(key);
+            }
+        }
+
+        // 自适应编码尚未选定时，批首的行先补足样本
+        let mut start = 0;
+        if let Some(sample) = &mut self.sample {
+            start = (ENCODING_SAMPLE_ROWS - sample.len()).min(num_rows);
+            sample.extend((0..start).map(|row| columns.iter().map(|c| c[row].clone()).collect()));
+            if sample.len() >= ENCODING_SAMPLE_ROWS {
+                self.flush_sample()?;
+            }
+        }
+        if start == num_rows {
+            return Ok(());
+        }
+
+        // 按行交错喂给各列，数据页的刷出顺序与逐行追加相同，输出逐字节一致
+        for (row, row_id) in (start..num_rows).zip(self.num_rows..) {
+            if self.sk_builder.is_sampled(row_id) {
+                let key_vals: Vec<Value> = self.key_col_ids.iter().map(|&i| columns[i][row].clone()).collect();
+                self.sk_builder.maybe_add(row_id, &key_vals);
+            }
+            for (col, values) in self.col_writers.iter_mut().zip(&mut columns) {
+                let value = std::mem::replace(&mut values[row], Value::Null);
+                let value = if col.meta.is_dropped { Value::Null } else { value };
+                col.add_value(value, &mut self.sink)?;
+            }
+        }
+        self.num_rows += (num_rows - start) as u32;
+        Ok(())
+    }
+
+    /// 校验第 `i` 列的一个值；超长 Bytes 按 `truncate_long_bytes` 原地截断，已删除列不校验
+    fn check_value(&self, i: usize, value: &mut Value) -> Result<()> {
+        let meta = &self.col_writers[i].meta;
+        if meta.is_dropped {
+            return Ok(());
+        }
+        if *value == Value::Null && !meta.is_nullable {
+            return Err(OlapError::SchemaMismatch(format!(
+                "column {i} ({}) is not nullable", meta.name,
+            )));
+        }
+        if !meta.field_type.accepts(value) {
+            return Err(OlapError::SchemaMismatch(format!(
+                "column {i} ({}) expects {:?}, got {value:?}", meta.name, meta.field_type,
+            )));
+        }
+        if let Value::Bytes(b) = value {
+            let max = meta.max_length as usize;
+            if max > 0 && b.len() > max {
+                if !self.truncate {
+                    return Err(OlapError::ValueTooLong(i, b.len(), meta.max_length));
+                }
+                b.truncate(max);
+            }
         }
+        Ok(())
+    }
+
+    /// 按样本为各列选定编码，再依次写入样本行
+    fn flush_sample(&mut self) -> Result<()> {
+        let Some(sample) = self.sample.take() else { return Ok(()) };
+        for (i, cw) in self.col_writers.iter_mut().enumerate() {
+            if cw.meta.is_dropped { continue; }
+            let values: Vec<Value> = sample.iter().map(|row| row[i].clone()).collect();
+            let encoding = encoding::choose_encoding(&values, cw.meta.field_type);
+            cw.set_encoding(encoding);
+            self.schema[i].encoding = encoding;
+        }
+        sample.into_iter().try_for_each(|row| self.write_row(row))
+    }
 
+    /// 写入一行已校验的值
+    fn write_row(&mut self, row: Vec<Value>) -> Result<()> {
         // ShortKey 每 1024 行记录一次前缀
         let key_vals: Vec<Value> = self.key_col_ids.iter()
             .filter_map(|&i| row.get(i).cloned())
             .collect();
         self.sk_builder.maybe_add(self.num_rows, &key_vals);
 
-        for (i, col) in self.col_writers.iter_mut().enumerate() {
-            col.add_value(row[i].clone())?;
+        for (col, value) in self.col_writers.iter_mut().zip(row) {
+            let value = if col.meta.is_dropped { Value::Null } else { value };
+            col.add_value(value, &mut self.sink)?;
         }
         self.num_rows += 1;
         Ok(())
     }
 
-    /// 完成写入，将整个 Segment 序列化到字节流
-    pub fn finalize<W: Write>(mut self, mut writer: W) -> Result<u64> {
-        let mut pos: u64 = 0;
+    /// 刷出剩余数据页并写入索引区与 footer，返回输出端与 Segment 总字节数
+    pub fn finish(self) -> Result<(W, u64)> {
+        let (sink, report) = self.finish_with_report()?;
+        Ok((sink, report.total_bytes))
+    }
 
-        // ── 文件头 ────────────────────────────────────────────────────────────
-        writer.write_all(MAGIC).map_err(|e| OlapError::SegmentIo(e.to_string()))?;
-        writer.write_all(&VERSION.to_le_bytes()).map_err(|e| OlapError::SegmentIo(e.to_string()))?;
-        pos += 12;
-
-        // ── DATA REGION ───────────────────────────────────────────────────────
-        let num_cols = self.col_writers.len();
-        let mut col_data:      Vec<Vec<u8>>      = Vec::with_capacity(num_cols);
-        let mut col_offsets:   Vec<u64>          = Vec::with_capacity(num_cols);
-        let mut ordinal_idxs:  Vec<OrdinalIndex> = Vec::new();
-        let mut zonemap_idxs:  Vec<ZoneMapIndex> = Vec::new();
-        let mut bloom_filters: Vec<BloomFilter>  = Vec::new();
-
-        for cw in self.col_writers {
-            let ordinal = cw.ordinal_index.clone();
-            let zonemap = cw.zone_map.clone();
-            let bf      = cw.bloom_filter.clone();
-            ordinal_idxs.push(ordinal);
-            zonemap_idxs.push(zonemap);
-            bloom_filters.push(bf);
-
-            let (data, _) = cw.finalize()?;
-            col_offsets.push(pos);
-            writer.write_all(&data).map_err(|e| OlapError::SegmentIo(e.to_string()))?;
-            pos += data.len() as u64;
-            col_data.push(data);
+    /// 同 `finish`，并返回各区域的字节分布
+    pub fn finish_with_report(mut self) -> Result<(W, SegmentWriteReport)> {
+        self.flush_sample()?;
+        // ── DATA REGION（剩余未满的页）─────────────────────────────────────────
+        for cw in &mut self.col_writers {
+            cw.flush(&mut self.sink)?;
         }
 
         // ── INDEX REGION ──────────────────────────────────────────────────────
+        let sink = &mut self.sink;
         let mut col_index_metas: Vec<ColumnIndexMeta> = Vec::new();
+        let mut per_column_index_bytes = Vec::with_capacity(self.col_writers.len());
 
-        for i in 0..num_cols {
-            let ord_bytes = ordinal_idxs[i].serialize();
-            let zm_bytes  = zonemap_idxs[i].serialize();
-            let bf_bytes  = bloom_filters[i].serialize();
+        for cw in &self.col_writers {
+            let ord_bytes    = cw.ordinal_index.serialize();
+            let zm_bytes     = cw.zone_map.serialize();
+            let bf_bytes     = cw.bloom_bytes();
+            let dict_bytes   = cw.dictionary_bytes();
+            let bitmap_bytes = cw.bitmap_bytes();
+            let pos          = sink.position();
+            let dict_offset  = pos + (ord_bytes.len() + zm_bytes.len() + bf_bytes.len()) as u64;
 
             let cm = ColumnIndexMeta {
+                encoding:       cw.meta.encoding,
+                compression:    cw.meta.compression,
+                data_size:      cw.data_size(),
                 ordinal_offset: pos,
                 ordinal_size:   ord_bytes.len() as u64,
                 zonemap_offset: pos + ord_bytes.len() as u64,
                 zonemap_size:   zm_bytes.len() as u64,
                 bf_offset:      pos + ord_bytes.len() as u64 + zm_bytes.len() as u64,
                 bf_size:        bf_bytes.len() as u64,
+                dict_offset,
+                dict_size:      dict_bytes.len() as u64,
+                bitmap_offset:  dict_offset + dict_bytes.len() as u64,
+                bitmap_size:    bitmap_bytes.len() as u64,
+                dictionary:     OnceLock::new(),
+                baseline_ordinal: None,
             };
 
-            writer.write_all(&ord_bytes).map_err(|e| OlapError::SegmentIo(e.to_string()))?;
-            writer.write_all(&zm_bytes).map_err(|e| OlapError::SegmentIo(e.to_string()))?;
-            writer.write_all(&bf_bytes).map_err(|e| OlapError::SegmentIo(e.to_string()))?;
-            pos += (ord_bytes.len() + zm_bytes.len() + bf_bytes.len()) as u64;
+            sink.write_all(&ord_bytes)?;
+            sink.write_all(&zm_bytes)?;
+            sink.write_all(&bf_bytes)?;
+            sink.write_all(&dict_bytes)?;
+            sink.write_all(&bitmap_bytes)?;
 
+            per_column_index_bytes.push(sink.position() - pos);
             col_index_metas.push(cm);
         }
 
         // ShortKey Index
         let sk_bytes  = self.sk_builder.index.serialize();
-        let sk_offset = pos;
+        let sk_offset = sink.position();
         let sk_size   = sk_bytes.len() as u64;
-        writer.write_all(&sk_bytes).map_err(|e| OlapError::SegmentIo(e.to_string()))?;
-        pos += sk_size;
+        sink.write_all(&sk_bytes)?;
 
         // ── FOOTER ────────────────────────────────────────────────────────────
+        let content_digest = sink.digest();
         let footer = SegmentFooter {
             num_rows:         self.num_rows,
-            num_columns:      num_cols as u32,
+            num_columns:      self.col_writers.len() as u32,
             short_key_offset: sk_offset,
             short_key_size:   sk_size,
             column_metas:     col_index_metas,
+            content_digest:   Some(content_digest),
+            key_check:        self.key_check,
         };
 
-        let footer_bytes = footer.serialize();
-        let footer_crc   = crc32fast::hash(&footer_bytes);
-        let footer_len   = footer_bytes.len() as u32;
+        let footer_bytes  = footer.serialize();
+        let footer_sum    = self.checksum.encode(&footer_bytes);
+        let footer_len    = footer_bytes.len() as u32;
+        let footer_offset = sink.position();
 
-        writer.write_all(&footer_bytes).map_err(|e| OlapError::SegmentIo(e.to_string()))?;
-        writer.write_all(&footer_crc.to_le_bytes()).map_err(|e| OlapError::SegmentIo(e.to_string()))?;
-        writer.write_all(&footer_len.to_le_bytes()).map_err(|e| OlapError::SegmentIo(e.to_string()))?;
-        writer.write_all(MAGIC).map_err(|e| OlapError::SegmentIo(e.to_string()))?;
-        pos += footer_bytes.len() as u64 + 16;
+        sink.write_all(&footer_bytes)?;
+        sink.write_all(&footer_sum)?;
+        sink.write_all(&footer_len.to_le_bytes())?;
+        sink.write_all(MAGIC)?;
 
-        Ok(pos)
+        let total_bytes = sink.position();
+        let report = SegmentWriteReport {
+            total_bytes,
+            header_bytes:          (MAGIC.len() + 4 + 2) as u64,
+            per_column_data_bytes: self.col_writers.iter().map(|cw| cw.data_size()).collect(),
+            per_column_index_bytes,
+            short_key_bytes:       sk_size,
+            footer_bytes:          total_bytes - footer_offset,
+            num_rows:              self.num_rows,
+            content_digest,
+        };
+        Ok((self.sink.into_inner(), report))
     }
 
-    pub fn num_rows(&self) -> u32 { self.num_rows }
+    pub fn num_rows(&self) -> u32 {
+        self.num_rows + self.sample.as_ref().map_or(0, |s| s.len() as u32)
+    }
     pub fn schema(&self) -> &[ColumnMeta] { &self.schema }
 }
 
 // ── SegmentReader ─────────────────────────────────────────────────────────────
 
+/// Segment 字节来源：内存缓冲区、共享的内存缓冲区或只读文件映射
+enum SegmentData {
+    Owned(Vec<u8>),
+    Shared(Arc<Vec<u8>>),
+    Mapped(memmap2::Mmap),
+}
+
+impl Deref for SegmentData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Self::Owned(v)  => v,
+            Self::Shared(v) => v,
+            Self::Mapped(m) => m,
+        }
+    }
+}
+
+/// 单列统计信息，供代价估算使用
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnStats {
+    pub num_pages:         usize,
+    pub num_rows:          u32,
+    /// 非 NULL 值的最小/最大值；全部为 NULL 时为 None
+    pub min:               Option<Value>,
+    pub max:               Option<Value>,
+    pub has_null:          bool,
+    /// 数据页落盘字节数（含页头与校验值）
+    pub compressed_size:   u64,
+    /// 以未压缩 payload 计的数据页字节数
+    pub uncompressed_size: u64,
+}
+
 pub struct SegmentReader {
-    data:   Vec<u8>,
-    footer: SegmentFooter,
-    schema: Vec<ColumnMeta>,
+    data:          SegmentData,
+    footer:        SegmentFooter,
+    schema:        Vec<ColumnMeta>,
+    /// 由文件版本决定的数据页头布局
+    page_format:   PageFormat,
+    /// 数据页与 footer 的校验算法
+    checksum:      ChecksumType,
+    /// footer 起始偏移，即内容摘要覆盖的字节数
+    footer_offset: usize,
+    /// 解密数据页的密钥，见 `with_encryption_key`
+    encryption_key: Option<EncryptionKey>,
 }
 
 impl SegmentReader {
     /// 从内存字节解析 Segment
     pub fn open(data: Vec<u8>, schema: Vec<ColumnMeta>) -> Result<Self> {
+        Self::from_data(SegmentData::Owned(data), schema)
+    }
+
+    /// 从共享的内存字节解析 Segment，多个 reader 可共用同一份缓冲区
+    pub fn open_shared(data: Arc<Vec<u8>>, schema: Vec<ColumnMeta>) -> Result<Self> {
+        Self::from_data(SegmentData::Shared(data), schema)
+    }
+
+    /// 以只读 mmap 方式打开 Segment 文件，页按需由操作系统换入
+    pub fn open_path(path: &Path, schema: Vec<ColumnMeta>) -> Result<Self> {
+        let io_err = |e: std::io::Error| OlapError::SegmentIo(format!("{}: {e}", path.display()));
+        let file = std::fs::File::open(path).map_err(io_err)?;
+        // SAFETY: Segment 文件写完即不可变，映射期间不会被截断或改写
+        let mmap = unsafe { memmap2::Mmap::map(&file) }.map_err(io_err)?;
+        Self::from_data(SegmentData::Mapped(mmap), schema)
+    }
+
+    fn from_data(data: SegmentData, schema: Vec<ColumnMeta>) -> Result<Self> {
         let n = data.len();
         if n < 20 || &data[n-8..] != MAGIC {
             return Err(OlapError::SegmentIo("invalid segment magic".into()));
         }
+        // 文件头：MAGIC + VERSION（+ footer 版本字节），防止尾部恰好匹配的截断/异类文件
+        if &data[0..8] != MAGIC {
+            return Err(OlapError::SegmentIo("invalid segment header magic".into()));
+        }
+        let version = u32::from_le_bytes(data[8..12].try_into().unwrap());
+        // 基线 version 2 文件头没有 footer 版本字节，footer 为基线布局；version≤4 统一使用 CRC32
+        let (footer_version, page_format, checksum_tag, header_len) = match version {
+            LEGACY_VERSION           => (None, PageFormat::Baseline, ChecksumType::Crc32.tag(), 12),
+            NO_PAGE_CODEC_VERSION    => (Some(data[12]), PageFormat::Legacy, ChecksumType::Crc32.tag(), 13),
+            NO_CHECKSUM_TYPE_VERSION => (Some(data[12]), PageFormat::Current, ChecksumType::Crc32.tag(), 13),
+            VERSION                  => (Some(data[12]), PageFormat::Current, data[13], 14),
+            v => return Err(OlapError::SegmentIo(format!("unknown segment version {v}"))),
+        };
+        let footer_layout = footer_version
+            .map(|v| FooterLayout::of(v).ok_or_else(|| {
+                OlapError::Unsupported(format!("segment footer version {v}"))
+            }))
+            .transpose()?;
+        let checksum = ChecksumType::from_tag(checksum_tag).ok_or_else(|| {
+            OlapError::Unsupported(format!("segment checksum type {checksum_tag}"))
+        })?;
+        let sum_end      = n - 12;
+        let sum_start    = sum_end.checked_sub(checksum.size())
+            .filter(|&start| start >= header_len)
+            .ok_or_else(|| OlapError::SegmentIo(format!("segment too short: {n} bytes")))?;
+        // footer_len 来自文件尾部，截断或损坏时可能大于剩余空间
         let footer_len   = u32::from_le_bytes(data[n-12..n-8].try_into().unwrap()) as usize;
-        let footer_crc   = u32::from_le_bytes(data[n-16..n-12].try_into().unwrap());
-        let footer_start = n - 16 - footer_len;
-        let footer_bytes = &data[footer_start..footer_start + footer_len];
+        if footer_len > sum_start - header_len {
+            return Err(OlapError::SegmentIo(format!(
+                "footer length {footer_len} exceeds segment size {n}",
+            )));
+        }
+        let footer_start = sum_start - footer_len;
+        let footer_bytes = &data[footer_start..sum_start];
 
-        if crc32fast::hash(footer_bytes) != footer_crc {
+        if !checksum.verify(footer_bytes, &data[sum_start..sum_end]) {
             return Err(OlapError::ChecksumMismatch);
         }
 
-        let footer = SegmentFooter::deserialize(footer_bytes)
-            .ok_or_else(|| OlapError::SegmentIo("cannot parse footer".into()))?;
+        let footer = match footer_layout {
+            None         => SegmentFooter::deserialize_baseline(footer_bytes, &data, &schema)?,
+            Some(layout) => SegmentFooter::deserialize(footer_bytes, layout)
+                .ok_or_else(|| OlapError::SegmentIo("cannot parse footer".into()))?,
+        };
+
+        // 加密 Segment 的页头在 codec 之后多出 nonce
+        let page_format = if footer.key_check.is_some() { PageFormat::Encrypted } else { page_format };
+
+        Ok(Self {
+            data, footer, schema, page_format, checksum,
+            footer_offset:  footer_start,
+            encryption_key: None,
+        })
+    }
+
+    /// 数据页是否已加密；加密的 Segment 需先 `with_encryption_key` 才能读取列值
+    pub fn is_encrypted(&self) -> bool { self.footer.key_check.is_some() }
 
-        Ok(Self { data, footer, schema })
+    /// 设置解密数据页的密钥，按 footer 中的密钥校验块验证，不匹配时返回 `Encryption`
+    ///
+    /// 未加密的 Segment 忽略密钥。索引（ZoneMap、BloomFilter 等）为明文，无需密钥即可使用。
+    pub fn with_encryption_key(mut self, key: EncryptionKey) -> Result<Self> {
+        if let Some(check) = &self.footer.key_check {
+            encryption::verify_key(&key, check)?;
+            self.encryption_key = Some(key);
+        }
+        Ok(self)
     }
 
     pub fn num_rows(&self) -> u32 { self.footer.num_rows }
+    pub fn schema(&self) -> &[ColumnMeta] { &self.schema }
 
-    /// 读取指定列的所有页值（简化实现：返回所有值）
-    pub fn read_column(&self, col_idx: usize) -> Result<Vec<Value>> {
-        use crate::page::PageDecoder;
+    /// 写入时记录的内容摘要；旧版本 Segment 没有摘要时为 None
+    pub fn content_digest(&self) -> Option<u64> { self.footer.content_digest }
 
-        let cm   = self.footer.column_metas.get(col_idx)
-            .ok_or_else(|| OlapError::SegmentIo(format!("col {col_idx} not found")))?;
-        let meta = self.schema.get(col_idx)
-            .ok_or_else(|| OlapError::SegmentIo("schema mismatch".into()))?;
+    /// 重新计算 footer 之前全部字节的 xxHash64 并与 footer 中的摘要比较
+    ///
+    /// footer 自身由 footer 校验和保护（`open` 时已验证），二者合起来覆盖整个文件。
+    /// 不一致返回 `ChecksumMismatch`；旧版本 Segment 没有摘要时返回 `Unsupported`。
+    pub fn verify_digest(&self) -> Result<()> {
+        let expected = self.footer.content_digest
+            .ok_or_else(|| OlapError::Unsupported("segment has no content digest".into()))?;
+        if xxhash_rust::xxh64::xxh64(&self.data[..self.footer_offset], 0) != expected {
+            return Err(OlapError::ChecksumMismatch);
+        }
+        Ok(())
+    }
+
+    /// 列投影：只读取 `col_idxs` 指定的列，按请求顺序返回
+    ///
+    /// 先校验全部下标，未选中的列不做任何 I/O 与解码。
+    pub fn read_columns(&self, col_idxs: &[usize]) -> Result<Vec<Vec<Value>>> {
+        let num_columns = self.schema.len().max(self.footer.num_columns as usize);
+        if let Some(&bad) = col_idxs.iter().find(|&&i| i >= num_columns) {
+            return Err(OlapError::SegmentIo(format!(
+                "col {bad} out of range (num_columns={num_columns})"
+            )));
+        }
+        col_idxs.iter().map(|&i| self.read_column(i)).collect()
+    }
+
+    /// 同 `read_columns`，用至多 `num_threads` 个线程并发解码各列，结果仍按请求顺序返回
+    ///
+    /// 各列只读共享的文件字节，互不依赖；任一列出错时返回请求顺序中第一个错误。
+    pub fn read_columns_parallel(&self, col_idxs: &[usize], num_threads: usize) -> Result<Vec<Vec<Value>>> {
+        let num_threads = num_threads.clamp(1, col_idxs.len().max(1));
+        if num_threads == 1 {
+            return self.read_columns(col_idxs);
+        }
+        let num_columns = self.schema.len().max(self.footer.num_columns as usize);
+        if let Some(&bad) = col_idxs.iter().find(|&&i| i >= num_columns) {
+            return Err(OlapError::SegmentIo(format!(
+                "col {bad} out of range (num_columns={num_columns})"
+            )));
+        }
 
+        // 线程从共享游标领取下一列，宽窄不一的列也能均衡分配
+        let next = AtomicUsize::new(0);
+        let mut results: Vec<Option<Result<Vec<Value>>>> = Vec::new();
+        results.resize_with(col_idxs.len(), || None);
+        std::thread::scope(|s| {
+            let workers: Vec<_> = (0..num_threads)
+                .map(|_| s.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let pos = next.fetch_add(1, AtomicOrdering::Relaxed);
+                        let Some(&col_idx) = col_idxs.get(pos) else { break };
+                        done.push((pos, self.read_column(col_idx)));
+                    }
+                    done
+                }))
+                .collect();
+            for w in workers {
+                for (pos, r) in w.join().expect("column reader thread panicked") {
+                    results[pos] = Some(r);
+                }
+            }
+        });
+        results.into_iter().map(|r| r.expect("every column is read")).collect()
+    }
+
+    /// 读取指定列的所有页值（简化实现：返回所有值）
+    ///
+    /// `col_idx` 超出 Segment 存储的列数但在 schema 内时（加列前写入的 Segment），
+    /// 返回 num_rows 个该列默认值。
+    pub fn read_column(&self, col_idx: usize) -> Result<Vec<Value>> {
+        if let Some(default) = self.missing_column_default(col_idx) {
+            return Ok(vec![default; self.footer.num_rows as usize]);
+        }
+        let (cm, meta) = self.column(col_idx)?;
         // OrdinalIndex 告诉我们每页的偏移
-        let ord_data  = &self.data[cm.ordinal_offset as usize
-            ..(cm.ordinal_offset + cm.ordinal_size) as usize];
-        let ord_index = OrdinalIndex::deserialize(ord_data);
+        let ord_index  = self.ordinal_index(cm)?;
 
         let mut all_values = Vec::new();
-        let page_count = ord_index.page_count();
-
-        for page_idx in 0..page_count {
-            let page_off = ord_index.find_page_offset(page_idx as u32 * 1024)
-                .unwrap_or(0) as usize;
-            let next_off = if page_idx + 1 < page_count {
-                ord_index.find_page_offset((page_idx as u32 + 1) * 1024)
-                    .unwrap_or_else(|| cm.ordinal_offset) as usize
-            } else {
-                cm.ordinal_offset as usize
+        for &entry in ord_index.entries() {
+            // 容错：跳过损坏页；缺少或错误的密钥不属于损坏，直接报错
+            match self.decode_page(cm, meta.field_type, entry) {
+                Ok(decoded)                       => all_values.extend(decoded.values),
+                Err(e @ OlapError::Encryption(_)) => return Err(e),
+                Err(_)                            => {}
+            }
+        }
+
+        Ok(all_values)
+    }
+
+    /// 读取 [start_row, start_row + num_rows) 范围内的值，只解码与范围相交的页
+    ///
+    /// 范围超出 Segment 末尾时截断。
+    pub fn read_column_range(
+        &self,
+        col_idx:   usize,
+        start_row: u32,
+        num_rows:  u32,
+    ) -> Result<Vec<Value>> {
+        let end = start_row.saturating_add(num_rows).min(self.footer.num_rows);
+        if start_row >= end {
+            return Ok(vec![]);
+        }
+        if let Some(default) = self.missing_column_default(col_idx) {
+            return Ok(vec![default; (end - start_row) as usize]);
+        }
+        let (cm, meta) = self.column(col_idx)?;
+        let ord_index  = self.ordinal_index(cm)?;
+        if ord_index.page_count() == 0 {
+            return Err(OlapError::SegmentIo(format!("col {col_idx} has no pages")));
+        }
+        let (first, last) = ord_index.page_range_for_rows(start_row, end);
+
+        let mut out = Vec::with_capacity((end - start_row) as usize);
+        for &entry in &ord_index.entries()[first..=last] {
+            let decoded = self.decode_page(cm, meta.field_type, entry)?;
+            let first_rid = decoded.first_row_id;
+            out.extend(decoded.values.into_iter()
+                .enumerate()
+                .filter(|&(i, _)| (start_row..end).contains(&(first_rid + i as u32)))
+                .map(|(_, v)| v));
+        }
+        Ok(out)
+    }
+
+    /// 点查：按行号读出一整行，每列只解码包含该行的一页
+    ///
+    /// 已删除列返回 `Value::Null`，加列前写入的 Segment 缺失的列返回默认值。
+    pub fn get_row(&self, row_id: u32) -> Result<Vec<Value>> {
+        if row_id >= self.footer.num_rows {
+            return Err(OlapError::SegmentIo(format!(
+                "row {row_id} out of range (num_rows={})", self.footer.num_rows,
+            )));
+        }
+        let num_columns = self.schema.len().max(self.footer.column_metas.len());
+        (0..num_columns).map(|col_idx| self.get_value(col_idx, row_id)).collect()
+    }
+
+    fn get_value(&self, col_idx: usize, row_id: u32) -> Result<Value> {
+        if self.schema.get(col_idx).is_some_and(|m| m.is_dropped) {
+            return Ok(Value::Null);
+        }
+        if let Some(default) = self.missing_column_default(col_idx) {
+            return Ok(default);
+        }
+        let (cm, meta) = self.column(col_idx)?;
+        let entry = self.ordinal_index(cm)?.find_page(row_id)
+            .ok_or_else(|| OlapError::SegmentIo(format!("col {col_idx} has no pages")))?;
+        let mut decoded = self.decode_page(cm, meta.field_type, entry)?;
+        let offset = (row_id - decoded.first_row_id) as usize;
+        if offset >= decoded.values.len() {
+            return Err(OlapError::SegmentIo(format!("row {row_id} not in page of col {col_idx}")));
+        }
+        Ok(decoded.values.swap_remove(offset))
+    }
+
+    /// 用 ShortKey 索引定位 key 前缀的下界行号，可作为 `read_column_range` 的起点
+    ///
+    /// `key_prefix` 与写入时一致：各 key 列 `to_sort_key` 的拼接。返回值按
+    /// ShortKey 采样间隔对齐，真正的匹配行在其后（最多一个间隔内）。
+    pub fn seek_row_for_key(&self, key_prefix: &[u8]) -> Result<u32> {
+        let index = ShortKeyIndex::deserialize(
+            self.region(self.footer.short_key_offset, self.footer.short_key_size)?,
+        );
+        Ok(index.seek_start(key_prefix))
+    }
+
+    /// 按排序键区间 [lo_key, hi_key] 读取一列，返回 (row_id, value)，row_id 升序
+    ///
+    /// 键的格式同 `seek_row_for_key`。先用 ShortKey 索引的 `seek_start` 与 `upper_bound`
+    /// 把行区间缩到采样间隔粒度，只解码与之相交的页，再按各行 key 列的实际值精确过滤。
+    pub fn scan_key_range(
+        &self,
+        lo_key:  &[u8],
+        hi_key:  &[u8],
+        col_idx: usize,
+    ) -> Result<Vec<(u32, Value)>> {
+        if lo_key > hi_key {
+            return Ok(vec![]);
+        }
+        let index = ShortKeyIndex::deserialize(
+            self.region(self.footer.short_key_offset, self.footer.short_key_size)?,
+        );
+        let start = index.seek_start(lo_key);
+        let end   = index.upper_bound(hi_key).unwrap_or(self.footer.num_rows).min(self.footer.num_rows);
+        if start >= end {
+            return Ok(vec![]);
+        }
+
+        let len = end - start;
+        let mut keys = vec![Vec::new(); len as usize];
+        for key_col in (0..self.schema.len()).filter(|&i| self.schema[i].is_key) {
+            for (key, v) in keys.iter_mut().zip(self.read_column_range(key_col, start, len)?) {
+                key.extend_from_slice(&v.to_sort_key());
+            }
+        }
+        let values = self.read_column_range(col_idx, start, len)?;
+        Ok((start..end).zip(keys).zip(values)
+            .filter(|((_, key), _)| lo_key <= key.as_slice() && key.as_slice() <= hi_key)
+            .map(|((row_id, _), v)| (row_id, v))
+            .collect())
+    }
+
+    /// 谓词下推：用 ZoneMap 只解码 min/max 与 [probe_min, probe_max] 有交集的页
+    ///
+    /// 等值探测（`probe_min == probe_max`）且写入时启用了逐页 BloomFilter 时，
+    /// 还会跳过过滤器判定不含该值的页。返回 (row_id, value)，row_id 为 Segment 内的绝对行号。
+    pub fn read_column_filtered(
+        &self,
+        col_idx:   usize,
+        probe_min: &[u8],
+        probe_max: &[u8],
+    ) -> Result<Vec<(u32, Value)>> {
+        if let Some(default) = self.missing_column_default(col_idx) {
+            let key = default.to_sort_key();
+            if default == Value::Null || key.as_slice() < probe_min || key.as_slice() > probe_max {
+                return Ok(vec![]);
+            }
+            return Ok((0..self.footer.num_rows).map(|r| (r, default.clone())).collect());
+        }
+        let (cm, meta) = self.column(col_idx)?;
+        let ord_index  = self.ordinal_index(cm)?;
+        let zone_map   = ZoneMapIndex::deserialize(self.region(cm.zonemap_offset, cm.zonemap_size)?);
+        let page_blooms = if probe_min == probe_max {
+            self.bloom_filters(cm)?.1
+        } else {
+            PageBloomIndex::default()
+        };
+
+        // 没有 ZoneMap（基线 version 2 文件）时每页都是候选
+        let pages = if cm.zonemap_size == 0 {
+            (0..ord_index.page_count() as u32).collect()
+        } else {
+            zone_map.filter(probe_min, probe_max)
+        };
+        let mut out = Vec::new();
+        for page_idx in pages {
+            if !page_blooms.may_contain(page_idx as usize, probe_min) {
+                continue;
+            }
+            let entry = *ord_index.entries().get(page_idx as usize)
+                .ok_or_else(|| OlapError::SegmentIo(format!("page {page_idx} not in ordinal index")))?;
+            let decoded = self.decode_page(cm, meta.field_type, entry)?;
+            let first   = decoded.first_row_id;
+            out.extend(decoded.values.into_iter()
+                .enumerate()
+                .map(|(i, v)| (first + i as u32, v)));
+        }
+        Ok(out)
+    }
+
+    /// 流式读取一列：每次只解压一页，页内值按需解码，不物化整列
+    ///
+    /// 某页损坏或解码失败时产出一次 `Err` 后结束。
+    pub fn stream_column(
+        &self,
+        col_idx: usize,
+    ) -> Result<Box<dyn Iterator<Item = Result<Value>> + '_>> {
+        if let Some(default) = self.missing_column_default(col_idx) {
+            let n = self.footer.num_rows as usize;
+            return Ok(Box::new(std::iter::repeat_n(default, n).map(Ok)));
+        }
+        let (cm, meta) = self.column(col_idx)?;
+        let ord_index  = self.ordinal_index(cm)?;
+        let dict       = self.global_dictionary(cm)?;
+        let (encoding, field_type) = match dict {
+            Some(_) => (PAGE_DICT_CODE_ENCODING, FieldType::Int32),
+            None    => (cm.encoding, meta.field_type),
+        };
+
+        let values = ord_index.entries().to_vec().into_iter().flat_map(move |(_, off, size)| {
+            let page = self.region(off, size)
+                .and_then(|data| RawPage::open(
+                    data, self.page_format, self.checksum, self.encryption_key.as_ref(), cm.compression,
+                ));
+            let values: Box<dyn Iterator<Item = Result<Value>>> = match page {
+                Ok(page) => Box::new(page.into_values(encoding, field_type)),
+                Err(e)   => Box::new(std::iter::once(Err(e))),
             };
+            values
+        });
+        let mut failed = false;
+        Ok(Box::new(values
+            .map(move |v| match dict {
+                Some(dict) => v.and_then(|v| dictionary_value(dict, v)),
+                None       => v,
+            })
+            .take_while(move |v| {
+                let more = !failed;
+                failed |= v.is_err();
+                more
+            })))
+    }
 
-            if page_off >= next_off || next_off > self.data.len() {
+    /// 按 schema 列顺序逐行返回，每列的页在需要时才解码
+    ///
+    /// 各列页边界可以不同；某页解码失败时，该页覆盖的行都返回 `Err`，
+    /// 之后的行继续正常产出。已删除列不解码，固定返回 `Value::Null`。
+    pub fn row_iter(&self) -> impl Iterator<Item = Result<Vec<Value>>> + '_ {
+        let mut init_err = None;
+        let mut cursors  = Vec::with_capacity(self.footer.column_metas.len());
+        for col_idx in 0..self.footer.column_metas.len() {
+            if self.schema.get(col_idx).is_some_and(|m| m.is_dropped) {
+                cursors.push(None);
                 continue;
             }
-            let page_data = &self.data[page_off..next_off];
-            match PageDecoder::decode(page_data, meta.encoding, meta.compression) {
-                Ok(decoded) => all_values.extend(decoded.values),
-                Err(_)      => {} // 容错：跳过损坏页
+            match self.column(col_idx).and_then(|(cm, meta)| {
+                Ok(ColumnCursor::new(cm, meta.field_type, self.ordinal_index(cm)?))
+            }) {
+                Ok(c)  => cursors.push(Some(c)),
+                Err(e) => { init_err = Some(e); break; }
             }
         }
+        let defaults = (self.footer.column_metas.len()..self.schema.len())
+            .map(|i| self.missing_column_default(i).unwrap_or(Value::Null))
+            .collect();
+        RowIter { reader: self, cursors, defaults, row: 0, init_err }
+    }
+
+    /// 按 key 列分组读出全部行，并按 `schema` 的模型折叠 key 相同的行
+    ///
+    /// Aggregate 表对 value 列应用各自的 `aggregate_type`（NULL 不参与 Sum/Max/Min），
+    /// Unique 表保留最后一行，Duplicate 表原样返回。结果按每个 key 首次出现的顺序排列。
+    pub fn read_aggregated(&self, schema: &TabletSchema) -> Result<Vec<Vec<Value>>> {
+        if schema.columns.len() != self.schema.len() {
+            return Err(OlapError::SchemaMismatch(format!(
+                "tablet schema has {} columns, segment has {}", schema.columns.len(), self.schema.len(),
+            )));
+        }
+        let key_cols: Vec<usize> = (0..schema.columns.len())
+            .filter(|&i| schema.columns[i].is_key)
+            .collect();
+        let aggs: Vec<Option<AggregateType>> = schema.columns.iter()
+            .map(|c| (!c.is_key).then_some(c.aggregate_type))
+            .collect();
 
-        Ok(all_values)
+        let mut out: Vec<Vec<Value>> = Vec::new();
+        let mut groups: HashMap<Vec<Vec<u8>>, usize> = HashMap::new();
+        for row in self.row_iter() {
+            let row = row?;
+            if schema.keys_type == KeysType::Duplicate {
+                out.push(row);
+                continue;
+            }
+            let key = key_cols.iter().map(|&i| row[i].to_sort_key()).collect();
+            match groups.get(&key) {
+                Some(&idx) => fold_row(&mut out[idx], row, schema.keys_type, &aggs),
+                None => {
+                    groups.insert(key, out.len());
+                    out.push(row);
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// 列统计信息：min/max 来自 ZoneMap，大小与 NULL 信息来自 footer 与各页页头
+    ///
+    /// 不解压、不解码任何数据页。
+    pub fn column_stats(&self, col_idx: usize) -> Result<ColumnStats> {
+        let num_rows = self.footer.num_rows;
+        if let Some(default) = self.missing_column_default(col_idx) {
+            let has_null = default == Value::Null;
+            let bound    = (!has_null && num_rows > 0).then_some(default);
+            return Ok(ColumnStats {
+                num_pages: 0, num_rows,
+                min: bound.clone(), max: bound, has_null: has_null && num_rows > 0,
+                compressed_size: 0, uncompressed_size: 0,
+            });
+        }
+        let (cm, meta) = self.column(col_idx)?;
+        let ord_index  = self.ordinal_index(cm)?;
+        let zone_map   = ZoneMapIndex::deserialize(self.region(cm.zonemap_offset, cm.zonemap_size)?);
+
+        // 全 NULL 页的 min/max 为空串；旧文件没有 all_null 标志，对定长类型解析失败即被跳过
+        let mut min: Option<&[u8]> = None;
+        let mut max: Option<&[u8]> = None;
+        for e in zone_map.entries() {
+            if e.all_null || Value::from_sort_key(&e.min, meta.field_type).is_none() { continue; }
+            if min.is_none_or(|m| e.min.as_slice() < m) { min = Some(&e.min); }
+            if max.is_none_or(|m| e.max.as_slice() > m) { max = Some(&e.max); }
+        }
+
+        let mut has_null          = false;
+        let mut uncompressed_size = 0u64;
+        for &(_, offset, size) in ord_index.entries() {
+            let header   = PageHeader::parse_format(self.region(offset, size)?, self.page_format)?;
+            let overhead = header.header_len() + header.bitmap_len() + self.checksum.size();
+            has_null |= header.has_nulls;
+            uncompressed_size += (overhead + header.uncomp_size) as u64;
+        }
+
+        Ok(ColumnStats {
+            num_pages: ord_index.entries().len(),
+            num_rows,
+            min: min.and_then(|k| Value::from_sort_key(k, meta.field_type)),
+            max: max.and_then(|k| Value::from_sort_key(k, meta.field_type)),
+            has_null,
+            compressed_size: cm.data_size,
+            uncompressed_size,
+        })
+    }
+
+    /// 列的 BitmapIndex；写入时未为该列构建时返回 None
+    pub fn bitmap_index(&self, col_idx: usize) -> Result<Option<BitmapIndex>> {
+        if self.missing_column_default(col_idx).is_some() {
+            return Ok(None);
+        }
+        let (cm, _) = self.column(col_idx)?;
+        if cm.bitmap_size == 0 {
+            return Ok(None);
+        }
+        BitmapIndex::deserialize(self.region(cm.bitmap_offset, cm.bitmap_size)?).map(Some)
+    }
+
+    /// 等值查询：返回值等于 `value` 的行号（升序）；该列没有 BitmapIndex 时返回 None
+    ///
+    /// NULL 不入索引，查询 NULL 总是返回空。
+    pub fn rows_for_value(&self, col_idx: usize, value: &Value) -> Result<Option<Vec<u32>>> {
+        Ok(self.bitmap_index(col_idx)?.map(|bi| match value {
+            Value::Null => vec![],
+            v           => bi.rows_for_value(&v.to_sort_key()),
+        }))
+    }
+
+    /// 列的 Segment 级字典（按字典码顺序）；该列未使用全局字典时返回 None
+    pub fn dictionary(&self, col_idx: usize) -> Result<Option<&[Vec<u8>]>> {
+        if self.missing_column_default(col_idx).is_some() {
+            return Ok(None);
+        }
+        let (cm, _) = self.column(col_idx)?;
+        Ok(self.global_dictionary(cm)?.map(GlobalDictionary::entries))
+    }
+
+    /// 等值探测：返回 false 时本列一定不含 `value`，可跳过整个 Segment
+    pub fn may_contain(&self, col_idx: usize, value: &Value) -> Result<bool> {
+        if let Some(default) = self.missing_column_default(col_idx) {
+            return Ok(default == *value);
+        }
+        let (cm, _) = self.column(col_idx)?;
+        let (bf, _) = self.bloom_filters(cm)?;
+        Ok(bf.may_contain(&value.to_sort_key()))
+    }
+
+    // ── 内部辅助 ──────────────────────────────────────────────────────────────
+
+    /// schema 中有、Segment 中没有的列（加列前写入）返回其默认值；已删除列返回 None
+    fn missing_column_default(&self, col_idx: usize) -> Option<Value> {
+        if col_idx < self.footer.column_metas.len() {
+            return None;
+        }
+        let meta = self.schema.get(col_idx).filter(|m| !m.is_dropped)?;
+        Some(meta.default_value.clone().unwrap_or(Value::Null))
+    }
+
+    fn column(&self, col_idx: usize) -> Result<(&ColumnIndexMeta, &ColumnMeta)> {
+        if self.schema.get(col_idx).is_some_and(|m| m.is_dropped) {
+            return Err(OlapError::ColumnNotFound(self.schema[col_idx].column_id));
+        }
+        let cm   = self.footer.column_metas.get(col_idx)
+            .ok_or_else(|| OlapError::SegmentIo(format!("col {col_idx} not found")))?;
+        let meta = self.schema.get(col_idx)
+            .ok_or_else(|| OlapError::SegmentIo("schema mismatch".into()))?;
+        Ok((cm, meta))
+    }
+
+    /// 取文件中 [offset, offset+size) 区间，越界返回错误而非 panic
+    fn region(&self, offset: u64, size: u64) -> Result<&[u8]> {
+        let (start, end) = (offset as usize, (offset + size) as usize);
+        self.data.get(start..end)
+            .ok_or_else(|| OlapError::SegmentIo(format!("region [{start},{end}) out of bounds")))
+    }
+
+    /// 列级 BloomFilter 与（可能为空的）逐页 BloomFilter
+    fn bloom_filters(&self, cm: &ColumnIndexMeta) -> Result<(BloomFilter, PageBloomIndex)> {
+        let region = self.region(cm.bf_offset, cm.bf_size)?;
+        let bf     = BloomFilter::deserialize(region);
+        let pages  = PageBloomIndex::deserialize(region.get(bf.serialized_len()..).unwrap_or(&[]));
+        Ok((bf, pages))
+    }
+
+    fn ordinal_index(&self, cm: &ColumnIndexMeta) -> Result<OrdinalIndex> {
+        if let Some(ordinal) = &cm.baseline_ordinal {
+            return Ok(ordinal.clone());
+        }
+        Ok(OrdinalIndex::deserialize(self.region(cm.ordinal_offset, cm.ordinal_size)?))
+    }
+
+    /// 解码 OrdinalIndex 条目 (first_row_id, offset, size) 指向的一页
+    ///
+    /// 编码/压缩以页头与 footer 中记录的为准，而非调用方传入的 schema。
+    /// 使用全局字典的列先解出字典码，再映射回原值。
+    fn decode_page(
+        &self,
+        cm:         &ColumnIndexMeta,
+        field_type: FieldType,
+        entry:      (u32, u64, u64),
+    ) -> Result<PageDecoder> {
+        let (_, page_off, page_size) = entry;
+        let page_data = self.region(page_off, page_size)?;
+        let Some(dict) = self.global_dictionary(cm)? else {
+            return PageDecoder::decode_format(
+                page_data, self.page_format, self.checksum, self.encryption_key.as_ref(),
+                cm.encoding, cm.compression, field_type,
+            );
+        };
+        let mut decoded = PageDecoder::decode_format(
+            page_data, self.page_format, self.checksum, self.encryption_key.as_ref(),
+            PAGE_DICT_CODE_ENCODING, cm.compression, FieldType::Int32,
+        )?;
+        decoded.values = std::mem::take(&mut decoded.values).into_iter()
+            .map(|v| dictionary_value(dict, v))
+            .collect::<Result<_>>()?;
+        // 字典码只是全局字典的物理存储方式，对外报告列的编码
+        decoded.encoding_used = EncodingType::Dictionary;
+        Ok(decoded)
+    }
+
+    /// 列的全局字典，首次调用时从索引区加载；不使用全局字典的列返回 None
+    fn global_dictionary<'a>(&self, cm: &'a ColumnIndexMeta) -> Result<Option<&'a GlobalDictionary>> {
+        if cm.dict_size == 0 {
+            return Ok(None);
+        }
+        if let Some(dict) = cm.dictionary.get() {
+            return Ok(Some(dict));
+        }
+        let dict = GlobalDictionary::deserialize(self.region(cm.dict_offset, cm.dict_size)?)?;
+        Ok(Some(cm.dictionary.get_or_init(|| dict)))
+    }
+}
+
+/// 字典码还原为字典中的值，NULL 原样返回
+fn dictionary_value(dict: &GlobalDictionary, v: Value) -> Result<Value> {
+    let Value::Int32(code) = v else { return Ok(v) };
+    let entry = dict.get(code as u32).ok_or_else(|| {
+        OlapError::Encoding(format!("dictionary code {code} out of range ({})", dict.len()))
+    })?;
+    Ok(Value::Bytes(entry.to_vec()))
+}
+
+// ── 合并 ──────────────────────────────────────────────────────────────────────
+
+/// 将多个已按 key 列有序的 Segment 做 k 路归并，返回新 Segment 的字节
+///
+/// key 列由 `ColumnMeta::is_key` 指定，没有任何列标记为 key 时视全部列为 key。
+/// `inputs` 按从旧到新排列，key 相同的行按此顺序处理：
+///
+/// - `Duplicate`：保留全部行
+/// - `Unique`   ：只保留最后（最新）一行
+/// - `Aggregate`：按各 value 列的 `aggregate` 折叠（NULL 不参与 Sum/Max/Min）
+pub fn merge_segments(
+    inputs:    Vec<SegmentReader>,
+    schema:    &[ColumnMeta],
+    keys_type: KeysType,
+) -> Result<Vec<u8>> {
+    merge_segments_with_options(inputs, schema, keys_type, SegmentWriterOptions::default())
+}
+
+/// 同 `merge_segments`，输出按 `opts` 写出（例如加密）；加密的输入需已设置密钥
+pub fn merge_segments_with_options(
+    inputs:    Vec<SegmentReader>,
+    schema:    &[ColumnMeta],
+    keys_type: KeysType,
+    opts:      SegmentWriterOptions,
+) -> Result<Vec<u8>> {
+    if inputs.iter().any(|r| r.schema().len() != schema.len()) {
+        return Err(OlapError::SchemaMismatch(format!(
+            "merge input column count differs from schema ({} columns)", schema.len(),
+        )));
+    }
+    let mut key_cols: Vec<usize> = (0..schema.len()).filter(|&i| schema[i].is_key).collect();
+    if key_cols.is_empty() {
+        key_cols = (0..schema.len()).collect();
+    }
+    let sort_key = |row: &[Value]| -> Vec<Vec<u8>> {
+        key_cols.iter().map(|&i| row[i].to_sort_key()).collect()
+    };
+    let aggs: Vec<Option<AggregateType>> = schema.iter()
+        .map(|c| (!c.is_key).then_some(c.aggregate))
+        .collect();
+
+    let mut iters: Vec<_> = inputs.iter().map(|r| r.row_iter()).collect();
+    let mut heap = BinaryHeap::new();
+    for (src, it) in iters.iter_mut().enumerate() {
+        if let Some(row) = it.next() {
+            let row = row?;
+            heap.push(Reverse(MergeItem { key: sort_key(&row), src, row }));
+        }
+    }
+
+    let mut writer  = SegmentWriter::with_options(schema.to_vec(), opts);
+    let mut pending: Option<(Vec<Vec<u8>>, Vec<Value>)> = None;
+    while let Some(Reverse(MergeItem { key, src, row })) = heap.pop() {
+        if let Some(next) = iters[src].next() {
+            let next = next?;
+            heap.push(Reverse(MergeItem { key: sort_key(&next), src, row: next }));
+        }
+
+        if keys_type == KeysType::Duplicate {
+            writer.append_row(row)?;
+            continue;
+        }
+        match &mut pending {
+            Some((k, acc)) if *k == key => fold_row(acc, row, keys_type, &aggs),
+            _ => {
+                if let Some((_, done)) = pending.replace((key, row)) {
+                    writer.append_row(done)?;
+                }
+            }
+        }
+    }
+    if let Some((_, done)) = pending {
+        writer.append_row(done)?;
+    }
+    Ok(writer.finish()?.0)
+}
+
+/// 归并堆元素：按 (key, 输入序号) 排序，key 相同时旧输入先出
+struct MergeItem {
+    key: Vec<Vec<u8>>,
+    src: usize,
+    row: Vec<Value>,
+}
+
+impl Ord for MergeItem {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (&self.key, self.src).cmp(&(&other.key, other.src))
+    }
+}
+
+impl PartialOrd for MergeItem {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+
+impl PartialEq for MergeItem {
+    fn eq(&self, other: &Self) -> bool { self.cmp(other) == Ordering::Equal }
+}
+
+impl Eq for MergeItem {}
+
+/// 将 key 相同、较新的一行并入 `acc`：Unique 整行替换，Aggregate 逐列聚合
+///
+/// `aggs[i]` 为 None 表示 key 列，保持不变。
+fn fold_row(acc: &mut [Value], row: Vec<Value>, keys_type: KeysType, aggs: &[Option<AggregateType>]) {
+    match keys_type {
+        KeysType::Duplicate => {}
+        KeysType::Unique    => acc.clone_from_slice(&row),
+        KeysType::Aggregate => {
+            for ((a, v), agg) in acc.iter_mut().zip(row).zip(aggs) {
+                if let Some(agg) = agg {
+                    aggregate_into(a, v, *agg);
+                }
+            }
+        }
+    }
+}
+
+/// 将较新的值 `v` 按聚合方式并入 `acc`；NULL 是 Sum/Max/Min 的单位元
+fn aggregate_into(acc: &mut Value, v: Value, agg: AggregateType) {
+    match agg {
+        AggregateType::Replace => *acc = v,
+        AggregateType::None    => {}
+        _ if v == Value::Null  => {}
+        _ if *acc == Value::Null => *acc = v,
+        AggregateType::Sum => {
+            *acc = match (&*acc, &v) {
+                (Value::Int8(a),    Value::Int8(b))    => Value::Int8(a.wrapping_add(*b)),
+                (Value::Int16(a),   Value::Int16(b))   => Value::Int16(a.wrapping_add(*b)),
+                (Value::Int32(a),   Value::Int32(b))   => Value::Int32(a.wrapping_add(*b)),
+                (Value::Int64(a),   Value::Int64(b))   => Value::Int64(a.wrapping_add(*b)),
+                (Value::Float32(a), Value::Float32(b)) => Value::Float32(a + b),
+                (Value::Float64(a), Value::Float64(b)) => Value::Float64(a + b),
+                (Value::Decimal(a), Value::Decimal(b)) => Value::Decimal(a.wrapping_add(*b)),
+                _ => return,
+            }
+        }
+        AggregateType::Max => if v.to_sort_key() > acc.to_sort_key() { *acc = v },
+        AggregateType::Min => if v.to_sort_key() < acc.to_sort_key() { *acc = v },
+    }
+}
+
+// ── CSV 导入 ──────────────────────────────────────────────────────────────────
+
+/// 逐行解析 CSV 并写入新的 SegmentWriter
+///
+/// 字段按 `schema` 顺序转换：整数/浮点按十进制解析，Decimal 取未缩放的整数值，
+/// Date 接受天数或 `YYYY-MM-DD`，DateTime 接受微秒数或 `YYYY-MM-DD[T| ]HH:MM:SS[.ffffff][Z]`，
+/// Bool 接受 true/false/1/0，Bytes 取原文。可空列的空字段写入 `Value::Null`。
+/// 支持双引号包裹与 `""` 转义，不支持跨行字段。解析失败返回带行号（从 1 开始）的 `OlapError::Encoding`。
+pub fn load_csv(
+    reader:     impl BufRead,
+    schema:     &[ColumnMeta],
+    has_header: bool,
+) -> Result<SegmentWriter> {
+    let mut writer = SegmentWriter::new(schema.to_vec());
+    for (idx, line) in reader.lines().enumerate() {
+        let line_no = idx + 1;
+        let line = line.map_err(|e| OlapError::SegmentIo(format!("line {line_no}: {e}")))?;
+        if (has_header && idx == 0) || line.trim().is_empty() {
+            continue;
+        }
+        let fields = split_csv_line(&line)
+            .ok_or_else(|| OlapError::Encoding(format!("line {line_no}: unterminated quote")))?;
+        if fields.len() != schema.len() {
+            return Err(OlapError::Encoding(format!(
+                "line {line_no}: expected {} fields, got {}", schema.len(), fields.len()
+            )));
+        }
+        let row = fields.iter().zip(schema)
+            .map(|(f, col)| parse_csv_field(f, col).map_err(|msg| OlapError::Encoding(
+                format!("line {line_no}, column {}: {msg}", col.name)
+            )))
+            .collect::<Result<Vec<_>>>()?;
+        writer.append_row(row)?;
+    }
+    Ok(writer)
+}
+
+/// 拆分一行 CSV；引号未闭合时返回 None
+fn split_csv_line(line: &str) -> Option<Vec<String>> {
+    let mut fields    = Vec::new();
+    let mut cur       = String::new();
+    let mut in_quotes = false;
+    let mut chars     = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, in_quotes) {
+            ('"', true) if chars.peek() == Some(&'"') => { cur.push('"'); chars.next(); }
+            ('"', true)                             => in_quotes = false,
+            ('"', false) if cur.is_empty()          => in_quotes = true,
+            (',', false)                            => fields.push(std::mem::take(&mut cur)),
+            _                                       => cur.push(c),
+        }
+    }
+    if in_quotes { return None; }
+    fields.push(cur);
+    Some(fields)
+}
+
+fn parse_csv_field(field: &str, col: &ColumnMeta) -> std::result::Result<Value, String> {
+    // 空字段：可空列为 NULL，非空 Bytes 列为空串，其余报错
+    if field.is_empty() {
+        if col.is_nullable {
+            return Ok(Value::Null);
+        }
+        if col.field_type != FieldType::Bytes {
+            return Err("empty value in NOT NULL column".into());
+        }
+    }
+    let bad = |e: &dyn std::fmt::Display| format!("cannot parse {field:?} as {:?}: {e}", col.field_type);
+    let t = field.trim();
+    Ok(match col.field_type {
+        FieldType::Int8       => Value::Int8(t.parse().map_err(|e| bad(&e))?),
+        FieldType::Int16      => Value::Int16(t.parse().map_err(|e| bad(&e))?),
+        FieldType::Int32      => Value::Int32(t.parse().map_err(|e| bad(&e))?),
+        FieldType::Int64      => Value::Int64(t.parse().map_err(|e| bad(&e))?),
+        FieldType::Float32    => Value::Float32(t.parse().map_err(|e| bad(&e))?),
+        FieldType::Float64    => Value::Float64(t.parse().map_err(|e| bad(&e))?),
+        FieldType::Decimal128 => Value::Decimal(t.parse().map_err(|e| bad(&e))?),
+        FieldType::Bool       => Value::Bool(parse_bool(t).ok_or_else(|| bad(&"invalid bool"))?),
+        FieldType::DateTime   => Value::DateTime(
+            parse_datetime_micros(t).ok_or_else(|| bad(&"invalid datetime"))?,
+        ),
+        FieldType::Date       => Value::Int32(parse_date_days(t).ok_or_else(|| bad(&"invalid date"))?),
+        FieldType::Bytes      => Value::Bytes(field.as_bytes().to_vec()),
+    })
+}
+
+/// 接受 true/false/1/0（不区分大小写）
+fn parse_bool(s: &str) -> Option<bool> {
+    match s.to_ascii_lowercase().as_str() {
+        "true" | "1"  => Some(true),
+        "false" | "0" => Some(false),
+        _             => None,
+    }
+}
+
+/// 解析天数或 `YYYY-MM-DD`（转为距 1970-01-01 的天数）
+fn parse_date_days(s: &str) -> Option<i32> {
+    if let Ok(days) = s.parse::<i32>() {
+        return Some(days);
+    }
+    i32::try_from(parse_ymd(s)?).ok()
+}
+
+/// `YYYY-MM-DD` → 距 1970-01-01 的天数
+fn parse_ymd(s: &str) -> Option<i64> {
+    let mut parts = s.splitn(3, '-');
+    let y: i64 = parts.next()?.parse().ok()?;
+    let m: u32 = parts.next()?.parse().ok()?;
+    let d: u32 = parts.next()?.parse().ok()?;
+    if !(1..=12).contains(&m) || !(1..=31).contains(&d) {
+        return None;
+    }
+    Some(days_from_civil(y, m, d))
+}
+
+/// 解析微秒数或 `YYYY-MM-DD[T| ]HH:MM:SS[.ffffff][Z]`（UTC）
+fn parse_datetime_micros(s: &str) -> Option<i64> {
+    if let Ok(micros) = s.parse::<i64>() {
+        return Some(micros);
+    }
+    let s = s.strip_suffix('Z').unwrap_or(s);
+    let (date, time) = s.split_once(['T', ' ']).unwrap_or((s, "00:00:00"));
+    let (hms, frac)  = time.split_once('.').unwrap_or((time, ""));
+    let mut parts = hms.splitn(3, ':');
+    let h:   i64 = parts.next()?.parse().ok()?;
+    let min: i64 = parts.next()?.parse().ok()?;
+    let sec: i64 = parts.next()?.parse().ok()?;
+    if h > 23 || min > 59 || sec > 60 || frac.len() > 6 || !frac.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let micros = if frac.is_empty() { 0 } else { frac.parse::<i64>().ok()? * 10i64.pow(6 - frac.len() as u32) };
+    let secs = parse_ymd(date)? * 86_400 + h * 3600 + min * 60 + sec;
+    secs.checked_mul(1_000_000)?.checked_add(micros)
+}
+
+// ── 行迭代器 ──────────────────────────────────────────────────────────────────
+
+/// 单列的页游标：持有当前已解码页，按行号前进
+struct ColumnCursor<'a> {
+    cm:         &'a ColumnIndexMeta,
+    field_type: FieldType,
+    ord_index:  OrdinalIndex,
+    /// 下一个待解码页在 OrdinalIndex 中的下标
+    next_page:  usize,
+    values:     std::vec::IntoIter<Value>,
+    /// 当前页（或解码失败页）覆盖到的行号（不含）
+    page_end:   u32,
+    failed:     bool,
+}
+
+impl<'a> ColumnCursor<'a> {
+    fn new(cm: &'a ColumnIndexMeta, field_type: FieldType, ord_index: OrdinalIndex) -> Self {
+        Self {
+            cm, field_type, ord_index,
+            next_page: 0,
+            values:    Vec::new().into_iter(),
+            page_end:  0,
+            failed:    false,
+        }
+    }
+
+    fn next_value(&mut self, reader: &SegmentReader, row: u32) -> Result<Value> {
+        if row >= self.page_end {
+            let entries = self.ord_index.entries();
+            let entry   = *entries.get(self.next_page)
+                .ok_or_else(|| OlapError::SegmentIo(format!("row {row} beyond last page")))?;
+            self.page_end = entries.get(self.next_page + 1)
+                .map(|&(rid, _, _)| rid)
+                .unwrap_or(reader.footer.num_rows);
+            self.next_page += 1;
+
+            match reader.decode_page(self.cm, self.field_type, entry) {
+                Ok(decoded) => {
+                    self.values = decoded.values.into_iter();
+                    self.failed = false;
+                }
+                Err(e) => {
+                    self.failed = true;
+                    return Err(e);
+                }
+            }
+        }
+        if self.failed {
+            return Err(OlapError::SegmentIo(format!("row {row} lies in a corrupt page")));
+        }
+        self.values.next()
+            .ok_or_else(|| OlapError::SegmentIo(format!("page ended before row {row}")))
+    }
+}
+
+struct RowIter<'a> {
+    reader:   &'a SegmentReader,
+    /// None 为已删除列
+    cursors:  Vec<Option<ColumnCursor<'a>>>,
+    /// Segment 中缺失的尾部列，每行追加其默认值
+    defaults: Vec<Value>,
+    row:      u32,
+    init_err: Option<OlapError>,
+}
+
+impl Iterator for RowIter<'_> {
+    type Item = Result<Vec<Value>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(e) = self.init_err.take() {
+            self.row = self.reader.footer.num_rows;
+            return Some(Err(e));
+        }
+        if self.row >= self.reader.footer.num_rows {
+            return None;
+        }
+        let row = self.row;
+        self.row += 1;
+
+        // 每列都要前进一行，即使前面的列已出错，保证各列游标对齐
+        let mut out = Ok(Vec::with_capacity(self.cursors.len() + self.defaults.len()));
+        for cursor in &mut self.cursors {
+            let v = match cursor {
+                Some(c) => c.next_value(self.reader, row),
+                None    => Ok(Value::Null),
+            };
+            match (&mut out, v) {
+                (Ok(vals), Ok(v)) => vals.push(v),
+                (Ok(_), Err(e))   => out = Err(e),
+                (Err(_), _)       => {}
+            }
+        }
+        if let Ok(vals) = &mut out {
+            vals.extend(self.defaults.iter().cloned());
+        }
+        Some(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field_type::FieldType;
+
+    fn schema() -> Vec<ColumnMeta> {
+        vec![
+            ColumnMeta::new(0, "k", FieldType::Int64).key(),
+            ColumnMeta::new(1, "name", FieldType::Bytes).nullable(),
+            ColumnMeta::new(2, "score", FieldType::Float64),
+        ]
+    }
+
+    fn rows(n: i64) -> Vec<Vec<Value>> {
+        (0..n)
+            .map(|i| {
+                let name = if i % 7 == 0 { Value::Null } else { Value::Bytes(format!("n{}", i % 13).into_bytes()) };
+                vec![Value::Int64(i), name, Value::Float64(i as f64 * 0.5)]
+            })
+            .collect()
+    }
+
+    fn to_columns(rows: &[Vec<Value>]) -> Vec<Vec<Value>> {
+        (0..rows[0].len()).map(|c| rows.iter().map(|r| r[c].clone()).collect()).collect()
+    }
+
+    fn write(schema: &[ColumnMeta], opts: SegmentWriterOptions, rows: Vec<Vec<Value>>) -> SegmentReader {
+        let mut w = SegmentWriter::with_options(schema.to_vec(), opts);
+        for row in rows {
+            w.append_row(row).unwrap();
+        }
+        SegmentReader::open(w.finish().unwrap().0, schema.to_vec()).unwrap()
+    }
+
+    fn pages_of(rows: usize) -> SegmentWriterOptions {
+        SegmentWriterOptions { page_max_rows: rows, ..Default::default() }
+    }
+
+    /// `read_column_filtered` 结果覆盖的行号区间
+    fn row_span(hits: &[(u32, Value)]) -> Option<(u32, u32)> {
+        Some((hits.first()?.0, hits.last()?.0))
+    }
+
+    // ── 基线 version 2 文件 ───────────────────────────────────────────────────
+
+    /// 由基线（version 2）的 `SegmentWriter::finalize` 写出：1100 行，每列两页，各列使用
+    /// `baseline_schema` 中的编码与压缩方式，第 i 行的值见 `baseline_row`
+    const BASELINE_V2_SEGMENT: &[u8] = include_bytes!("../testdata/segment_v2_baseline.seg");
+
+    fn baseline_schema() -> Vec<ColumnMeta> {
+        vec![
+            ColumnMeta::new(0, "id", FieldType::Int64),
+            ColumnMeta::new(1, "day", FieldType::Int32),
+            ColumnMeta::new(2, "city", FieldType::Bytes),
+            ColumnMeta::new(3, "score", FieldType::Float64),
+            ColumnMeta::new(4, "flag", FieldType::Int64).with_encoding(EncodingType::RunLength),
+            ColumnMeta::new(5, "qty", FieldType::Int16)
+                .with_encoding(EncodingType::Plain)
+                .with_compression(CompressionType::None),
+            ColumnMeta::new(6, "note", FieldType::Bytes).with_encoding(EncodingType::Plain),
+        ]
+    }
+
+    fn baseline_row(i: i64) -> Vec<Value> {
+        let cities = ["beijing", "shanghai", "shenzhen"];
+        vec![
+            Value::Int64(i * 3),
+            Value::Int32(19000 + (i / 100) as i32),
+            Value::Bytes(cities[(i % 3) as usize].as_bytes().to_vec()),
+            Value::Float64(i as f64 * 0.5),
+            Value::Int64(i / 500),
+            Value::Int16((i % 300) as i16 - 150),
+            Value::Bytes(format!("n{i}").into_bytes()),
+        ]
+    }
+
+    #[test]
+    fn baseline_v2_footer_is_parsed_and_pages_are_located() {
+        let reader = SegmentReader::open(BASELINE_V2_SEGMENT.to_vec(), baseline_schema()).unwrap();
+        assert_eq!(reader.num_rows(), 1100);
+        assert_eq!(reader.footer.column_metas.len(), 7);
+        assert_eq!(reader.content_digest(), None);
+        assert!(!reader.is_encrypted());
+        for col_idx in 0..7 {
+            let (cm, meta) = reader.column(col_idx).unwrap();
+            assert_eq!((cm.encoding, cm.compression), (meta.encoding, meta.compression));
+            let pages = reader.ordinal_index(cm).unwrap();
+            let rows: Vec<u32> = pages.entries().iter().map(|&(rid, _, _)| rid).collect();
+            assert_eq!(rows, [0, 1024], "col {col_idx}");
+            assert_eq!(cm.data_size, pages.entries().iter().map(|&(_, _, size)| size).sum::<u64>());
+        }
+        assert_eq!(reader.get_row(1050).unwrap(), baseline_row(1050));
+        // 基线 footer 不记录编码：schema 与写入时不符的列解码失败，而不是被当作别的格式
+        let mut wrong = baseline_schema();
+        wrong[2] = ColumnMeta::new(2, "city", FieldType::Bytes).with_encoding(EncodingType::PrefixDelta);
+        let reader = SegmentReader::open(BASELINE_V2_SEGMENT.to_vec(), wrong).unwrap();
+        assert!(reader.get_row(0).is_err());
+    }
+
+    #[test]
+    fn baseline_v2_segment_with_corrupt_footer_offsets_is_an_error() {
+        let n = BASELINE_V2_SEGMENT.len();
+        let footer_start = n - 16 - (24 + 7 * BASELINE_COLUMN_META_SIZE);
+        for data_end in [0u64, 11, n as u64, u64::MAX] {
+            let mut tampered = BASELINE_V2_SEGMENT.to_vec();
+            tampered[footer_start + 24..footer_start + 32].copy_from_slice(&data_end.to_le_bytes());
+            let crc = crc32fast::hash(&tampered[footer_start..n - 16]);
+            tampered[n - 16..n - 12].copy_from_slice(&crc.to_le_bytes());
+            assert!(matches!(
+                SegmentReader::open(tampered, baseline_schema()),
+                Err(OlapError::SegmentIo(_)),
+            ), "data_end {data_end}");
+        }
+        assert!(matches!(
+            SegmentReader::open(BASELINE_V2_SEGMENT.to_vec(), baseline_schema()[..6].to_vec()),
+            Err(OlapError::SchemaMismatch(_)),
+        ));
+    }
+
+    #[test]
+    fn baseline_v2_segment_reads_every_column() {
+        let reader   = SegmentReader::open(BASELINE_V2_SEGMENT.to_vec(), baseline_schema()).unwrap();
+        let expected = to_columns(&(0..1100).map(baseline_row).collect::<Vec<_>>());
+        for (col_idx, want) in expected.iter().enumerate() {
+            assert_eq!(&reader.read_column(col_idx).unwrap(), want, "col {col_idx}");
+            let streamed: Vec<Value> = reader.stream_column(col_idx).unwrap().map(Result::unwrap).collect();
+            assert_eq!(&streamed, want, "col {col_idx}");
+            assert_eq!(reader.read_column_range(col_idx, 1000, 50).unwrap(), want[1000..1050], "col {col_idx}");
+            assert_eq!(reader.column_stats(col_idx).unwrap().num_pages, 2);
+        }
+        let rows: Vec<Vec<Value>> = reader.row_iter().map(Result::unwrap).collect();
+        assert_eq!(rows, (0..1100).map(baseline_row).collect::<Vec<_>>());
+
+        // 基线的 ZoneMap 与 BloomFilter 不可用：过滤读取退化为全部页，等值探测总是可能包含
+        let probe = Value::Int64(2100).to_sort_key();
+        let hits  = reader.read_column_filtered(0, &probe, &probe).unwrap();
+        assert_eq!(hits.len(), 1100);
+        assert_eq!(hits[700], (700, Value::Int64(2100)));
+        assert!(reader.may_contain(2, &Value::Bytes(b"beijing".to_vec())).unwrap());
+        assert_eq!(reader.seek_row_for_key(&probe).unwrap(), 0);
+    }
+
+    #[test]
+    fn baseline_v2_segment_opens_through_mmap() {
+        let dir  = crate::test_util::TempDir::new("baseline-v2");
+        let path = dir.path().join("0.seg");
+        std::fs::write(&path, BASELINE_V2_SEGMENT).unwrap();
+        let reader = SegmentReader::open_path(&path, baseline_schema()).unwrap();
+        assert_eq!(reader.get_row(0).unwrap(), baseline_row(0));
+        assert_eq!(reader.get_row(1099).unwrap(), baseline_row(1099));
+    }
+
+    #[test]
+    fn tampered_footer_version_byte_is_unsupported() {
+        let mut w = SegmentWriter::new(schema());
+        for row in rows(10) {
+            w.append_row(row).unwrap();
+        }
+        let data = w.finish().unwrap().0;
+        for bad in [0u8, FOOTER_VERSION + 1, 0xff] {
+            let mut tampered = data.clone();
+            tampered[12] = bad;
+            match SegmentReader::open(tampered, schema()) {
+                Err(OlapError::Unsupported(msg)) => assert_eq!(msg, format!("segment footer version {bad}")),
+                other => panic!("footer version {bad}: {:?}", other.map(|_| ())),
+            }
+        }
+        let mut tampered = data.clone();
+        tampered[8..12].copy_from_slice(&99u32.to_le_bytes());
+        assert!(matches!(SegmentReader::open(tampered, schema()), Err(OlapError::SegmentIo(_))));
+        assert!(SegmentReader::open(data, schema()).is_ok());
+    }
+
+    #[test]
+    fn current_footer_matches_the_layout_registered_for_footer_version() {
+        let mut w = SegmentWriter::new(schema());
+        for row in rows(10) {
+            w.append_row(row).unwrap();
+        }
+        let reader = SegmentReader::open(w.finish().unwrap().0, schema()).unwrap();
+        let bytes  = reader.footer.serialize();
+        // serialize 的布局变化而 FOOTER_VERSION 未递增时，此处长度或解析结果对不上
+        let layout = FooterLayout::of(FOOTER_VERSION).unwrap();
+        assert_eq!(bytes.len(), 24 + schema().len() * layout.entry_size + 8 + 1);
+        let parsed = SegmentFooter::deserialize(&bytes, layout).unwrap();
+        assert_eq!(parsed.serialize(), bytes);
+        assert!(FooterLayout::of(FOOTER_VERSION + 1).is_none());
+    }
+
+    #[test]
+    fn baseline_v2_segment_with_tampered_version_is_rejected() {
+        for version in [3u32, 4, VERSION + 1] {
+            let mut tampered = BASELINE_V2_SEGMENT.to_vec();
+            tampered[8..12].copy_from_slice(&version.to_le_bytes());
+            assert!(SegmentReader::open(tampered, baseline_schema()).is_err(), "version {version}");
+        }
+    }
+
+    #[test]
+    fn zstd_and_lz4_columns_share_a_segment() {
+        let schema = vec![
+            ColumnMeta::new(0, "k", FieldType::Int64).key(),
+            ColumnMeta::new(1, "a", FieldType::Bytes).with_compression(CompressionType::Zstd { level: 19 }),
+            ColumnMeta::new(2, "b", FieldType::Bytes).with_compression(CompressionType::Lz4),
+        ];
+        let data: Vec<Vec<Value>> = (0..3000i64)
+            .map(|i| {
+                let s = Value::Bytes(format!("value-{}", i % 50).into_bytes());
+                vec![Value::Int64(i), s.clone(), s]
+            })
+            .collect();
+        let mut w = SegmentWriter::new(schema.clone());
+        for row in data.clone() {
+            w.append_row(row).unwrap();
+        }
+        let reader = SegmentReader::open(w.finish().unwrap().0, schema).unwrap();
+        let cols   = to_columns(&data);
+        assert!(matches!(reader.footer.column_metas[1].compression, CompressionType::Zstd { .. }));
+        assert_eq!(reader.footer.column_metas[2].compression, CompressionType::Lz4);
+        for (col_idx, want) in cols.iter().enumerate() {
+            assert_eq!(&reader.read_column(col_idx).unwrap(), want);
+        }
+    }
+
+    // ── 流式写出 ──────────────────────────────────────────────────────────────
+
+    /// 只统计写入字节数的输出端
+    struct CountingWriter(std::rc::Rc<std::cell::Cell<u64>>);
+
+    impl Write for CountingWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.set(self.0.get() + buf.len() as u64);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> { Ok(()) }
+    }
+
+    #[test]
+    fn streaming_writer_keeps_at_most_one_page_buffered() {
+        let schema = vec![
+            ColumnMeta::new(0, "v", FieldType::Int64)
+                .with_encoding(EncodingType::Plain)
+                .with_compression(CompressionType::None),
+        ];
+        let written = std::rc::Rc::new(std::cell::Cell::new(0));
+        let mut w = SegmentWriter::with_sink(schema, CountingWriter(written.clone())).unwrap();
+        let header = written.get();
+        assert_eq!(header, 14);
+
+        let mut peak = 0;
+        let rows = 200_000u64;
+        for i in 0..rows {
+            w.append_row(vec![Value::Int64(i.wrapping_mul(0x9e37_79b9_7f4a_7c15) as i64)]).unwrap();
+            // 已追加的原始字节中尚未写出的部分（写出的页另含页头与校验值，故只会偏小）
+            let buffered = ((i + 1) * 8).saturating_sub(written.get() - header);
+            peak = peak.max(buffered);
+        }
+        assert!(peak <= 8 * PAGE_MAX_ROWS as u64, "peak buffered {peak} bytes");
+        assert!(written.get() >= rows * 8 - peak);
+
+        let (_, total) = w.finish().unwrap();
+        assert_eq!(total, written.get());
+    }
+
+    // ── 页定位 ────────────────────────────────────────────────────────────────
+
+    #[test]
+    fn pages_with_unequal_row_counts_read_back_in_order() {
+        let schema = [
+            ColumnMeta::new(0, "k", FieldType::Int64),
+            ColumnMeta::new(1, "blob", FieldType::Bytes).with_encoding(EncodingType::Plain),
+        ];
+        // 值越来越长，按字节切页时后面的页行数越来越少；k 列仍按行数切页
+        let rows: Vec<Vec<Value>> = (0..600i64)
+            .map(|i| vec![Value::Int64(i), Value::Bytes(vec![b'x'; i as usize * 4])])
+            .collect();
+        let opts   = SegmentWriterOptions { page_max_rows: 256, page_max_bytes: 16 * 1024, ..Default::default() };
+        let reader = write(&schema, opts, rows.clone());
+
+        let (cm, _) = reader.column(1).unwrap();
+        let starts: Vec<u32> = reader.ordinal_index(cm).unwrap().entries().iter().map(|e| e.0).collect();
+        let counts: Vec<u32> = starts.windows(2).map(|w| w[1] - w[0]).collect();
+        assert!(counts.windows(2).any(|w| w[0] != w[1]), "page row counts {counts:?}");
+
+        let cols = to_columns(&rows);
+        for (col_idx, want) in cols.iter().enumerate() {
+            assert_eq!(&reader.read_column(col_idx).unwrap(), want);
+        }
+        for &start in &starts {
+            assert_eq!(reader.get_row(start).unwrap(), rows[start as usize]);
+            let from = start.saturating_sub(1);
+            assert_eq!(reader.read_column_range(1, from, 2).unwrap(), cols[1][from as usize..from as usize + 2]);
+        }
+    }
+
+    // ── 点查 ──────────────────────────────────────────────────────────────────
+
+    #[test]
+    fn get_row_fetches_scattered_rows_and_rejects_out_of_range_ids() {
+        let rows   = rows(5000);
+        let reader = write(&schema(), pages_of(700), rows.clone());
+        for row_id in [0u32, 1, 699, 700, 1234, 2799, 4200, 4999] {
+            assert_eq!(reader.get_row(row_id).unwrap(), rows[row_id as usize], "row {row_id}");
+        }
+        for row_id in [5000u32, u32::MAX] {
+            assert!(matches!(reader.get_row(row_id), Err(OlapError::SegmentIo(_))), "row {row_id}");
+        }
+    }
+
+    // ── 并行读列 ──────────────────────────────────────────────────────────────
+
+    #[test]
+    fn parallel_column_reads_match_serial_reads_in_request_order() {
+        let schema: Vec<ColumnMeta> = (0..8u32)
+            .map(|c| match c % 2 {
+                0 => ColumnMeta::new(c, &format!("i{c}"), FieldType::Int64),
+                _ => ColumnMeta::new(c, &format!("s{c}"), FieldType::Bytes).nullable(),
+            })
+            .collect();
+        let rows: Vec<Vec<Value>> = (0..20_000i64)
+            .map(|i| (0..8i64).map(|c| match c % 2 {
+                0 => Value::Int64(i * (c + 1)),
+                _ => Value::Bytes(format!("v{}", (i + c) % 97).into_bytes()),
+            }).collect())
+            .collect();
+        let reader = write(&schema, pages_of(1024), rows);
+
+        let order = [5usize, 0, 7, 2, 2, 6, 1, 3, 4];
+        let start  = std::time::Instant::now();
+        let serial = order.iter().map(|&c| reader.read_column(c)).collect::<Result<Vec<_>>>().unwrap();
+        let serial_time = start.elapsed();
+        for threads in [1, 3, 16] {
+            let start = std::time::Instant::now();
+            assert_eq!(reader.read_columns_parallel(&order, threads).unwrap(), serial, "{threads} threads");
+            // 宽松的耗时上限，只防止并行路径退化成远慢于串行
+            assert!(start.elapsed() < serial_time * 10 + std::time::Duration::from_millis(200));
+        }
+        assert!(matches!(reader.read_columns_parallel(&[0, 8], 4), Err(OlapError::SegmentIo(_))));
+        assert_eq!(reader.read_columns_parallel(&[], 4).unwrap(), Vec::<Vec<Value>>::new());
+    }
+
+    // ── ZoneMap 剪枝 ──────────────────────────────────────────────────────────
+
+    #[test]
+    fn zone_map_prunes_signed_ints_and_floats_across_page_boundaries() {
+        let schema = [
+            ColumnMeta::new(0, "v", FieldType::Int64).with_encoding(EncodingType::Plain),
+            ColumnMeta::new(1, "f", FieldType::Float64),
+        ];
+        // 每页 100 行：[-250,-151] [-150,-51] [-50,49] [50,149] [150,249]
+        let rows = (-250..250i64).map(|i| vec![Value::Int64(i), Value::Float64(i as f64 / 4.0)]).collect();
+        let reader = write(&schema, pages_of(100), rows);
+        let int = |v: i64| Value::Int64(v).to_sort_key();
+        let flt = |v: f64| Value::Float64(v).to_sort_key();
+
+        let hits = reader.read_column_filtered(0, &int(-60), &int(-40)).unwrap();
+        assert_eq!(row_span(&hits), Some((100, 299)));
+        let hits = reader.read_column_filtered(0, &int(-1), &int(1)).unwrap();
+        assert_eq!(row_span(&hits), Some((200, 299)));
+        assert!(hits.contains(&(249, Value::Int64(-1))) && hits.contains(&(251, Value::Int64(1))));
+        let hits = reader.read_column_filtered(0, &int(10), &int(60)).unwrap();
+        assert_eq!(row_span(&hits), Some((200, 399)));
+        assert!(reader.read_column_filtered(0, &int(-1000), &int(-251)).unwrap().is_empty());
+        assert!(reader.read_column_filtered(0, &int(250), &int(i64::MAX)).unwrap().is_empty());
+
+        let hits = reader.read_column_filtered(1, &flt(-0.5), &flt(0.5)).unwrap();
+        assert_eq!(row_span(&hits), Some((200, 299)));
+        let hits = reader.read_column_filtered(1, &flt(-30.0), &flt(-20.0)).unwrap();
+        assert_eq!(row_span(&hits), Some((100, 199)));
+        assert!(reader.read_column_filtered(1, &flt(f64::NEG_INFINITY), &flt(-62.6)).unwrap().is_empty());
+    }
+
+    // ── append_batch ──────────────────────────────────────────────────────────
+
+    #[test]
+    fn append_batch_matches_append_row_byte_for_byte() {
+        let small_pages = SegmentWriterOptions { page_max_rows: 64, short_key_interval: 100, ..Default::default() };
+        let adaptive    = SegmentWriterOptions { adaptive_encoding: true, ..Default::default() };
+        for opts in [SegmentWriterOptions::default(), small_pages, adaptive] {
+            let data = rows(1000);
+
+            let mut by_row = SegmentWriter::with_options(schema(), opts.clone());
+            for row in data.clone() {
+                by_row.append_row(row).unwrap();
+            }
+            let mut by_batch = SegmentWriter::with_options(schema(), opts.clone());
+            by_batch.append_batch(to_columns(&data[..300])).unwrap();
+            by_batch.append_batch(to_columns(&data[300..])).unwrap();
+
+            let a = by_row.finish().unwrap().0;
+            let b = by_batch.finish().unwrap().0;
+            assert_eq!(a, b);
+
+            let reader = SegmentReader::open(b, schema()).unwrap();
+            assert_eq!(reader.num_rows(), 1000);
+            assert_eq!(reader.read_column(0).unwrap(), to_columns(&data)[0]);
+        }
+    }
+
+    #[test]
+    fn append_batch_rejects_bad_shapes_without_writing() {
+        let mut w = SegmentWriter::new(schema());
+        let mut cols = to_columns(&rows(10));
+        cols.pop();
+        assert!(matches!(w.append_batch(cols), Err(OlapError::RowWidthMismatch(3, 2))));
+
+        let mut cols = to_columns(&rows(10));
+        cols[2].pop();
+        assert!(matches!(w.append_batch(cols), Err(OlapError::SchemaMismatch(_))));
+
+        let mut cols = to_columns(&rows(10));
+        cols[0][5] = Value::Null;
+        assert!(matches!(w.append_batch(cols), Err(OlapError::SchemaMismatch(_))));
+        assert_eq!(w.num_rows(), 0);
+    }
+
+    // ── 谓词下推 ──────────────────────────────────────────────────────────────
+
+    #[test]
+    fn filtered_read_decodes_only_the_matching_page() {
+        let schema = [
+            ColumnMeta::new(0, "v", FieldType::Int64)
+                .with_encoding(EncodingType::Plain)
+                .with_compression(CompressionType::None),
+        ];
+        let mut w = SegmentWriter::with_options(schema.to_vec(), pages_of(100));
+        for i in 0..300 {
+            w.append_row(vec![Value::Int64(i)]).unwrap();
+        }
+        let mut data = w.finish().unwrap().0;
+
+        // 破坏第 0、2 页的 payload：只要被解码就会报错
+        let reader  = SegmentReader::open(data.clone(), schema.to_vec()).unwrap();
+        let (cm, _) = reader.column(0).unwrap();
+        let pages   = reader.ordinal_index(cm).unwrap().entries().to_vec();
+        assert_eq!(pages.len(), 3);
+        for (_, off, _) in [pages[0], pages[2]] {
+            data[off as usize + crate::page::PAGE_HEADER_SIZE + 3] ^= 0xff;
+        }
+        let reader = SegmentReader::open(data, schema.to_vec()).unwrap();
+        // read_column 跳过损坏页，只剩中间一页
+        assert_eq!(reader.read_column(0).unwrap().len(), 100);
+
+        let key  = |v: i64| Value::Int64(v).to_sort_key();
+        let hits = reader.read_column_filtered(0, &key(120), &key(150)).unwrap();
+        assert_eq!(hits, (100..200).map(|i| (i as u32, Value::Int64(i))).collect::<Vec<_>>());
+        assert!(reader.read_column_filtered(0, &key(50), &key(150)).is_err());
+    }
+
+    // ── BloomFilter 等值探测 ──────────────────────────────────────────────────
+
+    #[test]
+    fn may_contain_finds_present_values_and_rejects_most_absent_ones() {
+        let schema = [ColumnMeta::new(0, "order_id", FieldType::Int64).key()];
+        let reader = write(&schema, Default::default(), (0..2000).map(|i| vec![Value::Int64(i * 7)]).collect());
+
+        assert!((0..2000).all(|i| reader.may_contain(0, &Value::Int64(i * 7)).unwrap()));
+        let false_hits = (0..2000)
+            .filter(|i| reader.may_contain(0, &Value::Int64(i * 7 + 3)).unwrap())
+            .count();
+        assert!(false_hits < 100, "{false_hits} of 2000 absent values reported present");
+        assert!(reader.may_contain(1, &Value::Int64(0)).is_err());
+    }
+
+    // ── 行迭代 ────────────────────────────────────────────────────────────────
+
+    /// 与 examples/basic_usage.rs 写入的 2000 行相同
+    fn order_rows() -> Vec<Vec<Value>> {
+        let statuses = ["pending", "paid", "shipped", "delivered", "cancelled"];
+        (0..2000i64)
+            .map(|i| vec![
+                Value::Int32(20240101 + (i % 180) as i32),
+                Value::Int64(1_000_000 + i),
+                Value::Int64(10000 + i % 1000),
+                Value::Float64(99.9 + i as f64 * 0.5),
+                Value::Bytes(statuses[i as usize % 5].as_bytes().to_vec()),
+            ])
+            .collect()
+    }
+
+    fn order_schema() -> Vec<ColumnMeta> {
+        vec![
+            ColumnMeta::new(0, "order_date", FieldType::Int32).key(),
+            ColumnMeta::new(1, "order_id", FieldType::Int64).key(),
+            ColumnMeta::new(2, "user_id", FieldType::Int64).with_encoding(EncodingType::Plain),
+            ColumnMeta::new(3, "amount", FieldType::Float64).with_encoding(EncodingType::Plain),
+            ColumnMeta::new(4, "status", FieldType::Bytes).with_encoding(EncodingType::Plain),
+        ]
+    }
+
+    #[test]
+    fn row_iter_round_trips_the_example_dataset() {
+        let rows = order_rows();
+        // 按字节切页：各列的页边界互不相同
+        let opts   = SegmentWriterOptions { page_max_bytes: 3000, ..Default::default() };
+        let reader = write(&order_schema(), opts, rows.clone());
+        let page_starts = |col| {
+            let (cm, _) = reader.column(col).unwrap();
+            reader.ordinal_index(cm).unwrap().entries().iter().map(|e| e.0).collect::<Vec<_>>()
+        };
+        assert_ne!(page_starts(0), page_starts(2));
+        assert_ne!(page_starts(2), page_starts(4));
+
+        let mut n = 0;
+        for (i, row) in reader.row_iter().enumerate() {
+            assert_eq!(row.unwrap(), rows[i], "row {i}");
+            n += 1;
+        }
+        assert_eq!(n, 2000);
+    }
+
+    #[test]
+    fn row_iter_reports_a_corrupt_page_per_row_and_continues() {
+        let mut w = SegmentWriter::with_options(order_schema(), pages_of(500));
+        for row in order_rows() {
+            w.append_row(row).unwrap();
+        }
+        let mut data = w.finish().unwrap().0;
+        let reader   = SegmentReader::open(data.clone(), order_schema()).unwrap();
+        let (cm, _)  = reader.column(3).unwrap();
+        let (_, off, _) = reader.ordinal_index(cm).unwrap().entries()[1];
+        data[off as usize + crate::page::PAGE_HEADER_SIZE + 3] ^= 0xff;
+
+        let reader  = SegmentReader::open(data, order_schema()).unwrap();
+        let results: Vec<_> = reader.row_iter().collect();
+        assert_eq!(results.len(), 2000);
+        assert!(results[..500].iter().all(Result::is_ok));
+        assert!(results[500..1000].iter().all(Result::is_err));
+        assert_eq!(results[1000].as_ref().unwrap(), &order_rows()[1000]);
+    }
+
+    // ── CSV 导入 ──────────────────────────────────────────────────────────────
+
+    fn csv_schema() -> Vec<ColumnMeta> {
+        vec![
+            ColumnMeta::new(0, "id", FieldType::Int64).key(),
+            ColumnMeta::new(1, "city", FieldType::Bytes),
+            ColumnMeta::new(2, "amount", FieldType::Float64).nullable(),
+        ]
+    }
+
+    #[test]
+    fn csv_loads_rows_with_nulls_and_quoted_fields() {
+        let csv = "id,city,amount\n1,beijing,9.5\n2,\"new york, ny\",\n\n3,\"say \"\"hi\"\"\",-1\n";
+        let w = load_csv(csv.as_bytes(), &csv_schema(), true).unwrap();
+        assert_eq!(w.num_rows(), 3);
+        let reader = SegmentReader::open(w.finish().unwrap().0, csv_schema()).unwrap();
+        let rows: Vec<Vec<Value>> = reader.row_iter().map(Result::unwrap).collect();
+        assert_eq!(rows, vec![
+            vec![Value::Int64(1), Value::Bytes(b"beijing".to_vec()), Value::Float64(9.5)],
+            vec![Value::Int64(2), Value::Bytes(b"new york, ny".to_vec()), Value::Null],
+            vec![Value::Int64(3), Value::Bytes(b"say \"hi\"".to_vec()), Value::Float64(-1.0)],
+        ]);
+    }
+
+    #[test]
+    fn csv_malformed_number_reports_its_line() {
+        let csv = "1,beijing,9.5\n2,shanghai,12.x\n";
+        let Err(OlapError::Encoding(msg)) = load_csv(csv.as_bytes(), &csv_schema(), false) else {
+            panic!("expected an encoding error");
+        };
+        assert!(msg.contains("line 2") && msg.contains("amount"), "{msg}");
+
+        // 非空列的空字段、字段数不符、未闭合的引号
+        for (csv, line) in [("1,a,1\n,b,2\n", "line 2"), ("1,a\n", "line 1"), ("1,\"a,1\n", "line 1")] {
+            let Err(OlapError::Encoding(msg)) = load_csv(csv.as_bytes(), &csv_schema(), false) else {
+                panic!("expected an encoding error for {csv:?}");
+            };
+            assert!(msg.contains(line), "{msg}");
+        }
+    }
+
+    // ── 合并 ──────────────────────────────────────────────────────────────────
+
+    fn merge_schema() -> Vec<ColumnMeta> {
+        vec![
+            ColumnMeta::new(0, "k", FieldType::Int64).key(),
+            ColumnMeta::new(1, "sum", FieldType::Int64).with_aggregate(AggregateType::Sum).nullable(),
+            ColumnMeta::new(2, "max", FieldType::Float64).with_aggregate(AggregateType::Max),
+            ColumnMeta::new(3, "last", FieldType::Bytes).with_aggregate(AggregateType::Replace),
+        ]
+    }
+
+    fn merge_row(k: i64, sum: Option<i64>, max: f64, last: &str) -> Vec<Value> {
+        vec![
+            Value::Int64(k),
+            sum.map_or(Value::Null, Value::Int64),
+            Value::Float64(max),
+            Value::Bytes(last.as_bytes().to_vec()),
+        ]
+    }
+
+    /// 旧输入 key 1、2、4，新输入 key 2、3、4
+    fn merged(keys_type: KeysType) -> Vec<Vec<Value>> {
+        let old = vec![merge_row(1, Some(1), 1.0, "a"), merge_row(2, Some(2), 9.0, "b"), merge_row(4, None, 4.0, "c")];
+        let new = vec![merge_row(2, Some(20), 3.0, "B"), merge_row(3, Some(3), 3.0, "d"), merge_row(4, Some(40), 5.0, "C")];
+        let inputs = vec![write(&merge_schema(), Default::default(), old), write(&merge_schema(), Default::default(), new)];
+        let out = merge_segments(inputs, &merge_schema(), keys_type).unwrap();
+        SegmentReader::open(out, merge_schema()).unwrap().row_iter().map(Result::unwrap).collect()
+    }
+
+    #[test]
+    fn merge_duplicate_keeps_every_row_in_key_order() {
+        assert_eq!(merged(KeysType::Duplicate), vec![
+            merge_row(1, Some(1), 1.0, "a"),
+            merge_row(2, Some(2), 9.0, "b"),
+            merge_row(2, Some(20), 3.0, "B"),
+            merge_row(3, Some(3), 3.0, "d"),
+            merge_row(4, None, 4.0, "c"),
+            merge_row(4, Some(40), 5.0, "C"),
+        ]);
+    }
+
+    #[test]
+    fn merge_unique_keeps_the_newest_row_per_key() {
+        assert_eq!(merged(KeysType::Unique), vec![
+            merge_row(1, Some(1), 1.0, "a"),
+            merge_row(2, Some(20), 3.0, "B"),
+            merge_row(3, Some(3), 3.0, "d"),
+            merge_row(4, Some(40), 5.0, "C"),
+        ]);
+    }
+
+    #[test]
+    fn merge_aggregate_folds_value_columns() {
+        assert_eq!(merged(KeysType::Aggregate), vec![
+            merge_row(1, Some(1), 1.0, "a"),
+            merge_row(2, Some(22), 9.0, "B"),
+            merge_row(3, Some(3), 3.0, "d"),
+            merge_row(4, Some(40), 5.0, "C"),
+        ]);
+    }
+
+    // ── 读时聚合 ──────────────────────────────────────────────────────────────
+
+    fn agg_table(keys_type: KeysType) -> TabletSchema {
+        use crate::common::ColumnType;
+        use crate::meta::ColumnSchema;
+        TabletSchema::new(keys_type, vec![
+            ColumnSchema::key(0, "k", ColumnType::Int64),
+            ColumnSchema::value(1, "sum", ColumnType::Int64, AggregateType::Sum),
+            ColumnSchema::value(2, "max", ColumnType::Int32, AggregateType::Max),
+            ColumnSchema::value(3, "min", ColumnType::Float64, AggregateType::Min),
+            ColumnSchema::value(4, "last", ColumnType::Int64, AggregateType::Replace),
+        ])
+    }
+
+    fn agg_reader(keys_type: KeysType) -> (SegmentReader, TabletSchema) {
+        let table = agg_table(keys_type);
+        let row = |k: i64, sum: Option<i64>, max: Option<i32>, min: Option<f64>, last: Option<i64>| vec![
+            Value::Int64(k),
+            sum.map_or(Value::Null, Value::Int64),
+            max.map_or(Value::Null, Value::Int32),
+            min.map_or(Value::Null, Value::Float64),
+            last.map_or(Value::Null, Value::Int64),
+        ];
+        let rows = vec![
+            row(2, Some(5), Some(-3), Some(1.5), Some(1)),
+            row(1, None, None, None, Some(7)),
+            row(2, Some(-2), Some(-1), Some(-0.5), None),
+            row(1, Some(4), Some(9), Some(2.0), Some(8)),
+            row(2, None, Some(-7), None, Some(3)),
+            row(3, None, None, None, None),
+        ];
+        (write(&table.segment_schema(), Default::default(), rows), table)
+    }
+
+    #[test]
+    fn read_aggregated_applies_each_aggregate_function() {
+        let (reader, table) = agg_reader(KeysType::Aggregate);
+        assert_eq!(reader.read_aggregated(&table).unwrap(), vec![
+            // Sum 5-2，Max -1，Min -0.5，Replace 取最后一行（即使为 NULL 之后又写入 3）
+            vec![Value::Int64(2), Value::Int64(3), Value::Int32(-1), Value::Float64(-0.5), Value::Int64(3)],
+            // NULL 是 Sum/Max/Min 的单位元
+            vec![Value::Int64(1), Value::Int64(4), Value::Int32(9), Value::Float64(2.0), Value::Int64(8)],
+            vec![Value::Int64(3), Value::Null, Value::Null, Value::Null, Value::Null],
+        ]);
+    }
+
+    #[test]
+    fn read_aggregated_follows_the_table_model() {
+        let (reader, table) = agg_reader(KeysType::Duplicate);
+        assert_eq!(reader.read_aggregated(&table).unwrap().len(), 6);
+
+        let (reader, table) = agg_reader(KeysType::Unique);
+        let rows = reader.read_aggregated(&table).unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0], vec![Value::Int64(2), Value::Null, Value::Int32(-7), Value::Null, Value::Int64(3)]);
+
+        let mut narrow = agg_table(KeysType::Aggregate);
+        narrow.columns.pop();
+        assert!(matches!(reader.read_aggregated(&narrow), Err(OlapError::SchemaMismatch(_))));
+    }
+
+    // ── Decimal ───────────────────────────────────────────────────────────────
+
+    #[test]
+    fn decimal_column_round_trips_through_a_segment() {
+        let schema = [
+            ColumnMeta::new(0, "id", FieldType::Int64).key(),
+            ColumnMeta::new(1, "price", FieldType::Decimal128).nullable(),
+        ];
+        // 未缩放值：scale 2 时 1999 即 19.99
+        let prices = [1999i128, -5, 0, i128::MAX, i128::MIN, 10_000_000_000_000_000_000_000];
+        let rows: Vec<Vec<Value>> = (0..3000i64)
+            .map(|i| {
+                let price = if i % 11 == 0 { Value::Null } else { Value::Decimal(prices[i as usize % 6] / (i as i128 % 3 + 1)) };
+                vec![Value::Int64(i), price]
+            })
+            .collect();
+        let reader = write(&schema, Default::default(), rows.clone());
+        assert_eq!(reader.read_column(1).unwrap(), to_columns(&rows)[1]);
+
+        let stats = reader.column_stats(1).unwrap();
+        let decimals = rows.iter().filter_map(|r| match r[1] { Value::Decimal(d) => Some(d), _ => None });
+        assert_eq!(stats.min, Some(Value::Decimal(decimals.clone().min().unwrap())));
+        assert_eq!(stats.max, Some(Value::Decimal(decimals.max().unwrap())));
+        assert!(stats.has_null);
+    }
+
+    // ── Bool ──────────────────────────────────────────────────────────────────
+
+    #[test]
+    fn bool_column_round_trips_and_prunes_an_all_false_page() {
+        let schema = [ColumnMeta::new(0, "paid", FieldType::Bool)];
+        // 第 0 页全为 false，其余页交替
+        let rows: Vec<Vec<Value>> = (0..400).map(|i| vec![Value::Bool(i >= 100 && i % 2 == 0)]).collect();
+        let reader = write(&schema, pages_of(100), rows.clone());
+        assert_eq!(reader.read_column(0).unwrap(), to_columns(&rows)[0]);
+        assert_eq!(reader.footer.column_metas[0].encoding, EncodingType::BoolBitPacked);
+
+        let stats = reader.column_stats(0).unwrap();
+        assert_eq!((stats.min, stats.max), (Some(Value::Bool(false)), Some(Value::Bool(true))));
+
+        let t = Value::Bool(true).to_sort_key();
+        let hits = reader.read_column_filtered(0, &t, &t).unwrap();
+        assert_eq!(row_span(&hits), Some((100, 399)));
+        assert!(hits.iter().all(|(row, v)| *v == Value::Bool(*row % 2 == 0)));
+        let f = Value::Bool(false).to_sort_key();
+        assert_eq!(row_span(&reader.read_column_filtered(0, &f, &f).unwrap()), Some((0, 399)));
+    }
+
+    // ── DateTime ──────────────────────────────────────────────────────────────
+
+    #[test]
+    fn monotonic_timestamps_compress_well_under_delta() {
+        let delta = [ColumnMeta::new(0, "ts", FieldType::DateTime)];
+        let plain = [ColumnMeta::new(0, "ts", FieldType::DateTime).with_encoding(EncodingType::Plain)];
+        // 约每秒一条，带毫秒级抖动
+        let rows: Vec<Vec<Value>> = (0..10_000i64)
+            .map(|i| vec![Value::DateTime(1_700_000_000_000_000 + i * 1_000_000 + (i * 7919 % 1000) * 1000)])
+            .collect();
+        let by_delta = write(&delta, Default::default(), rows.clone());
+        let by_plain = write(&plain, Default::default(), rows.clone());
+
+        assert_eq!(by_delta.footer.column_metas[0].encoding, EncodingType::DeltaBinary);
+        assert_eq!(by_delta.read_column(0).unwrap(), to_columns(&rows)[0]);
+        let delta_size = by_delta.column_stats(0).unwrap().compressed_size;
+        let plain_size = by_plain.column_stats(0).unwrap().compressed_size;
+        assert!(delta_size * 2 < plain_size, "delta {delta_size} vs plain {plain_size}");
+        assert!(delta_size < 10_000 * 8 / 3, "delta {delta_size}");
+    }
+
+    // ── 按字节切页 ────────────────────────────────────────────────────────────
+
+    #[test]
+    fn wide_bytes_column_flushes_pages_before_the_row_cap() {
+        let schema = [
+            ColumnMeta::new(0, "id", FieldType::Int64),
+            ColumnMeta::new(1, "body", FieldType::Bytes).with_encoding(EncodingType::Plain),
+        ];
+        let rows: Vec<Vec<Value>> = (0..1000i64)
+            .map(|i| vec![Value::Int64(i), Value::Bytes(format!("{i:0>1000}").into_bytes())])
+            .collect();
+        let reader = write(&schema, Default::default(), rows.clone());
+
+        let page_rows = |col| {
+            let (cm, _) = reader.column(col).unwrap();
+            let starts: Vec<u32> = reader.ordinal_index(cm).unwrap().entries().iter().map(|e| e.0).collect();
+            starts.iter().zip(starts.iter().skip(1).chain([&1000])).map(|(a, b)| b - a).collect::<Vec<_>>()
+        };
+        assert_eq!(page_rows(0), vec![1000]);
+        let body_pages = page_rows(1);
+        assert!(body_pages.len() >= 1000 * 1000 / PAGE_TARGET_BYTES, "{body_pages:?}");
+        // 每页约 64KB 原始数据
+        assert!(body_pages.iter().all(|&n| n as usize * 1004 <= PAGE_TARGET_BYTES + 1004), "{body_pages:?}");
+        assert_eq!(reader.read_column(1).unwrap(), to_columns(&rows)[1]);
+    }
+
+    // ── 写入选项 ──────────────────────────────────────────────────────────────
+
+    #[test]
+    fn smaller_page_cap_yields_more_ordinal_entries() {
+        let page_count = |opts| {
+            let reader  = write(&schema(), opts, rows(3000));
+            let (cm, _) = reader.column(0).unwrap();
+            assert_eq!(reader.read_column(0).unwrap(), to_columns(&rows(3000))[0]);
+            reader.ordinal_index(cm).unwrap().page_count()
+        };
+        assert_eq!(page_count(SegmentWriterOptions::default()), 3);
+        assert_eq!(page_count(pages_of(128)), 3000usize.div_ceil(128));
+
+        // 短键间隔同样生效：间隔越小，定位越精确
+        let reader = write(&schema(), SegmentWriterOptions { short_key_interval: 100, ..Default::default() }, rows(3000));
+        assert_eq!(reader.seek_row_for_key(&Value::Int64(2550).to_sort_key()).unwrap(), 2500);
+    }
+
+    // ── 列统计 ────────────────────────────────────────────────────────────────
+
+    #[test]
+    fn column_stats_match_the_appended_data() {
+        let rows: Vec<Vec<Value>> = (0..2500i64)
+            .map(|i| {
+                let name = if i == 1234 { Value::Null } else { Value::Bytes(format!("n{:04}", (i * 37) % 2500).into_bytes()) };
+                vec![Value::Int64(i - 1000), name, Value::Float64(((i * 7919) % 2500) as f64 - 1250.5)]
+            })
+            .collect();
+        let reader = write(&schema(), pages_of(500), rows);
+
+        let k = reader.column_stats(0).unwrap();
+        assert_eq!((k.num_pages, k.num_rows), (5, 2500));
+        assert_eq!((k.min, k.max), (Some(Value::Int64(-1000)), Some(Value::Int64(1499))));
+        assert!(!k.has_null);
+        assert!(k.compressed_size > 0 && k.uncompressed_size > 0);
+
+        let name = reader.column_stats(1).unwrap();
+        assert_eq!(name.min, Some(Value::Bytes(b"n0000".to_vec())));
+        assert_eq!(name.max, Some(Value::Bytes(b"n2499".to_vec())));
+        assert!(name.has_null);
+
+        let score = reader.column_stats(2).unwrap();
+        assert_eq!((score.min, score.max), (Some(Value::Float64(-1250.5)), Some(Value::Float64(1248.5))));
+        assert!(reader.column_stats(3).is_err());
+    }
+
+    // ── ShortKey 定位 ─────────────────────────────────────────────────────────
+
+    #[test]
+    fn seek_row_for_key_returns_an_aligned_start_before_the_match() {
+        let schema = [ColumnMeta::new(0, "k", FieldType::Int64).key(), ColumnMeta::new(1, "v", FieldType::Int32)];
+        // 偶数 key，第 i 行为 2i
+        let rows: Vec<Vec<Value>> = (0..5000i64).map(|i| vec![Value::Int64(i * 2), Value::Int32(i as i32)]).collect();
+        let reader = write(&schema, Default::default(), rows);
+        let key = |k: i64| Value::Int64(k).to_sort_key();
+
+        for (probe, want) in [(6001, 2048), (2 * 3000, 2048), (2 * 1100, 1024), (2 * 4999, 4096), (10, 0)] {
+            let start = reader.seek_row_for_key(&key(probe)).unwrap();
+            assert_eq!(start, want, "probe {probe}");
+            assert_eq!(start % 1024, 0);
+            // 从起点往后一个采样间隔内能找到第一个 >= probe 的行
+            let keys = reader.read_column_range(0, start, 1025).unwrap();
+            assert!(keys.iter().any(|k| k.as_i64().unwrap() >= probe), "probe {probe}");
+            assert!(keys[0].as_i64().unwrap() < probe || start == 0);
+        }
+        assert_eq!(reader.seek_row_for_key(&key(-5)).unwrap(), 0);
+        assert_eq!(reader.seek_row_for_key(&key(1_000_000)).unwrap(), 4096);
+    }
+
+    #[test]
+    fn scan_key_range_returns_contiguous_rows_and_nothing_outside_the_stored_keys() {
+        let schema = [ColumnMeta::new(0, "k", FieldType::Int64).key(), ColumnMeta::new(1, "v", FieldType::Int32)];
+        // 偶数 key，第 i 行为 2i
+        let rows: Vec<Vec<Value>> = (0..5000i64).map(|i| vec![Value::Int64(i * 2), Value::Int32(i as i32)]).collect();
+        let reader = write(&schema, Default::default(), rows);
+        let key = |k: i64| Value::Int64(k).to_sort_key();
+
+        // [2001, 4100] 内的偶数 key 为 2002..=4100，即第 1001..=2050 行，跨过 2048 的采样点
+        let hits = reader.scan_key_range(&key(2001), &key(4100), 1).unwrap();
+        let want: Vec<(u32, Value)> = (1001..=2050u32).map(|i| (i, Value::Int32(i as i32))).collect();
+        assert_eq!(hits, want);
+        assert_eq!(reader.scan_key_range(&key(0), &key(0), 0).unwrap(), vec![(0, Value::Int64(0))]);
+
+        assert!(reader.scan_key_range(&key(-100), &key(-1), 1).unwrap().is_empty());
+        assert!(reader.scan_key_range(&key(10_000), &key(20_000), 1).unwrap().is_empty());
+        assert!(reader.scan_key_range(&key(4100), &key(2001), 1).unwrap().is_empty());
+        let all = reader.scan_key_range(&key(i64::MIN), &key(i64::MAX), 1).unwrap();
+        assert_eq!((all.len(), all.last().unwrap().0), (5000, 4999));
+    }
+
+    // ── 逐页 BloomFilter ──────────────────────────────────────────────────────
+
+    #[test]
+    fn point_probe_with_page_blooms_decodes_a_single_page() {
+        let schema = [
+            ColumnMeta::new(0, "order_id", FieldType::Int64)
+                .with_encoding(EncodingType::Plain)
+                .with_compression(CompressionType::None),
+        ];
+        // 值打散：每页的 min/max 都覆盖几乎整个值域，ZoneMap 无法剪枝
+        let ids: Vec<i64> = (0..4000).map(|i| i * 7919 % 4000).collect();
+        let segment = |granularity| {
+            let opts = SegmentWriterOptions {
+                page_max_rows:     1000,
+                bloom_granularity: granularity,
+                bloom_fpp:         0.001,
+                ..Default::default()
+            };
+            let mut w = SegmentWriter::with_options(schema.to_vec(), opts);
+            for &id in &ids {
+                w.append_row(vec![Value::Int64(id)]).unwrap();
+            }
+            // 破坏第 2 页以外的全部页：只要被解码就会报错
+            let mut data = w.finish().unwrap().0;
+            let reader   = SegmentReader::open(data.clone(), schema.to_vec()).unwrap();
+            let (cm, _)  = reader.column(0).unwrap();
+            for (page, &(_, off, _)) in reader.ordinal_index(cm).unwrap().entries().iter().enumerate() {
+                if page != 2 {
+                    data[off as usize + crate::page::PAGE_HEADER_SIZE + 3] ^= 0xff;
+                }
+            }
+            SegmentReader::open(data, schema.to_vec()).unwrap()
+        };
+
+        let target = ids[2500];
+        let probe  = Value::Int64(target).to_sort_key();
+        let reader = segment(crate::index::BloomGranularity::Page);
+        // 结果是候选页的全部行：只有第 2 页被解码
+        let hits   = reader.read_column_filtered(0, &probe, &probe).unwrap();
+        assert_eq!(hits.len(), 1000);
+        assert_eq!(hits.first().map(|h| h.0), Some(2000));
+        assert!(hits.contains(&(2500, Value::Int64(target))));
+        let reader = segment(crate::index::BloomGranularity::Segment);
+        assert!(reader.read_column_filtered(0, &probe, &probe).is_err());
+    }
+
+
+    // ── 文件头校验 ────────────────────────────────────────────────────────────
+
+    #[test]
+    fn corrupt_header_magic_with_intact_tail_is_rejected() {
+        let mut w = SegmentWriter::new(schema());
+        for row in rows(10) {
+            w.append_row(row).unwrap();
+        }
+        let data = w.finish().unwrap().0;
+        for pos in [0usize, 7] {
+            let mut tampered = data.clone();
+            tampered[pos] ^= 0xff;
+            assert_eq!(tampered[tampered.len() - 8..], *MAGIC);
+            match SegmentReader::open(tampered, schema()) {
+                Err(OlapError::SegmentIo(msg)) => assert_eq!(msg, "invalid segment header magic"),
+                other => panic!("byte {pos}: {:?}", other.map(|_| ())),
+            }
+        }
+    }
+
+    #[test]
+    fn short_files_and_oversized_footer_len_are_rejected_without_panicking() {
+        let mut w = SegmentWriter::new(schema());
+        for row in rows(10) {
+            w.append_row(row).unwrap();
+        }
+        let data = w.finish().unwrap().0;
+        let n    = data.len();
+
+        assert!(matches!(SegmentReader::open(data[..5].to_vec(), schema()), Err(OlapError::SegmentIo(_))));
+        assert!(matches!(SegmentReader::open(Vec::new(), schema()), Err(OlapError::SegmentIo(_))));
+        for footer_len in [n as u32, u32::MAX] {
+            let mut tampered = data.clone();
+            tampered[n-12..n-8].copy_from_slice(&footer_len.to_le_bytes());
+            match SegmentReader::open(tampered, schema()) {
+                Err(OlapError::SegmentIo(msg)) => assert!(msg.contains("footer length"), "{msg}"),
+                other => panic!("footer_len {footer_len}: {:?}", other.map(|_| ())),
+            }
+        }
+        let reader = SegmentReader::open(data, schema()).unwrap();
+        assert_eq!(reader.num_rows(), 10);
+    }
+
+
+    // ── 写入校验 ──────────────────────────────────────────────────────────────
+
+    #[test]
+    fn null_in_a_key_column_is_rejected_with_its_index() {
+        let mut w = SegmentWriter::new(schema());
+        match w.append_row(vec![Value::Null, Value::Null, Value::Float64(1.0)]) {
+            Err(OlapError::SchemaMismatch(msg)) => assert_eq!(msg, "column 0 (k) is not nullable"),
+            other => panic!("{other:?}"),
+        }
+        // 可空列接受 NULL；被拒绝的行不计入
+        w.append_row(vec![Value::Int64(1), Value::Null, Value::Float64(1.0)]).unwrap();
+        let reader = SegmentReader::open(w.finish().unwrap().0, schema()).unwrap();
+        assert_eq!(reader.num_rows(), 1);
+    }
+
+    #[test]
+    fn value_of_the_wrong_type_is_rejected_with_its_index() {
+        let mut w = SegmentWriter::new(schema());
+        for (row, col) in [
+            (vec![Value::Bytes(b"1".to_vec()), Value::Null, Value::Float64(1.0)], 0),
+            (vec![Value::Int32(1), Value::Null, Value::Float64(1.0)], 0),
+            (vec![Value::Int64(1), Value::Int64(2), Value::Float64(1.0)], 1),
+            (vec![Value::Int64(1), Value::Null, Value::Float32(1.0)], 2),
+        ] {
+            match w.append_row(row) {
+                Err(OlapError::SchemaMismatch(msg)) => assert!(msg.starts_with(&format!("column {col} ")), "{msg}"),
+                other => panic!("column {col}: {other:?}"),
+            }
+        }
+    }
+
+
+    #[test]
+    fn rows_of_the_wrong_width_report_expected_and_actual() {
+        let mut w = SegmentWriter::new(schema());
+        for (row, got) in [
+            (vec![], 0),
+            (vec![Value::Int64(1), Value::Null], 2),
+            (vec![Value::Int64(1), Value::Null, Value::Float64(1.0), Value::Int64(9)], 4),
+        ] {
+            match w.append_row(row) {
+                Err(e @ OlapError::RowWidthMismatch(3, n)) if n == got => {
+                    assert_eq!(e.to_string(), format!("row width mismatch: expected 3 values, got {got}"));
+                }
+                other => panic!("width {got}: {other:?}"),
+            }
+        }
+        let cols = to_columns(&rows(4));
+        assert!(matches!(w.append_batch(cols[..2].to_vec()), Err(OlapError::RowWidthMismatch(3, 2))));
+        let reader = SegmentReader::open(w.finish().unwrap().0, schema()).unwrap();
+        assert_eq!(reader.num_rows(), 0);
+    }
+
+    fn short_name_schema() -> Vec<ColumnMeta> {
+        vec![
+            ColumnMeta::new(0, "k", FieldType::Int64).key(),
+            ColumnMeta::new(1, "name", FieldType::Bytes).with_max_length(4),
+        ]
+    }
+
+    fn name_row(k: i64, name: &str) -> Vec<Value> {
+        vec![Value::Int64(k), Value::Bytes(name.as_bytes().to_vec())]
+    }
+
+    #[test]
+    fn bytes_longer_than_max_length_are_rejected() {
+        let mut w = SegmentWriter::new(short_name_schema());
+        w.append_row(name_row(0, "abcd")).unwrap();
+        assert!(matches!(w.append_row(name_row(1, "abcde")), Err(OlapError::ValueTooLong(1, 5, 4))));
+        let reader = SegmentReader::open(w.finish().unwrap().0, short_name_schema()).unwrap();
+        assert_eq!(reader.read_column(1).unwrap(), [Value::Bytes(b"abcd".to_vec())]);
+    }
+
+    #[test]
+    fn bytes_longer_than_max_length_are_truncated_when_enabled() {
+        let opts   = SegmentWriterOptions { truncate_long_bytes: true, ..Default::default() };
+        let reader = write(&short_name_schema(), opts, vec![name_row(0, "abcd"), name_row(1, "abcde"), name_row(2, "")]);
+        assert_eq!(reader.read_column(1).unwrap(), [
+            Value::Bytes(b"abcd".to_vec()),
+            Value::Bytes(b"abcd".to_vec()),
+            Value::Bytes(vec![]),
+        ]);
+    }
+
+    #[test]
+    fn bytes_are_unbounded_unless_the_column_sets_max_length() {
+        assert_eq!(ColumnMeta::new(1, "blob", FieldType::Bytes).max_length, 0);
+        let big = "x".repeat(200 * 1024);
+        let row    = vec![Value::Int64(0), Value::Bytes(big.clone().into_bytes()), Value::Float64(0.0)];
+        let reader = write(&schema(), Default::default(), vec![row]);
+        assert_eq!(reader.read_column(1).unwrap(), [Value::Bytes(big.into_bytes())]);
+    }
+
+
+    // ── 键序校验 ──────────────────────────────────────────────────────────────
+
+    #[test]
+    fn enforce_sort_order_accepts_sorted_rows_and_rejects_regressions() {
+        let schema = vec![
+            ColumnMeta::new(0, "k1", FieldType::Bytes).key(),
+            ColumnMeta::new(1, "k2", FieldType::Int64).key(),
+            ColumnMeta::new(2, "v", FieldType::Int32),
+        ];
+        let row = |k1: &str, k2: i64| vec![Value::Bytes(k1.as_bytes().to_vec()), Value::Int64(k2), Value::Int32(0)];
+        let sorted = [row("a", 5), row("a", 5), row("a", 9), row("ab", -3), row("b", i64::MIN)];
+        let opts = SegmentWriterOptions { enforce_sort_order: true, ..Default::default() };
+
+        let mut w = SegmentWriter::with_options(schema.clone(), opts.clone());
+        for r in sorted.iter().cloned() {
+            w.append_row(r).unwrap();
+        }
+        // 首个 key 列回退即拒绝，与后续 key 列的大小无关
+        match w.append_row(row("a", 100)) {
+            Err(OlapError::Unsupported(msg)) => assert_eq!(msg, "rows not in key order"),
+            other => panic!("{other:?}"),
+        }
+        // 被拒绝的行不推进上一行的 key
+        w.append_row(row("b", 0)).unwrap();
+        assert!(matches!(w.append_batch(to_columns(&[row("c", 0), row("bz", 0)])), Err(OlapError::Unsupported(_))));
+        let reader = SegmentReader::open(w.finish().unwrap().0, schema.clone()).unwrap();
+        assert_eq!(reader.num_rows(), 6);
+
+        // 默认关闭（Duplicate 表），乱序行照常写入
+        let mut w = SegmentWriter::new(schema);
+        for r in sorted.into_iter().rev() {
+            w.append_row(r).unwrap();
+        }
+        assert!(!SegmentWriterOptions::default().enforce_sort_order);
+    }
+
+    // ── 写入报告 ──────────────────────────────────────────────────────────────
+
+    #[test]
+    fn write_report_regions_sum_to_the_total() {
+        let mut w = SegmentWriter::with_options(schema(), pages_of(500));
+        for row in rows(3000) {
+            w.append_row(row).unwrap();
+        }
+        let mut out = Vec::new();
+        let report  = w.finalize_with_report(&mut out).unwrap();
+        assert_eq!(report.total_bytes, out.len() as u64);
+        assert_eq!(report.num_rows, 3000);
+        assert_eq!(report.per_column_data_bytes.len(), 3);
+        assert_eq!(report.per_column_index_bytes.len(), 3);
+        assert!(report.per_column_data_bytes.iter().all(|&n| n > 0));
+        assert!(report.short_key_bytes > 0);
+        let sum = report.header_bytes
+            + report.per_column_data_bytes.iter().sum::<u64>()
+            + report.per_column_index_bytes.iter().sum::<u64>()
+            + report.short_key_bytes
+            + report.footer_bytes;
+        assert_eq!(sum, report.total_bytes);
+
+        // 各列数据字节数与 footer 中记录的数据区一致
+        let reader = SegmentReader::open(out, schema()).unwrap();
+        for (col_idx, &bytes) in report.per_column_data_bytes.iter().enumerate() {
+            assert_eq!(reader.footer.column_metas[col_idx].data_size, bytes, "col {col_idx}");
+        }
+    }
+
+
+    // ── 校验算法 ──────────────────────────────────────────────────────────────
+
+    #[test]
+    fn each_checksum_type_round_trips_and_detects_corruption() {
+        for checksum in [ChecksumType::Crc32, ChecksumType::XxHash64] {
+            let opts = SegmentWriterOptions { page_max_rows: 500, checksum, ..Default::default() };
+            let mut w = SegmentWriter::with_options(schema(), opts);
+            for row in rows(2000) {
+                w.append_row(row).unwrap();
+            }
+            let data = w.finish().unwrap().0;
+            assert_eq!(data[13], checksum.tag());
+            let reader = SegmentReader::open(data.clone(), schema()).unwrap();
+            assert_eq!(reader.read_column(0).unwrap(), to_columns(&rows(2000))[0]);
+
+            // 数据页损坏：读到该页时报错
+            let (cm, _) = reader.column(2).unwrap();
+            let (first_row, off, _) = reader.ordinal_index(cm).unwrap().entries()[1];
+            let mut page = data.clone();
+            page[off as usize + crate::page::PAGE_HEADER_SIZE + 3] ^= 0xff;
+            let reader = SegmentReader::open(page, schema()).unwrap();
+            assert!(matches!(reader.get_row(first_row), Err(OlapError::ChecksumMismatch)), "{checksum:?}");
+            assert!(reader.get_row(0).is_ok());
+
+            // footer 损坏：打开即失败
+            let mut footer = data.clone();
+            let last_footer_byte = data.len() - 12 - checksum.size() - 1;
+            footer[last_footer_byte] ^= 0xff;
+            assert!(matches!(SegmentReader::open(footer, schema()), Err(OlapError::ChecksumMismatch)), "{checksum:?}");
+        }
+    }
+
+
+    // ── 内容摘要 ──────────────────────────────────────────────────────────────
+
+    #[test]
+    fn flipped_data_byte_fails_digest_while_footer_still_opens() {
+        let encode = || {
+            let mut w = SegmentWriter::with_options(schema(), pages_of(500));
+            for row in rows(2000) {
+                w.append_row(row).unwrap();
+            }
+            w.finish().unwrap().0
+        };
+        let data   = encode();
+        let reader = SegmentReader::open(data.clone(), schema()).unwrap();
+        reader.verify_digest().unwrap();
+        // 同样的输入产生同样的摘要，副本间可据此比对
+        let digest = reader.content_digest().unwrap();
+        assert_eq!(SegmentReader::open(encode(), schema()).unwrap().content_digest(), Some(digest));
+
+        let (cm, _) = reader.column(0).unwrap();
+        let (_, off, _) = reader.ordinal_index(cm).unwrap().entries()[2];
+        let mut tampered = data;
+        tampered[off as usize + crate::page::PAGE_HEADER_SIZE + 3] ^= 0xff;
+        let reader = SegmentReader::open(tampered, schema()).unwrap();
+        assert_eq!(reader.content_digest(), Some(digest));
+        assert!(matches!(reader.verify_digest(), Err(OlapError::ChecksumMismatch)));
+    }
+
+    // ── 页加密 ────────────────────────────────────────────────────────────────
+
+    #[test]
+    fn encrypted_segment_round_trips_and_rejects_a_wrong_key() {
+        let key  = EncryptionKey::new([7; 32]);
+        let opts = SegmentWriterOptions { page_max_rows: 300, encryption: Some(key.clone()), ..Default::default() };
+        let rows = rows(1000);
+        let mut w = SegmentWriter::with_options(schema(), opts);
+        for row in rows.clone() {
+            w.append_row(row).unwrap();
+        }
+        let data = w.finish().unwrap().0;
+
+        let reader = SegmentReader::open(data.clone(), schema()).unwrap();
+        assert!(reader.is_encrypted());
+        assert!(matches!(reader.read_column(0), Err(OlapError::Encryption(_))));
+        let reader = reader.with_encryption_key(key).unwrap();
+        for (col_idx, want) in to_columns(&rows).iter().enumerate() {
+            assert_eq!(&reader.read_column(col_idx).unwrap(), want);
+        }
+        assert_eq!(reader.get_row(777).unwrap(), rows[777]);
+        reader.verify_digest().unwrap();
+
+        let wrong = SegmentReader::open(data, schema()).unwrap().with_encryption_key(EncryptionKey::new([8; 32]));
+        match wrong {
+            Err(OlapError::Encryption(msg)) => assert_eq!(msg, "wrong encryption key"),
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+    }
+
+    // ── 全局字典 ──────────────────────────────────────────────────────────────
+
+    #[test]
+    fn low_cardinality_column_stores_one_shared_dictionary() {
+        let schema = [
+            ColumnMeta::new(0, "order_id", FieldType::Int64).key(),
+            ColumnMeta::new(1, "status", FieldType::Bytes),
+        ];
+        let statuses = ["cancelled", "paid", "pending", "shipped"];
+        let rows: Vec<Vec<Value>> = (0..5000i64)
+            .map(|i| vec![Value::Int64(i), Value::Bytes(statuses[(i * 7 % 4) as usize].as_bytes().to_vec())])
+            .collect();
+        let opts = SegmentWriterOptions { global_dictionary_columns: vec![1], ..pages_of(1024) };
+        let mut w = SegmentWriter::with_options(schema.to_vec(), opts);
+        for row in rows.clone() {
+            w.append_row(row).unwrap();
+        }
+        let data   = w.finish().unwrap().0;
+        let reader = SegmentReader::open(data.clone(), schema.to_vec()).unwrap();
+        let (cm, _) = reader.column(1).unwrap();
+        assert_eq!(reader.ordinal_index(cm).unwrap().page_count(), 5);
+        assert!(cm.dict_size > 0);
+        let entries: Vec<&[u8]> = reader.dictionary(1).unwrap().unwrap().iter().map(Vec::as_slice).collect();
+        let mut sorted = entries.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, statuses.map(str::as_bytes));
+
+        // 既非页内 min 也非 max 的取值只在字典中出现一次
+        assert_eq!(data.windows(7).filter(|w| *w == b"pending").count(), 1);
+        assert_eq!(reader.read_column(1).unwrap(), to_columns(&rows)[1]);
+    }
+
+    #[test]
+    fn global_dictionary_is_opt_in_per_column() {
+        let schema = [
+            ColumnMeta::new(0, "order_id", FieldType::Int64).key(),
+            ColumnMeta::new(1, "status", FieldType::Bytes),
+            ColumnMeta::new(2, "uuid", FieldType::Bytes),
+        ];
+        let rows: Vec<Vec<Value>> = (0..3000i64)
+            .map(|i| vec![
+                Value::Int64(i),
+                Value::Bytes(["paid", "pending"][(i % 2) as usize].as_bytes().to_vec()),
+                Value::Bytes(format!("{:032x}", i * 2_654_435_761).into_bytes()),
+            ])
+            .collect();
+        // 默认不使用全局字典；只有列出的列使用，高基数列照旧使用页内字典
+        for (opts, want) in [
+            (pages_of(1024), [false, false]),
+            (SegmentWriterOptions { global_dictionary_columns: vec![1], ..pages_of(1024) }, [true, false]),
+        ] {
+            let reader = write(&schema, opts, rows.clone());
+            for (col_idx, want) in [1, 2].into_iter().zip(want) {
+                let (cm, meta) = reader.column(col_idx).unwrap();
+                assert_eq!(cm.dict_size > 0, want, "col {col_idx}");
+                assert_eq!(reader.read_column(col_idx).unwrap(), to_columns(&rows)[col_idx]);
+                // 全局字典与页内字典的页都报告 Dictionary
+                let entry = reader.ordinal_index(cm).unwrap().entries()[0];
+                let page  = reader.decode_page(cm, meta.field_type, entry).unwrap();
+                assert_eq!(page.encoding_used, EncodingType::Dictionary, "col {col_idx}");
+            }
+        }
+    }
+
+
+    // ── BitmapIndex ───────────────────────────────────────────────────────────
+
+    #[test]
+    fn bitmap_index_returns_rows_for_each_status() {
+        let schema = [
+            ColumnMeta::new(0, "order_id", FieldType::Int64).key(),
+            ColumnMeta::new(1, "status", FieldType::Bytes).nullable(),
+        ];
+        let statuses = ["cancelled", "paid", "pending", "shipped"];
+        let status_of = |i: u32| (!i.is_multiple_of(11)).then(|| statuses[(i * 7 % 4) as usize]);
+        let opts = SegmentWriterOptions { bitmap_index_columns: vec![1], ..pages_of(1000) };
+        let rows = (0..5000u32)
+            .map(|i| {
+                let status = status_of(i).map_or(Value::Null, |s| Value::Bytes(s.as_bytes().to_vec()));
+                vec![Value::Int64(i as i64), status]
+            })
+            .collect();
+        let reader = write(&schema, opts, rows);
+
+        for status in statuses {
+            let want: Vec<u32> = (0..5000).filter(|&i| status_of(i) == Some(status)).collect();
+            let got = reader.rows_for_value(1, &Value::Bytes(status.as_bytes().to_vec())).unwrap().unwrap();
+            assert_eq!(got, want, "{status}");
+        }
+        let bi = reader.bitmap_index(1).unwrap().unwrap();
+        assert_eq!(bi.keys().collect::<Vec<_>>(), statuses.map(str::as_bytes));
+        assert!(reader.rows_for_value(1, &Value::Bytes(b"refunded".to_vec())).unwrap().unwrap().is_empty());
+        // 未建索引的列返回 None
+        assert!(reader.rows_for_value(0, &Value::Int64(3)).unwrap().is_none());
+    }
+
+
+    // ── 全 NULL 页 ────────────────────────────────────────────────────────────
+
+    #[test]
+    fn zone_map_flags_all_null_and_mixed_pages() {
+        let schema = [
+            ColumnMeta::new(0, "k", FieldType::Int64).key(),
+            ColumnMeta::new(1, "discount", FieldType::Int64).nullable(),
+        ];
+        // 第 0 页部分 NULL，第 1 页全 NULL，第 2 页无 NULL
+        let discount = |i: i64| match i / 100 {
+            0 if i % 3 == 0 => Value::Null,
+            1               => Value::Null,
+            _               => Value::Int64(i % 50),
+        };
+        let rows   = (0..300).map(|i| vec![Value::Int64(i), discount(i)]).collect();
+        let reader = write(&schema, pages_of(100), rows);
+        let (cm, _) = reader.column(1).unwrap();
+        let zm = ZoneMapIndex::deserialize(reader.region(cm.zonemap_offset, cm.zonemap_size).unwrap());
+        let flags: Vec<(bool, bool)> = zm.entries().iter().map(|e| (e.has_null, e.all_null)).collect();
+        assert_eq!(flags, [(true, false), (true, true), (false, false)]);
+        assert_eq!(zm.null_pages(), [0, 1]);
+
+        // 非 NULL 探测值不会落到全 NULL 页
+        let (lo, hi) = (Value::Int64(i64::MIN).to_sort_key(), Value::Int64(i64::MAX).to_sort_key());
+        assert_eq!(zm.filter(&lo, &hi), [0, 2]);
+        let hits = reader.read_column_filtered(1, &lo, &hi).unwrap();
+        assert!(hits.iter().all(|&(row, _)| !(100..200).contains(&row)));
+        assert_eq!(hits.len(), 200);
+    }
+
+
+    // ── ShortKey 前缀 ─────────────────────────────────────────────────────────
+
+    /// 按 `ShortKeyIndex::serialize` 的格式解析出 (row_id, prefix)
+    fn short_key_entries(reader: &SegmentReader) -> Vec<(u32, Vec<u8>)> {
+        let data = reader.region(reader.footer.short_key_offset, reader.footer.short_key_size).unwrap();
+        let n    = u32::from_le_bytes(data[0..4].try_into().unwrap());
+        let mut pos = 4;
+        (0..n)
+            .map(|_| {
+                let rid = u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap());
+                let len = u32::from_le_bytes(data[pos + 4..pos + 8].try_into().unwrap()) as usize;
+                pos += 8 + len;
+                (rid, data[pos - len..pos].to_vec())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn short_key_prefix_covers_only_key_columns() {
+        let schema = [
+            ColumnMeta::new(0, "day", FieldType::Int32).key(),
+            ColumnMeta::new(1, "order_id", FieldType::Int64).key(),
+            ColumnMeta::new(2, "note", FieldType::Bytes),
+        ];
+        let row  = |i: i64| vec![Value::Int32(20240101 + (i / 100) as i32), Value::Int64(i), Value::Bytes(vec![b'x'; 40])];
+        let opts = SegmentWriterOptions { short_key_interval: 100, ..Default::default() };
+        let reader  = write(&schema, opts.clone(), (0..300).map(row).collect());
+        let entries = short_key_entries(&reader);
+        assert_eq!(entries.iter().map(|e| e.0).collect::<Vec<_>>(), [0, 100, 200]);
+        for (rid, prefix) in entries {
+            let r = row(rid as i64);
+            assert_eq!(prefix, [r[0].to_sort_key(), r[1].to_sort_key()].concat(), "row {rid}");
+        }
+
+        // 没有 key 列时前缀为空
+        let no_keys: Vec<ColumnMeta> = schema.iter().map(|m| ColumnMeta { is_key: false, ..m.clone() }).collect();
+        let reader = write(&no_keys, opts, (0..300).map(row).collect());
+        assert!(short_key_entries(&reader).iter().all(|(_, prefix)| prefix.is_empty()));
+    }
+
+
+    // ── 由 TabletSchema 建写入器 ──────────────────────────────────────────────
+
+    #[test]
+    fn writer_from_tablet_schema_derives_matching_column_metas() {
+        use crate::common::ColumnType;
+        use crate::meta::ColumnSchema;
+        let table = TabletSchema::new(KeysType::Unique, vec![
+            ColumnSchema::key(0, "day", ColumnType::Date),
+            ColumnSchema::key(1, "order_id", ColumnType::Int64),
+            ColumnSchema::varchar(2, "status", 16, false),
+            ColumnSchema::value(3, "amount", ColumnType::Decimal { precision: 18, scale: 2 }, AggregateType::Replace),
+            ColumnSchema::value(4, "paid", ColumnType::Bool, AggregateType::Replace),
+        ]);
+        let mut w = SegmentWriter::from_tablet_schema(&table);
+        assert_eq!(w.schema().len(), table.columns.len());
+        for (meta, col) in w.schema().iter().zip(&table.columns) {
+            assert_eq!(meta.column_id, col.column_id);
+            assert_eq!(meta.name, col.name);
+            assert_eq!(meta.field_type, FieldType::from(col.column_type));
+            assert_eq!((meta.is_key, meta.is_nullable), (col.is_key, col.is_nullable), "{}", col.name);
+            assert_eq!(meta.encoding, ColumnMeta::new(0, "", meta.field_type).encoding);
+        }
+        assert_eq!(w.schema()[2].max_length, 16);
+
+        // Unique 表按 key 有序写入
+        let row = |day: i32, id: i64| vec![
+            Value::Int32(day), Value::Int64(id), Value::Bytes(b"paid".to_vec()), Value::Decimal(1999), Value::Bool(true),
+        ];
+        w.append_row(row(19000, 2)).unwrap();
+        assert!(matches!(w.append_row(row(19000, 1)), Err(OlapError::Unsupported(_))));
+        assert!(matches!(w.append_row(vec![Value::Null; 5]), Err(OlapError::SchemaMismatch(_))));
+        let reader = SegmentReader::open(w.finish().unwrap().0, table.segment_schema()).unwrap();
+        assert_eq!(reader.get_row(0).unwrap(), row(19000, 2));
     }
 }
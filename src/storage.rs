@@ -1,10 +1,20 @@
 //! 顶层存储协调器
 
-use crate::common::{CompactionType, DbId, Result, RowsetId, SchemaHash, TabletId, TableId};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use crate::common::{
+    CompactionType, DbId, OlapError, Result, RowsetId, SchemaHash, TabletId, TableId, Version,
+};
 use crate::meta::{RowsetMeta, TabletMeta, TabletSchema};
-use crate::partition::PartitionInfo;
+use crate::encryption::EncryptionKey;
+use crate::segment::{merge_segments_with_options, SegmentReader, SegmentWriter, SegmentWriterOptions};
+use crate::partition::{Partition, PartitionInfo, RangePartitionItem};
 use crate::table::{CatalogManager, OlapTable};
-use crate::tablet::{Tablet, TabletManager};
+use crate::tablet::{Tablet, TabletDescription, TabletManager};
+use crate::wal::{Wal, WalEntry};
 
 /// 创建表时每个分区的规格
 pub struct PartitionSpec {
@@ -14,22 +24,78 @@ pub struct PartitionSpec {
     pub schema_hash:  SchemaHash,
 }
 
+/// Stale Rowset 保留时长（秒），超过后才会被 `gc_stale_rowsets` 回收
+pub const STALE_ROWSET_EXPIRE_SECS: u64 = 30 * 60;
+
+/// `schedule_compaction` 每轮最多返回的 Tablet 数
+pub const MAX_COMPACTION_CANDIDATES: usize = 10;
+
+/// 引擎级统计，见 `StorageEngine::metrics`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EngineMetrics {
+    pub num_databases:      usize,
+    pub num_tables:         usize,
+    pub num_tablets:        usize,
+    /// 含尚未回收的 Stale Rowset
+    pub total_rowsets:      usize,
+    /// 只计可见 Rowset
+    pub total_visible_rows: u64,
+    /// 含尚未回收的 Stale Rowset
+    pub total_disk_bytes:   u64,
+}
+
 /// 单节点 OLAP 存储引擎
 pub struct StorageEngine {
-    pub data_dir:        String,
+    /// 数据目录，Tablet 按 `tablet_id % data_dirs.len()` 固定落在其中一个
+    pub data_dirs:       Vec<String>,
     pub tablet_manager:  TabletManager,
     pub catalog_manager: CatalogManager,
+    /// Rowset 发布日志，位于 `{data_dirs[0]}/rowset.wal`
+    wal:                 Wal,
+    /// 后台 Compaction 线程，未启动时为 None
+    compaction_workers:  Mutex<Option<CompactionWorkers>>,
+    /// 正在合并中的 Tablet，防止多个线程同时合并同一个 Tablet
+    compacting:          Mutex<HashSet<TabletId>>,
+    /// 加密 Segment 的密钥；Compaction 用它读取加密的输入，并以同一密钥加密输出
+    encryption_key:      Option<EncryptionKey>,
+}
+
+/// 后台 Compaction 线程组
+struct CompactionWorkers {
+    /// (是否停止, 唤醒等待中的线程)
+    stop:    Arc<(Mutex<bool>, Condvar)>,
+    handles: Vec<JoinHandle<()>>,
 }
 
 impl StorageEngine {
-    pub fn new_single_dir(data_dir: &str) -> Self {
+    /// 多数据目录；WAL 放在第一个目录下
+    ///
+    /// # Panics
+    /// `data_dirs` 为空时 panic。
+    pub fn new(data_dirs: Vec<String>) -> Self {
+        assert!(!data_dirs.is_empty(), "StorageEngine needs at least one data dir");
+        let wal = Wal::new(format!("{}/rowset.wal", data_dirs[0]));
         Self {
-            data_dir:        data_dir.into(),
+            data_dirs,
             tablet_manager:  TabletManager::new(),
             catalog_manager: CatalogManager::new(),
+            wal,
+            compaction_workers: Mutex::new(None),
+            compacting:         Mutex::new(HashSet::new()),
+            encryption_key:     None,
         }
     }
 
+    pub fn new_single_dir(data_dir: &str) -> Self {
+        Self::new(vec![data_dir.into()])
+    }
+
+    /// 设置加密 Segment 的密钥；未设置时合并含加密 Segment 的 Rowset 返回 `Encryption`
+    pub fn with_encryption_key(mut self, key: EncryptionKey) -> Self {
+        self.encryption_key = Some(key);
+        self
+    }
+
     // ── DDL ───────────────────────────────────────────────────────────────────
 
     pub fn create_database(&self, db_id: DbId, db_name: &str) -> Result<()> {
@@ -37,6 +103,7 @@ impl StorageEngine {
     }
 
     /// 创建 Table 同时创建所有 Partition / Tablet
+    #[allow(clippy::too_many_arguments)]
     pub fn create_table_with_partitions(
         &self,
         db_id:            DbId,
@@ -47,18 +114,59 @@ impl StorageEngine {
         partition_specs:  Vec<PartitionSpec>,
         _replication_num: u32,
     ) -> Result<()> {
-        // 1. 为每个分区的每个桶创建 Tablet
-        for spec in &partition_specs {
-            for &tid in &spec.tablet_ids {
-                let meta = TabletMeta::new(tid, spec.partition_id, schema.clone());
-                self.tablet_manager.create_tablet(meta)?;
-            }
-        }
+        // 1. 为每个分区的每个桶创建 Tablet，tablet_id 冲突时一个也不创建
+        let metas = partition_specs.iter()
+            .flat_map(|spec| spec.tablet_ids.iter().map(move |&tid| (tid, spec.partition_id)))
+            .map(|(tid, partition_id)| TabletMeta::new(tid, partition_id, schema.clone()))
+            .collect();
+        self.tablet_manager.create_tablets(metas)?;
         // 2. 将 Table 注册进 Catalog
         let table = OlapTable::new(table_id, table_name, schema, partition_info);
         self.catalog_manager.add_table(db_id, table)
     }
 
+    /// 删除库及其全部表，并移除这些表各分区（含 rollup 索引）的所有 Tablet
+    ///
+    /// 删除后用 `CatalogManager::save` 原子地写回 `catalog_dir()`，重启后该库不会复活。
+    /// 库不存在时返回 `DatabaseNotFound`；Segment 文件不在此删除。
+    pub fn drop_database(&self, db_id: DbId) -> Result<()> {
+        let tables = self.catalog_manager.drop_database(db_id)?;
+        let tablet_ids: Vec<TabletId> = tables.iter()
+            .flat_map(|t| t.read().unwrap().tablet_ids())
+            .collect();
+        self.tablet_manager.drop_tablets(&tablet_ids);
+        self.catalog_manager.save(&self.catalog_dir())
+    }
+
+    /// 运行时为 RANGE 表追加分区并创建其 Tablet
+    ///
+    /// 持有表写锁依次：校验分区区间（见 `PartitionInfo::check_range_partition`）、
+    /// 用 `create_tablets` 一次建好全部 Tablet（任一已存在则一个也不建，返回 `TabletExists`）、
+    /// 最后登记分区。任一步失败都不会留下孤立 Tablet 或没有 Tablet 的分区。
+    pub fn add_partition(
+        &self,
+        db_id:     DbId,
+        table_id:  TableId,
+        item:      RangePartitionItem,
+        partition: Partition,
+        spec:      PartitionSpec,
+    ) -> Result<()> {
+        if spec.partition_id != partition.partition_id {
+            return Err(OlapError::Unsupported(format!(
+                "spec partition_id {} != partition {}", spec.partition_id, partition.partition_id
+            )));
+        }
+        let table_arc = self.catalog_manager.get_table(db_id, table_id)?;
+        let mut table = table_arc.write().unwrap();
+
+        table.partition_info.check_range_partition(&item, &partition)?;
+        let metas = spec.tablet_ids.iter()
+            .map(|&tid| TabletMeta::new(tid, spec.partition_id, table.schema.clone()))
+            .collect();
+        self.tablet_manager.create_tablets(metas)?;
+        table.partition_info.add_range_partition(item, partition)
+    }
+
     // ── 低级 Tablet 操作 ──────────────────────────────────────────────────────
 
     pub fn create_tablet(&self, meta: TabletMeta) -> Result<Tablet> {
@@ -73,44 +181,996 @@ impl StorageEngine {
         self.tablet_manager.drop_tablet(tablet_id, schema_hash)
     }
 
+    /// Tablet 的版本拓扑：最大版本、cumulative 分界点、各 Rowset 状态、版本图的边与空洞
+    pub fn describe_tablet(&self, tablet_id: TabletId, schema_hash: SchemaHash) -> Result<TabletDescription> {
+        Ok(self.tablet_manager.get_tablet(tablet_id, schema_hash)?.describe())
+    }
+
     // ── Rowset 发布 ───────────────────────────────────────────────────────────
 
-    /// 将一个已提交的 Rowset 发布到对应 Tablet（Load 完成后调用）
+    /// 将一个已提交的 Rowset 发布到对应 Tablet（Load 完成后调用），返回其版本
+    ///
+    /// `rowset.version` 为 `Version::AUTO` 时分配该 Tablet 的下一个版本。
+    /// 分配、写 WAL、修改内存在同一把 Tablet 写锁内完成，崩溃后可由 `recover` 恢复。
     pub fn publish_rowset(
         &self,
         tablet_id:   TabletId,
         schema_hash: SchemaHash,
         rowset:      RowsetMeta,
-    ) -> Result<()> {
+    ) -> Result<Version> {
         let tablet = self.tablet_manager.get_tablet(tablet_id, schema_hash)?;
-        tablet.add_rowset(rowset)
+        tablet.add_rowset_with(rowset, |rs| self.wal.append(tablet_id, schema_hash, rs))
+    }
+
+    /// 启动时重放 WAL，返回实际恢复的 Rowset 数（含 compaction 输出）
+    ///
+    /// 需在 Tablet 创建之后调用；找不到 Tablet 或已存在的 Rowset 会被跳过，
+    /// 因此重复调用是幂等的。compaction 记录在登记输出的同时把其输入标记为 Stale，
+    /// cumulative compaction 记录还一并恢复 `compact_tablet` 推进后的分界点。
+    /// 日志末尾残缺的记录会先被截掉，之后的发布接在最后一条有效记录后。
+    pub fn recover(&self) -> Result<usize> {
+        self.wal.truncate_torn_tail()?;
+        let (records, _) = self.wal.read_all()?;
+        let mut applied = 0;
+        for rec in records {
+            let Ok(tablet) = self.tablet_manager.get_tablet(rec.tablet_id, rec.schema_hash) else {
+                continue;
+            };
+            let replayed = match rec.entry {
+                WalEntry::Publish(rs) => tablet.add_rowset(rs),
+                WalEntry::Compaction { output, inputs, cumulative_point } => {
+                    if let Some(point) = cumulative_point {
+                        tablet.advance_cumulative_point(point);
+                    }
+                    tablet.replace_rowsets_with(output, &inputs, |_| Ok(())).map(|_| ())
+                }
+                WalEntry::CumulativePoint(point) => {
+                    tablet.advance_cumulative_point(point);
+                    continue;
+                }
+            };
+            if replayed.is_ok() {
+                applied += 1;
+            }
+        }
+        Ok(applied)
+    }
+
+    /// 回收所有 Tablet 上过期的 Stale Rowset，返回待删除的 Segment 文件路径（见 `segment_path`）
+    pub fn gc_stale_rowsets(&self) -> Vec<String> {
+        self.tablet_manager
+            .all_tablets()
+            .iter()
+            .flat_map(|t| t.collect_stale_rowsets(STALE_ROWSET_EXPIRE_SECS))
+            .flat_map(|rs| {
+                (0..rs.num_segments).map(move |i| self.segment_path(rs.tablet_id, rs.rowset_id, i))
+            })
+            .collect()
+    }
+
+    // ── Segment 落盘 ──────────────────────────────────────────────────────────
+
+    /// 将 Segment 写到 `segment_path(...)`，必要时创建目录，返回文件字节数
+    pub fn write_segment(
+        &self,
+        tablet_id: TabletId,
+        rowset_id: RowsetId,
+        seg_idx:   u32,
+        writer:    SegmentWriter,
+    ) -> Result<u64> {
+        let io_err = |e: std::io::Error| OlapError::SegmentIo(e.to_string());
+
+        let path = self.segment_path(tablet_id, rowset_id, seg_idx);
+        if let Some(dir) = std::path::Path::new(&path).parent() {
+            std::fs::create_dir_all(dir).map_err(io_err)?;
+        }
+        let file = std::fs::File::create(&path).map_err(io_err)?;
+        let mut out = std::io::BufWriter::new(file);
+        let size = writer.finalize(&mut out)?;
+        out.into_inner()
+            .map_err(|e| io_err(e.into_error()))?
+            .sync_all()
+            .map_err(io_err)?;
+        Ok(size)
     }
 
     // ── Compaction 调度 ───────────────────────────────────────────────────────
 
+    /// 启动 `num_threads` 个后台线程，每隔 `interval` 合并一个得分最高的 Tablet
+    ///
+    /// 线程只持有引擎的弱引用，引擎释放后自行退出；已启动时返回 `Unsupported`。
+    pub fn start_compaction_workers(
+        self:        &Arc<Self>,
+        num_threads: usize,
+        interval:    Duration,
+    ) -> Result<()> {
+        let mut slot = self.compaction_workers.lock().unwrap();
+        if slot.is_some() {
+            return Err(OlapError::Unsupported("compaction workers already running".into()));
+        }
+        let stop = Arc::new((Mutex::new(false), Condvar::new()));
+        let handles = (0..num_threads)
+            .map(|i| {
+                let engine = Arc::downgrade(self);
+                let stop   = Arc::clone(&stop);
+                std::thread::Builder::new()
+                    .name(format!("compaction-{i}"))
+                    .spawn(move || compaction_worker_loop(engine, stop, interval))
+                    .map_err(|e| OlapError::Unsupported(format!("spawn compaction worker: {e}")))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        *slot = Some(CompactionWorkers { stop, handles });
+        Ok(())
+    }
+
+    /// 通知后台线程退出并等待其结束；未启动时为空操作
+    pub fn stop_compaction_workers(&self) {
+        let Some(workers) = self.compaction_workers.lock().unwrap().take() else { return };
+        let (lock, cvar) = &*workers.stop;
+        *lock.lock().unwrap() = true;
+        cvar.notify_all();
+        for h in workers.handles {
+            let _ = h.join();
+        }
+    }
+
+    /// 合并一轮：按得分从高到低找到第一个可合并且未被其他线程占用的 Tablet
+    ///
+    /// 返回被合并的 tablet_id。
+    pub fn run_compaction_round(&self, ctype: CompactionType) -> Option<TabletId> {
+        for (tid, shash, score) in self.tablet_manager.collect_compaction_candidates(ctype) {
+            if score <= 0.0 || !self.compacting.lock().unwrap().insert(tid) {
+                continue;
+            }
+            let done = match ctype {
+                CompactionType::Cumulative => self.compact_tablet(tid, shash),
+                CompactionType::Base       => self.base_compact_tablet(tid, shash),
+            };
+            self.compacting.lock().unwrap().remove(&tid);
+            if let Ok(true) = done {
+                return Some(tid);
+            }
+        }
+        None
+    }
+
+    /// 合并一个 Tablet Cumulative 区中版本连续的 Rowset，发布新 Rowset 并把输入标记为 Stale，
+    /// 同时把 cumulative 分界点推进到输出版本的末尾；二者写在同一条 WAL 记录中
+    ///
+    /// 没有可合并的 Rowset 时返回 `Ok(false)`。
+    pub fn compact_tablet(&self, tablet_id: TabletId, schema_hash: SchemaHash) -> Result<bool> {
+        let tablet = self.tablet_manager.get_tablet(tablet_id, schema_hash)?;
+        let inputs = tablet.pick_rowsets_for_compaction();
+        Ok(self.merge_rowsets(&tablet, schema_hash, &inputs, true)?.is_some())
+    }
+
+    /// 合并 Base 区最旧的一段连续 Rowset（见 `Tablet::pick_base_compaction`），不移动分界点
+    ///
+    /// 未达到触发阈值时返回 `Ok(false)`。
+    pub fn base_compact_tablet(&self, tablet_id: TabletId, schema_hash: SchemaHash) -> Result<bool> {
+        let tablet = self.tablet_manager.get_tablet(tablet_id, schema_hash)?;
+        let Some(inputs) = tablet.pick_base_compaction() else {
+            return Ok(false);
+        };
+        Ok(self.merge_rowsets(&tablet, schema_hash, &inputs, false)?.is_some())
+    }
+
+    /// 把版本连续的 `inputs` 合并为一个新 Rowset 发布，并把输入标记为 Stale
+    ///
+    /// 输入 Rowset 在磁盘上有 Segment 文件、或挂载了内存 Segment 时会真正合并数据，
+    /// 后者的输出同样挂在 Tablet 上；全部没有数据时只合并元数据。
+    /// 有输入 Segment 已加密时用 `with_encryption_key` 设置的密钥读取，输出同样加密；
+    /// 引擎没有密钥时在写出任何数据之前返回 `Encryption`，输入保持不变。
+    /// `advance_point` 为 true 时把 cumulative 分界点推进到输出版本的末尾，与发布写进同一条 WAL 记录。
+    /// 返回输出版本；`inputs` 为空时返回 None。
+    fn merge_rowsets(
+        &self,
+        tablet:        &Tablet,
+        schema_hash:   SchemaHash,
+        inputs:        &[RowsetMeta],
+        advance_point: bool,
+    ) -> Result<Option<Version>> {
+        let tablet_id = tablet.tablet_id();
+        let (Some(first), Some(last)) = (inputs.first(), inputs.last()) else {
+            return Ok(None);
+        };
+
+        let rowset_id = tablet.next_rowset_id();
+        let mut output = RowsetMeta::new(
+            rowset_id, tablet_id, first.partition_id,
+            Version::new(first.version.start, last.version.end),
+            inputs.iter().map(|r| r.num_rows).sum(),
+            inputs.iter().map(|r| r.data_disk_size).sum(),
+        );
+
+        // 收集输入 Segment：优先用 Tablet 上挂载的内存 Segment，其次是磁盘文件
+        let mut readers = Vec::new();
+        let (mut missing, mut in_memory) = (0, 0);
+        let tablet_schema = tablet.schema();
+        let schema        = tablet_schema.segment_schema();
+        let mut encrypted = false;
+        for rs in inputs {
+            let mut opened = Vec::new();
+            if let Some(segments) = tablet.segment_bytes(rs.rowset_id) {
+                in_memory += 1;
+                for bytes in segments {
+                    opened.push(SegmentReader::open_shared(bytes, schema.clone())?);
+                }
+            } else {
+                for seg in 0..rs.num_segments {
+                    let path = self.segment_path(tablet_id, rs.rowset_id, seg);
+                    if Path::new(&path).exists() {
+                        opened.push(SegmentReader::open_path(Path::new(&path), schema.clone())?);
+                    } else {
+                        missing += 1;
+                    }
+                }
+            }
+            for reader in opened {
+                if !reader.is_encrypted() {
+                    readers.push(reader);
+                    continue;
+                }
+                let key = self.encryption_key.clone().ok_or_else(|| OlapError::Encryption(format!(
+                    "tablet {tablet_id}: rowset {} is encrypted and the engine has no encryption key",
+                    rs.rowset_id,
+                )))?;
+                readers.push(reader.with_encryption_key(key)?);
+                encrypted = true;
+            }
+        }
+        let data_inputs = inputs.iter().filter(|r| !r.is_delete()).count();
+        if in_memory > 0 && in_memory < data_inputs {
+            return Err(OlapError::Unsupported(format!(
+                "tablet {tablet_id}: cannot merge in-memory rowsets with on-disk rowsets"
+            )));
+        }
+        let (mut written, mut attached) = (None, false);
+        if !readers.is_empty() {
+            if missing > 0 {
+                return Err(OlapError::SegmentIo(format!(
+                    "tablet {tablet_id}: {missing} input segment files missing"
+                )));
+            }
+            let opts = SegmentWriterOptions {
+                encryption: if encrypted { self.encryption_key.clone() } else { None },
+                ..Default::default()
+            };
+            let bytes = merge_segments_with_options(readers, &schema, tablet_schema.keys_type, opts)?;
+            output.num_segments   = 1;
+            output.data_disk_size = bytes.len() as u64;
+            if in_memory > 0 {
+                // 输入全在内存中：输出同样挂在 Tablet 上，不落盘
+                output.segment_paths = vec![];
+                tablet.attach_segment_bytes(rowset_id, bytes);
+                attached = true;
+            } else {
+                let path = self.segment_path(tablet_id, rowset_id, 0);
+                write_file(&path, &bytes)?;
+                output.segment_paths = vec![RowsetMeta::segment_file_name(tablet_id, rowset_id, 0)];
+                written = Some(path);
+            }
+        }
+
+        // 输出、"输入被替换"与分界点写进同一条 WAL 记录，重启后不会出现输入与输出同时可见，
+        // 也不会出现输出已发布而分界点未推进
+        let input_ids: Vec<RowsetId> = inputs.iter().map(|r| r.rowset_id).collect();
+        let point     = advance_point.then_some(last.version.end);
+        let published = tablet.replace_rowsets_with(output, &input_ids, |rs| {
+            self.wal.append_compaction(tablet_id, schema_hash, rs, &input_ids, point)
+        });
+        match published {
+            Ok(version) => {
+                if let Some(point) = point {
+                    tablet.advance_cumulative_point(point);
+                }
+                Ok(Some(version))
+            }
+            Err(e) => {
+                if let Some(path) = written {
+                    let _ = std::fs::remove_file(path);
+                }
+                if attached {
+                    tablet.detach_segment_bytes(rowset_id);
+                }
+                Err(e)
+            }
+        }
+    }
+
     /// 调度一轮 Compaction，返回得分最高的 tablet_id 列表
+    ///
+    /// Base 只返回 `Tablet::pick_base_compaction` 有结果的 Tablet。
     pub fn schedule_compaction(&self, ctype: CompactionType) -> Vec<TabletId> {
         self.tablet_manager
             .collect_compaction_candidates(ctype)
             .into_iter()
-            .take(10)
+            .filter(|&(tid, shash, _)| ctype == CompactionType::Cumulative || self.base_eligible(tid, shash))
+            .take(MAX_COMPACTION_CANDIDATES)
+            .map(|(tid, _, _)| tid)
+            .collect()
+    }
+
+    /// 同 `schedule_compaction`，但每个分片最多入选 `per_shard_limit` 个 Tablet
+    pub fn schedule_compaction_balanced(
+        &self,
+        ctype:           CompactionType,
+        per_shard_limit: usize,
+    ) -> Vec<TabletId> {
+        self.tablet_manager
+            .collect_compaction_candidates_balanced(ctype, per_shard_limit)
+            .into_iter()
+            .take(MAX_COMPACTION_CANDIDATES)
             .map(|(tid, _, _)| tid)
             .collect()
     }
 
     // ── 辅助 ─────────────────────────────────────────────────────────────────
 
+    fn base_eligible(&self, tablet_id: TabletId, schema_hash: SchemaHash) -> bool {
+        self.tablet_manager.get_tablet(tablet_id, schema_hash)
+            .is_ok_and(|t| t.pick_base_compaction().is_some())
+    }
+
     pub fn tablet_count(&self) -> usize {
         self.tablet_manager.tablet_count()
     }
 
+    /// 汇总 Catalog 与所有 Tablet 的统计；逐分片加读锁，不阻塞其他分片的写入
+    pub fn metrics(&self) -> EngineMetrics {
+        let mut m = EngineMetrics {
+            num_databases: self.catalog_manager.num_databases(),
+            num_tables:    self.catalog_manager.num_tables(),
+            ..Default::default()
+        };
+        self.tablet_manager.for_each_tablet(|t| {
+            let (rowsets, rows, bytes) = t.rowset_totals();
+            m.num_tablets        += 1;
+            m.total_rowsets      += rowsets;
+            m.total_visible_rows += rows;
+            m.total_disk_bytes   += bytes;
+        });
+        m
+    }
+
+    /// Catalog 持久化目录：`{data_dirs[0]}/catalog`
+    pub fn catalog_dir(&self) -> PathBuf {
+        Path::new(&self.data_dirs[0]).join("catalog")
+    }
+
+    /// Tablet 所在的数据目录：`data_dirs[tablet_id % data_dirs.len()]`
+    pub fn data_dir_for(&self, tablet_id: TabletId) -> &str {
+        &self.data_dirs[(tablet_id % self.data_dirs.len() as u64) as usize]
+    }
+
     /// 生成 Segment 文件在磁盘上的绝对路径
+    ///
+    /// 位置只由 id 决定；`RowsetMeta::segment_paths` 只记录文件名，不随数据目录变化。
     pub fn segment_path(
         &self,
         tablet_id: TabletId,
         rowset_id: RowsetId,
         seg_idx:   u32,
     ) -> String {
-        format!("{}/{}/{}_{}.seg", self.data_dir, tablet_id, rowset_id, seg_idx)
+        format!("{}/{}/{}_{}.seg", self.data_dir_for(tablet_id), tablet_id, rowset_id, seg_idx)
+    }
+}
+
+// ── 内部辅助 ──────────────────────────────────────────────────────────────────
+
+fn compaction_worker_loop(
+    engine:   Weak<StorageEngine>,
+    stop:     Arc<(Mutex<bool>, Condvar)>,
+    interval: Duration,
+) {
+    let (lock, cvar) = &*stop;
+    loop {
+        let stopped = cvar
+            .wait_timeout_while(lock.lock().unwrap(), interval, |s| !*s)
+            .unwrap().0;
+        if *stopped { return; }
+        drop(stopped);
+
+        let Some(engine) = engine.upgrade() else { return };
+        engine.run_compaction_round(CompactionType::Cumulative);
+    }
+}
+
+/// 创建目录并写入整个文件，落盘后返回
+fn write_file(path: &str, bytes: &[u8]) -> Result<()> {
+    let io_err = |e: std::io::Error| OlapError::SegmentIo(format!("{path}: {e}"));
+    if let Some(dir) = Path::new(path).parent() {
+        std::fs::create_dir_all(dir).map_err(io_err)?;
+    }
+    let mut file = std::fs::File::create(path).map_err(io_err)?;
+    std::io::Write::write_all(&mut file, bytes).map_err(io_err)?;
+    file.sync_all().map_err(io_err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{AggregateType, ColumnType, KeysType};
+    use crate::field_type::Value;
+    use crate::meta::{ColumnSchema, RowsetState};
+    use crate::partition::{BucketType, MaterializedIndex, PartitionKey, RangeBound};
+    use crate::test_util::TempDir;
+
+    const TABLET: TabletId = 10;
+
+    fn schema(keys_type: KeysType) -> TabletSchema {
+        TabletSchema::new(keys_type, vec![
+            ColumnSchema::key(0, "k", ColumnType::Int64),
+            ColumnSchema::value(1, "v", ColumnType::Int64, AggregateType::Sum),
+        ])
+    }
+
+    /// 在 `dir` 上打开引擎并创建 TABLET，返回引擎与 schema_hash
+    fn open_engine(dir: &TempDir, keys_type: KeysType) -> (StorageEngine, SchemaHash) {
+        let engine = StorageEngine::new_single_dir(dir.path_str());
+        let schema = schema(keys_type);
+        let hash   = schema.schema_hash;
+        engine.create_tablet(TabletMeta::new(TABLET, 1, schema)).unwrap();
+        (engine, hash)
+    }
+
+    fn publish(engine: &StorageEngine, hash: SchemaHash, rowset_id: RowsetId) -> Version {
+        let rs = RowsetMeta::new(rowset_id, TABLET, 1, Version::AUTO, 10, 100);
+        engine.publish_rowset(TABLET, hash, rs).unwrap()
+    }
+
+    /// 写出一个单 Segment 的 Rowset（行为 (k, 1)）并发布
+    fn load(engine: &StorageEngine, hash: SchemaHash, rowset_id: RowsetId, keys: &[i64]) -> Version {
+        let tablet = engine.get_tablet(TABLET, hash).unwrap();
+        let mut writer = SegmentWriter::from_tablet_schema(&tablet.schema());
+        for &k in keys {
+            writer.append_row(vec![Value::Int64(k), Value::Int64(1)]).unwrap();
+        }
+        let size = engine.write_segment(TABLET, rowset_id, 0, writer).unwrap();
+        let rs = RowsetMeta::new(rowset_id, TABLET, 1, Version::AUTO, keys.len() as u64, size);
+        engine.publish_rowset(TABLET, hash, rs).unwrap()
+    }
+
+    /// 读出 [0, max_version] 可见的全部行
+    fn visible_rows(engine: &StorageEngine, hash: SchemaHash) -> Vec<Vec<Value>> {
+        let tablet = engine.get_tablet(TABLET, hash).unwrap();
+        let schema = tablet.schema().segment_schema();
+        let mut rows = Vec::new();
+        for rs in tablet.capture_consistent_versions(0, tablet.max_version()).unwrap() {
+            for seg in 0..rs.num_segments {
+                let path   = engine.segment_path(TABLET, rs.rowset_id, seg);
+                let reader = SegmentReader::open_path(Path::new(&path), schema.clone()).unwrap();
+                rows.extend(reader.row_iter().map(|r| r.unwrap()));
+            }
+        }
+        rows
+    }
+
+    fn versions(engine: &StorageEngine, hash: SchemaHash) -> Vec<Version> {
+        engine.describe_tablet(TABLET, hash).unwrap().version_edges
+    }
+
+    // ── 并发发布 ──────────────────────────────────────────────────────────────
+
+    #[test]
+    fn concurrent_auto_publishes_get_gapless_unique_versions() {
+        const THREADS: u64 = 8;
+        const PER_THREAD: u64 = 50;
+        let dir = TempDir::new("engine-concurrent-publish");
+        let (engine, hash) = open_engine(&dir, KeysType::Duplicate);
+
+        let mut got: Vec<i64> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..THREADS)
+                .map(|t| {
+                    let engine = &engine;
+                    s.spawn(move || {
+                        (0..PER_THREAD)
+                            .map(|i| publish(engine, hash, t * PER_THREAD + i).start)
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles.into_iter().flat_map(|h| h.join().unwrap()).collect()
+        });
+        got.sort_unstable();
+        let total = (THREADS * PER_THREAD) as i64;
+        assert_eq!(got, (0..total).collect::<Vec<_>>());
+        assert_eq!(engine.get_tablet(TABLET, hash).unwrap().max_version(), total - 1);
+        assert_eq!(versions(&engine, hash).len(), total as usize);
+    }
+
+    // ── 版本拓扑 ──────────────────────────────────────────────────────────────
+
+    #[test]
+    fn describe_tablet_reports_gaps_and_every_rowset_state() {
+        let dir = TempDir::new("engine-describe");
+        let (engine, hash) = open_engine(&dir, KeysType::Duplicate);
+        for id in 0..3 {
+            load(&engine, hash, id, &[id as i64]);
+        }
+        assert!(engine.compact_tablet(TABLET, hash).unwrap());
+        let rs = RowsetMeta::new(100, TABLET, 1, Version::new(5, 6), 4, 40);
+        engine.publish_rowset(TABLET, hash, rs).unwrap();
+
+        let desc = engine.describe_tablet(TABLET, hash).unwrap();
+        assert_eq!(desc.max_version, 6);
+        assert_eq!(desc.version_edges, vec![Version::new(0, 2), Version::new(5, 6)]);
+        assert_eq!(desc.gaps, vec![Version::new(3, 4)]);
+
+        let rowsets: Vec<(Version, RowsetState, u64)> = desc.rowsets.iter()
+            .map(|(_, v, state, rows)| (*v, state.clone(), *rows))
+            .collect();
+        assert_eq!(rowsets, vec![
+            (Version::point(0),   RowsetState::Stale,   1),
+            (Version::new(0, 2),  RowsetState::Visible, 3),
+            (Version::point(1),   RowsetState::Stale,   1),
+            (Version::point(2),   RowsetState::Stale,   1),
+            (Version::new(5, 6),  RowsetState::Visible, 4),
+        ]);
+        assert!(desc.rowsets.iter().any(|&(id, ..)| id == 100));
+
+        assert!(matches!(engine.describe_tablet(TABLET + 1, hash), Err(OlapError::TabletNotFound(..))));
+    }
+
+    // ── WAL 恢复 ──────────────────────────────────────────────────────────────
+
+    #[test]
+    fn recover_restores_published_versions_after_restart() {
+        let dir = TempDir::new("engine-restart");
+        let (engine, hash) = open_engine(&dir, KeysType::Duplicate);
+        for id in 0..3 {
+            publish(&engine, hash, id);
+        }
+        let before = versions(&engine, hash);
+        drop(engine);
+
+        let (engine, hash) = open_engine(&dir, KeysType::Duplicate);
+        assert_eq!(engine.recover().unwrap(), 3);
+        assert_eq!(versions(&engine, hash), before);
+        assert_eq!(engine.get_tablet(TABLET, hash).unwrap().max_version(), 2);
+        // 重复调用幂等
+        assert_eq!(engine.recover().unwrap(), 0);
+    }
+
+    #[test]
+    fn publishes_after_torn_tail_survive_second_restart() {
+        let dir = TempDir::new("engine-torn");
+        let (engine, hash) = open_engine(&dir, KeysType::Duplicate);
+        for id in 0..3 {
+            publish(&engine, hash, id);
+        }
+        drop(engine);
+        let wal_path = dir.path().join("rowset.wal");
+        let mut data = std::fs::read(&wal_path).unwrap();
+        data.extend_from_slice(&[0x40, 0, 0, 0, 1, 2, 3]);
+        std::fs::write(&wal_path, &data).unwrap();
+
+        let (engine, hash) = open_engine(&dir, KeysType::Duplicate);
+        assert_eq!(engine.recover().unwrap(), 3);
+        for id in 3..6 {
+            publish(&engine, hash, id);
+        }
+        drop(engine);
+
+        let (engine, hash) = open_engine(&dir, KeysType::Duplicate);
+        assert_eq!(engine.recover().unwrap(), 6);
+        assert_eq!(engine.get_tablet(TABLET, hash).unwrap().max_version(), 5);
+    }
+
+    // ── Compaction ────────────────────────────────────────────────────────────
+
+    #[test]
+    fn compaction_survives_restart_without_resurrecting_inputs() {
+        let dir = TempDir::new("engine-compact-restart");
+        let (engine, hash) = open_engine(&dir, KeysType::Duplicate);
+        for id in 0..3 {
+            load(&engine, hash, id, &[id as i64 * 10, id as i64 * 10 + 1]);
+        }
+        assert!(engine.compact_tablet(TABLET, hash).unwrap());
+        assert_eq!(visible_rows(&engine, hash).len(), 6);
+        drop(engine);
+
+        let (engine, hash) = open_engine(&dir, KeysType::Duplicate);
+        assert_eq!(engine.recover().unwrap(), 4);
+        assert_eq!(versions(&engine, hash), vec![Version::new(0, 2)]);
+        assert_eq!(visible_rows(&engine, hash).len(), 6);
+        assert_eq!(engine.metrics().total_visible_rows, 6);
+
+        // 重启后继续发布与合并
+        load(&engine, hash, 10, &[100]);
+        load(&engine, hash, 11, &[101]);
+        assert!(engine.compact_tablet(TABLET, hash).unwrap());
+        assert_eq!(visible_rows(&engine, hash).len(), 8);
+    }
+
+    #[test]
+    fn many_rowset_tablet_collapses_to_one_rowset() {
+        let dir = TempDir::new("engine-collapse");
+        let (engine, hash) = open_engine(&dir, KeysType::Duplicate);
+        for id in 0..12 {
+            load(&engine, hash, id, &[id as i64, 100 + id as i64]);
+        }
+        assert!(engine.compact_tablet(TABLET, hash).unwrap());
+        assert!(!engine.compact_tablet(TABLET, hash).unwrap());
+
+        let tablet = engine.get_tablet(TABLET, hash).unwrap();
+        let merged = tablet.capture_consistent_versions(0, 11).unwrap();
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].version, Version::new(0, 11));
+        assert_eq!(merged[0].segment_paths, vec![RowsetMeta::segment_file_name(TABLET, merged[0].rowset_id, 0)]);
+        assert!(Path::new(&engine.segment_path(TABLET, merged[0].rowset_id, 0)).exists());
+
+        let mut keys: Vec<i64> = visible_rows(&engine, hash).iter()
+            .map(|r| r[0].as_i64().unwrap())
+            .collect();
+        keys.sort_unstable();
+        let mut want: Vec<i64> = (0..12).chain(100..112).collect();
+        want.sort_unstable();
+        assert_eq!(keys, want);
+    }
+
+    #[test]
+    fn tablet_cloned_after_compaction_owns_its_segment_paths() {
+        const CLONE: TabletId = 21;
+        let dir = TempDir::new("engine-clone-compacted");
+        let (engine, hash) = open_engine(&dir, KeysType::Duplicate);
+        for id in 0..3 {
+            load(&engine, hash, id, &[id as i64, 10 + id as i64]);
+        }
+        assert!(engine.compact_tablet(TABLET, hash).unwrap());
+
+        let clone = engine.tablet_manager.clone_tablet(TABLET, hash, CLONE).unwrap();
+        let rowsets = clone.capture_consistent_versions(0, 2).unwrap();
+        assert_eq!(rowsets.len(), 1);
+        let rs = &rowsets[0];
+        assert_eq!(rs.segment_paths, [RowsetMeta::segment_file_name(CLONE, rs.rowset_id, 0)]);
+
+        // 按约定把文件拷到新 Tablet 的位置后，删除源 Tablet 及其目录不影响克隆
+        let (src, dst) = (engine.segment_path(TABLET, rs.rowset_id, 0), engine.segment_path(CLONE, rs.rowset_id, 0));
+        std::fs::create_dir_all(Path::new(&dst).parent().unwrap()).unwrap();
+        std::fs::copy(&src, &dst).unwrap();
+        engine.drop_tablet(TABLET, hash).unwrap();
+        std::fs::remove_dir_all(Path::new(&src).parent().unwrap()).unwrap();
+
+        let schema = clone.schema().segment_schema();
+        let reader = SegmentReader::open_path(Path::new(&dst), schema).unwrap();
+        let mut keys: Vec<i64> = reader.read_column(0).unwrap().iter().map(|v| v.as_i64().unwrap()).collect();
+        keys.sort_unstable();
+        assert_eq!(keys, [0, 1, 2, 10, 11, 12]);
+    }
+
+    #[test]
+    fn failed_compaction_publish_removes_output_file() {
+        let dir = TempDir::new("engine-compact-orphan");
+        let (engine, hash) = open_engine(&dir, KeysType::Duplicate);
+        load(&engine, hash, 0, &[1]);
+        load(&engine, hash, 1, &[2]);
+        drop(engine);
+
+        // 重启后先恢复，再让 WAL 路径被目录占住，使 compaction 记录追加失败
+        let (engine, hash) = open_engine(&dir, KeysType::Duplicate);
+        engine.recover().unwrap();
+        let wal_path = dir.path().join("rowset.wal");
+        std::fs::remove_file(&wal_path).unwrap();
+        std::fs::create_dir_all(&wal_path).unwrap();
+
+        assert!(engine.compact_tablet(TABLET, hash).is_err());
+        assert!(!Path::new(&engine.segment_path(TABLET, 2, 0)).exists());
+        assert_eq!(versions(&engine, hash), vec![Version::point(0), Version::point(1)]);
+    }
+
+    #[test]
+    fn cumulative_point_survives_restart() {
+        let dir = TempDir::new("engine-cumu-point");
+        let (engine, hash) = open_engine(&dir, KeysType::Duplicate);
+        for id in 0..4 {
+            publish(&engine, hash, id);
+        }
+        assert!(engine.compact_tablet(TABLET, hash).unwrap());
+        let point = engine.get_tablet(TABLET, hash).unwrap().cumulative_layer_point();
+        assert_eq!(point, 3);
+        // 发布与分界点推进是同一条 WAL 记录
+        let (records, _) = engine.wal.read_all().unwrap();
+        assert_eq!(records.len(), 5);
+        assert!(matches!(records[4].entry, WalEntry::Compaction { cumulative_point: Some(3), .. }));
+        drop(engine);
+
+        let (engine, hash) = open_engine(&dir, KeysType::Duplicate);
+        engine.recover().unwrap();
+        let tablet = engine.get_tablet(TABLET, hash).unwrap();
+        assert_eq!(tablet.cumulative_layer_point(), point);
+        // 已进入 Base 区的 Rowset 不会再被 cumulative compaction 选中
+        assert!(tablet.pick_rowsets_for_compaction().is_empty());
+    }
+
+    #[test]
+    fn gc_returns_on_disk_segment_paths() {
+        let dir    = TempDir::new("engine-gc");
+        let engine = StorageEngine::new_single_dir(dir.path_str());
+        let mut meta = TabletMeta::new(TABLET, 1, schema(KeysType::Duplicate));
+        for (id, version) in [(0, Version::point(0)), (1, Version::point(1)), (2, Version::new(0, 1))] {
+            let mut rs = RowsetMeta::new(id, TABLET, 1, version, 1, 10);
+            rs.state = RowsetState::Visible;
+            if id < 2 {
+                rs.state    = RowsetState::Stale;
+                rs.stale_at = Some(0);
+            }
+            meta.rowsets.insert(id, rs);
+        }
+        engine.create_tablet(meta).unwrap();
+
+        let mut paths = engine.gc_stale_rowsets();
+        paths.sort();
+        assert_eq!(paths, vec![engine.segment_path(TABLET, 0, 0), engine.segment_path(TABLET, 1, 0)]);
+        assert!(engine.gc_stale_rowsets().is_empty());
+    }
+
+    #[test]
+    fn compaction_merges_in_memory_segments() {
+        let dir = TempDir::new("engine-compact-mem");
+        let (engine, hash) = open_engine(&dir, KeysType::Duplicate);
+        let tablet = engine.get_tablet(TABLET, hash).unwrap();
+        for id in 0..3 {
+            let mut w = SegmentWriter::from_tablet_schema(&tablet.schema());
+            w.append_row(vec![Value::Int64(id as i64), Value::Int64(1)]).unwrap();
+            tablet.attach_segment_bytes(id, w.finish().unwrap().0);
+            publish(&engine, hash, id);
+        }
+        assert!(engine.compact_tablet(TABLET, hash).unwrap());
+
+        let schema  = tablet.schema().segment_schema();
+        let readers = tablet.open_segment_readers(Version::new(0, 2), &schema).unwrap();
+        assert_eq!(readers.len(), 1);
+        let keys = readers[0].read_column(0).unwrap();
+        assert_eq!(keys, vec![Value::Int64(0), Value::Int64(1), Value::Int64(2)]);
+        assert!(!Path::new(&engine.segment_path(TABLET, 3, 0)).exists());
+    }
+
+    #[test]
+    fn encrypted_rowsets_compact_only_with_the_engine_key() {
+        let dir = TempDir::new("engine-compact-enc");
+        let (engine, hash) = open_engine(&dir, KeysType::Duplicate);
+        let key  = EncryptionKey::new([7; 32]);
+        let opts = SegmentWriterOptions { encryption: Some(key.clone()), ..Default::default() };
+        for id in 0..3 {
+            let mut w = SegmentWriter::with_options(schema(KeysType::Duplicate).segment_schema(), opts.clone());
+            w.append_row(vec![Value::Int64(id as i64), Value::Int64(1)]).unwrap();
+            let size = engine.write_segment(TABLET, id, 0, w).unwrap();
+            engine.publish_rowset(TABLET, hash, RowsetMeta::new(id, TABLET, 1, Version::AUTO, 1, size)).unwrap();
+        }
+
+        // 引擎没有密钥：写出任何数据之前报错，输入 Rowset 保持不变
+        match engine.compact_tablet(TABLET, hash) {
+            Err(OlapError::Encryption(msg)) => assert!(msg.contains("rowset 0 is encrypted"), "{msg}"),
+            other => panic!("{other:?}"),
+        }
+        assert_eq!(versions(&engine, hash), vec![Version::new(0, 0), Version::new(1, 1), Version::new(2, 2)]);
+        assert!(!Path::new(&engine.segment_path(TABLET, 3, 0)).exists());
+        drop(engine);
+
+        // 带密钥重启后合并成功，输出同样加密
+        let engine = StorageEngine::new_single_dir(dir.path_str()).with_encryption_key(key.clone());
+        engine.create_tablet(TabletMeta::new(TABLET, 1, schema(KeysType::Duplicate))).unwrap();
+        engine.recover().unwrap();
+        assert!(engine.compact_tablet(TABLET, hash).unwrap());
+        assert_eq!(versions(&engine, hash), vec![Version::new(0, 2)]);
+        let path   = engine.segment_path(TABLET, 3, 0);
+        let reader = SegmentReader::open_path(Path::new(&path), schema(KeysType::Duplicate).segment_schema()).unwrap();
+        assert!(reader.is_encrypted());
+        let reader = reader.with_encryption_key(key).unwrap();
+        assert_eq!(reader.read_column(0).unwrap(), vec![Value::Int64(0), Value::Int64(1), Value::Int64(2)]);
+    }
+
+    // ── Segment 落盘 ──────────────────────────────────────────────────────────
+
+    #[test]
+    fn write_segment_creates_file_with_segment_magic() {
+        let dir = TempDir::new("engine-write-seg");
+        let (engine, hash) = open_engine(&dir, KeysType::Duplicate);
+        let tablet = engine.get_tablet(TABLET, hash).unwrap();
+        let mut writer = SegmentWriter::from_tablet_schema(&tablet.schema());
+        for k in 0..100 {
+            writer.append_row(vec![Value::Int64(k), Value::Int64(k * 2)]).unwrap();
+        }
+        let size = engine.write_segment(TABLET, 7, 0, writer).unwrap();
+
+        let path = engine.segment_path(TABLET, 7, 0);
+        assert!(path.starts_with(dir.path_str()));
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(bytes.len() as u64, size);
+        assert_eq!(&bytes[..8], b"OLAPSEG\0");
+        assert_eq!(&bytes[bytes.len() - 8..], b"OLAPSEG\0");
+    }
+
+    #[test]
+    fn write_segment_maps_io_errors_to_segment_io() {
+        let dir = TempDir::new("engine-write-seg-err");
+        let (engine, hash) = open_engine(&dir, KeysType::Duplicate);
+        // Tablet 目录被同名文件占住
+        std::fs::write(dir.path().join(TABLET.to_string()), b"x").unwrap();
+        let writer = SegmentWriter::from_tablet_schema(&engine.get_tablet(TABLET, hash).unwrap().schema());
+        assert!(matches!(engine.write_segment(TABLET, 7, 0, writer), Err(OlapError::SegmentIo(_))));
+    }
+
+    #[test]
+    fn segment_written_to_disk_reopens_through_mmap() {
+        let dir = TempDir::new("engine-open-path");
+        let (engine, hash) = open_engine(&dir, KeysType::Duplicate);
+        let schema = engine.get_tablet(TABLET, hash).unwrap().schema();
+        let mut writer = SegmentWriter::from_tablet_schema(&schema);
+        for k in 0..3000 {
+            writer.append_row(vec![Value::Int64(k), Value::Int64(k % 7)]).unwrap();
+        }
+        engine.write_segment(TABLET, 7, 0, writer).unwrap();
+
+        let path   = engine.segment_path(TABLET, 7, 0);
+        let reader = SegmentReader::open_path(Path::new(&path), schema.segment_schema()).unwrap();
+        assert_eq!(reader.num_rows(), 3000);
+        assert_eq!(reader.read_column(1).unwrap(), (0..3000).map(|k| Value::Int64(k % 7)).collect::<Vec<_>>());
+        assert_eq!(reader.get_row(2500).unwrap(), vec![Value::Int64(2500), Value::Int64(2500 % 7)]);
+
+        let missing = engine.segment_path(TABLET, 8, 0);
+        assert!(matches!(
+            SegmentReader::open_path(Path::new(&missing), schema.segment_schema()),
+            Err(OlapError::SegmentIo(_)),
+        ));
+    }
+
+    #[test]
+    fn tablets_are_placed_round_robin_across_data_dirs() {
+        let dirs: Vec<TempDir> = (0..3).map(|i| TempDir::new(&format!("engine-dir{i}"))).collect();
+        let engine = StorageEngine::new(dirs.iter().map(|d| d.path_str().to_string()).collect());
+        for tablet_id in 0..9u64 {
+            let dir = dirs[(tablet_id % 3) as usize].path_str();
+            assert_eq!(engine.data_dir_for(tablet_id), dir);
+            for (rowset_id, seg_idx) in [(0, 0), (0, 1), (5, 0), (9, 3)] {
+                let path = engine.segment_path(tablet_id, rowset_id, seg_idx);
+                assert!(path.starts_with(&format!("{dir}/{tablet_id}/")), "{path}");
+            }
+        }
+
+        // 同一 Tablet 的 Segment 文件都落在同一目录
+        let schema = schema(KeysType::Duplicate);
+        for seg_idx in 0..2 {
+            engine.write_segment(4, 1, seg_idx, SegmentWriter::from_tablet_schema(&schema)).unwrap();
+        }
+        assert!(dirs[1].path().join("4/1_0.seg").exists());
+        assert!(dirs[1].path().join("4/1_1.seg").exists());
+        assert!(!dirs[0].path().join("4").exists() && !dirs[2].path().join("4").exists());
+    }
+
+    // ── 分区 DDL ──────────────────────────────────────────────────────────────
+
+    fn range_partition(partition_id: u64, tablets: Vec<TabletId>) -> Partition {
+        let bucket = BucketType::Hash { bucket_columns: vec!["k".into()], num_buckets: tablets.len() as u32 };
+        Partition::new(partition_id, MaterializedIndex::new(partition_id, tablets), bucket)
+    }
+
+    fn item(partition_id: u64, upper: i64) -> RangePartitionItem {
+        RangePartitionItem { partition_id, upper_bound: RangeBound::Key(PartitionKey::Int(upper)) }
+    }
+
+    fn spec(partition_id: u64, tablet_ids: Vec<TabletId>) -> PartitionSpec {
+        PartitionSpec { partition_id, tablet_ids, schema_hash: schema(KeysType::Duplicate).schema_hash }
+    }
+
+    /// 库 1 / 表 100，一个分区 p1（k < 100，Tablet 1、2）
+    fn engine_with_range_table(dir: &TempDir) -> StorageEngine {
+        let engine = StorageEngine::new_single_dir(dir.path_str());
+        engine.create_database(1, "db").unwrap();
+        let p1   = range_partition(1, vec![1, 2]);
+        let info = PartitionInfo::range(
+            vec!["k".into()], vec![item(1, 100)], [(1, p1)].into_iter().collect(),
+        );
+        engine.create_table_with_partitions(
+            1, 100, "t", schema(KeysType::Duplicate), info, vec![spec(1, vec![1, 2])], 1,
+        ).unwrap();
+        engine
+    }
+
+    fn partition_ids(engine: &StorageEngine) -> Vec<u64> {
+        let table = engine.catalog_manager.get_table(1, 100).unwrap();
+        let mut ids: Vec<u64> = table.read().unwrap().partition_info.partitions.keys().copied().collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    #[test]
+    fn add_partition_creates_tablets_and_registers_partition() {
+        let dir    = TempDir::new("engine-add-part");
+        let engine = engine_with_range_table(&dir);
+        engine.add_partition(1, 100, item(2, 200), range_partition(2, vec![3, 4]), spec(2, vec![3, 4])).unwrap();
+
+        assert_eq!(partition_ids(&engine), vec![1, 2]);
+        assert_eq!(engine.tablet_count(), 4);
+        let table = engine.catalog_manager.get_table(1, 100).unwrap();
+        let found = table.read().unwrap().partition_info.find_partition("150").unwrap().partition_id;
+        assert_eq!(found, 2);
+    }
+
+    #[test]
+    fn add_partition_with_existing_tablet_changes_nothing() {
+        let dir    = TempDir::new("engine-add-part-dup");
+        let engine = engine_with_range_table(&dir);
+        let hash   = schema(KeysType::Duplicate).schema_hash;
+        let rs     = RowsetMeta::new(0, 2, 1, Version::AUTO, 5, 50);
+        engine.publish_rowset(2, hash, rs).unwrap();
+
+        let err = engine.add_partition(1, 100, item(2, 200), range_partition(2, vec![3, 2]), spec(2, vec![3, 2]));
+        assert!(matches!(err, Err(OlapError::TabletExists(2))));
+        assert_eq!(partition_ids(&engine), vec![1]);
+        assert_eq!(engine.tablet_count(), 2);
+        // 已有 Tablet 的数据不受影响
+        assert_eq!(engine.get_tablet(2, hash).unwrap().max_version(), 0);
+    }
+
+    #[test]
+    fn add_partition_rejects_overlap_before_creating_tablets() {
+        let dir    = TempDir::new("engine-add-part-overlap");
+        let engine = engine_with_range_table(&dir);
+        let err = engine.add_partition(1, 100, item(2, 50), range_partition(2, vec![3]), spec(2, vec![3]));
+        assert!(matches!(err, Err(OlapError::PartitionOverlap(_))));
+        assert_eq!(engine.tablet_count(), 2);
+        assert_eq!(partition_ids(&engine), vec![1]);
+    }
+
+    #[test]
+    fn drop_database_removes_catalog_entry_and_every_partition_tablet() {
+        let dir    = TempDir::new("engine-drop-db");
+        let engine = engine_with_range_table(&dir);
+        engine.add_partition(1, 100, item(2, 200), range_partition(2, vec![3, 4]), spec(2, vec![3, 4])).unwrap();
+        engine.create_database(2, "other").unwrap();
+        let info = PartitionInfo::range(
+            vec!["k".into()], vec![item(3, 100)], [(3, range_partition(3, vec![5]))].into_iter().collect(),
+        );
+        engine.create_table_with_partitions(2, 200, "t", schema(KeysType::Duplicate), info, vec![spec(3, vec![5])], 1)
+            .unwrap();
+        assert_eq!(engine.tablet_count(), 5);
+
+        engine.drop_database(1).unwrap();
+        assert!(matches!(engine.catalog_manager.get_table(1, 100), Err(OlapError::DatabaseNotFound(1))));
+        let hash = schema(KeysType::Duplicate).schema_hash;
+        for tablet_id in 1..=4 {
+            assert!(matches!(engine.get_tablet(tablet_id, hash), Err(OlapError::TabletNotFound(_))), "{tablet_id}");
+        }
+        // 其他库的表与 Tablet 不受影响
+        assert_eq!(engine.tablet_count(), 1);
+        assert!(engine.get_tablet(5, hash).is_ok());
+        assert!(engine.catalog_manager.get_table(2, 200).is_ok());
+
+        // 重新加载持久化的 Catalog：被删的库不再出现，其他库保留
+        let reloaded = CatalogManager::load(&engine.catalog_dir()).unwrap();
+        assert!(matches!(reloaded.get_table(1, 100), Err(OlapError::DatabaseNotFound(1))));
+        assert!(reloaded.get_table(2, 200).is_ok());
+        assert_eq!((reloaded.num_databases(), reloaded.num_tables()), (1, 1));
+
+        assert!(matches!(engine.drop_database(1), Err(OlapError::DatabaseNotFound(1))));
+    }
+
+    // ── 引擎统计 ──────────────────────────────────────────────────────────────
+
+    #[test]
+    fn metrics_sum_catalog_and_tablet_rowsets() {
+        let dir    = TempDir::new("engine-metrics");
+        let engine = engine_with_range_table(&dir);
+        engine.create_database(2, "empty").unwrap();
+        let hash = schema(KeysType::Duplicate).schema_hash;
+        for (rowset_id, tablet_id, rows, bytes) in [(0, 1, 100, 1_000), (1, 1, 50, 700), (0, 2, 7, 30)] {
+            let rs = RowsetMeta::new(rowset_id, tablet_id, 1, Version::AUTO, rows, bytes);
+            engine.publish_rowset(tablet_id, hash, rs).unwrap();
+        }
+        assert_eq!(engine.metrics(), EngineMetrics {
+            num_databases:      2,
+            num_tables:         1,
+            num_tablets:        2,
+            total_rowsets:      3,
+            total_visible_rows: 157,
+            total_disk_bytes:   1_730,
+        });
+
+        // Stale Rowset 仍占磁盘，但不再计入可见行数
+        engine.get_tablet(1, hash).unwrap().mark_rowset_stale(1);
+        let m = engine.metrics();
+        assert_eq!((m.total_rowsets, m.total_visible_rows, m.total_disk_bytes), (3, 107, 1_730));
     }
 }
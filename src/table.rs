@@ -1,9 +1,12 @@
 //! Table 元数据与 Catalog
 
 use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
-use crate::common::{DbId, OlapError, Result, TableId, TabletId};
-use crate::meta::TabletSchema;
+use crate::common::{put_bytes, ByteReader, DbId, OlapError, Result, TableId, TabletId};
+use crate::field_type::Value;
+use crate::meta::{ColumnSchema, TabletSchema};
 use crate::partition::PartitionInfo;
 
 // ── OlapTable ─────────────────────────────────────────────────────────────────
@@ -25,6 +28,16 @@ impl OlapTable {
         Self { table_id, table_name: table_name.into(), schema, partition_info }
     }
 
+    /// Schema 变更：追加一列，已有 Segment 读取该列时返回 `default`
+    pub fn add_column(&mut self, col: ColumnSchema, default: Value) -> Result<()> {
+        self.schema.add_column(col, default)
+    }
+
+    /// Schema 变更：逻辑删除一个 value 列
+    pub fn drop_column(&mut self, column_id: u32) -> Result<()> {
+        self.schema.drop_column(column_id)
+    }
+
     /// 将行路由到对应的 TabletId（分区路由 + 桶路由）
     ///
     /// - `partition_key`：参与分区判断的列值（如日期字符串）
@@ -34,19 +47,62 @@ impl OlapTable {
         partition_key: &str,
         sort_key:      &str,
     ) -> Result<TabletId> {
-        let partition = self.partition_info.find_partition(partition_key)?;
+        self.tablet_for_row_values(
+            &[Value::Bytes(partition_key.as_bytes().to_vec())],
+            &[Value::Bytes(sort_key.as_bytes().to_vec())],
+        )
+    }
+
+    /// 按列值路由：`partition_vals` 对应分区列，`bucket_vals` 按 `bucket_columns` 顺序
+    pub fn tablet_for_row_values(
+        &self,
+        partition_vals: &[Value],
+        bucket_vals:    &[Value],
+    ) -> Result<TabletId> {
+        let partition = self.partition_info.find_partition_for_values(partition_vals)?;
         partition
-            .tablet_for_key(sort_key)
-            .ok_or_else(|| OlapError::PartitionNotFound(sort_key.into()))
+            .tablet_for_values(bucket_vals)
+            .ok_or_else(|| OlapError::PartitionNotFound(format!("{bucket_vals:?}")))
+    }
+
+    /// 所有分区（含 rollup 索引）的 TabletId，升序
+    pub fn tablet_ids(&self) -> Vec<TabletId> {
+        let mut ids: Vec<TabletId> = self.partition_info.partitions.values()
+            .flat_map(|p| p.tablet_ids())
+            .collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// `table_id u64 | table_name | TabletSchema | PartitionInfo`
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&self.table_id.to_le_bytes());
+        put_bytes(&mut out, self.table_name.as_bytes());
+        self.schema.serialize_into(&mut out);
+        self.partition_info.serialize_into(&mut out);
+        out
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<Self> {
+        let mut r = ByteReader::new(data, "table");
+        let table_id       = r.u64()?;
+        let table_name     = r.string()?;
+        let schema         = TabletSchema::read_from(&mut r)?;
+        let partition_info = PartitionInfo::read_from(&mut r)?;
+        r.finish()?;
+        Ok(Self { table_id, table_name, schema, partition_info })
     }
 }
 
 // ── Database ──────────────────────────────────────────────────────────────────
 
 struct Database {
-    _db_id:   DbId,
-    _db_name: String,
+    db_id:    DbId,
+    db_name:  String,
     tables:   HashMap<TableId, Arc<RwLock<OlapTable>>>,
+    /// table_name → table_id
+    names:    HashMap<String, TableId>,
 }
 
 // ── CatalogManager ────────────────────────────────────────────────────────────
@@ -64,14 +120,22 @@ impl CatalogManager {
     pub fn create_database(&self, db_id: DbId, db_name: &str) -> Result<()> {
         self.databases.write().unwrap().insert(
             db_id,
-            Database { _db_id: db_id, _db_name: db_name.into(), tables: HashMap::new() },
+            Database {
+                db_id, db_name: db_name.into(),
+                tables: HashMap::new(), names: HashMap::new(),
+            },
         );
         Ok(())
     }
 
+    /// 注册表；同库内表名或 table_id 已存在时返回 `TableExists`
     pub fn add_table(&self, db_id: DbId, table: OlapTable) -> Result<()> {
         let mut dbs = self.databases.write().unwrap();
         let db = dbs.get_mut(&db_id).ok_or(OlapError::DatabaseNotFound(db_id))?;
+        if db.names.contains_key(&table.table_name) || db.tables.contains_key(&table.table_id) {
+            return Err(OlapError::TableExists(db_id, table.table_name));
+        }
+        db.names.insert(table.table_name.clone(), table.table_id);
         db.tables.insert(table.table_id, Arc::new(RwLock::new(table)));
         Ok(())
     }
@@ -88,15 +152,429 @@ impl CatalogManager {
             .ok_or(OlapError::TableNotFound(db_id, table_id))
     }
 
+    pub fn get_table_by_name(
+        &self,
+        db_id: DbId,
+        name:  &str,
+    ) -> Result<Arc<RwLock<OlapTable>>> {
+        let dbs = self.databases.read().unwrap();
+        let db  = dbs.get(&db_id).ok_or(OlapError::DatabaseNotFound(db_id))?;
+        db.names.get(name)
+            .and_then(|tid| db.tables.get(tid))
+            .cloned()
+            .ok_or_else(|| OlapError::TableNameNotFound(db_id, name.into()))
+    }
+
+    pub fn num_databases(&self) -> usize {
+        self.databases.read().unwrap().len()
+    }
+
+    /// 所有库的表总数
+    pub fn num_tables(&self) -> usize {
+        self.databases.read().unwrap().values().map(|db| db.tables.len()).sum()
+    }
+
     pub fn drop_table(&self, db_id: DbId, table_id: TableId) -> Result<()> {
         let mut dbs = self.databases.write().unwrap();
         let db = dbs.get_mut(&db_id).ok_or(OlapError::DatabaseNotFound(db_id))?;
-        db.tables.remove(&table_id)
-            .map(|_| ())
-            .ok_or(OlapError::TableNotFound(db_id, table_id))
+        let table = db.tables.remove(&table_id)
+            .ok_or(OlapError::TableNotFound(db_id, table_id))?;
+        db.names.remove(&table.read().unwrap().table_name);
+        Ok(())
+    }
+
+    /// 删除库，返回其中的全部表供调用方清理 Tablet
+    pub fn drop_database(&self, db_id: DbId) -> Result<Vec<Arc<RwLock<OlapTable>>>> {
+        let db = self.databases.write().unwrap()
+            .remove(&db_id)
+            .ok_or(OlapError::DatabaseNotFound(db_id))?;
+        Ok(db.tables.into_values().collect())
+    }
+
+    /// 重命名表，同时更新库内的表名索引
+    ///
+    /// 表不存在返回 `TableNotFound`；同库内已有同名的其他表时返回 `TableExists`。
+    pub fn rename_table(&self, db_id: DbId, table_id: TableId, new_name: &str) -> Result<()> {
+        let mut dbs = self.databases.write().unwrap();
+        let db = dbs.get_mut(&db_id).ok_or(OlapError::DatabaseNotFound(db_id))?;
+        let table = db.tables.get(&table_id)
+            .ok_or(OlapError::TableNotFound(db_id, table_id))?;
+        if db.names.get(new_name).is_some_and(|&tid| tid != table_id) {
+            return Err(OlapError::TableExists(db_id, new_name.into()));
+        }
+        let mut table = table.write().unwrap();
+        db.names.remove(&table.table_name);
+        db.names.insert(new_name.into(), table_id);
+        table.table_name = new_name.into();
+        Ok(())
+    }
+}
+
+// ── 持久化 ────────────────────────────────────────────────────────────────────
+//
+// 目录布局：
+//   catalog.meta                         crc32 u32 | generation u64 | n_db u32
+//                                        | n_db × (db_id u64 | db_name | n u32 | n × table_id u64)
+//   table_{db_id}_{table_id}.{gen}.meta  crc32 u32 | OlapTable::serialize()
+// 每次保存使用新的 generation，表文件名带 generation，不会覆盖旧 catalog.meta 引用的文件。
+// 保存顺序：全部文件写临时文件并 fsync → rename 表文件 → fsync 目录 → rename catalog.meta
+// → fsync 目录；catalog.meta 的 rename 是提交点，之前任意时刻崩溃都仍能加载旧版本。
+// catalog.meta 未引用的旧表文件与残留临时文件在保存成功后删除。
+
+const CATALOG_FILE: &str = "catalog.meta";
+
+impl CatalogManager {
+    /// 将全部库与表的元数据写入 `dir`
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        std::fs::create_dir_all(dir).map_err(catalog_err)?;
+        let dbs = self.databases.read().unwrap();
+        let generation = match read_file(&dir.join(CATALOG_FILE)) {
+            Ok(Some(m)) if m.len() >= 8 => u64::from_le_bytes(m[0..8].try_into().unwrap()) + 1,
+            _ => 1,
+        };
+
+        let mut db_ids: Vec<_> = dbs.keys().copied().collect();
+        db_ids.sort_unstable();
+        let mut manifest = Vec::new();
+        let mut staged = Vec::new();
+        manifest.extend_from_slice(&generation.to_le_bytes());
+        manifest.extend_from_slice(&(db_ids.len() as u32).to_le_bytes());
+        for db_id in db_ids {
+            let db = &dbs[&db_id];
+            manifest.extend_from_slice(&db.db_id.to_le_bytes());
+            put_bytes(&mut manifest, db.db_name.as_bytes());
+
+            let mut table_ids: Vec<_> = db.tables.keys().copied().collect();
+            table_ids.sort_unstable();
+            manifest.extend_from_slice(&(table_ids.len() as u32).to_le_bytes());
+            for table_id in table_ids {
+                manifest.extend_from_slice(&table_id.to_le_bytes());
+                let path = dir.join(table_file(db_id, table_id, generation));
+                let tmp  = write_temp(&path, &db.tables[&table_id].read().unwrap().serialize())?;
+                staged.push((tmp, path));
+            }
+        }
+        let manifest_path = dir.join(CATALOG_FILE);
+        let manifest_tmp  = write_temp(&manifest_path, &manifest)?;
+
+        for (tmp, path) in &staged {
+            std::fs::rename(tmp, path).map_err(catalog_err)?;
+        }
+        sync_dir(dir)?;
+        std::fs::rename(&manifest_tmp, &manifest_path).map_err(catalog_err)?;
+        sync_dir(dir)?;
+
+        for entry in std::fs::read_dir(dir).map_err(catalog_err)? {
+            let name = entry.map_err(catalog_err)?.file_name();
+            let name = name.to_string_lossy();
+            let stale = name.ends_with(".meta.tmp")
+                || name.starts_with("table_") && name.ends_with(".meta")
+                    && !staged.iter().any(|(_, path)| path.ends_with(name.as_ref()));
+            if stale {
+                std::fs::remove_file(dir.join(name.as_ref())).map_err(catalog_err)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 从 `save` 写出的目录重建 Catalog；目录中没有 catalog.meta 时返回空 Catalog
+    pub fn load(dir: &Path) -> Result<Self> {
+        let catalog = Self::new();
+        let Some(manifest) = read_file(&dir.join(CATALOG_FILE))? else {
+            return Ok(catalog);
+        };
+
+        let mut r = ByteReader::new(&manifest, "catalog");
+        let generation = r.u64()?;
+        let n_dbs = r.u32()?;
+        for _ in 0..n_dbs {
+            let db_id   = r.u64()?;
+            let db_name = r.string()?;
+            catalog.create_database(db_id, &db_name)?;
+            let n_tables = r.u32()?;
+            for _ in 0..n_tables {
+                let table_id = r.u64()?;
+                let path = dir.join(table_file(db_id, table_id, generation));
+                let data = read_file(&path)?.ok_or_else(|| OlapError::CatalogIo(format!(
+                    "missing table file {}", path.display()
+                )))?;
+                let table = OlapTable::deserialize(&data)?;
+                if table.table_id != table_id {
+                    return Err(OlapError::CatalogIo(format!(
+                        "{} holds table_id {}", path.display(), table.table_id
+                    )));
+                }
+                catalog.add_table(db_id, table)?;
+            }
+        }
+        r.finish()?;
+        Ok(catalog)
     }
 }
 
+fn table_file(db_id: DbId, table_id: TableId, generation: u64) -> String {
+    format!("table_{db_id}_{table_id}.{generation}.meta")
+}
+
+fn catalog_err(e: std::io::Error) -> OlapError {
+    OlapError::CatalogIo(e.to_string())
+}
+
+/// 写入 `crc32 | payload` 到 `path` 对应的临时文件并 fsync，返回临时文件路径；由调用方 rename 到位
+fn write_temp(path: &Path, payload: &[u8]) -> Result<PathBuf> {
+    let mut buf = Vec::with_capacity(4 + payload.len());
+    buf.extend_from_slice(&crc32fast::hash(payload).to_le_bytes());
+    buf.extend_from_slice(payload);
+
+    let tmp = path.with_extension("meta.tmp");
+    {
+        let mut f = std::fs::File::create(&tmp).map_err(catalog_err)?;
+        f.write_all(&buf).map_err(catalog_err)?;
+        f.sync_all().map_err(catalog_err)?;
+    }
+    Ok(tmp)
+}
+
+/// fsync 目录本身，使其中的 rename 持久化
+fn sync_dir(dir: &Path) -> Result<()> {
+    std::fs::File::open(dir).and_then(|d| d.sync_all()).map_err(catalog_err)
+}
+
+/// 读取并校验 `write_temp` 写出的文件；不存在时返回 None
+fn read_file(path: &Path) -> Result<Option<Vec<u8>>> {
+    let mut data = match std::fs::read(path) {
+        Ok(d) => d,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(catalog_err(e)),
+    };
+    if data.len() < 4 {
+        return Err(OlapError::CatalogIo(format!("{} is truncated", path.display())));
+    }
+    let crc = u32::from_le_bytes(data[0..4].try_into().unwrap());
+    if crc32fast::hash(&data[4..]) != crc {
+        return Err(OlapError::ChecksumMismatch);
+    }
+    data.drain(..4);
+    Ok(Some(data))
+}
+
 impl Default for CatalogManager {
     fn default() -> Self { Self::new() }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{ColumnType, KeysType, PartitionId};
+    use crate::partition::{BucketType, MaterializedIndex, Partition, PartitionKey, RangePartitionItem};
+
+    /// `orders` 表：按 order_date 分两个 RANGE 分区，按 (user_id, order_id) HASH 分 8 桶
+    fn orders(table_id: TableId, name: &str) -> OlapTable {
+        let schema = TabletSchema::new(KeysType::Duplicate, vec![
+            ColumnSchema::key(0, "order_date", ColumnType::Int32),
+            ColumnSchema::key(1, "user_id", ColumnType::Int64),
+            ColumnSchema::key(2, "order_id", ColumnType::Int64),
+        ]);
+        let bucket = BucketType::Hash {
+            bucket_columns: vec!["user_id".into(), "order_id".into()],
+            num_buckets:    8,
+        };
+        let partition = |pid: PartitionId| {
+            let tablets = (0..8).map(|b| pid * 100 + b).collect();
+            Partition::new(pid, MaterializedIndex::new(pid, tablets), bucket.clone())
+        };
+        let mut info = PartitionInfo::range(vec!["order_date".into()], vec![], HashMap::new());
+        for (pid, bound) in [(1, 20240701), (2, 20250101)] {
+            let item = RangePartitionItem { partition_id: pid, upper_bound: PartitionKey::Int(bound).into() };
+            info.add_range_partition(item, partition(pid)).unwrap();
+        }
+        OlapTable::new(table_id, name, schema, info)
+    }
+
+    // ── 行路由 ────────────────────────────────────────────────────────────────
+
+    #[test]
+    fn two_column_hash_bucket_is_deterministic() {
+        let table = orders(1, "orders");
+        let route = |date: i32, user: i64, order: i64| {
+            table.tablet_for_row_values(&[Value::Int32(date)], &[Value::Int64(user), Value::Int64(order)]).unwrap()
+        };
+
+        let mut buckets = std::collections::HashSet::new();
+        for i in 0..200 {
+            let tid = route(20240315, i % 17, 1_000_000 + i);
+            assert_eq!(tid, route(20240315, i % 17, 1_000_000 + i));
+            assert_eq!(tid, orders(1, "orders").tablet_for_row_values(
+                &[Value::Int32(20240315)], &[Value::Int64(i % 17), Value::Int64(1_000_000 + i)],
+            ).unwrap());
+            assert!((100..108).contains(&tid));
+            // 同一桶号在另一分区映射到该分区自己的 Tablet
+            assert_eq!(route(20240920, i % 17, 1_000_000 + i), tid + 100);
+            buckets.insert(tid);
+        }
+        assert_eq!(buckets.len(), 8);
+        // 两列都参与哈希：交换列值通常换桶
+        assert!((0..50).any(|i| route(20240315, i, i + 1) != route(20240315, i + 1, i)));
+        assert!(table.tablet_for_row_values(&[Value::Int32(20250101)], &[Value::Int64(1), Value::Int64(2)]).is_err());
+    }
+
+    // ── 按名查表 ──────────────────────────────────────────────────────────────
+
+    #[test]
+    fn table_lookup_by_name_found_missing_and_after_drop() {
+        let catalog = CatalogManager::new();
+        catalog.create_database(1, "sales").unwrap();
+        catalog.add_table(1, orders(10, "orders")).unwrap();
+        catalog.add_table(1, orders(11, "refunds")).unwrap();
+
+        assert_eq!(catalog.get_table_by_name(1, "orders").unwrap().read().unwrap().table_id, 10);
+        assert_eq!(catalog.get_table_by_name(1, "refunds").unwrap().read().unwrap().table_id, 11);
+        assert!(matches!(catalog.get_table_by_name(1, "users"), Err(OlapError::TableNameNotFound(1, _))));
+        assert!(matches!(catalog.get_table_by_name(2, "orders"), Err(OlapError::DatabaseNotFound(2))));
+
+        assert!(matches!(catalog.add_table(1, orders(12, "orders")), Err(OlapError::TableExists(1, _))));
+        assert_eq!(catalog.get_table_by_name(1, "orders").unwrap().read().unwrap().table_id, 10);
+
+        catalog.drop_table(1, 10).unwrap();
+        assert!(matches!(catalog.get_table_by_name(1, "orders"), Err(OlapError::TableNameNotFound(1, _))));
+        catalog.add_table(1, orders(12, "orders")).unwrap();
+        assert_eq!(catalog.get_table_by_name(1, "orders").unwrap().read().unwrap().table_id, 12);
+    }
+
+    // ── 重命名 ────────────────────────────────────────────────────────────────
+
+    #[test]
+    fn rename_table_moves_the_name_and_rejects_collisions_and_missing_tables() {
+        let catalog = CatalogManager::new();
+        catalog.create_database(1, "sales").unwrap();
+        catalog.add_table(1, orders(10, "orders")).unwrap();
+        catalog.add_table(1, orders(11, "refunds")).unwrap();
+
+        catalog.rename_table(1, 10, "orders_2024").unwrap();
+        assert_eq!(catalog.get_table(1, 10).unwrap().read().unwrap().table_name, "orders_2024");
+        assert_eq!(catalog.get_table_by_name(1, "orders_2024").unwrap().read().unwrap().table_id, 10);
+        assert!(matches!(catalog.get_table_by_name(1, "orders"), Err(OlapError::TableNameNotFound(1, _))));
+        // 旧名空出后可被新表使用；改成自己当前的名字不算冲突
+        catalog.add_table(1, orders(12, "orders")).unwrap();
+        catalog.rename_table(1, 10, "orders_2024").unwrap();
+
+        assert!(matches!(catalog.rename_table(1, 10, "refunds"), Err(OlapError::TableExists(1, _))));
+        assert_eq!(catalog.get_table(1, 10).unwrap().read().unwrap().table_name, "orders_2024");
+        assert_eq!(catalog.get_table_by_name(1, "refunds").unwrap().read().unwrap().table_id, 11);
+
+        assert!(matches!(catalog.rename_table(1, 99, "x"), Err(OlapError::TableNotFound(1, 99))));
+        assert!(matches!(catalog.rename_table(2, 10, "x"), Err(OlapError::DatabaseNotFound(2))));
+    }
+
+    // ── 持久化 ────────────────────────────────────────────────────────────────
+
+    fn route(catalog: &CatalogManager, db_id: DbId, table_id: TableId, date: i32, user: i64) -> u64 {
+        let table = catalog.get_table(db_id, table_id).unwrap();
+        let table = table.read().unwrap();
+        table.tablet_for_row_values(&[Value::Int32(date)], &[Value::Int64(user), Value::Int64(user * 31)]).unwrap()
+    }
+
+    #[test]
+    fn catalog_save_and_load_restores_tables_and_routing() {
+        let dir = crate::test_util::TempDir::new("catalog-save");
+        let catalog = CatalogManager::new();
+        catalog.create_database(1, "sales").unwrap();
+        catalog.create_database(2, "archive").unwrap();
+        catalog.add_table(1, orders(10, "orders")).unwrap();
+        catalog.add_table(1, orders(11, "refunds")).unwrap();
+        catalog.add_table(2, orders(20, "orders_2023")).unwrap();
+        catalog.save(dir.path()).unwrap();
+
+        let loaded = CatalogManager::load(dir.path()).unwrap();
+        assert_eq!((loaded.num_databases(), loaded.num_tables()), (2, 3));
+        for (db_id, table_id, name) in [(1, 10, "orders"), (1, 11, "refunds"), (2, 20, "orders_2023")] {
+            let table = loaded.get_table_by_name(db_id, name).unwrap();
+            let table = table.read().unwrap();
+            let orig  = catalog.get_table(db_id, table_id).unwrap();
+            assert_eq!(table.table_id, table_id);
+            assert_eq!(table.schema.schema_hash, orig.read().unwrap().schema.schema_hash);
+            for (date, user) in [(20240101, 1), (20240315, 42), (20241231, 7)] {
+                assert_eq!(route(&loaded, db_id, table_id, date, user), route(&catalog, db_id, table_id, date, user));
+            }
+        }
+
+        // 删表后再次保存，旧表文件被清理，重新加载不再包含该表
+        catalog.drop_table(1, 11).unwrap();
+        catalog.save(dir.path()).unwrap();
+        assert!(!dir.path().join("table_1_11.1.meta").exists());
+        assert!(!dir.path().join("table_1_10.1.meta").exists());
+        assert!(dir.path().join("table_1_10.2.meta").exists());
+        let loaded = CatalogManager::load(dir.path()).unwrap();
+        assert!(matches!(loaded.get_table(1, 11), Err(OlapError::TableNotFound(1, 11))));
+        assert_eq!(loaded.num_tables(), 2);
+    }
+
+    #[test]
+    fn catalog_load_from_empty_or_corrupt_dir() {
+        let dir = crate::test_util::TempDir::new("catalog-corrupt");
+        assert_eq!(CatalogManager::load(dir.path()).unwrap().num_databases(), 0);
+
+        let catalog = CatalogManager::new();
+        catalog.create_database(1, "sales").unwrap();
+        catalog.add_table(1, orders(10, "orders")).unwrap();
+        catalog.save(dir.path()).unwrap();
+        let path = dir.path().join("table_1_10.1.meta");
+        let mut data = std::fs::read(&path).unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 0xff;
+        std::fs::write(&path, data).unwrap();
+        assert!(matches!(CatalogManager::load(dir.path()), Err(OlapError::ChecksumMismatch)));
+    }
+
+    #[test]
+    fn interrupted_catalog_save_leaves_previous_version_loadable() {
+        let dir = crate::test_util::TempDir::new("catalog-crash");
+        let catalog = CatalogManager::new();
+        catalog.create_database(1, "sales").unwrap();
+        catalog.add_table(1, orders(10, "orders")).unwrap();
+        catalog.save(dir.path()).unwrap();
+
+        // 模拟下一次保存在 rename catalog.meta 之前崩溃：新 generation 的表文件已到位，
+        // 另有未 rename 的临时文件残留
+        catalog.rename_table(1, 10, "orders_v2").unwrap();
+        let table = catalog.get_table(1, 10).unwrap().read().unwrap().serialize();
+        let tmp = write_temp(&dir.path().join(table_file(1, 10, 2)), &table).unwrap();
+        std::fs::rename(tmp, dir.path().join(table_file(1, 10, 2))).unwrap();
+        write_temp(&dir.path().join(CATALOG_FILE), b"partial").unwrap();
+
+        let loaded = CatalogManager::load(dir.path()).unwrap();
+        assert!(loaded.get_table_by_name(1, "orders").is_ok());
+        assert!(loaded.get_table_by_name(1, "orders_v2").is_err());
+
+        // 重新保存成功后只剩新版本文件，临时文件被清理
+        catalog.save(dir.path()).unwrap();
+        let mut names: Vec<_> = std::fs::read_dir(dir.path()).unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["catalog.meta", "table_1_10.2.meta"]);
+        let loaded = CatalogManager::load(dir.path()).unwrap();
+        assert!(loaded.get_table_by_name(1, "orders_v2").is_ok());
+    }
+
+    // ── Schema 变更 ───────────────────────────────────────────────────────────
+
+    #[test]
+    fn table_drop_column_keeps_keys_and_routing() {
+        let mut table = orders(1, "orders");
+        table.schema.add_column(
+            ColumnSchema::value(3, "amount", ColumnType::Int64, crate::common::AggregateType::None),
+            Value::Null,
+        ).unwrap();
+        let route = |t: &OlapTable| {
+            t.tablet_for_row_values(&[Value::Int32(20240315)], &[Value::Int64(7), Value::Int64(9)]).unwrap()
+        };
+        let before = route(&table);
+
+        table.drop_column(3).unwrap();
+        assert_eq!((table.schema.num_columns(), table.schema.num_live_columns()), (4, 3));
+        assert_eq!(route(&table), before);
+        assert!(matches!(table.drop_column(1), Err(OlapError::Unsupported(_))));
+        assert!(matches!(table.drop_column(3), Err(OlapError::ColumnNotFound(3))));
+    }
+}
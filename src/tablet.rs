@@ -1,27 +1,32 @@
 //! Tablet 运行时状态（Version Graph + Tablet 句柄 + TabletManager）
 
+use std::collections::hash_map::Entry;
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::{Arc, RwLock};
 use crate::common::{
-    CompactionType, OlapError, Result, SchemaHash, TabletId, Version,
+    CompactionType, OlapError, Result, RowsetId, SchemaHash, TabletId, Version,
 };
-use crate::meta::{RowsetMeta, RowsetState, TabletMeta, TabletSchema};
+use crate::field_type::ColumnMeta;
+use crate::meta::{normalize_segment_path, unix_now_secs, RowsetMeta, RowsetState, TabletMeta, TabletSchema};
+use crate::segment::SegmentReader;
 
 // ── Version 图 ────────────────────────────────────────────────────────────────
 //
-// 有向图：每条边 start → end 代表一个覆盖 [start,end] 的 Rowset。
+// 有向图：每条边 start → end 代表一个覆盖 [start,end] 的 Rowset，要求 start <= end。
 // 支持：
 //   • 检测版本空洞（O(V+E) BFS）
 //   • 找出覆盖 [lo,hi] 的最小 Rowset 集合
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct VersionGraph {
     // start_version → { end_version, ... }
     adj: HashMap<i64, HashSet<i64>>,
 }
 
 impl VersionGraph {
+    /// 调用方需保证 `v.start <= v.end`（见 `Tablet::add_rowset`）
     pub fn add_edge(&mut self, v: Version) {
+        debug_assert!(v.start <= v.end, "backward version edge {v}");
         self.adj.entry(v.start).or_default().insert(v.end);
     }
 
@@ -34,46 +39,161 @@ impl VersionGraph {
         }
     }
 
-    /// BFS 找从 lo 到 hi 的覆盖路径，返回经过的 Version 列表。
+    /// 找从 lo 到 hi 的覆盖路径（Rowset 数最少），返回经过的 Version 列表
+    ///
+    /// 把边 [start,end] 看作节点 start → end+1。先从终点 hi+1 反向 BFS 求各节点
+    /// 到终点的最少边数，再从 lo 正向走：每一步在仍处于最短路上的边中选 end 最大的，
+    /// 因此边数相同时优先较新的（版本更高的）Rowset。
     pub fn find_covering_path(&self, lo: i64, hi: i64) -> Option<Vec<Version>> {
-        let mut queue: VecDeque<(i64, Vec<Version>)> = VecDeque::new();
-        queue.push_back((lo, vec![]));
-        let mut visited: HashSet<i64> = HashSet::new();
-        visited.insert(lo);
-
-        while let Some((cur, path)) = queue.pop_front() {
-            if let Some(ends) = self.adj.get(&cur) {
-                // 优先尝试跨度最大的边
-                let mut sorted: Vec<i64> = ends.iter().copied().collect();
-                sorted.sort_unstable_by(|a, b| b.cmp(a));
-
-                for &end in &sorted {
-                    let mut new_path = path.clone();
-                    new_path.push(Version::new(cur, end));
-
-                    if end == hi {
-                        return Some(new_path);
-                    }
-                    if end < hi && !visited.contains(&(end + 1)) {
-                        visited.insert(end + 1);
-                        queue.push_back((end + 1, new_path));
-                    }
+        let target = hi.checked_add(1)?;
+
+        // 反向邻接：end+1 → { start }，只保留落在 [lo, hi] 内的边
+        let mut rev: HashMap<i64, Vec<i64>> = HashMap::new();
+        for (&start, ends) in &self.adj {
+            if start < lo { continue; }
+            for &end in ends.iter().filter(|&&e| e <= hi) {
+                rev.entry(end + 1).or_default().push(start);
+            }
+        }
+
+        let mut dist: HashMap<i64, usize> = HashMap::new();
+        let mut queue = VecDeque::new();
+        dist.insert(target, 0);
+        queue.push_back(target);
+        while let Some(node) = queue.pop_front() {
+            let d = dist[&node];
+            for &prev in rev.get(&node).into_iter().flatten() {
+                if let Entry::Vacant(e) = dist.entry(prev) {
+                    e.insert(d + 1);
+                    queue.push_back(prev);
                 }
             }
         }
-        None
+
+        let mut path = Vec::with_capacity(*dist.get(&lo)?);
+        let mut cur  = lo;
+        while cur != target {
+            let want = dist[&cur] - 1;
+            let end  = self.adj.get(&cur)?
+                .iter()
+                .copied()
+                .filter(|&e| e <= hi && dist.get(&(e + 1)) == Some(&want))
+                .max()?;
+            path.push(Version::new(cur, end));
+            cur = end + 1;
+        }
+        Some(path)
+    }
+
+    /// [lo, hi] 中没有被任何边覆盖的版本区间，按升序排列
+    ///
+    /// 与 `find_covering_path` 一致，只考虑完全落在 [lo, hi] 内的边。
+    /// 边互相重叠但无法首尾相接时可能没有空洞却仍找不到覆盖路径。
+    pub fn find_gaps(&self, lo: i64, hi: i64) -> Vec<Version> {
+        let mut gaps = Vec::new();
+        let mut next = lo; // 尚未覆盖的最小版本
+        for v in self.edges().into_iter().filter(|v| v.start >= lo && v.end <= hi) {
+            if v.start > next {
+                gaps.push(Version::new(next, v.start - 1));
+            }
+            next = next.max(v.end + 1);
+        }
+        if next <= hi {
+            gaps.push(Version::new(next, hi));
+        }
+        gaps
     }
 
     pub fn has_version_holes(&self, lo: i64, hi: i64) -> bool {
         self.find_covering_path(lo, hi).is_none()
     }
+
+    /// 把边 [start,end] 视为 start → end+1 的有向边，检测是否成环
+    ///
+    /// 所有边都满足 start <= end 时不可能成环，仅用于调试断言。
+    pub fn has_cycle(&self) -> bool {
+        // 0 = 未访问，1 = 在栈上，2 = 已完成
+        let mut color: HashMap<i64, u8> = HashMap::new();
+        for &root in self.adj.keys() {
+            if color.contains_key(&root) { continue; }
+            let mut stack: Vec<(i64, Vec<i64>)> = vec![(root, self.successors(root))];
+            color.insert(root, 1);
+            while let Some((node, next)) = stack.last_mut() {
+                match next.pop() {
+                    Some(succ) => match color.get(&succ) {
+                        Some(1) => return true,
+                        Some(_) => {}
+                        None    => {
+                            color.insert(succ, 1);
+                            let succs = self.successors(succ);
+                            stack.push((succ, succs));
+                        }
+                    },
+                    None => {
+                        color.insert(*node, 2);
+                        stack.pop();
+                    }
+                }
+            }
+        }
+        false
+    }
+
+    fn successors(&self, node: i64) -> Vec<i64> {
+        self.adj.get(&node)
+            .map(|ends| ends.iter().map(|&e| e.wrapping_add(1)).collect())
+            .unwrap_or_default()
+    }
+
+    /// 所有边，按 (start, end) 升序
+    pub fn edges(&self) -> Vec<Version> {
+        let mut out: Vec<Version> = self.adj.iter()
+            .flat_map(|(&start, ends)| ends.iter().map(move |&end| Version::new(start, end)))
+            .collect();
+        out.sort_unstable_by_key(|v| (v.start, v.end));
+        out
+    }
+
+    // ── 序列化 ────────────────────────────────────────────────────────────────
+    //
+    // num_edges u32 | (start i64, end i64) × num_edges，边按 `edges()` 顺序
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let edges = self.edges();
+        let mut out = Vec::with_capacity(4 + edges.len() * 16);
+        out.extend_from_slice(&(edges.len() as u32).to_le_bytes());
+        for v in &edges {
+            out.extend_from_slice(&v.start.to_le_bytes());
+            out.extend_from_slice(&v.end.to_le_bytes());
+        }
+        out
+    }
+
+    /// 截断的输入只恢复完整的边；start > end 的非法边被丢弃
+    pub fn deserialize(data: &[u8]) -> Self {
+        let mut g = Self::default();
+        if data.len() < 4 { return g; }
+        let n = u32::from_le_bytes(data[0..4].try_into().unwrap()) as usize;
+        for i in 0..n {
+            let b = 4 + i * 16;
+            if b + 16 > data.len() { break; }
+            let start = i64::from_le_bytes(data[b..b+8].try_into().unwrap());
+            let end   = i64::from_le_bytes(data[b+8..b+16].try_into().unwrap());
+            if start <= end {
+                g.add_edge(Version::new(start, end));
+            }
+        }
+        g
+    }
 }
 
 // ── Tablet 内部状态 ───────────────────────────────────────────────────────────
 
 pub struct TabletInner {
-    pub meta:          TabletMeta,
-    pub version_graph: VersionGraph,
+    pub meta:               TabletMeta,
+    pub version_graph:      VersionGraph,
+    /// 直接挂在内存中的 Segment 字节（rowset_id → 各 Segment），不落盘的测试与工具用
+    pub in_memory_segments: HashMap<RowsetId, Vec<Arc<Vec<u8>>>>,
 }
 
 impl TabletInner {
@@ -82,12 +202,68 @@ impl TabletInner {
         for rs in meta.rowsets.values() {
             vg.add_edge(rs.version);
         }
-        Self { meta, version_graph: vg }
+        Self { meta, version_graph: vg, in_memory_segments: HashMap::new() }
+    }
+
+    fn mark_stale(&mut self, rowset_id: RowsetId) {
+        let version = match self.meta.rowsets.get_mut(&rowset_id) {
+            Some(rs) => {
+                rs.mark_stale();
+                rs.version
+            }
+            None => return,
+        };
+        self.version_graph.remove_edge(version);
+    }
+
+    /// 见 `Tablet::capture_consistent_versions`；由调用方持有锁
+    fn capture_consistent_versions(&self, lo: i64, hi: i64) -> Result<Vec<RowsetMeta>> {
+        let path = self.version_graph
+            .find_covering_path(lo, hi)
+            .ok_or_else(|| {
+                let gaps = self.version_graph.find_gaps(lo, hi);
+                let gaps: Vec<String> = gaps.iter().map(|g| g.to_string()).collect();
+                OlapError::MissingVersions(format!("[{lo},{hi}], gaps: {}", gaps.join(" ")))
+            })?;
+
+        let rowsets = path
+            .iter()
+            .filter_map(|v| {
+                self.meta.rowsets.values().find(|r| r.version == *v).cloned()
+            })
+            .collect();
+        Ok(rowsets)
     }
 }
 
+/// Tablet 版本拓扑的只读视图，见 `Tablet::describe`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TabletDescription {
+    pub max_version:            i64,
+    pub cumulative_layer_point: i64,
+    /// (rowset_id, 版本, 状态, 行数)，按版本升序，含未回收的 Stale Rowset
+    pub rowsets:                Vec<(RowsetId, Version, RowsetState, u64)>,
+    /// 版本图中的边（不含 Stale Rowset），按 (start, end) 升序
+    pub version_edges:          Vec<Version>,
+    /// [0, max_version] 中未被任何边覆盖的版本区间
+    pub gaps:                   Vec<Version>,
+}
+
+/// 某一时刻 Tablet 元数据与版本图的深拷贝，用于迁移与创建副本
+#[derive(Debug, Clone)]
+pub struct TabletMetaSnapshot {
+    pub meta:          TabletMeta,
+    pub version_graph: VersionGraph,
+}
+
 // ── Tablet 句柄 ───────────────────────────────────────────────────────────────
 
+/// 平均大小低于该值的 Rowset 在 cumulative 打分中受到小文件惩罚
+pub const SMALL_ROWSET_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Base 区首段连续 Rowset 至少达到该数量才触发 base compaction
+pub const BASE_COMPACTION_MIN_ROWSETS: usize = 5;
+
 #[derive(Clone)]
 pub struct Tablet(Arc<RwLock<TabletInner>>);
 
@@ -96,6 +272,39 @@ impl Tablet {
         Self(Arc::new(RwLock::new(TabletInner::new(meta))))
     }
 
+    /// 从快照重建 Tablet，版本图直接沿用快照中的状态
+    pub fn from_snapshot(snapshot: TabletMetaSnapshot) -> Self {
+        let TabletMetaSnapshot { meta, version_graph } = snapshot;
+        Self(Arc::new(RwLock::new(TabletInner {
+            meta, version_graph, in_memory_segments: HashMap::new(),
+        })))
+    }
+
+    /// 在同一把读锁下复制元数据与版本图，二者彼此一致
+    pub fn snapshot(&self) -> TabletMetaSnapshot {
+        let inner = self.0.read().unwrap();
+        TabletMetaSnapshot {
+            meta:          inner.meta.clone(),
+            version_graph: inner.version_graph.clone(),
+        }
+    }
+
+    /// 在同一把读锁下汇总版本图与 Rowset 状态
+    pub fn describe(&self) -> TabletDescription {
+        let inner = self.0.read().unwrap();
+        let mut rowsets: Vec<_> = inner.meta.rowsets.values()
+            .map(|r| (r.rowset_id, r.version, r.state.clone(), r.num_rows))
+            .collect();
+        rowsets.sort_by_key(|&(id, v, _, _)| (v.start, v.end, id));
+        TabletDescription {
+            max_version:            inner.meta.max_version,
+            cumulative_layer_point: inner.meta.cumulative_layer_point,
+            rowsets,
+            version_edges:          inner.version_graph.edges(),
+            gaps:                   inner.version_graph.find_gaps(0, inner.meta.max_version),
+        }
+    }
+
     pub fn tablet_id(&self) -> TabletId {
         self.0.read().unwrap().meta.tablet_id
     }
@@ -112,59 +321,302 @@ impl Tablet {
         self.0.read().unwrap().meta.max_version
     }
 
+    /// Cumulative 区与 Base 区的分界：`version.end <= point` 的 Rowset 属于 Base 区
+    pub fn cumulative_layer_point(&self) -> i64 {
+        self.0.read().unwrap().meta.cumulative_layer_point
+    }
+
+    /// Cumulative compaction 完成后推进分界点；只前进，不后退
+    pub fn advance_cumulative_point(&self, new_point: i64) {
+        let mut inner = self.0.write().unwrap();
+        if new_point > inner.meta.cumulative_layer_point {
+            inner.meta.cumulative_layer_point = new_point;
+        }
+    }
+
+    /// 紧随当前最大版本的单点版本
+    pub fn next_version(&self) -> Version {
+        Version::point(self.max_version() + 1)
+    }
+
     /// 将一个已提交的 Rowset 发布到本 Tablet
-    pub fn add_rowset(&self, mut rs: RowsetMeta) -> Result<()> {
+    ///
+    /// 版本区间必须满足 start <= end，否则返回 `Unsupported`。
+    pub fn add_rowset(&self, rs: RowsetMeta) -> Result<()> {
+        self.add_rowset_with(rs, |_| Ok(())).map(|_| ())
+    }
+
+    /// 同 `add_rowset`，但整个过程持有写锁：
+    ///
+    /// 1. `rs.version == Version::AUTO` 时分配 `max_version + 1`
+    /// 2. 校验版本区间、rowset_id 以及与可见 Rowset 的重叠（见 `fits_visible_versions`）
+    /// 3. 调用 `before_apply`（如写 WAL），失败则不修改 Tablet
+    /// 4. 登记 Rowset
+    ///
+    /// 并发发布因此不会拿到相同的版本。返回最终版本。
+    pub fn add_rowset_with(
+        &self,
+        rs:           RowsetMeta,
+        before_apply: impl FnOnce(&RowsetMeta) -> Result<()>,
+    ) -> Result<Version> {
+        self.replace_rowsets_with(rs, &[], before_apply)
+    }
+
+    /// 同 `add_rowset_with`，并在同一把写锁内把 `inputs` 标记为 Stale
+    ///
+    /// 用于发布 compaction 输出：读者不会同时看到输出与其输入。
+    pub fn replace_rowsets_with(
+        &self,
+        mut rs:       RowsetMeta,
+        inputs:       &[RowsetId],
+        before_apply: impl FnOnce(&RowsetMeta) -> Result<()>,
+    ) -> Result<Version> {
         let mut inner = self.0.write().unwrap();
+        if rs.version == Version::AUTO {
+            rs.version = Version::point(inner.meta.max_version + 1);
+        }
+        if rs.version.start > rs.version.end {
+            return Err(OlapError::Unsupported(format!("backward version {}", rs.version)));
+        }
         if inner.meta.rowsets.contains_key(&rs.rowset_id) {
             return Err(OlapError::VersionExists(rs.version));
         }
+        if !fits_visible_versions(&inner.meta, rs.version) {
+            return Err(OlapError::VersionExists(rs.version));
+        }
+        before_apply(&rs)?;
         rs.state = RowsetState::Visible;
         inner.version_graph.add_edge(rs.version);
+        debug_assert!(!inner.version_graph.has_cycle());
         if rs.version.end > inner.meta.max_version {
             inner.meta.max_version = rs.version.end;
         }
+        let version = rs.version;
         inner.meta.rowsets.insert(rs.rowset_id, rs);
-        Ok(())
+        for &id in inputs {
+            inner.mark_stale(id);
+        }
+        Ok(version)
     }
 
     /// 找出覆盖 [lo, hi] 版本范围的最小 Rowset 元数据集合
+    ///
+    /// 结果按版本升序，其中可能有删除条件 Rowset（`is_delete()`），
+    /// 读取方应以其条件过滤版本更早的数据 Rowset。
     pub fn capture_consistent_versions(&self, lo: i64, hi: i64) -> Result<Vec<RowsetMeta>> {
+        self.0.read().unwrap().capture_consistent_versions(lo, hi)
+    }
+
+    /// Compaction 优先级得分，越高越该合并
+    ///
+    /// Base 只统计 `version.end <= cumulative_layer_point` 的可见 Rowset，
+    /// Cumulative 只统计其余的。记 n 为参与统计的 Rowset 数，s 为其 `num_segments` 之和，
+    /// avg 为平均 `data_disk_size`：
+    ///
+    /// - Cumulative：`n + s + n × max(0, 1 − avg / SMALL_ROWSET_BYTES)`，
+    ///   小 Rowset 越多、越碎，得分越高
+    /// - Base：`n + s / 2`，只看文件数量，不奖励小文件
+    pub fn compute_compaction_score(&self, ctype: CompactionType) -> f64 {
         let inner = self.0.read().unwrap();
-        let path = inner.version_graph
-            .find_covering_path(lo, hi)
-            .ok_or_else(|| OlapError::MissingVersions(format!("[{lo},{hi}]")))?;
+        let point = inner.meta.cumulative_layer_point;
+        let (mut n, mut segments, mut bytes) = (0u64, 0u64, 0u64);
+        for r in inner.meta.rowsets.values()
+            .filter(|r| r.state == RowsetState::Visible)
+            .filter(|r| (r.version.end <= point) == (ctype == CompactionType::Base))
+        {
+            n        += 1;
+            segments += r.num_segments as u64;
+            bytes    += r.data_disk_size;
+        }
+        if n == 0 {
+            return 0.0;
+        }
+        let (n, segments) = (n as f64, segments as f64);
+        match ctype {
+            CompactionType::Cumulative => {
+                let avg     = bytes as f64 / n;
+                let penalty = n * (1.0 - avg / SMALL_ROWSET_BYTES as f64).max(0.0);
+                n + segments + penalty
+            }
+            CompactionType::Base => n + segments / 2.0,
+        }
+    }
 
-        let rowsets = path
-            .iter()
-            .filter_map(|v| {
-                inner.meta.rowsets.values().find(|r| r.version == *v).cloned()
-            })
+    /// 选出待合并的 Rowset：Cumulative 区（`version.end > cumulative_layer_point`）的
+    /// 可见 Rowset 中版本首尾相接的最长一段（至少 2 个）
+    ///
+    /// 删除条件 Rowset 不参与合并，会把版本切成两段。
+    pub fn pick_rowsets_for_compaction(&self) -> Vec<RowsetMeta> {
+        let inner = self.0.read().unwrap();
+        let point = inner.meta.cumulative_layer_point;
+        let mut visible: Vec<&RowsetMeta> = inner.meta.rowsets.values()
+            .filter(|r| r.state == RowsetState::Visible && r.version.end > point)
+            .filter(|r| !r.is_delete())
             .collect();
-        Ok(rowsets)
+        visible.sort_by_key(|r| (r.version.start, r.version.end));
+
+        let (mut best, mut run) = (0..0, 0..0);
+        for i in 0..visible.len() {
+            if i == 0 || visible[i].version.start != visible[i - 1].version.end + 1 {
+                run.start = i;
+            }
+            run.end = i + 1;
+            if run.len() > best.len() {
+                best = run.clone();
+            }
+        }
+        if best.len() < 2 {
+            return vec![];
+        }
+        visible[best].iter().map(|r| (*r).clone()).collect()
     }
 
-    /// Compaction 优先级得分（可见 Rowset 数量）
-    pub fn compute_compaction_score(&self, _ctype: CompactionType) -> f64 {
+    /// (Rowset 数, 可见行数, 磁盘字节数)；Rowset 数与字节数含未回收的 Stale Rowset
+    pub fn rowset_totals(&self) -> (usize, u64, u64) {
         let inner = self.0.read().unwrap();
-        inner.meta.rowsets.values()
-            .filter(|r| r.state == RowsetState::Visible)
-            .count() as f64
+        let rowsets = inner.meta.rowsets.values();
+        let visible_rows = rowsets.clone().filter(|r| r.is_visible()).map(|r| r.num_rows).sum();
+        let disk_bytes   = rowsets.clone().map(|r| r.data_disk_size).sum();
+        (rowsets.len(), visible_rows, disk_bytes)
+    }
+
+    /// 选出 base compaction 的输入：Base 区（`version.end <= cumulative_layer_point`）
+    /// 可见 Rowset 中从最旧版本开始、首尾相接的那一段
+    ///
+    /// 该段不足 `BASE_COMPACTION_MIN_ROWSETS` 个时返回 None。删除条件 Rowset 会截断该段。
+    pub fn pick_base_compaction(&self) -> Option<Vec<RowsetMeta>> {
+        let inner = self.0.read().unwrap();
+        let point = inner.meta.cumulative_layer_point;
+        let mut base: Vec<&RowsetMeta> = inner.meta.rowsets.values()
+            .filter(|r| r.state == RowsetState::Visible && r.version.end <= point)
+            .collect();
+        base.sort_by_key(|r| (r.version.start, r.version.end));
+
+        let mut run: Vec<RowsetMeta> = Vec::new();
+        for r in base {
+            let contiguous = run.last().is_none_or(|prev| r.version.start == prev.version.end + 1);
+            if !contiguous || r.is_delete() {
+                break;
+            }
+            run.push(r.clone());
+        }
+        (run.len() >= BASE_COMPACTION_MIN_ROWSETS).then_some(run)
+    }
+
+    /// 本 Tablet 内尚未使用的 Rowset id（当前最大值 + 1）
+    pub fn next_rowset_id(&self) -> u64 {
+        let inner = self.0.read().unwrap();
+        inner.meta.rowsets.keys().max().map_or(0, |m| m + 1)
     }
 
     /// 将指定 Rowset 标记为 Stale（compaction 后调用）
     pub fn mark_rowset_stale(&self, rowset_id: u64) {
+        self.0.write().unwrap().mark_stale(rowset_id);
+    }
+
+    /// 回收过期的 Stale Rowset：从元数据中移除并返回
+    ///
+    /// 只回收 Stale 超过 `older_than_secs` 秒、且其版本区间已被存活 Rowset
+    /// 完整覆盖的那些（被单个可见 Rowset 包含，或由可见 Rowset 链覆盖），
+    /// 保证任何读取路径都不再依赖它。
+    pub fn collect_stale_rowsets(&self, older_than_secs: u64) -> Vec<RowsetMeta> {
+        let now = unix_now_secs();
         let mut inner = self.0.write().unwrap();
-        let version = match inner.meta.rowsets.get_mut(&rowset_id) {
-            Some(rs) => {
-                rs.mark_stale();
-                rs.version
+        let visible: Vec<Version> = inner.meta.rowsets.values()
+            .filter(|r| r.state == RowsetState::Visible)
+            .map(|r| r.version)
+            .collect();
+        let covered = |v: Version| {
+            visible.iter().any(|w| w.start <= v.start && v.end <= w.end)
+                || inner.version_graph.find_covering_path(v.start, v.end).is_some()
+        };
+        let expired: Vec<_> = inner.meta.rowsets.values()
+            .filter(|r| r.state == RowsetState::Stale)
+            .filter(|r| r.stale_at.is_some_and(|t| now.saturating_sub(t) >= older_than_secs))
+            .filter(|r| covered(r.version))
+            .map(|r| r.rowset_id)
+            .collect();
+        for id in &expired {
+            inner.in_memory_segments.remove(id);
+        }
+        expired.iter()
+            .filter_map(|id| inner.meta.rowsets.remove(id))
+            .collect()
+    }
+
+    // ── 内存 Segment ──────────────────────────────────────────────────────────
+
+    /// 为 Rowset 追加一个内存中的 Segment，按追加顺序作为该 Rowset 的第 0、1… 个 Segment
+    ///
+    /// 可在发布 Rowset 之前或之后调用；Rowset 被回收时一并释放。
+    pub fn attach_segment_bytes(&self, rowset_id: RowsetId, bytes: Vec<u8>) {
+        let mut inner = self.0.write().unwrap();
+        inner.in_memory_segments.entry(rowset_id).or_default().push(Arc::new(bytes));
+    }
+
+    /// Rowset 挂载的内存 Segment，没有挂载时返回 None
+    pub fn segment_bytes(&self, rowset_id: RowsetId) -> Option<Vec<Arc<Vec<u8>>>> {
+        self.0.read().unwrap().in_memory_segments.get(&rowset_id).cloned()
+    }
+
+    /// 释放 Rowset 挂载的内存 Segment
+    pub fn detach_segment_bytes(&self, rowset_id: RowsetId) {
+        self.0.write().unwrap().in_memory_segments.remove(&rowset_id);
+    }
+
+    /// 捕获覆盖 `version` 的 Rowset（同 `capture_consistent_versions`），
+    /// 按版本顺序为其内存 Segment 打开 `SegmentReader`
+    ///
+    /// 捕获版本与取出各 Segment 的 `Arc` 在同一次持锁内完成，期间被回收的 Rowset
+    /// 不会让二者不一致；解析 Segment 在释放锁之后进行。
+    /// 删除条件 Rowset 没有数据，被跳过；数据 Rowset 没有挂载内存 Segment 时返回 `SegmentIo`。
+    pub fn open_segment_readers(
+        &self,
+        version: Version,
+        schema:  &[ColumnMeta],
+    ) -> Result<Vec<SegmentReader>> {
+        let segments: Vec<Arc<Vec<u8>>> = {
+            let inner   = self.0.read().unwrap();
+            let rowsets = inner.capture_consistent_versions(version.start, version.end)?;
+            let mut segments = Vec::new();
+            for rs in rowsets.iter().filter(|r| !r.is_delete()) {
+                let attached = inner.in_memory_segments.get(&rs.rowset_id).ok_or_else(|| {
+                    OlapError::SegmentIo(format!("rowset {} has no in-memory segments", rs.rowset_id))
+                })?;
+                segments.extend(attached.iter().cloned());
             }
-            None => return,
+            segments
         };
-        inner.version_graph.remove_edge(version);
+        segments.into_iter()
+            .map(|bytes| SegmentReader::open_shared(bytes, schema.to_vec()))
+            .collect()
     }
 }
 
+/// 新版本 `v` 与可见 Rowset 的关系是否合法：
+///
+/// - 与所有可见 Rowset 都不相交：普通追加
+/// - 相交的可见 Rowset 都落在 `v` 内，且首尾相接恰好拼成 `v`（至少 2 个）：compaction 输出
+///
+/// 其余情况（与某个可见 Rowset 部分重叠、区间完全相同、或被其包含）都会让版本图产生歧义。
+fn fits_visible_versions(meta: &TabletMeta, v: Version) -> bool {
+    let mut hit: Vec<Version> = meta.rowsets.values()
+        .filter(|r| r.state == RowsetState::Visible)
+        .map(|r| r.version)
+        .filter(|w| w.start <= v.end && v.start <= w.end)
+        .collect();
+    if hit.is_empty() {
+        return true;
+    }
+    if hit.len() < 2 || hit.iter().any(|w| w.start < v.start || w.end > v.end) {
+        return false;
+    }
+    hit.sort_by_key(|w| w.start);
+    hit[0].start == v.start
+        && hit[hit.len() - 1].end == v.end
+        && hit.windows(2).all(|p| p[1].start == p[0].end + 1)
+}
+
 // ── Tablet 分片注册表 ─────────────────────────────────────────────────────────
 
 const NUM_SHARDS: usize = 64;
@@ -192,11 +644,49 @@ impl TabletManager {
         &self.shards[(tablet_id as usize) % NUM_SHARDS]
     }
 
+    /// (tablet_id, schema_hash) 已存在时返回 `TabletExists`，不替换已有 Tablet
     pub fn create_tablet(&self, meta: TabletMeta) -> Result<Tablet> {
-        let key = (meta.tablet_id, meta.schema_hash);
-        let tablet = Tablet::new(meta);
-        self.shard(key.0).write().unwrap().tablets.insert(key, tablet.clone());
-        Ok(tablet)
+        let tablet_id = meta.tablet_id;
+        let mut shard = self.shard(tablet_id).write().unwrap();
+        match shard.tablets.entry((tablet_id, meta.schema_hash)) {
+            Entry::Occupied(_) => Err(OlapError::TabletExists(tablet_id)),
+            Entry::Vacant(e)   => Ok(e.insert(Tablet::new(meta)).clone()),
+        }
+    }
+
+    /// 批量创建 Tablet，按分片分组，每个分片只取一次写锁
+    ///
+    /// 任一 (tablet_id, schema_hash) 已存在或在 `metas` 中重复时返回 `TabletExists`，
+    /// 并移除本次已插入的 Tablet。返回的句柄与 `metas` 顺序一致。
+    pub fn create_tablets(&self, metas: Vec<TabletMeta>) -> Result<Vec<Tablet>> {
+        let tablets: Vec<Tablet> = metas.into_iter().map(Tablet::new).collect();
+        let mut by_shard: Vec<Vec<&Tablet>> = vec![Vec::new(); NUM_SHARDS];
+        for t in &tablets {
+            by_shard[(t.tablet_id() as usize) % NUM_SHARDS].push(t);
+        }
+
+        let mut created: Vec<(TabletId, SchemaHash)> = Vec::new();
+        for (shard, group) in self.shards.iter().zip(&by_shard) {
+            if group.is_empty() { continue; }
+            let mut shard = shard.write().unwrap();
+            let mut seen = HashSet::new();
+            let dup = group.iter()
+                .map(|t| (t.tablet_id(), t.schema_hash()))
+                .find(|key| shard.tablets.contains_key(key) || !seen.insert(*key));
+            if let Some((tablet_id, _)) = dup {
+                drop(shard);
+                for (id, hash) in created {
+                    self.shard(id).write().unwrap().tablets.remove(&(id, hash));
+                }
+                return Err(OlapError::TabletExists(tablet_id));
+            }
+            for t in group {
+                let key = (t.tablet_id(), t.schema_hash());
+                shard.tablets.insert(key, (*t).clone());
+                created.push(key);
+            }
+        }
+        Ok(tablets)
     }
 
     pub fn get_tablet(&self, tablet_id: TabletId, schema_hash: SchemaHash) -> Result<Tablet> {
@@ -207,6 +697,38 @@ impl TabletManager {
             .ok_or(OlapError::TabletNotFound(tablet_id))
     }
 
+    /// 以 `src_id` 的元数据快照注册新 Tablet `new_id`（同一 schema_hash）
+    ///
+    /// 各 Rowset 的 tablet_id 与 Segment 文件名前缀改写为 `new_id`（带目录的旧路径先规范化为文件名）；
+    /// 只复制元数据，Segment 文件由调用方拷贝到 `StorageEngine::segment_path(new_id, ..)`。`new_id` 已存在时返回 `TabletExists`。
+    pub fn clone_tablet(
+        &self,
+        src_id:   TabletId,
+        src_hash: SchemaHash,
+        new_id:   TabletId,
+    ) -> Result<Tablet> {
+        let mut snapshot = self.get_tablet(src_id, src_hash)?.snapshot();
+        snapshot.meta.tablet_id = new_id;
+        let src_prefix = format!("{src_id}_");
+        for rs in snapshot.meta.rowsets.values_mut() {
+            rs.tablet_id = new_id;
+            for path in &mut rs.segment_paths {
+                // 调用方直接构造的元数据可能仍带目录，先规范化为文件名
+                let name = normalize_segment_path(src_id, std::mem::take(path));
+                *path = match name.strip_prefix(&src_prefix) {
+                    Some(rest) => format!("{new_id}_{rest}"),
+                    None       => name,
+                };
+            }
+        }
+
+        let mut shard = self.shard(new_id).write().unwrap();
+        match shard.tablets.entry((new_id, src_hash)) {
+            Entry::Occupied(_) => Err(OlapError::TabletExists(new_id)),
+            Entry::Vacant(e)   => Ok(e.insert(Tablet::from_snapshot(snapshot)).clone()),
+        }
+    }
+
     pub fn drop_tablet(&self, tablet_id: TabletId, schema_hash: SchemaHash) -> Result<()> {
         self.shard(tablet_id)
             .write().unwrap()
@@ -215,10 +737,41 @@ impl TabletManager {
             .ok_or(OlapError::TabletNotFound(tablet_id))
     }
 
+    /// 移除 `tablet_ids` 中各 Tablet 的所有 schema_hash 版本，每个分片只取一次写锁；返回移除的个数
+    pub fn drop_tablets(&self, tablet_ids: &[TabletId]) -> usize {
+        let mut by_shard: Vec<HashSet<TabletId>> = vec![HashSet::new(); NUM_SHARDS];
+        for &id in tablet_ids {
+            by_shard[(id as usize) % NUM_SHARDS].insert(id);
+        }
+        let mut dropped = 0;
+        for (shard, ids) in self.shards.iter().zip(&by_shard) {
+            if ids.is_empty() { continue; }
+            let mut shard = shard.write().unwrap();
+            let before = shard.tablets.len();
+            shard.tablets.retain(|(id, _), _| !ids.contains(id));
+            dropped += before - shard.tablets.len();
+        }
+        dropped
+    }
+
     pub fn tablet_count(&self) -> usize {
         self.shards.iter().map(|s| s.read().unwrap().tablets.len()).sum()
     }
 
+    /// 逐个分片持读锁遍历 Tablet，不会同时锁住多个分片
+    pub fn for_each_tablet(&self, mut f: impl FnMut(&Tablet)) {
+        for shard in &self.shards {
+            shard.read().unwrap().tablets.values().for_each(&mut f);
+        }
+    }
+
+    /// 所有 Tablet 句柄的快照
+    pub fn all_tablets(&self) -> Vec<Tablet> {
+        self.shards.iter()
+            .flat_map(|s| s.read().unwrap().tablets.values().cloned().collect::<Vec<_>>())
+            .collect()
+    }
+
     /// 遍历所有 Tablet，返回 (tablet_id, schema_hash, compaction_score)
     pub fn collect_compaction_candidates(
         &self, ctype: CompactionType,
@@ -234,8 +787,418 @@ impl TabletManager {
         result.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap());
         result
     }
+
+    /// 同 `collect_compaction_candidates`，但每个分片最多贡献 `per_shard_limit` 个得分最高的
+    /// Tablet，再统一按得分排序，避免热点分片占满候选
+    pub fn collect_compaction_candidates_balanced(
+        &self, ctype: CompactionType, per_shard_limit: usize,
+    ) -> Vec<(TabletId, SchemaHash, f64)> {
+        let mut result = Vec::new();
+        for shard in &self.shards {
+            let mut local: Vec<_> = shard.read().unwrap().tablets.iter()
+                .map(|((tid, shash), tablet)| (*tid, *shash, tablet.compute_compaction_score(ctype)))
+                .collect();
+            local.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap());
+            local.truncate(per_shard_limit);
+            result.extend(local);
+        }
+        result.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap());
+        result
+    }
 }
 
 impl Default for TabletManager {
     fn default() -> Self { Self::new() }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{AggregateType, ColumnType, KeysType};
+    use crate::field_type::Value;
+    use crate::meta::ColumnSchema;
+    use crate::segment::SegmentWriter;
+
+    fn schema() -> TabletSchema {
+        TabletSchema::new(KeysType::Duplicate, vec![
+            ColumnSchema::key(0, "k", ColumnType::Int64),
+            ColumnSchema::value(1, "v", ColumnType::Int64, AggregateType::Sum),
+        ])
+    }
+
+    fn tablet() -> Tablet {
+        Tablet::new(TabletMeta::new(1, 1, schema()))
+    }
+
+    fn rowset(id: RowsetId, version: Version) -> RowsetMeta {
+        RowsetMeta::new(id, 1, 1, version, 1, 10)
+    }
+
+    fn segment_bytes(keys: &[i64]) -> Vec<u8> {
+        let mut w = SegmentWriter::from_tablet_schema(&schema());
+        for &k in keys {
+            w.append_row(vec![Value::Int64(k), Value::Int64(k * 10)]).unwrap();
+        }
+        w.finish().unwrap().0
+    }
+
+    // ── 内存 Segment ──────────────────────────────────────────────────────────
+
+    #[test]
+    fn reads_in_memory_segments_through_tablet() {
+        let t = tablet();
+        t.attach_segment_bytes(0, segment_bytes(&[1, 2]));
+        t.add_rowset(rowset(0, Version::point(0))).unwrap();
+        t.add_rowset(rowset(1, Version::point(1))).unwrap();
+        t.attach_segment_bytes(1, segment_bytes(&[3]));
+
+        let readers = t.open_segment_readers(Version::new(0, 1), &schema().segment_schema()).unwrap();
+        let values: Vec<Value> = readers.iter().flat_map(|r| r.read_column(1).unwrap()).collect();
+        assert_eq!(values, vec![Value::Int64(10), Value::Int64(20), Value::Int64(30)]);
+    }
+
+    #[test]
+    fn data_rowset_without_in_memory_segments_is_an_error() {
+        let t = tablet();
+        t.add_rowset(rowset(0, Version::point(0))).unwrap();
+        let err = t.open_segment_readers(Version::point(0), &schema().segment_schema());
+        assert!(matches!(err, Err(OlapError::SegmentIo(_))));
+    }
+
+    // ── TabletManager ─────────────────────────────────────────────────────────
+
+    fn meta(tablet_id: TabletId) -> TabletMeta {
+        TabletMeta::new(tablet_id, 1, schema())
+    }
+
+    #[test]
+    fn create_tablet_rejects_existing_key_and_keeps_data() {
+        let mgr = TabletManager::new();
+        let t = mgr.create_tablet(meta(5)).unwrap();
+        t.add_rowset(rowset(0, Version::point(0))).unwrap();
+
+        assert!(matches!(mgr.create_tablet(meta(5)), Err(OlapError::TabletExists(5))));
+        let kept = mgr.get_tablet(5, schema().schema_hash).unwrap();
+        assert_eq!(kept.max_version(), 0);
+    }
+
+    #[test]
+    fn create_tablets_registers_hundreds_across_shards() {
+        let mgr = TabletManager::new();
+        let ids: Vec<TabletId> = (1000..1500).collect();
+        let tablets = mgr.create_tablets(ids.iter().map(|&id| meta(id)).collect()).unwrap();
+
+        assert_eq!(mgr.tablet_count(), 500);
+        let returned: Vec<TabletId> = tablets.iter().map(Tablet::tablet_id).collect();
+        assert_eq!(returned, ids);
+        for &id in &ids {
+            assert_eq!(mgr.get_tablet(id, schema().schema_hash).unwrap().tablet_id(), id);
+        }
+    }
+
+    #[test]
+    fn create_tablets_rolls_back_on_duplicate() {
+        let mgr = TabletManager::new();
+        mgr.create_tablet(meta(1300)).unwrap();
+
+        // 与已有 Tablet 冲突：本批一个也不留下
+        let batch = (1000..1400).map(meta).collect();
+        assert!(matches!(mgr.create_tablets(batch), Err(OlapError::TabletExists(1300))));
+        assert_eq!(mgr.tablet_count(), 1);
+
+        // 批内重复
+        let mut batch: Vec<TabletMeta> = (2000..2300).map(meta).collect();
+        batch.push(meta(2150));
+        assert!(matches!(mgr.create_tablets(batch), Err(OlapError::TabletExists(2150))));
+        assert_eq!(mgr.tablet_count(), 1);
+        assert!(mgr.get_tablet(2000, schema().schema_hash).is_err());
+    }
+
+    #[test]
+    fn cloned_tablet_copies_versions_and_rewrites_paths() {
+        let mgr  = TabletManager::new();
+        let hash = schema().schema_hash;
+        let src  = mgr.create_tablet(meta(1)).unwrap();
+        src.add_rowset(rowset(0, Version::new(0, 1))).unwrap();
+        src.add_rowset(rowset(1, Version::point(2))).unwrap();
+        src.advance_cumulative_point(1);
+
+        let clone = mgr.clone_tablet(1, hash, 7).unwrap();
+        assert_eq!(clone.tablet_id(), 7);
+        assert_eq!(mgr.get_tablet(7, hash).unwrap().max_version(), 2);
+        let (a, b) = (src.describe(), clone.describe());
+        assert_eq!(a.rowsets, b.rowsets);
+        assert_eq!(a.version_edges, b.version_edges);
+        assert_eq!(b.cumulative_layer_point, 1);
+        let rowsets = clone.capture_consistent_versions(0, 2).unwrap();
+        assert_eq!(rowsets.len(), 2);
+        for rs in &rowsets {
+            assert_eq!(rs.tablet_id, 7);
+            assert_eq!(rs.segment_paths, [format!("7_{}_0.seg", rs.rowset_id)]);
+        }
+
+        // 两者此后互不影响；目标已存在或源不存在时报错
+        clone.add_rowset(RowsetMeta::new(2, 7, 1, Version::point(3), 1, 10)).unwrap();
+        assert_eq!(src.max_version(), 2);
+        assert!(matches!(mgr.clone_tablet(1, hash, 7), Err(OlapError::TabletExists(7))));
+        assert!(mgr.clone_tablet(99, hash, 8).is_err());
+    }
+
+    #[test]
+    fn balanced_candidates_surface_tablets_outside_a_hot_shard() {
+        let mgr = TabletManager::new();
+        let add = |tablet_id: TabletId, n: i64| {
+            let t = mgr.create_tablet(meta(tablet_id)).unwrap();
+            for v in 0..n {
+                t.add_rowset(RowsetMeta::new(v as RowsetId, tablet_id, 1, Version::point(v), 1000, 1024)).unwrap();
+            }
+        };
+        // 分片 0 内 12 个高分 Tablet，其余 5 个分片各 1 个低分 Tablet
+        let hot: Vec<TabletId> = (1..=12).map(|k| k * NUM_SHARDS as TabletId).collect();
+        hot.iter().for_each(|&id| add(id, 10));
+        (1..=5).for_each(|id| add(id, 3));
+
+        let cumu = CompactionType::Cumulative;
+        let global: Vec<TabletId> = mgr.collect_compaction_candidates(cumu).iter().take(10).map(|c| c.0).collect();
+        assert!(global.iter().all(|id| hot.contains(id)));
+
+        let balanced = mgr.collect_compaction_candidates_balanced(cumu, 2);
+        assert_eq!(balanced.len(), 7);
+        assert!(balanced[..2].iter().all(|c| hot.contains(&c.0)));
+        let mut others: Vec<TabletId> = balanced[2..].iter().map(|c| c.0).collect();
+        others.sort_unstable();
+        assert_eq!(others, [1, 2, 3, 4, 5]);
+        assert!(balanced.windows(2).all(|w| w[0].2 >= w[1].2));
+    }
+
+    // ── Compaction 打分 ───────────────────────────────────────────────────────
+
+    /// 版本 0..n 各一个 Rowset，每个 `bytes` 字节
+    fn tablet_with_rowsets(n: i64, bytes: u64) -> Tablet {
+        let t = tablet();
+        for v in 0..n {
+            t.add_rowset(RowsetMeta::new(v as RowsetId, 1, 1, Version::point(v), 1000, bytes)).unwrap();
+        }
+        t
+    }
+
+    #[test]
+    fn many_small_rowsets_outscore_few_large_for_cumulative() {
+        let small = tablet_with_rowsets(20, 1024 * 1024);
+        let large = tablet_with_rowsets(3, 4 * SMALL_ROWSET_BYTES);
+        let cumu  = CompactionType::Cumulative;
+        assert!(small.compute_compaction_score(cumu) > large.compute_compaction_score(cumu));
+        // 同样的 Rowset 数，越碎得分越高
+        assert!(tablet_with_rowsets(5, 1024).compute_compaction_score(cumu)
+            > tablet_with_rowsets(5, SMALL_ROWSET_BYTES).compute_compaction_score(cumu));
+        assert_eq!(tablet().compute_compaction_score(cumu), 0.0);
+    }
+
+    #[test]
+    fn base_and_cumulative_scores_split_at_the_layer_point() {
+        let t = tablet_with_rowsets(10, 1024);
+        assert_eq!(t.compute_compaction_score(CompactionType::Base), 0.0);
+        t.advance_cumulative_point(5);
+        // Base：6 个 Rowset、6 个 Segment，不计小文件惩罚
+        assert_eq!(t.compute_compaction_score(CompactionType::Base), 6.0 + 3.0);
+        let cumu = t.compute_compaction_score(CompactionType::Cumulative);
+        assert!(cumu > 4.0 + 4.0 && cumu <= 4.0 + 4.0 + 4.0, "{cumu}");
+    }
+
+    #[test]
+    fn base_and_cumulative_pickers_choose_disjoint_rowsets() {
+        let t = tablet_with_rowsets(10, 1024);
+        // 分界点之前全部属于 Cumulative 区
+        assert!(t.pick_base_compaction().is_none());
+        assert_eq!(t.pick_rowsets_for_compaction().len(), 10);
+
+        t.advance_cumulative_point(5);
+        let base: Vec<i64> = t.pick_base_compaction().unwrap().iter().map(|r| r.version.end).collect();
+        let cumu: Vec<i64> = t.pick_rowsets_for_compaction().iter().map(|r| r.version.end).collect();
+        assert_eq!(base, [0, 1, 2, 3, 4, 5]);
+        assert_eq!(cumu, [6, 7, 8, 9]);
+
+        // 分界点只前进
+        t.advance_cumulative_point(2);
+        assert_eq!(t.cumulative_layer_point(), 5);
+        t.advance_cumulative_point(8);
+        assert_eq!(t.pick_rowsets_for_compaction().len(), 0);
+        assert_eq!(t.pick_base_compaction().unwrap().len(), 9);
+    }
+
+    #[test]
+    fn base_picker_waits_for_enough_contiguous_base_rowsets() {
+        let t = tablet_with_rowsets(8, 1024);
+        t.advance_cumulative_point(BASE_COMPACTION_MIN_ROWSETS as i64 - 2);
+        assert!(t.pick_base_compaction().is_none());
+
+        t.advance_cumulative_point(BASE_COMPACTION_MIN_ROWSETS as i64 - 1);
+        let run = t.pick_base_compaction().unwrap();
+        assert_eq!(run.len(), BASE_COMPACTION_MIN_ROWSETS);
+        assert_eq!(run.first().map(|r| r.version.start), Some(0));
+
+        // 删除条件 Rowset 截断首段
+        let t = tablet_with_rowsets(3, 1024);
+        t.add_rowset(RowsetMeta::new_delete(3, 1, 1, Version::point(3), crate::meta::DeletePredicate::new())).unwrap();
+        for v in 4..10 {
+            t.add_rowset(RowsetMeta::new(v as RowsetId, 1, 1, Version::point(v), 1000, 1024)).unwrap();
+        }
+        t.advance_cumulative_point(9);
+        assert!(t.pick_base_compaction().is_none());
+    }
+
+    // ── VersionGraph ──────────────────────────────────────────────────────────
+
+    fn graph(edges: &[(i64, i64)]) -> VersionGraph {
+        let mut g = VersionGraph::default();
+        for &(start, end) in edges {
+            g.add_edge(Version::new(start, end));
+        }
+        g
+    }
+
+    #[test]
+    fn version_graph_round_trips_through_bytes() {
+        let g = graph(&[(0, 3), (0, 1), (2, 3), (4, 4), (5, 9), (5, 5), (6, 9)]);
+        let restored = VersionGraph::deserialize(&g.serialize());
+        assert_eq!(restored.edges(), g.edges());
+        assert_eq!(restored.serialize(), g.serialize());
+        assert_eq!(g.edges().len(), 7);
+
+        assert!(VersionGraph::deserialize(&[]).edges().is_empty());
+        let bytes = g.serialize();
+        assert_eq!(VersionGraph::deserialize(&bytes[..bytes.len() - 1]).edges(), g.edges()[..6]);
+    }
+
+    #[test]
+    fn deserialized_version_graph_finds_the_same_paths() {
+        let g = graph(&[(0, 3), (0, 1), (2, 3), (4, 4), (5, 9), (5, 5), (6, 9), (11, 12)]);
+        let restored = VersionGraph::deserialize(&g.serialize());
+        for (lo, hi) in [(0, 9), (0, 3), (2, 4), (5, 9), (6, 9), (0, 12), (11, 12)] {
+            assert_eq!(restored.find_covering_path(lo, hi), g.find_covering_path(lo, hi), "[{lo},{hi}]");
+        }
+        assert!(restored.find_covering_path(0, 12).is_none());
+    }
+
+    #[test]
+    fn missing_versions_error_names_each_gap() {
+        let t = tablet();
+        t.add_rowset(rowset(0, Version::new(0, 1))).unwrap();
+        t.add_rowset(rowset(1, Version::new(3, 4))).unwrap();
+        assert_eq!(t.snapshot().version_graph.find_gaps(0, 4), [Version::point(2)]);
+        match t.capture_consistent_versions(0, 4) {
+            Err(OlapError::MissingVersions(msg)) => assert_eq!(msg, "[0,4], gaps: [2,2]"),
+            other => panic!("{:?}", other.map(|v| v.len())),
+        }
+        assert_eq!(graph(&[(1, 1), (5, 6)]).find_gaps(0, 8), [Version::point(0), Version::new(2, 4), Version::new(7, 8)]);
+        assert!(graph(&[(0, 1), (2, 4)]).find_gaps(0, 4).is_empty());
+    }
+
+    // ── 版本校验 ──────────────────────────────────────────────────────────────
+
+    #[test]
+    fn backward_version_is_rejected() {
+        let t = tablet();
+        t.add_rowset(rowset(0, Version::new(0, 1))).unwrap();
+        assert!(matches!(t.add_rowset(rowset(1, Version::new(5, 2))), Err(OlapError::Unsupported(_))));
+        assert_eq!(t.max_version(), 1);
+        assert_eq!(t.describe().version_edges, vec![Version::new(0, 1)]);
+
+        // 反序列化时丢弃非法边
+        let mut bytes = graph(&[(0, 1)]).serialize();
+        bytes[0] = 2;
+        bytes.extend_from_slice(&5i64.to_le_bytes());
+        bytes.extend_from_slice(&2i64.to_le_bytes());
+        assert_eq!(VersionGraph::deserialize(&bytes).edges(), vec![Version::new(0, 1)]);
+    }
+
+    #[test]
+    fn well_formed_chain_is_accepted_without_cycles() {
+        let t = tablet();
+        for (id, v) in [(0, Version::new(0, 4)), (1, Version::point(5)), (2, Version::new(6, 9))] {
+            t.add_rowset(rowset(id, v)).unwrap();
+        }
+        assert_eq!(t.max_version(), 9);
+        assert!(!t.snapshot().version_graph.has_cycle());
+        assert_eq!(t.capture_consistent_versions(0, 9).unwrap().len(), 3);
+        assert!(!graph(&[(0, 0), (1, 3), (0, 3), (4, 4)]).has_cycle());
+    }
+
+    #[test]
+    fn overlapping_versions_are_rejected_and_clean_appends_accepted() {
+        let t = tablet();
+        t.add_rowset(rowset(0, Version::new(0, 1))).unwrap();
+        t.add_rowset(rowset(1, Version::new(2, 3))).unwrap();
+
+        for (id, v) in [(2, Version::new(2, 3)), (3, Version::new(0, 2)), (4, Version::new(3, 5)), (5, Version::point(1))] {
+            match t.add_rowset(rowset(id, v)) {
+                Err(OlapError::VersionExists(got)) => assert_eq!(got, v),
+                other => panic!("{v}: {other:?}"),
+            }
+        }
+        assert_eq!(t.describe().version_edges, [Version::new(0, 1), Version::new(2, 3)]);
+
+        t.add_rowset(rowset(6, Version::point(4))).unwrap();
+        // 恰好拼接若干可见 Rowset 的区间是 compaction 输出
+        t.add_rowset(rowset(7, Version::new(0, 3))).unwrap();
+        assert_eq!(t.max_version(), 4);
+    }
+
+    // ── 最少 Rowset 覆盖 ──────────────────────────────────────────────────────
+
+    #[test]
+    fn covering_path_prefers_the_single_wide_rowset() {
+        let t = tablet();
+        t.add_rowset(rowset(0, Version::new(0, 1))).unwrap();
+        t.add_rowset(rowset(1, Version::new(2, 3))).unwrap();
+        t.add_rowset(rowset(2, Version::new(0, 3))).unwrap();
+        let picked: Vec<Version> = t.capture_consistent_versions(0, 3).unwrap().iter().map(|r| r.version).collect();
+        assert_eq!(picked, vec![Version::new(0, 3)]);
+
+        // 路径同样短时优先较新的 Rowset
+        let g = graph(&[(0, 0), (1, 3), (0, 2), (3, 3), (0, 1), (2, 3)]);
+        assert_eq!(g.find_covering_path(0, 3), Some(vec![Version::new(0, 2), Version::point(3)]));
+        let g = graph(&[(0, 0), (1, 1), (2, 2), (3, 3), (0, 1), (2, 3), (1, 3)]);
+        assert_eq!(g.find_covering_path(0, 3).unwrap().len(), 2);
+    }
+
+    // ── Stale Rowset 回收 ─────────────────────────────────────────────────────
+
+    #[test]
+    fn compaction_inputs_become_collectible_but_output_does_not() {
+        let t = tablet();
+        for v in 0..3 {
+            t.add_rowset(rowset(v as RowsetId, Version::point(v))).unwrap();
+        }
+        t.replace_rowsets_with(rowset(3, Version::new(0, 2)), &[0, 1, 2], |_| Ok(())).unwrap();
+
+        // 尚未过期的不回收
+        assert!(t.collect_stale_rowsets(3600).is_empty());
+        let mut collected: Vec<RowsetId> = t.collect_stale_rowsets(0).iter().map(|r| r.rowset_id).collect();
+        collected.sort_unstable();
+        assert_eq!(collected, vec![0, 1, 2]);
+        assert!(t.collect_stale_rowsets(0).is_empty());
+
+        let rowsets = t.describe().rowsets;
+        assert_eq!(rowsets.len(), 1);
+        assert_eq!((rowsets[0].0, rowsets[0].2.clone()), (3, RowsetState::Visible));
+        assert_eq!(t.capture_consistent_versions(0, 2).unwrap()[0].rowset_id, 3);
+    }
+
+    #[test]
+    fn stale_rowset_still_needed_by_a_read_path_is_kept() {
+        let t = tablet();
+        t.add_rowset(rowset(0, Version::point(0))).unwrap();
+        t.add_rowset(rowset(1, Version::point(1))).unwrap();
+        t.attach_segment_bytes(1, segment_bytes(&[1]));
+        // 没有任何可见 Rowset 覆盖版本 1
+        t.mark_rowset_stale(1);
+        assert!(t.collect_stale_rowsets(0).is_empty());
+        assert!(t.segment_bytes(1).is_some());
+
+        t.replace_rowsets_with(rowset(2, Version::new(1, 2)), &[], |_| Ok(())).unwrap();
+        let collected = t.collect_stale_rowsets(0);
+        assert_eq!(collected.iter().map(|r| r.rowset_id).collect::<Vec<_>>(), vec![1]);
+        assert!(t.segment_bytes(1).is_none());
+    }
+}
@@ -0,0 +1,321 @@
+//! Rowset 发布预写日志（WAL）
+//!
+//! `publish_rowset` 先把 RowsetMeta 追加进日志并落盘，再修改内存中的 Tablet；
+//! 重启后由 `StorageEngine::recover` 重放。
+//!
+//! 记录格式：
+//! ```text
+//! len u32 | crc32(payload) u32 | payload
+//! payload = tablet_id u64 | schema_hash u32 | [kind u8] | body
+//! ```
+//! `len` 的最高位（`TYPED_FLAG`）置位时 payload 带 kind 字节，其余位为 payload 长度；
+//! 不带 kind 的记录即发布记录，body 为 `RowsetMeta::serialize()`：
+//!
+//! | kind | body |
+//! |------|------|
+//! | 1 compaction | n u32 \| n × 被替换的 rowset_id u64 \| 输出的 `RowsetMeta::serialize()` |
+//! | 2 cumulative point | 新分界点 i64（只读：旧日志中与 compaction 分开记录的分界点） |
+//! | 3 cumulative compaction | 新分界点 i64 \| 同 kind 1 |
+//!
+//! cumulative compaction 的发布与分界点推进写在同一条 kind 3 记录中，重放时一并生效，
+//! 不会出现输出已发布而分界点未推进的中间状态。
+//! 末尾残缺或校验失败的记录（写到一半时崩溃）及其后的内容会被忽略，并在首次追加前截掉，
+//! 否则之后追加的记录会落在无法解析的字节之后，下次重启时丢失。
+
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use crate::common::{OlapError, Result, RowsetId, SchemaHash, TabletId};
+use crate::meta::RowsetMeta;
+
+/// `len` 最高位：payload 带 kind 字节
+const TYPED_FLAG: u32 = 1 << 31;
+
+const KIND_COMPACTION:            u8 = 1;
+const KIND_CUMULATIVE_POINT:      u8 = 2;
+const KIND_CUMULATIVE_COMPACTION: u8 = 3;
+
+/// 一条日志记录
+#[derive(Debug, Clone)]
+pub struct WalRecord {
+    pub tablet_id:   TabletId,
+    pub schema_hash: SchemaHash,
+    pub entry:       WalEntry,
+}
+
+#[derive(Debug, Clone)]
+pub enum WalEntry {
+    /// 发布一个 Rowset
+    Publish(RowsetMeta),
+    /// 发布 compaction 输出，并把 `inputs` 标记为 Stale；
+    /// cumulative compaction 同时把分界点推进到 `cumulative_point`
+    Compaction { output: RowsetMeta, inputs: Vec<RowsetId>, cumulative_point: Option<i64> },
+    /// cumulative 分界点推进到该值；只出现在旧日志中
+    CumulativePoint(i64),
+}
+
+pub struct Wal {
+    path: PathBuf,
+    /// 首次追加时才创建文件
+    file: Mutex<Option<File>>,
+}
+
+impl Wal {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self { path: path.as_ref().to_path_buf(), file: Mutex::new(None) }
+    }
+
+    pub fn path(&self) -> &Path { &self.path }
+
+    /// 追加一条记录并 fsync，返回后即可安全地修改内存状态
+    pub fn append(
+        &self,
+        tablet_id:   TabletId,
+        schema_hash: SchemaHash,
+        rowset:      &RowsetMeta,
+    ) -> Result<()> {
+        let mut payload = record_prefix(tablet_id, schema_hash);
+        payload.extend_from_slice(&rowset.serialize());
+        self.append_payload(payload, false)
+    }
+
+    /// 追加一条 compaction 记录：`output` 替换 `inputs`，`cumulative_point` 为 Some 时
+    /// 同时推进 cumulative 分界点，重放时三者一并生效
+    pub fn append_compaction(
+        &self,
+        tablet_id:        TabletId,
+        schema_hash:      SchemaHash,
+        output:           &RowsetMeta,
+        inputs:           &[RowsetId],
+        cumulative_point: Option<i64>,
+    ) -> Result<()> {
+        let mut payload = record_prefix(tablet_id, schema_hash);
+        match cumulative_point {
+            Some(point) => {
+                payload.push(KIND_CUMULATIVE_COMPACTION);
+                payload.extend_from_slice(&point.to_le_bytes());
+            }
+            None => payload.push(KIND_COMPACTION),
+        }
+        payload.extend_from_slice(&(inputs.len() as u32).to_le_bytes());
+        for id in inputs {
+            payload.extend_from_slice(&id.to_le_bytes());
+        }
+        payload.extend_from_slice(&output.serialize());
+        self.append_payload(payload, true)
+    }
+
+    fn append_payload(&self, payload: Vec<u8>, typed: bool) -> Result<()> {
+        let len = payload.len() as u32 | if typed { TYPED_FLAG } else { 0 };
+        let mut rec = Vec::with_capacity(8 + payload.len());
+        rec.extend_from_slice(&len.to_le_bytes());
+        rec.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+        rec.extend_from_slice(&payload);
+
+        let mut guard = self.file.lock().unwrap();
+        if guard.is_none() {
+            if let Some(dir) = self.path.parent() {
+                std::fs::create_dir_all(dir).map_err(wal_err)?;
+            }
+            self.truncate_torn_tail_locked()?;
+            let f = OpenOptions::new().create(true).append(true).open(&self.path).map_err(wal_err)?;
+            *guard = Some(f);
+        }
+        let f = guard.as_mut().unwrap();
+        f.write_all(&rec).map_err(wal_err)?;
+        f.sync_data().map_err(wal_err)
+    }
+
+    /// 读出所有完整记录，以及这些记录占用的字节数（即最后一条有效记录的末尾偏移）；
+    /// 日志不存在时返回 `(vec![], 0)`
+    pub fn read_all(&self) -> Result<(Vec<WalRecord>, u64)> {
+        let mut data = Vec::new();
+        match File::open(&self.path) {
+            Ok(mut f) => { f.read_to_end(&mut data).map_err(wal_err)?; }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((vec![], 0)),
+            Err(e) => return Err(wal_err(e)),
+        }
+
+        let mut out = Vec::new();
+        let mut pos = 0;
+        while let Some(header) = data.get(pos..pos + 8) {
+            let raw = u32::from_le_bytes(header[0..4].try_into().unwrap());
+            let crc = u32::from_le_bytes(header[4..8].try_into().unwrap());
+            let len = (raw & !TYPED_FLAG) as usize;
+            let Some(payload) = data.get(pos + 8..pos + 8 + len) else { break };
+            if crc32fast::hash(payload) != crc { break; }
+            let Some(rec) = decode_record(payload, raw & TYPED_FLAG != 0) else { break };
+            out.push(rec);
+            pos += 8 + len;
+        }
+        Ok((out, pos as u64))
+    }
+
+    /// 把日志截断到最后一条有效记录的末尾并落盘，返回截断后的长度
+    ///
+    /// 持有追加锁重新扫描，不会截掉并发追加中的记录。
+    pub fn truncate_torn_tail(&self) -> Result<u64> {
+        let _guard = self.file.lock().unwrap();
+        self.truncate_torn_tail_locked()
+    }
+
+    /// 调用方需持有 `self.file` 的锁
+    fn truncate_torn_tail_locked(&self) -> Result<u64> {
+        let (_, valid_len) = self.read_all()?;
+        let f = match OpenOptions::new().write(true).open(&self.path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(wal_err(e)),
+        };
+        if f.metadata().map_err(wal_err)?.len() > valid_len {
+            f.set_len(valid_len).map_err(wal_err)?;
+            f.sync_all().map_err(wal_err)?;
+        }
+        Ok(valid_len)
+    }
+}
+
+fn wal_err(e: std::io::Error) -> OlapError {
+    OlapError::Wal(e.to_string())
+}
+
+fn record_prefix(tablet_id: TabletId, schema_hash: SchemaHash) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(&tablet_id.to_le_bytes());
+    out.extend_from_slice(&schema_hash.to_le_bytes());
+    out
+}
+
+fn decode_record(data: &[u8], typed: bool) -> Option<WalRecord> {
+    let tablet_id   = u64::from_le_bytes(data.get(0..8)?.try_into().unwrap());
+    let schema_hash = u32::from_le_bytes(data.get(8..12)?.try_into().unwrap());
+    let body        = &data[12..];
+    let entry = if !typed {
+        WalEntry::Publish(RowsetMeta::deserialize(body).ok()?)
+    } else {
+        let (&kind, body) = body.split_first()?;
+        match kind {
+            KIND_COMPACTION | KIND_CUMULATIVE_COMPACTION => {
+                let (cumulative_point, body) = if kind == KIND_CUMULATIVE_COMPACTION {
+                    let point = i64::from_le_bytes(body.get(0..8)?.try_into().unwrap());
+                    (Some(point), &body[8..])
+                } else {
+                    (None, body)
+                };
+                let n    = u32::from_le_bytes(body.get(0..4)?.try_into().unwrap()) as usize;
+                let end  = n.checked_mul(8)?.checked_add(4)?;
+                let ids  = body.get(4..end)?;
+                let inputs = ids.chunks_exact(8)
+                    .map(|c| u64::from_le_bytes(c.try_into().unwrap()))
+                    .collect();
+                let output = RowsetMeta::deserialize(&body[end..]).ok()?;
+                WalEntry::Compaction { output, inputs, cumulative_point }
+            }
+            KIND_CUMULATIVE_POINT => {
+                let point = i64::from_le_bytes(body.get(0..8)?.try_into().unwrap());
+                WalEntry::CumulativePoint(point)
+            }
+            _ => return None,
+        }
+    };
+    Some(WalRecord { tablet_id, schema_hash, entry })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::Version;
+    use crate::test_util::TempDir;
+
+    fn rowset(id: u64) -> RowsetMeta {
+        RowsetMeta::new(id, 7, 1, Version::point(id as i64), 10, 100)
+    }
+
+    #[test]
+    fn torn_tail_is_truncated_before_next_append() {
+        let dir = TempDir::new("wal-torn");
+        let wal = Wal::new(dir.path().join("rowset.wal"));
+        for id in 0..3 {
+            wal.append(7, 42, &rowset(id)).unwrap();
+        }
+        let (_, valid_len) = wal.read_all().unwrap();
+
+        // 模拟写到一半时崩溃：尾部留下一段无法解析的字节
+        let mut f = OpenOptions::new().append(true).open(wal.path()).unwrap();
+        f.write_all(&[0xff, 0x01, 0x00, 0x00, 0xab]).unwrap();
+        drop(f);
+
+        let wal = Wal::new(dir.path().join("rowset.wal"));
+        let (records, len) = wal.read_all().unwrap();
+        assert_eq!((records.len(), len), (3, valid_len));
+
+        wal.append(7, 42, &rowset(3)).unwrap();
+        let (records, _) = wal.read_all().unwrap();
+        let ids: Vec<u64> = records.iter()
+            .map(|r| match &r.entry {
+                WalEntry::Publish(rs) => rs.rowset_id,
+                other => panic!("unexpected {other:?}"),
+            })
+            .collect();
+        assert_eq!(ids, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn bad_crc_record_and_everything_after_it_are_dropped() {
+        let dir = TempDir::new("wal-crc");
+        let wal = Wal::new(dir.path().join("rowset.wal"));
+        wal.append(7, 42, &rowset(0)).unwrap();
+        let (_, first_len) = wal.read_all().unwrap();
+        wal.append(7, 42, &rowset(1)).unwrap();
+
+        let mut data = std::fs::read(wal.path()).unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 0xff;
+        std::fs::write(wal.path(), &data).unwrap();
+
+        let wal = Wal::new(dir.path().join("rowset.wal"));
+        assert_eq!(wal.truncate_torn_tail().unwrap(), first_len);
+        assert_eq!(std::fs::metadata(wal.path()).unwrap().len(), first_len);
+        assert_eq!(wal.read_all().unwrap().0.len(), 1);
+    }
+
+    #[test]
+    fn missing_log_reads_empty() {
+        let dir = TempDir::new("wal-missing");
+        let wal = Wal::new(dir.path().join("rowset.wal"));
+        let (records, len) = wal.read_all().unwrap();
+        assert!(records.is_empty());
+        assert_eq!(len, 0);
+        assert_eq!(wal.truncate_torn_tail().unwrap(), 0);
+    }
+
+    #[test]
+    fn compaction_record_round_trips_after_publish_records() {
+        let dir = TempDir::new("wal-compaction");
+        let wal = Wal::new(dir.path().join("rowset.wal"));
+        wal.append(7, 42, &rowset(0)).unwrap();
+        let output = RowsetMeta::new(9, 7, 1, Version::new(0, 1), 20, 200);
+        wal.append_compaction(7, 42, &output, &[0, 1], None).unwrap();
+        wal.append_compaction(7, 42, &output, &[2, 3], Some(1)).unwrap();
+        // 旧日志中单独记录的分界点仍能读出
+        let mut legacy = record_prefix(7, 42);
+        legacy.push(KIND_CUMULATIVE_POINT);
+        legacy.extend_from_slice(&1i64.to_le_bytes());
+        wal.append_payload(legacy, true).unwrap();
+
+        let (records, _) = wal.read_all().unwrap();
+        assert_eq!(records.len(), 4);
+        assert!(matches!(&records[0].entry, WalEntry::Publish(rs) if rs.rowset_id == 0));
+        for (rec, want_inputs, want_point) in [(&records[1], vec![0, 1], None), (&records[2], vec![2, 3], Some(1))] {
+            match &rec.entry {
+                WalEntry::Compaction { output, inputs, cumulative_point } => {
+                    assert_eq!((output.rowset_id, output.version), (9, Version::new(0, 1)));
+                    assert_eq!((inputs, *cumulative_point), (&want_inputs, want_point));
+                }
+                other => panic!("unexpected {other:?}"),
+            }
+        }
+        assert!(matches!(records[3].entry, WalEntry::CumulativePoint(1)));
+    }
+}